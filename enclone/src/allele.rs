@@ -7,10 +7,13 @@ use vdj_ann::refx;
 use self::refx::RefData;
 use debruijn::{dna_string::DnaString, Mer};
 use enclone_core::defs::{CloneInfo, EncloneControl, ExactClonotype};
+use io_utils::open_userfile_for_read;
 use itertools::Itertools;
 use rayon::prelude::*;
 use stats_utils::percent_ratio;
 use std::cmp::{max, min, PartialOrd};
+use std::collections::HashMap;
+use std::io::BufRead;
 use std::time::Instant;
 use vector_utils::{erase_if, next_diff, next_diff1_2, next_diff1_3, reverse_sort, unique_sort};
 
@@ -22,6 +25,7 @@ pub fn find_alleles(
     refdata: &RefData,
     ctl: &EncloneControl,
     exact_clonotypes: &Vec<ExactClonotype>,
+    skip_donors: &[usize],
 ) -> Vec<(usize, usize, DnaString, usize, bool)> {
     // Derive consensus sequences for alternate alleles of V segments.
     //
@@ -54,7 +58,8 @@ pub fn find_alleles(
     // (donor, ref id, alt seq, support, is_ref):
     let mut alt_refs = Vec::<(usize, usize, DnaString, usize, bool)>::new();
 
-    // Organize data by reference ID.  Note that we ignore exact subclonotypes having four chains.
+    // Organize data by reference ID.  Note that we ignore exact subclonotypes having four chains,
+    // and cells from the donors in skip_donors (sorted), whose alleles are known already.
 
     let mut allxy =
         vec![Vec::<(usize, Vec<u8>, Vec<usize>, usize, usize, String)>::new(); refdata.refs.len()];
@@ -77,7 +82,7 @@ pub fn find_alleles(
                 {
                     for l in 0..x.clones.len() {
                         let donor = x.clones[l][j].donor_index;
-                        if donor.is_some() {
+                        if donor.is_some() && skip_donors.binary_search(&donor.unwrap()).is_err() {
                             allxy[id].push((
                                 donor.unwrap(),
                                 y.seq_del.clone(),
@@ -503,6 +508,138 @@ pub fn find_alleles(
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
+// Replace the alternate alleles for donors and V segments named in a FASTA file of donor
+// alleles (DONOR_ALLELES), as written by NOVEL_ALLELES_FASTA or DONOR_REF_FILE, by the alleles
+// in the file.  Each header begins with reference record id:donor name:allele number:gene name.
+//
+// Only the (donor, V segment) pairs that appear in the file are changed.  For these, computed
+// alleles that are identical to a reference V segment are kept, because NOVEL_ALLELES_FASTA does
+// not write them.  Alleles are then numbered in sorted order as before, so that an allele set
+// that is written and read back in gets the same numbers.
+
+pub fn inject_alleles(
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    alt_refs: &mut Vec<(usize, usize, DnaString, usize, bool)>,
+) -> Result<(), String> {
+    let filename = &ctl.gen_opt.donor_alleles;
+    let mut to_donor = HashMap::<&str, usize>::new();
+    for (i, d) in ctl.origin_info.donor_list.iter().enumerate() {
+        to_donor.insert(d.as_str(), i);
+    }
+    let mut to_ref = HashMap::<String, usize>::new();
+    let mut vrefs = Vec::<Vec<u8>>::new();
+    for id in 0..refdata.refs.len() {
+        if refdata.is_v(id) {
+            to_ref.insert(format!("{}", refdata.id[id]), id);
+            vrefs.push(refdata.refs[id].to_ascii_vec());
+        }
+    }
+    unique_sort(&mut vrefs);
+    let mut text = String::new();
+    let f = open_userfile_for_read(filename);
+    for line in f.lines() {
+        text += &line.unwrap();
+        text += "\n";
+    }
+    let mut alleles = Vec::<(usize, usize, DnaString, usize, bool)>::new();
+    for (header, fields, seq) in parse_donor_alleles(&text, filename)?.iter() {
+        let id = match to_ref.get(&fields[0]) {
+            Some(id) if refdata.name[*id] == fields[3] => *id,
+            _ => {
+                return Err(format!(
+                    "\nThe DONOR_ALLELES file {} refers to the reference sequence {}:{}, which \
+                    is not a V segment in the reference now being used.\n",
+                    filename, fields[0], fields[3]
+                ));
+            }
+        };
+        if seq.len() != refdata.refs[id].len() || !seq.bytes().all(|c| b"ACGT".contains(&c)) {
+            return Err(format!(
+                "\nThe sequence for {} in the DONOR_ALLELES file {} does not consist of \
+                {} bases A, C, G and T, matching the length of the reference sequence.\n",
+                header,
+                filename,
+                refdata.refs[id].len()
+            ));
+        }
+        if let Some(donor) = to_donor.get(fields[1].as_str()) {
+            alleles.push((*donor, id, DnaString::from_dna_string(seq), 0, false));
+        }
+    }
+    merge_donor_alleles(alt_refs, alleles, &vrefs);
+    Ok(())
+}
+
+// Parse the records in a DONOR_ALLELES file, returning for each its header, the four fields at
+// the beginning of the header, and its sequence.
+
+fn parse_donor_alleles(
+    text: &str,
+    filename: &str,
+) -> Result<Vec<(String, Vec<String>, String)>, String> {
+    let mut records = Vec::<(String, String)>::new();
+    for s in text.lines() {
+        if s.starts_with('>') {
+            records.push((s[1..].to_string(), String::new()));
+        } else if !records.is_empty() {
+            records.last_mut().unwrap().1 += s.trim();
+        } else if !s.is_empty() {
+            return Err(format!(
+                "\nThe DONOR_ALLELES file {} is not in FASTA format.\n",
+                filename
+            ));
+        }
+    }
+    let mut parsed = Vec::<(String, Vec<String>, String)>::new();
+    for (header, seq) in records.into_iter() {
+        let fields = header
+            .split(' ')
+            .next()
+            .unwrap()
+            .split(':')
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+        if fields.len() != 4 || fields[2].parse::<usize>().is_err() {
+            return Err(format!(
+                "\nThe DONOR_ALLELES file {} has a header line >{}, which does not begin with \
+                reference record id:donor name:allele number:gene name.\n",
+                filename, header
+            ));
+        }
+        parsed.push((header, fields, seq));
+    }
+    Ok(parsed)
+}
+
+// Merge donor alleles read from a file into the computed alleles, as described above.  A computed
+// allele that is also in the file is kept as is.  The sequences of the reference V segments are
+// given by vrefs, which is sorted.
+
+fn merge_donor_alleles(
+    alt_refs: &mut Vec<(usize, usize, DnaString, usize, bool)>,
+    mut alleles: Vec<(usize, usize, DnaString, usize, bool)>,
+    vrefs: &[Vec<u8>],
+) {
+    let mut pairs = alleles.iter().map(|x| (x.0, x.1)).collect::<Vec<_>>();
+    unique_sort(&mut pairs);
+    alt_refs.retain(|x| {
+        pairs.binary_search(&(x.0, x.1)).is_err()
+            || vrefs.binary_search(&x.2.to_ascii_vec()).is_ok()
+            || alleles.iter().any(|y| (x.0, x.1, &x.2) == (y.0, y.1, &y.2))
+    });
+    alleles.retain(|y| {
+        !alt_refs
+            .iter()
+            .any(|x| (x.0, x.1, &x.2) == (y.0, y.1, &y.2))
+    });
+    alt_refs.append(&mut alleles);
+    alt_refs.sort();
+    alt_refs.dedup_by(|a, b| (a.0, a.1, &a.2) == (b.0, b.1, &b.2));
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
 // Update reference sequences for V segments by substituting in alt alleles if better.
 // Computational performance dubious because of full alt_refs traversal.
 
@@ -598,3 +735,78 @@ pub fn sub_alts(
     }
     ctl.perf_stats(&t, "substituting alt alleles");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use string_utils::{strme, TextUtils};
+
+    #[test]
+    fn test_donor_alleles_round_trip() {
+        let dna = |s: &str| DnaString::from_dna_string(s);
+
+        // Reference V segments 1 and 2, and computed alleles for donors 0 and 1.  The second
+        // allele of segment 1 for donor 0 is identical to segment 2.
+
+        let vrefs = vec![b"AAAAAA".to_vec(), b"AAAACC".to_vec()];
+        let mut computed = vec![
+            (0, 1, dna("AAAAAT"), 5, false),
+            (0, 1, dna("AAAACC"), 3, false),
+            (0, 2, dna("GAAACC"), 2, false),
+            (1, 1, dna("AAATAA"), 4, false),
+        ];
+        computed.sort();
+
+        // Write the novel alleles as NOVEL_ALLELES_FASTA does, numbering them within each donor
+        // and segment, and read them back in.
+
+        let names = ["d1", "d2"];
+        let mut text = String::new();
+        let mut number = 0;
+        for (i, x) in computed.iter().enumerate() {
+            if i > 0 && (x.0, x.1) != (computed[i - 1].0, computed[i - 1].1) {
+                number = 0;
+            }
+            number += 1;
+            let seq = x.2.to_ascii_vec();
+            if vrefs.binary_search(&seq).is_err() {
+                text += &format!(
+                    ">{}:{}:{}:V{} nearest=V{}\n{}\n",
+                    x.1 + 100,
+                    names[x.0],
+                    number,
+                    x.1,
+                    x.1,
+                    strme(&seq)
+                );
+            }
+        }
+        let read = |text: &str| {
+            let mut alleles = Vec::new();
+            for (_, fields, seq) in parse_donor_alleles(text, "f").unwrap().iter() {
+                let donor = names.iter().position(|n| *n == fields[1]).unwrap();
+                let id = fields[0].force_usize() - 100;
+                alleles.push((donor, id, dna(seq), 0, false));
+            }
+            alleles
+        };
+        let mut alt_refs = computed.clone();
+        merge_donor_alleles(&mut alt_refs, read(&text), &vrefs);
+        assert!(alt_refs == computed);
+
+        // A changed allele replaces only the alleles for its donor and segment.
+
+        let mut alt_refs = computed.clone();
+        merge_donor_alleles(&mut alt_refs, read(">101:d1:1:V1\nAAAAAG\n"), &vrefs);
+        let mut expected = vec![
+            (0, 1, dna("AAAAAG"), 0, false),
+            (0, 1, dna("AAAACC"), 3, false),
+            (0, 2, dna("GAAACC"), 2, false),
+            (1, 1, dna("AAATAA"), 4, false),
+        ];
+        expected.sort();
+        assert!(alt_refs == expected);
+        assert!(parse_donor_alleles("ACGT\n", "f").is_err());
+        assert!(parse_donor_alleles(">101:d1:V1\nACGT\n", "f").is_err());
+    }
+}
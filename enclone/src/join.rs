@@ -38,6 +38,8 @@ pub fn join_exacts(
     raw_joins: &mut Vec<(i32, i32)>,
     sr: &Vec<Vec<Double>>,
     dref: &Vec<DonorReferenceItem>,
+    known: &Vec<bool>,
+    saved_joins: &Vec<(usize, usize)>,
 ) -> EquivRel {
     //
     // Run special option for joining by barcode identity.
@@ -140,6 +142,7 @@ pub fn join_exacts(
             &mut pot,
             &refdata,
             dref,
+            known,
         );
 
         // Run two passes.
@@ -607,5 +610,13 @@ pub fn join_exacts(
             raw_joins.push((results[l].5[j].0 as i32, results[l].5[j].1 as i32));
         }
     }
-    finish_join(ctl, info, &results, join_info)
+    let mut eq = finish_join(ctl, info, &results, join_info);
+
+    // Add joins from a saved join state (LOAD_JOIN).
+
+    for (k1, k2) in saved_joins.iter() {
+        raw_joins.push((*k1 as i32, *k2 as i32));
+        eq.join(*k1 as i32, *k2 as i32);
+    }
+    eq
 }
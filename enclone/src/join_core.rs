@@ -20,6 +20,7 @@ pub fn join_core(
     pot: &mut Vec<PotentialJoin>,
     refdata: &RefData,
     dref: &Vec<DonorReferenceItem>,
+    known: &Vec<bool>,
 ) {
     let mut eq: EquivRel = EquivRel::new((j - i) as i32);
     for k1 in i..j {
@@ -33,6 +34,12 @@ pub fn join_core(
             if !ctl.force && (eq.class_id((k1 - i) as i32) == eq.class_id((k2 - i) as i32)) {
                 continue;
             }
+
+            // Do nothing if both were joined in a saved join state (LOAD_JOIN).
+
+            if known[k1] && known[k2] {
+                continue;
+            }
             if join_one(
                 is_bcr,
                 k1,
@@ -0,0 +1,460 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// This file provides functions to save and restore the state of joining (options SAVE_JOIN and
+// LOAD_JOIN), so that datasets can be added to an existing analysis without repeating all the
+// work.  The state consists of the datasets and donors, the donor reference alleles, the exact
+// subclonotypes together with the identifiers of their clonotypes, and the raw joins between
+// exact subclonotypes.
+//
+// Exact subclonotypes are identified by their content (donor, and for each chain, the chain type,
+// V..J sequence and constant region name).  This allows them to be found again if cells from new
+// datasets are added to them.  An exact subclonotype whose content is not unique is not saved.
+//
+// The file is tab-delimited text, and the first field of each line is the record type.
+
+use debruijn::dna_string::DnaString;
+use enclone_core::defs::{CloneInfo, EncloneControl, ExactClonotype};
+use io_utils::{fwriteln, open_for_read};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use string_utils::strme;
+use vdj_ann::refx::RefData;
+use vector_utils::unique_sort;
+
+const HEADER: &str = "enclone join state 1";
+
+#[derive(Default)]
+pub struct JoinState {
+    pub datasets: Vec<String>,
+    pub donors: Vec<String>,
+    // {(donor, ref id, ref name, alt seq, support, is_ref)}:
+    pub alleles: Vec<(String, usize, String, String, usize, bool)>,
+    pub exacts: HashMap<String, usize>, // map exact subclonotype key to clonotype id
+    pub joins: Vec<(String, String)>,   // raw joins, as pairs of keys
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Compute the keys of exact subclonotypes.  The key is None if the exact subclonotype has no
+// cells or its key is not unique.
+
+pub fn exact_keys(
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    exact_clonotypes: &Vec<ExactClonotype>,
+) -> Vec<Option<String>> {
+    let mut keys = Vec::<Option<String>>::new();
+    let mut count = HashMap::<String, usize>::new();
+    for ex in exact_clonotypes.iter() {
+        if ex.clones.is_empty() {
+            keys.push(None);
+            continue;
+        }
+        let mut key = String::new();
+        if let Some(d) = ex.clones[0][0].donor_index {
+            key += &ctl.origin_info.donor_list[d];
+        }
+        for x in ex.share.iter() {
+            let c = x.c_ref_id.map(|c| refdata.name[c].as_str()).unwrap_or("");
+            key += &format!(";{}:{}:{}", x.chain_type, strme(&x.seq), c);
+        }
+        *count.entry(key.clone()).or_insert(0) += 1;
+        keys.push(Some(key));
+    }
+    for key in keys.iter_mut() {
+        if key.is_some() && count[key.as_ref().unwrap()] > 1 {
+            *key = None;
+        }
+    }
+    keys
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Read the join state file given by LOAD_JOIN.
+
+pub fn read_join_state(ctl: &EncloneControl) -> Result<Option<JoinState>, String> {
+    if ctl.join_alg_opt.load_join.is_empty() {
+        return Ok(None);
+    }
+    let f = open_for_read![&ctl.join_alg_opt.load_join];
+    Ok(Some(parse_join_state(f, &ctl.join_alg_opt.load_join)?))
+}
+
+fn parse_join_state<R: BufRead>(f: R, path: &str) -> Result<JoinState, String> {
+    let mut state = JoinState::default();
+    for (i, line) in f.lines().enumerate() {
+        let s = line.map_err(|e| format!("\nUnable to read {}: {}.\n", path, e))?;
+        if i == 0 {
+            if s != HEADER {
+                return Err(format!(
+                    "\nThe file {} does not appear to be a join state file saved by \
+                    SAVE_JOIN.\n",
+                    path
+                ));
+            }
+            continue;
+        }
+        let fields = s.split('\t').collect::<Vec<&str>>();
+        let bad = || {
+            format!(
+                "\nLine {} of the join state file {} is malformed.\n",
+                i + 1,
+                path
+            )
+        };
+        match fields[0] {
+            "dataset" if fields.len() == 2 => state.datasets.push(fields[1].to_string()),
+            "donor" if fields.len() == 2 => state.donors.push(fields[1].to_string()),
+            "allele" if fields.len() == 7 => {
+                let ref_id = fields[2].parse::<usize>().map_err(|_| bad())?;
+                let support = fields[5].parse::<usize>().map_err(|_| bad())?;
+                state.alleles.push((
+                    fields[1].to_string(),
+                    ref_id,
+                    fields[3].to_string(),
+                    fields[4].to_string(),
+                    support,
+                    fields[6] == "true",
+                ));
+            }
+            "exact" if fields.len() == 3 => {
+                let id = fields[1].parse::<usize>().map_err(|_| bad())?;
+                state.exacts.insert(fields[2].to_string(), id);
+            }
+            "join" if fields.len() == 3 => {
+                state
+                    .joins
+                    .push((fields[1].to_string(), fields[2].to_string()));
+            }
+            _ => return Err(bad()),
+        }
+    }
+    Ok(state)
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Return the sorted indices in donor_list of the donors in the saved state.  Alternate alleles
+// are not computed for these donors, but rather restored from the saved state.
+
+pub fn saved_donors(ctl: &EncloneControl, state: &JoinState) -> Vec<usize> {
+    let mut saved = Vec::<usize>::new();
+    for d in state.donors.iter() {
+        if let Some(i) = ctl.origin_info.donor_list.iter().position(|x| x == d) {
+            saved.push(i);
+        }
+    }
+    unique_sort(&mut saved);
+    saved
+}
+
+// Replace the alternate alleles for donors in the saved state by the saved alleles.  Donors that
+// are new get the alleles that were just computed.
+
+pub fn restore_alleles(
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    state: &JoinState,
+    alt_refs: &mut Vec<(usize, usize, DnaString, usize, bool)>,
+) -> Result<(), String> {
+    let mut to_donor = HashMap::<&str, usize>::new();
+    for (i, d) in ctl.origin_info.donor_list.iter().enumerate() {
+        to_donor.insert(d.as_str(), i);
+    }
+    let saved = saved_donors(ctl, state);
+    alt_refs.retain(|x| saved.binary_search(&x.0).is_err());
+    for x in state.alleles.iter() {
+        if let Some(donor) = to_donor.get(x.0.as_str()) {
+            if x.1 >= refdata.refs.len() || refdata.name[x.1] != x.2 {
+                return Err(format!(
+                    "\nThe join state file {} refers to the reference sequence {}, which is not \
+                    in the reference now being used.  The same reference must be used for all \
+                    runs.\n",
+                    ctl.join_alg_opt.load_join, x.2
+                ));
+            }
+            alt_refs.push((*donor, x.1, DnaString::from_dna_string(&x.3), x.4, x.5));
+        }
+    }
+    alt_refs.sort();
+    Ok(())
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Find the info entries whose exact subclonotypes are in the saved state, and translate the
+// saved joins into pairs of info indices.  Pairs of known info entries need not be compared
+// again.
+
+pub fn saved_joins(
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    state: &Option<JoinState>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    info: &Vec<CloneInfo>,
+    known: &mut Vec<bool>,
+    joins: &mut Vec<(usize, usize)>,
+) {
+    *known = vec![false; info.len()];
+    joins.clear();
+    if state.is_none() {
+        return;
+    }
+    let state = state.as_ref().unwrap();
+    let keys = exact_keys(ctl, refdata, exact_clonotypes);
+    let mut to_info = HashMap::<&str, usize>::new();
+    for i in 0..info.len() {
+        if let Some(key) = &keys[info[i].clonotype_index] {
+            if state.exacts.contains_key(key) {
+                known[i] = true;
+                to_info.entry(key.as_str()).or_insert(i);
+            }
+        }
+    }
+    for (k1, k2) in state.joins.iter() {
+        if let (Some(i1), Some(i2)) = (to_info.get(k1.as_str()), to_info.get(k2.as_str())) {
+            joins.push((*i1, *i2));
+        }
+    }
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Assign a join id to each clonotype, and record it in its exact subclonotypes.
+
+pub fn assign_join_ids(
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    state: &Option<JoinState>,
+    info: &Vec<CloneInfo>,
+    orbits: &Vec<Vec<i32>>,
+    exact_clonotypes: &mut Vec<ExactClonotype>,
+) {
+    if ctl.join_alg_opt.save_join.is_empty() && state.is_none() {
+        return;
+    }
+    let keys = exact_keys(ctl, refdata, exact_clonotypes);
+    let mut clonotypes = Vec::<Vec<usize>>::new();
+    for o in orbits.iter() {
+        let mut exacts = Vec::<usize>::new();
+        for id in o.iter() {
+            exacts.push(info[*id as usize].clonotype_index);
+        }
+        unique_sort(&mut exacts);
+        clonotypes.push(exacts);
+    }
+    let empty = HashMap::<String, usize>::new();
+    let saved = state.as_ref().map(|s| &s.exacts).unwrap_or(&empty);
+    let ids = choose_join_ids(&clonotypes, &keys, saved);
+    for i in 0..clonotypes.len() {
+        for u in clonotypes[i].iter() {
+            exact_clonotypes[*u].join_id = Some(ids[i]);
+        }
+    }
+}
+
+// Choose join ids, given the exact subclonotypes of each clonotype, the keys of the exact
+// subclonotypes, and the saved map from keys to clonotype ids.  A clonotype containing exact
+// subclonotypes from the saved state gets the smallest of their clonotype ids that has not
+// already been used.  Other clonotypes get new ids, which are larger than all saved ids.
+
+fn choose_join_ids(
+    clonotypes: &[Vec<usize>],
+    keys: &[Option<String>],
+    saved: &HashMap<String, usize>,
+) -> Vec<usize> {
+    let mut next = 1 + saved.values().max().unwrap_or(&0);
+    let mut used = HashSet::<usize>::new();
+    let mut join_ids = Vec::<usize>::new();
+    for exacts in clonotypes.iter() {
+        let mut ids = Vec::<usize>::new();
+        for u in exacts.iter() {
+            if let Some(key) = &keys[*u] {
+                if let Some(id) = saved.get(key) {
+                    if !used.contains(id) {
+                        ids.push(*id);
+                    }
+                }
+            }
+        }
+        let id = match ids.iter().min() {
+            Some(id) => *id,
+            None => {
+                next += 1;
+                next - 1
+            }
+        };
+        used.insert(id);
+        join_ids.push(id);
+    }
+    join_ids
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Write the join state file given by SAVE_JOIN.  Entries in the loaded state that do not
+// correspond to anything in this run are carried over unchanged.
+
+pub fn write_join_state(
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    state: &Option<JoinState>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    info: &Vec<CloneInfo>,
+    raw_joins: &Vec<Vec<usize>>,
+    alt_refs: &Vec<(usize, usize, DnaString, usize, bool)>,
+) -> Result<(), String> {
+    if ctl.join_alg_opt.save_join.is_empty() {
+        return Ok(());
+    }
+    let empty = JoinState::default();
+    let old = state.as_ref().unwrap_or(&empty);
+    let f = File::create(&ctl.join_alg_opt.save_join);
+    if f.is_err() {
+        return Err(format!(
+            "\nUnable to create the file {} specified by SAVE_JOIN.\n",
+            ctl.join_alg_opt.save_join
+        ));
+    }
+    let mut f = BufWriter::new(f.unwrap());
+    fwriteln!(f, "{}", HEADER);
+    let mut datasets = old.datasets.clone();
+    for d in ctl.origin_info.dataset_id.iter() {
+        if !datasets.contains(d) {
+            datasets.push(d.clone());
+        }
+    }
+    for d in datasets.iter() {
+        fwriteln!(f, "dataset\t{}", d);
+    }
+    let mut donors = old.donors.clone();
+    donors.append(&mut ctl.origin_info.donor_list.clone());
+    unique_sort(&mut donors);
+    for d in donors.iter() {
+        fwriteln!(f, "donor\t{}", d);
+    }
+    for x in old.alleles.iter() {
+        if !ctl.origin_info.donor_list.contains(&x.0) {
+            fwriteln!(
+                f,
+                "allele\t{}\t{}\t{}\t{}\t{}\t{}",
+                x.0,
+                x.1,
+                x.2,
+                x.3,
+                x.4,
+                x.5
+            );
+        }
+    }
+    for x in alt_refs.iter() {
+        fwriteln!(
+            f,
+            "allele\t{}\t{}\t{}\t{}\t{}\t{}",
+            ctl.origin_info.donor_list[x.0],
+            x.1,
+            refdata.name[x.1],
+            x.2.to_string(),
+            x.3,
+            x.4
+        );
+    }
+
+    // Write exact subclonotypes and joins.
+
+    let keys = exact_keys(ctl, refdata, exact_clonotypes);
+    let mut present = HashSet::<&str>::new();
+    for u in 0..exact_clonotypes.len() {
+        if let (Some(key), Some(id)) = (&keys[u], exact_clonotypes[u].join_id) {
+            present.insert(key.as_str());
+            fwriteln!(f, "exact\t{}\t{}", id, key);
+        }
+    }
+    for (key, id) in old.exacts.iter() {
+        if !present.contains(key.as_str()) {
+            fwriteln!(f, "exact\t{}\t{}", id, key);
+        }
+    }
+    let mut joins = Vec::<(&str, &str)>::new();
+    for i1 in 0..raw_joins.len() {
+        for i2 in raw_joins[i1].iter() {
+            let (u1, u2) = (info[i1].clonotype_index, info[*i2].clonotype_index);
+            if u1 < u2 && present.contains(keys[u1].as_deref().unwrap_or("")) {
+                if present.contains(keys[u2].as_deref().unwrap_or("")) {
+                    joins.push((keys[u1].as_deref().unwrap(), keys[u2].as_deref().unwrap()));
+                }
+            }
+        }
+    }
+    for (k1, k2) in old.joins.iter() {
+        if !present.contains(k1.as_str()) || !present.contains(k2.as_str()) {
+            joins.push((k1.as_str(), k2.as_str()));
+        }
+    }
+    unique_sort(&mut joins);
+    for (k1, k2) in joins.iter() {
+        fwriteln!(f, "join\t{}\t{}", k1, k2);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_join_state() {
+        let text = "enclone join state 1\n\
+                    dataset\t123085\n\
+                    donor\td1\n\
+                    allele\td1\t17\tIGHV3-7\tACGT\t4\ttrue\n\
+                    exact\t3\td1;IGH:ACGT:IGHG1;IGK:TTTT:IGKC\n\
+                    join\tk1\tk2\n";
+        let state = parse_join_state(text.as_bytes(), "state").unwrap();
+        assert_eq!(state.datasets, vec!["123085".to_string()]);
+        assert_eq!(state.donors, vec!["d1".to_string()]);
+        assert_eq!(
+            state.alleles,
+            vec![(
+                "d1".to_string(),
+                17,
+                "IGHV3-7".to_string(),
+                "ACGT".to_string(),
+                4,
+                true
+            )]
+        );
+        assert_eq!(state.exacts["d1;IGH:ACGT:IGHG1;IGK:TTTT:IGKC"], 3);
+        assert_eq!(state.joins, vec![("k1".to_string(), "k2".to_string())]);
+        assert!(parse_join_state("not a join state\n".as_bytes(), "state").is_err());
+        let bad = "enclone join state 1\nexact\tx\tkey\n";
+        assert!(parse_join_state(bad.as_bytes(), "state").is_err());
+    }
+
+    #[test]
+    fn test_choose_join_ids() {
+        let mut saved = HashMap::<String, usize>::new();
+        saved.insert("a".to_string(), 2);
+        saved.insert("b".to_string(), 5);
+        saved.insert("c".to_string(), 3);
+        let keys = vec![
+            Some("a".to_string()),
+            Some("b".to_string()),
+            Some("c".to_string()),
+            None,
+            Some("d".to_string()),
+        ];
+
+        // The first clonotype merges saved clonotypes 2 and 5 and keeps 2.  The second contains
+        // saved clonotype 3.  The third has only new exact subclonotypes, and the fourth again
+        // contains saved clonotype 2, which has been used, so both get new ids.
+
+        let clonotypes = vec![vec![0, 1], vec![2, 3], vec![4], vec![0]];
+        assert_eq!(
+            choose_join_ids(&clonotypes, &keys, &saved),
+            vec![2, 3, 6, 7]
+        );
+    }
+}
@@ -7,6 +7,7 @@ pub mod innate;
 pub mod join;
 pub mod join2;
 pub mod join_core;
+pub mod join_state;
 pub mod misc1;
 pub mod misc2;
 pub mod misc3;
@@ -256,6 +256,7 @@ pub fn create_exact_subclonotype_core(
                     frac_reads_used: tig_bc[t][m].frac_reads_used,
                     marked: false,
                     v_ref_id: tig_bc[t][m].v_ref_id,
+                    productive: tig_bc[t][m].productive,
                 });
             }
             clones.push(x);
@@ -415,7 +416,12 @@ pub fn find_exact_subclonotypes(
             || (ctl.join_alg_opt.basic_h.is_none() && !ctl.gen_opt.pre_eval)
         {
             if !clones.is_empty() {
-                res.1.push(ExactClonotype { share, clones });
+                res.1.push(ExactClonotype {
+                    share,
+                    clones,
+                    join_id: None,
+                    doublet_score: None,
+                });
             }
         }
     });
@@ -460,6 +466,8 @@ pub fn find_exact_subclonotypes(
                     exacts2.push(ExactClonotype {
                         share: ex.share.clone(),
                         clones: vec![ex.clones[j].clone()],
+                        join_id: None,
+                        doublet_score: None,
                     });
                 }
             } else {
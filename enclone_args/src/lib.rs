@@ -2,7 +2,9 @@
 
 use io_utils::*;
 
+pub mod load_embedding;
 pub mod load_gex;
+pub mod load_gex_alt;
 pub mod load_gex_core;
 pub mod load_gex_util;
 pub mod proc_args;
@@ -12,7 +14,10 @@ pub mod proc_args_check;
 pub mod proc_args_post;
 pub mod process_special_arg1;
 pub mod process_special_arg2;
+pub mod read_airr;
 pub mod read_json;
+pub mod read_util;
+pub mod vdj_cache;
 
 // parse_csv_pure: same as parse_csv, but don't strip out quotes
 
@@ -0,0 +1,167 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+//
+// Load two-dimensional embeddings (UMAP and t-SNE) of gene expression cells.  These come from
+// Cell Ranger projection files, from an AnnData file, or from a CSV file specified by the
+// EMBEDDING option.  Values from the last override the others, but only for the barcodes that
+// appear in it; other cells keep the coordinates from Cell Ranger or AnnData.
+
+use std::collections::HashMap;
+use string_utils::parse_csv;
+
+// Parse a Cell Ranger projection file, having lines barcode,x,y after a header line.
+
+pub fn parse_projection(
+    contents: &str,
+    source: &str,
+) -> Result<HashMap<String, (f64, f64)>, String> {
+    let mut proj = HashMap::<String, (f64, f64)>::new();
+    for (i, line) in contents.lines().enumerate() {
+        if i == 0 || line.is_empty() {
+            continue;
+        }
+        let fields = line.split(',').collect::<Vec<&str>>();
+        let xy = fields
+            .iter()
+            .skip(1)
+            .map(|v| v.parse::<f64>())
+            .collect::<Vec<_>>();
+        if fields.len() != 3 || xy.iter().any(|v| v.is_err()) {
+            return Err(format!(
+                "\nLine {} of the projection file\n{}\nis not of the form barcode,x,y.\n",
+                i + 1,
+                source
+            ));
+        }
+        proj.insert(
+            fields[0].to_string(),
+            (*xy[0].as_ref().unwrap(), *xy[1].as_ref().unwrap()),
+        );
+    }
+    Ok(proj)
+}
+
+// Parse the CSV file given by EMBEDDING.  It must have a barcode field, and one or both of the
+// field pairs umap_x,umap_y and tsne_x,tsne_y.  If there is more than one dataset, there must
+// also be a dataset field, whose values are dataset names.  Empty values are allowed.
+
+pub fn parse_embedding_csv(
+    contents: &str,
+    source: &str,
+    dataset_ids: &[String],
+    umap: &mut [HashMap<String, (f64, f64)>],
+    tsne: &mut [HashMap<String, (f64, f64)>],
+) -> Result<(), String> {
+    let lines = contents.lines().collect::<Vec<&str>>();
+    if lines.is_empty() {
+        return Err(format!("\nThe EMBEDDING file\n{}\nis empty.\n", source));
+    }
+    let header = parse_csv(lines[0]);
+    let pos = |name: &str| header.iter().position(|f| f == name);
+    let (bc_pos, ds_pos) = (pos("barcode"), pos("dataset"));
+    if bc_pos.is_none() {
+        return Err(format!(
+            "\nThe EMBEDDING file\n{}\ndoes not have a barcode field.\n",
+            source
+        ));
+    }
+    if ds_pos.is_none() && dataset_ids.len() > 1 {
+        return Err(format!(
+            "\nThe EMBEDDING file\n{}\ndoes not have a dataset field, which is required \
+            because there is more than one dataset.\n",
+            source
+        ));
+    }
+    let mut pairs = Vec::<(usize, usize, bool)>::new();
+    for (method, is_umap) in [("umap", true), ("tsne", false)] {
+        let (x, y) = (pos(&format!("{}_x", method)), pos(&format!("{}_y", method)));
+        if x.is_some() != y.is_some() {
+            return Err(format!(
+                "\nThe EMBEDDING file\n{}\nhas only one of the fields {}_x and {}_y.\n",
+                source, method, method
+            ));
+        }
+        if let (Some(x), Some(y)) = (x, y) {
+            pairs.push((x, y, is_umap));
+        }
+    }
+    if pairs.is_empty() {
+        return Err(format!(
+            "\nThe EMBEDDING file\n{}\nhas neither the fields umap_x,umap_y nor the fields \
+            tsne_x,tsne_y.\n",
+            source
+        ));
+    }
+    for i in 1..lines.len() {
+        if lines[i].is_empty() {
+            continue;
+        }
+        let fields = parse_csv(lines[i]);
+        if fields.len() != header.len() {
+            return Err(format!(
+                "\nLine {} of the EMBEDDING file\n{}\ndoes not have the same number of fields \
+                as the header line.\n",
+                i + 1,
+                source
+            ));
+        }
+        let mut li = 0;
+        if let Some(p) = ds_pos {
+            match dataset_ids.iter().position(|d| *d == fields[p]) {
+                Some(l) => li = l,
+                None => {
+                    return Err(format!(
+                        "\nLine {} of the EMBEDDING file\n{}\nrefers to the dataset {}, which \
+                        is not one of the datasets.\n",
+                        i + 1,
+                        source,
+                        fields[p]
+                    ));
+                }
+            }
+        }
+        let bc = &fields[bc_pos.unwrap()];
+        for (x, y, is_umap) in pairs.iter() {
+            if fields[*x].is_empty() && fields[*y].is_empty() {
+                continue;
+            }
+            let (vx, vy) = (fields[*x].parse::<f64>(), fields[*y].parse::<f64>());
+            if vx.is_err() || vy.is_err() {
+                return Err(format!(
+                    "\nLine {} of the EMBEDDING file\n{}\nhas a coordinate that is not a \
+                    number.\n",
+                    i + 1,
+                    source
+                ));
+            }
+            let emb = if *is_umap {
+                &mut umap[li]
+            } else {
+                &mut tsne[li]
+            };
+            emb.insert(bc.clone(), (vx.unwrap(), vy.unwrap()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_embedding() {
+        let proj = "Barcode,UMAP-1,UMAP-2\nAAAC-1,1.5,-2\n";
+        let p = parse_projection(proj, "projection.csv").unwrap();
+        assert_eq!(p["AAAC-1"], (1.5, -2.0));
+        assert!(parse_projection("Barcode,UMAP-1\nAAAC-1,1.5\n", "projection.csv").is_err());
+
+        let ids = vec!["d1".to_string(), "d2".to_string()];
+        let (mut umap, mut tsne) = (vec![HashMap::new(); 2], vec![HashMap::new(); 2]);
+        let csv = "dataset,barcode,tsne_x,tsne_y\nd2,AAAC-1,3,4\nd1,AAAG-1,,\n";
+        parse_embedding_csv(csv, "e.csv", &ids, &mut umap, &mut tsne).unwrap();
+        assert!(umap[1].is_empty() && tsne[0].is_empty());
+        assert_eq!(tsne[1]["AAAC-1"], (3.0, 4.0));
+        let csv = "barcode,umap_x,umap_y\nAAAC-1,3,4\n";
+        assert!(parse_embedding_csv(csv, "e.csv", &ids, &mut umap, &mut tsne).is_err());
+    }
+}
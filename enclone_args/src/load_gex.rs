@@ -1,9 +1,12 @@
 // Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
 //
 // Load gene expression and feature barcoding (antibody, antigen) data from
-// Cell Ranger outputs.
+// Cell Ranger outputs, or gene expression data from an AnnData file or MEX directory.
 
+use crate::load_embedding::parse_embedding_csv;
 use crate::load_gex_core::load_gex;
+use enclone_core::antigen::find_antigen_features;
+use enclone_core::cell_type::{marker_cell_type, marker_feature_ids};
 use enclone_core::defs::{EncloneControl, GexInfo};
 
 #[cfg(target_os = "windows")]
@@ -12,8 +15,9 @@ use hdf5::Dataset;
 use hdf5x::Dataset;
 
 use mirror_sparse_matrix::MirrorSparseMatrix;
+use ndarray::s;
 use rayon::prelude::*;
-use std::{collections::HashMap, time::Instant};
+use std::{collections::HashMap, fs::read_to_string, time::Instant};
 use vector_utils::{bin_position, unique_sort};
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -38,6 +42,8 @@ pub fn get_gex_info(ctl: &mut EncloneControl) -> Result<GexInfo, String> {
     let mut cell_type = Vec::<HashMap<String, String>>::new();
     let mut cell_type_specified = Vec::<bool>::new();
     let mut pca = Vec::<HashMap<String, Vec<f64>>>::new();
+    let mut umap = Vec::<HashMap<String, (f64, f64)>>::new();
+    let mut tsne = Vec::<HashMap<String, (f64, f64)>>::new();
     let mut gex_mults = Vec::<f64>::new();
     let mut fb_mults = Vec::<f64>::new();
     let mut gex_cell_barcodes = Vec::<Vec<String>>::new();
@@ -66,6 +72,8 @@ pub fn get_gex_info(ctl: &mut EncloneControl) -> Result<GexInfo, String> {
         &mut cell_type,
         &mut cell_type_specified,
         &mut pca,
+        &mut umap,
+        &mut tsne,
         &mut gex_mults,
         &mut fb_mults,
         &mut gex_cell_barcodes,
@@ -77,12 +85,26 @@ pub fn get_gex_info(ctl: &mut EncloneControl) -> Result<GexInfo, String> {
         &mut metrics,
     )?;
     let t = Instant::now();
-    if ctl.gen_opt.gene_scan_test.is_some() && !ctl.gen_opt.accept_inconsistent {
+    if !ctl.gen_opt.embedding.is_empty() {
+        let f = &ctl.gen_opt.embedding;
+        let contents =
+            read_to_string(f).map_err(|e| format!("\nUnable to read {}: {}.\n", f, e))?;
+        parse_embedding_csv(
+            &contents,
+            f,
+            &ctl.origin_info.dataset_id,
+            &mut umap,
+            &mut tsne,
+        )?;
+    }
+    if (ctl.gen_opt.gene_scan_test.is_some() || ctl.gen_opt.diff_exp)
+        && !ctl.gen_opt.accept_inconsistent
+    {
         let mut allf = gex_features.clone();
         unique_sort(&mut allf);
         if allf.len() != 1 {
             let mut msg = format!(
-                "\nCurrently, SCAN requires that all datasets have identical \
+                "\nCurrently, SCAN and DIFF_EXP require that all datasets have identical \
                  features, and they do not.\n\
                 There are {} datasets and {} feature sets after removal of \
                  duplicates.\n",
@@ -105,7 +127,7 @@ pub fn get_gex_info(ctl: &mut EncloneControl) -> Result<GexInfo, String> {
         let gex_outs = &ctl.origin_info.gex_path;
         for i in 0..ctl.origin_info.dataset_path.len() {
             // let bin_file = format!("{}/feature_barcode_matrix.bin", gex_outs[i]);
-            if !gex_outs[i].is_empty()
+            if !gex_outs[i].is_empty() && !h5_paths[i].is_empty()
             /* && !(path_exists(&bin_file) && !ctl.gen_opt.force_h5) */
             {
                 let f = &h5_paths[i];
@@ -162,6 +184,109 @@ pub fn get_gex_info(ctl: &mut EncloneControl) -> Result<GexInfo, String> {
             }
         }
     }
+    // Compute cell types from marker genes, for datasets lacking celltypes.csv.
+
+    if ctl.gen_opt.marker_cell_types {
+        for li in 0..gex_features.len() {
+            if cell_type_specified[li] || gex_features[li].is_empty() {
+                continue;
+            }
+            let ids = marker_feature_ids(&gex_features[li]);
+
+            // The whole matrix arrays are read, unless H5_SLICE is specified, in which case
+            // entries are read per cell.
+
+            let (mut d, mut ind) = (Vec::<u32>::new(), Vec::<u32>::new());
+            let mut readers = None;
+            if !gex_matrices[li].initialized() {
+                if li >= h5_data.len() || h5_data[li].is_none() {
+                    if ctl.clono_filt_opt_def.marked_b
+                        || ctl.gen_opt.mark_stats
+                        || ctl.gen_opt.mark_stats2
+                    {
+                        return Err(format!(
+                            "\nMARKED_B, MARK_STATS and MARK_STATS2 require cell types, which \
+                             are computed from gene expression data,\nbut no gene expression \
+                             matrix was found for dataset {}.\n",
+                            ctl.origin_info.dataset_id[li]
+                        ));
+                    }
+                    continue;
+                }
+                let h5_err = |e| format!("\nFailed to read gene expression data: {}.\n", e);
+                let d_reader = h5_data[li].as_ref().unwrap().as_reader();
+                let ind_reader = h5_indices[li].as_ref().unwrap().as_reader();
+                if ctl.gen_opt.h5_pre {
+                    d = d_reader.read_raw().map_err(h5_err)?;
+                    ind = ind_reader.read_raw().map_err(h5_err)?;
+                } else {
+                    readers = Some((d_reader, ind_reader));
+                }
+            }
+            let types = (0..gex_cell_barcodes[li].len())
+                .into_par_iter()
+                .map(|k| -> Result<String, String> {
+                    let p = bin_position(&gex_barcodes[li], &gex_cell_barcodes[li][k]);
+                    if p < 0 {
+                        return Ok(String::new());
+                    }
+                    let p = p as usize;
+                    let mut counts = Vec::<(usize, usize)>::new();
+                    if gex_matrices[li].initialized() {
+                        counts = gex_matrices[li].row(p);
+                    } else {
+                        let z1 = h5_indptr[li][p] as usize;
+                        let z2 = h5_indptr[li][p + 1] as usize;
+                        if let Some((d_reader, ind_reader)) = readers.as_ref() {
+                            let h5_err =
+                                |e| format!("\nFailed to read gene expression data: {}.\n", e);
+                            let d: Vec<u32> =
+                                d_reader.read_slice(s![z1..z2]).map_err(h5_err)?.to_vec();
+                            let ind: Vec<u32> =
+                                ind_reader.read_slice(s![z1..z2]).map_err(h5_err)?.to_vec();
+                            for j in 0..d.len() {
+                                counts.push((ind[j] as usize, d[j] as usize));
+                            }
+                        } else {
+                            for j in z1..z2 {
+                                counts.push((ind[j] as usize, d[j] as usize));
+                            }
+                        }
+                    }
+                    counts.retain(|x| is_gex[li][x.0]);
+                    Ok(marker_cell_type(&counts, &ids))
+                })
+                .collect::<Result<Vec<String>, String>>()?;
+            for k in 0..types.len() {
+                if !types[k].is_empty() {
+                    cell_type[li].insert(gex_cell_barcodes[li][k].clone(), types[k].clone());
+                }
+            }
+        }
+    }
+    let mut antigen_names = Vec::<String>::new();
+    let mut antigen_fids = Vec::<(Vec<usize>, Vec<usize>)>::new();
+    if !ctl.gen_opt.antigen_controls.is_empty() {
+        for i in 0..gex_features.len() {
+            if gex_features[i].is_empty() {
+                antigen_fids.push((Vec::new(), Vec::new()));
+                continue;
+            }
+            let (names, ids, controls) = find_antigen_features(
+                &gex_features[i],
+                &ctl.gen_opt.antigens,
+                &ctl.gen_opt.antigen_controls,
+            )?;
+            if !antigen_names.is_empty() && names != antigen_names {
+                return Err("\nThe antigens are not the same for all datasets.\n".to_string());
+            }
+            antigen_names = names;
+            antigen_fids.push((ids, controls));
+        }
+        if antigen_names.is_empty() {
+            return Err("\nANTIGEN_CONTROLS requires feature barcode data.\n".to_string());
+        }
+    }
     ctl.perf_stats(&t, "after load_gex");
 
     // Answer.
@@ -184,6 +309,10 @@ pub fn get_gex_info(ctl: &mut EncloneControl) -> Result<GexInfo, String> {
         cell_type,
         cell_type_specified,
         pca,
+        umap,
+        tsne,
+        antigen_names,
+        antigen_fids,
         gex_cell_barcodes,
         gex_mults,
         fb_mults,
@@ -0,0 +1,438 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+//
+// Load gene expression data that are not laid out as Cell Ranger outs, as is the case for data
+// that have been processed in scanpy or Seurat and then exported.  Two forms are supported:
+// 1. an AnnData file (.h5ad);
+// 2. a MEX directory, containing matrix.mtx, features.tsv (or genes.tsv) and barcodes.tsv, any
+//    of which may be gzipped.
+// Such data are assumed to be filtered, so every barcode is treated as a cell.  Counts must be
+// integers.  For an AnnData file, they are taken from layers/counts, raw/X or X, whichever is
+// found first, and specified obs columns are returned, so that they can be used as per-barcode
+// variables, as are UMAP and t-SNE coordinates from obsm.
+
+use flate2::read::MultiGzDecoder;
+use io_utils::path_exists;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Read;
+
+#[cfg(target_os = "windows")]
+use hdf5::{
+    types::{VarLenAscii, VarLenUnicode},
+    Dataset, Group,
+};
+#[cfg(not(target_os = "windows"))]
+use hdf5x::{
+    types::{VarLenAscii, VarLenUnicode},
+    Dataset, Group,
+};
+
+#[derive(Default)]
+pub struct AltGex {
+    pub barcodes: Vec<String>,                       // sorted
+    pub features: Vec<String>,                       // id, name and feature type, tab-separated
+    pub matrix: Vec<Vec<(i32, i32)>>,                // for each barcode, (feature index, count)
+    pub obs: Vec<(String, HashMap<String, String>)>, // obs column name, barcode --> value
+    pub umap: HashMap<String, (f64, f64)>,           // barcode --> UMAP coordinates
+    pub tsne: HashMap<String, (f64, f64)>,           // barcode --> t-SNE coordinates
+}
+
+// Determine if a GEX path is an AnnData file or a MEX directory.
+
+pub fn is_alt_gex(path: &str) -> bool {
+    path.ends_with(".h5ad") || mex_file(path, "matrix.mtx").is_some()
+}
+
+fn mex_file(dir: &str, name: &str) -> Option<String> {
+    for f in [format!("{}/{}", dir, name), format!("{}/{}.gz", dir, name)] {
+        if path_exists(&f) {
+            return Some(f);
+        }
+    }
+    None
+}
+
+fn read_maybe_gz(path: &str) -> Result<String, String> {
+    let f = File::open(path).map_err(|e| format!("\nUnable to open {}: {}.\n", path, e))?;
+    let mut s = String::new();
+    let r = if path.ends_with(".gz") {
+        MultiGzDecoder::new(f).read_to_string(&mut s)
+    } else {
+        let mut f = f;
+        f.read_to_string(&mut s)
+    };
+    r.map_err(|e| format!("\nUnable to read {}: {}.\n", path, e))?;
+    Ok(s)
+}
+
+// Sort the barcodes, permuting the matrix rows accordingly, and sort each row by feature.
+
+fn sort_alt_gex(x: &mut AltGex, source: &str) -> Result<(), String> {
+    let mut ids = (0..x.barcodes.len()).collect::<Vec<usize>>();
+    ids.sort_by(|a, b| x.barcodes[*a].cmp(&x.barcodes[*b]));
+    for i in 1..ids.len() {
+        if x.barcodes[ids[i]] == x.barcodes[ids[i - 1]] {
+            return Err(format!(
+                "\nThe barcode {} appears more than once in {}.\n",
+                x.barcodes[ids[i]], source
+            ));
+        }
+    }
+    let mut barcodes = Vec::<String>::new();
+    let mut matrix = Vec::<Vec<(i32, i32)>>::new();
+    for i in ids.iter() {
+        barcodes.push(std::mem::take(&mut x.barcodes[*i]));
+        let mut row = std::mem::take(&mut x.matrix[*i]);
+        row.sort_unstable();
+        matrix.push(row);
+    }
+    x.barcodes = barcodes;
+    x.matrix = matrix;
+    Ok(())
+}
+
+fn count(v: f64, source: &str) -> Result<i32, String> {
+    if (v - v.round()).abs() > 1e-6 || v < 0.0 {
+        return Err(format!(
+            "\nThe matrix in {} contains the value {}, but enclone requires raw UMI counts, \
+            which are nonnegative integers.\n",
+            source, v
+        ));
+    }
+    Ok(v.round() as i32)
+}
+
+// Parse the three files in a MEX directory.  The matrix has a row for each feature and a
+// column for each barcode.
+
+pub fn parse_mex(
+    mtx: &str,
+    features: &str,
+    barcodes: &str,
+    source: &str,
+) -> Result<AltGex, String> {
+    let mut x = AltGex::default();
+    for line in barcodes.lines() {
+        if !line.is_empty() {
+            x.barcodes
+                .push(line.split('\t').next().unwrap().to_string());
+        }
+    }
+    for line in features.lines() {
+        if line.is_empty() {
+            continue;
+        }
+        let f = line.split('\t').collect::<Vec<&str>>();
+        let name = if f.len() >= 2 { f[1] } else { f[0] };
+        let ftype = if f.len() >= 3 {
+            f[2]
+        } else {
+            "Gene Expression"
+        };
+        x.features.push(format!("{}\t{}\t{}", f[0], name, ftype));
+    }
+    x.matrix = vec![Vec::new(); x.barcodes.len()];
+    let mut header = true;
+    for line in mtx.lines() {
+        if line.starts_with('%') || line.trim().is_empty() {
+            continue;
+        }
+        let f = line.split_whitespace().collect::<Vec<&str>>();
+        let bad = || {
+            format!(
+                "\nThe line\n{}\nin the matrix file in {} is invalid.\n",
+                line, source
+            )
+        };
+        if f.len() != 3 {
+            return Err(bad());
+        }
+        if header {
+            header = false;
+            if f[0].parse::<usize>() != Ok(x.features.len())
+                || f[1].parse::<usize>() != Ok(x.barcodes.len())
+            {
+                return Err(format!(
+                    "\nThe matrix in {} has dimensions {} x {}, which is inconsistent with \
+                    its {} features and {} barcodes.\n",
+                    source,
+                    f[0],
+                    f[1],
+                    x.features.len(),
+                    x.barcodes.len()
+                ));
+            }
+            continue;
+        }
+        let (i, j, v) = (
+            f[0].parse::<usize>(),
+            f[1].parse::<usize>(),
+            f[2].parse::<f64>(),
+        );
+        if i.is_err() || j.is_err() || v.is_err() {
+            return Err(bad());
+        }
+        let (i, j) = (i.unwrap(), j.unwrap());
+        if i == 0 || i > x.features.len() || j == 0 || j > x.barcodes.len() {
+            return Err(bad());
+        }
+        let v = count(v.unwrap(), source)?;
+        if v > 0 {
+            x.matrix[j - 1].push((i as i32 - 1, v));
+        }
+    }
+    sort_alt_gex(&mut x, source)?;
+    Ok(x)
+}
+
+fn load_mex(dir: &str) -> Result<AltGex, String> {
+    let mtx = mex_file(dir, "matrix.mtx").unwrap();
+    let features = mex_file(dir, "features.tsv").or_else(|| mex_file(dir, "genes.tsv"));
+    let barcodes = mex_file(dir, "barcodes.tsv");
+    if features.is_none() || barcodes.is_none() {
+        return Err(format!(
+            "\nThe MEX directory\n{}\nmust contain features.tsv (or genes.tsv) and \
+            barcodes.tsv, either of which may be gzipped.\n",
+            dir
+        ));
+    }
+    parse_mex(
+        &read_maybe_gz(&mtx)?,
+        &read_maybe_gz(&features.unwrap())?,
+        &read_maybe_gz(&barcodes.unwrap())?,
+        dir,
+    )
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+fn h5_err(path: &str, e: impl Display) -> String {
+    format!(
+        "\nencountered error reading AnnData file\n{}\nas follows\n{}\n",
+        path, e
+    )
+}
+
+fn h5ad_strings(ds: &Dataset, path: &str) -> Result<Vec<String>, String> {
+    if let Ok(x) = ds.as_reader().read_raw::<VarLenUnicode>() {
+        return Ok(x.iter().map(|s| s.to_string()).collect());
+    }
+    let x = ds
+        .as_reader()
+        .read_raw::<VarLenAscii>()
+        .map_err(|e| h5_err(path, e))?;
+    Ok(x.iter().map(|s| s.to_string()).collect())
+}
+
+// Read a column of a dataframe (obs or var).  Categorical columns are stored either as a group
+// containing categories and codes, or, in older files, as codes, with the categories in
+// __categories.
+
+fn h5ad_column(g: &Group, name: &str, path: &str) -> Result<Vec<String>, String> {
+    let (cats, codes) = if let Ok(c) = g.group(name) {
+        let cats = c.dataset("categories").map_err(|e| h5_err(path, e))?;
+        (cats, c.dataset("codes").map_err(|e| h5_err(path, e))?)
+    } else {
+        let ds = g.dataset(name).map_err(|e| h5_err(path, e))?;
+        match g.dataset(&format!("__categories/{}", name)) {
+            Ok(cats) => (cats, ds),
+            Err(_) => {
+                if let Ok(x) = h5ad_strings(&ds, path) {
+                    return Ok(x);
+                }
+                let x: Vec<f64> = ds.as_reader().read_raw().map_err(|e| h5_err(path, e))?;
+                return Ok(x.iter().map(|v| format!("{}", v)).collect());
+            }
+        }
+    };
+    let cats = h5ad_strings(&cats, path)?;
+    let codes: Vec<i64> = codes.as_reader().read_raw().map_err(|e| h5_err(path, e))?;
+    let mut x = Vec::<String>::new();
+    for k in codes.iter() {
+        if *k >= 0 && (*k as usize) < cats.len() {
+            x.push(cats[*k as usize].clone());
+        } else {
+            x.push(String::new());
+        }
+    }
+    Ok(x)
+}
+
+// Read the index of a dataframe, whose name is given by the attribute _index.
+
+fn h5ad_index(g: &Group, path: &str) -> Result<Vec<String>, String> {
+    let mut name = "_index".to_string();
+    if let Ok(a) = g.attr("_index") {
+        if let Ok(s) = a.as_reader().read_scalar::<VarLenUnicode>() {
+            name = s.to_string();
+        }
+    }
+    h5ad_column(g, &name, path)
+}
+
+fn h5ad_encoding(g: &Group) -> String {
+    for name in ["encoding-type", "h5sparse_format"] {
+        if let Ok(a) = g.attr(name) {
+            if let Ok(s) = a.as_reader().read_scalar::<VarLenUnicode>() {
+                return s.to_string();
+            }
+        }
+    }
+    String::new()
+}
+
+fn load_h5ad(path: &str, obs_cols: &[String]) -> Result<AltGex, String> {
+    #[cfg(not(target_os = "windows"))]
+    let h = hdf5x::File::open(&path).map_err(|e| h5_err(path, e))?;
+    #[cfg(target_os = "windows")]
+    let h = hdf5::File::open(&path).map_err(|e| h5_err(path, e))?;
+    let mut x = AltGex::default();
+
+    // Read barcodes and requested obs columns.
+
+    let obs = h.group("obs").map_err(|e| h5_err(path, e))?;
+    x.barcodes = h5ad_index(&obs, path)?;
+    for col in obs_cols.iter() {
+        let vals = h5ad_column(&obs, col, path).map_err(|_| {
+            format!(
+                "\nThe obs column {} specified by GEX_OBS could not be read from the AnnData \
+                file\n{}\n",
+                col, path
+            )
+        })?;
+        let mut m = HashMap::<String, String>::new();
+        for i in 0..vals.len().min(x.barcodes.len()) {
+            m.insert(x.barcodes[i].clone(), vals[i].clone());
+        }
+        x.obs.push((col.clone(), m));
+    }
+
+    // Read UMAP and t-SNE coordinates, if present.
+
+    for (key, emb) in [("obsm/X_umap", &mut x.umap), ("obsm/X_tsne", &mut x.tsne)] {
+        if let Ok(ds) = h.dataset(key) {
+            let shape = ds.shape();
+            if shape.len() != 2 || shape[0] != x.barcodes.len() || shape[1] < 2 {
+                return Err(format!(
+                    "\nThe embedding {} in the AnnData file\n{}\nhas unexpected dimensions.\n",
+                    key, path
+                ));
+            }
+            let data: Vec<f64> = ds.as_reader().read_raw().map_err(|e| h5_err(path, e))?;
+            for i in 0..shape[0] {
+                let (px, py) = (data[i * shape[1]], data[i * shape[1] + 1]);
+                emb.insert(x.barcodes[i].clone(), (px, py));
+            }
+        }
+    }
+
+    // Find the counts and read features.
+
+    let mut loc = "X";
+    for l in ["layers/counts", "raw/X"] {
+        if h.group(l).is_ok() || h.dataset(l).is_ok() {
+            loc = l;
+            break;
+        }
+    }
+    let var = if loc == "raw/X" { "raw/var" } else { "var" };
+    let var = h.group(var).map_err(|e| h5_err(path, e))?;
+    let names = h5ad_index(&var, path)?;
+    let ids = h5ad_column(&var, "gene_ids", path).unwrap_or_else(|_| names.clone());
+    let types = h5ad_column(&var, "feature_types", path)
+        .unwrap_or_else(|_| vec!["Gene Expression".to_string(); names.len()]);
+    for j in 0..names.len() {
+        x.features
+            .push(format!("{}\t{}\t{}", ids[j], names[j], types[j]));
+    }
+
+    // Read the matrix, which has a row for each barcode, and is either sparse or dense.
+
+    let (nobs, nvar) = (x.barcodes.len(), x.features.len());
+    x.matrix = vec![Vec::new(); nobs];
+    let source = format!("{}:{}", path, loc);
+    if let Ok(g) = h.group(loc) {
+        let read = |name: &str| -> Result<Vec<f64>, String> {
+            let ds = g.dataset(name).map_err(|e| h5_err(path, e))?;
+            ds.as_reader().read_raw().map_err(|e| h5_err(path, e))
+        };
+        let (data, indices, indptr) = (read("data")?, read("indices")?, read("indptr")?);
+        let csc = h5ad_encoding(&g).contains("csc");
+        let n = if csc { nvar } else { nobs };
+        if indptr.len() != n + 1 || data.len() != indices.len() {
+            return Err(format!(
+                "\nThe sparse matrix {} has inconsistent dimensions.\n",
+                source
+            ));
+        }
+        for i in 0..n {
+            for k in indptr[i] as usize..indptr[i + 1] as usize {
+                let (j, v) = (indices[k] as usize, count(data[k], &source)?);
+                if j >= if csc { nobs } else { nvar } {
+                    return Err(format!(
+                        "\nThe sparse matrix {} has an invalid index.\n",
+                        source
+                    ));
+                }
+                if v > 0 && csc {
+                    x.matrix[j].push((i as i32, v));
+                } else if v > 0 {
+                    x.matrix[i].push((j as i32, v));
+                }
+            }
+        }
+    } else {
+        let ds = h.dataset(loc).map_err(|e| h5_err(path, e))?;
+        if ds.shape() != vec![nobs, nvar] {
+            return Err(format!(
+                "\nThe matrix {} has dimensions inconsistent with obs and var.\n",
+                source
+            ));
+        }
+        let data: Vec<f64> = ds.as_reader().read_raw().map_err(|e| h5_err(path, e))?;
+        for i in 0..nobs {
+            for j in 0..nvar {
+                let v = count(data[i * nvar + j], &source)?;
+                if v > 0 {
+                    x.matrix[i].push((j as i32, v));
+                }
+            }
+        }
+    }
+    sort_alt_gex(&mut x, path)?;
+    Ok(x)
+}
+
+// Load an AnnData file or MEX directory.  The obs columns are only used for AnnData.
+
+pub fn load_alt_gex(path: &str, obs_cols: &[String]) -> Result<AltGex, String> {
+    if path.ends_with(".h5ad") {
+        load_h5ad(path, obs_cols)
+    } else {
+        load_mex(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mex() {
+        let mtx = "%%MatrixMarket matrix coordinate integer general\n%\n3 2 4\n\
+                   1 1 5\n3 1 2\n2 2 1\n3 2 7\n";
+        let features = "ENSG1\tCD3E\tGene Expression\nENSG2\tCD19\tGene Expression\n\
+                        ENSG3\tMS4A1\tGene Expression\n";
+        let barcodes = "TTTC-1\nAAAC-1\n";
+        let x = parse_mex(mtx, features, barcodes, "test").unwrap();
+        assert_eq!(x.barcodes, vec!["AAAC-1".to_string(), "TTTC-1".to_string()]);
+        assert_eq!(x.matrix, vec![vec![(1, 1), (2, 7)], vec![(0, 5), (2, 2)]]);
+        assert_eq!(x.features[1], "ENSG2\tCD19\tGene Expression");
+
+        // Normalized values and inconsistent dimensions are rejected.
+
+        assert!(parse_mex(&mtx.replace("1 1 5", "1 1 0.5"), features, barcodes, "t").is_err());
+        assert!(parse_mex(&mtx.replace("3 2 4", "3 3 4"), features, barcodes, "t").is_err());
+    }
+}
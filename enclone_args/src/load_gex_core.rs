@@ -1,7 +1,10 @@
 // Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
 //
-// Load gene expression and feature barcoding (antibody, antigen) data from Cell Ranger outputs.
+// Load gene expression and feature barcoding (antibody, antigen) data from Cell Ranger outputs,
+// or gene expression data from an AnnData file or MEX directory.
 
+use crate::load_embedding::parse_projection;
+use crate::load_gex_alt::{is_alt_gex, load_alt_gex};
 use crate::load_gex_util::*;
 use crate::*;
 use enclone_core::defs::EncloneControl;
@@ -42,6 +45,8 @@ pub fn load_gex(
     cell_type: &mut Vec<HashMap<String, String>>,
     cell_type_specified: &mut Vec<bool>,
     pca: &mut Vec<HashMap<String, Vec<f64>>>,
+    umap: &mut Vec<HashMap<String, (f64, f64)>>,
+    tsne: &mut Vec<HashMap<String, (f64, f64)>>,
     gex_mults: &mut Vec<f64>,
     fb_mults: &mut Vec<f64>,
     gex_cell_barcodes: &mut Vec<Vec<String>>,
@@ -82,6 +87,9 @@ pub fn load_gex(
         Vec<String>,
         u64,
         Vec<(String, u32, u32, u32)>,
+        Vec<(String, HashMap<String, String>)>,
+        HashMap<String, (f64, f64)>,
+        HashMap<String, (f64, f64)>,
     )>::new();
     for i in 0..ctl.origin_info.gex_path.len() {
         results.push((
@@ -113,6 +121,9 @@ pub fn load_gex(
             Vec::<String>::new(),
             0,
             Vec::new(),
+            Vec::new(),
+            HashMap::new(),
+            HashMap::new(),
         ));
     }
     let gex_outs = &ctl.origin_info.gex_path;
@@ -127,7 +138,34 @@ pub fn load_gex(
     results.par_iter_mut().for_each(|r| {
         let pathlist = &mut r.15;
         let i = r.0;
-        if !gex_outs[i].is_empty() {
+        if !gex_outs[i].is_empty() && is_alt_gex(&gex_outs[i]) {
+            // Load from an AnnData file or MEX directory.  There are no read counts, so the
+            // multipliers are set to one.
+
+            let mut obs_cols = Vec::<String>::new();
+            if !ctl.gen_opt.gex_obs.is_empty() && gex_outs[i].ends_with(".h5ad") {
+                for c in ctl.gen_opt.gex_obs.split(',') {
+                    obs_cols.push(c.to_string());
+                }
+            }
+            match load_alt_gex(&gex_outs[i], &obs_cols) {
+                Err(e) => r.11 = e,
+                Ok(x) => {
+                    pathlist.push(gex_outs[i].clone());
+                    r.3 = MirrorSparseMatrix::build_from_vec(&x.matrix, &x.barcodes, &x.features);
+                    r.4 = Some(1.0);
+                    if x.features.iter().any(|f| f.contains("\tAntibody")) {
+                        r.5 = Some(1.0);
+                    }
+                    r.1 = x.features;
+                    r.2 = x.barcodes;
+                    r.6 = r.2.clone();
+                    r.28 = x.obs;
+                    r.29 = x.umap;
+                    r.30 = x.tsne;
+                }
+            }
+        } else if !gex_outs[i].is_empty() {
             // First define the path where the GEX files should live, and make sure that the path
             // exists.
 
@@ -203,6 +241,8 @@ pub fn load_gex(
             let feature_metrics_file = find_feature_metrics_file(&ctl, &outs, &analysis, pathlist);
             let metrics_file = find_metrics_file(&ctl, &outs, &analysis, pathlist);
             let cluster_file = find_cluster_file(&ctl, &outs, &analysis, pathlist);
+            let umap_file = find_projection_file(&ctl, &outs, &analysis, "umap", pathlist);
+            let tsne_file = find_projection_file(&ctl, &outs, &analysis, "tsne", pathlist);
 
             // Proceed.
 
@@ -333,16 +373,6 @@ pub fn load_gex(
                     r.8.insert(barcode.to_string(), cell_type.to_string());
                     r.10 = true;
                 }
-            } else if ctl.gen_opt.mark_stats
-                || ctl.gen_opt.mark_stats2
-                || ctl.clono_filt_opt_def.marked_b
-            {
-                r.11 = format!(
-                    "\nIf you use MARK_STATS or MARK_STATS2 or MARKED_B, celltypes.csv has to \
-                    exist, and this file\n{}\ndoes not exist.\n",
-                    types_file
-                );
-                return;
             }
 
             // Read json metrics file.  Note that we do not enforce the requirement of this
@@ -467,6 +497,20 @@ pub fn load_gex(
                 r.9.insert(barcode.to_string(), y);
             }
 
+            // Read UMAP and t-SNE projections, if present.
+
+            for (file, proj) in [(&umap_file, &mut r.29), (&tsne_file, &mut r.30)] {
+                if !file.is_empty() {
+                    match parse_projection(&read_to_string(file).unwrap(), file) {
+                        Ok(x) => *proj = x,
+                        Err(e) => {
+                            r.11 = e;
+                            return;
+                        }
+                    }
+                }
+            }
+
             // Read graph clusters, and also get the cell barcodes from that.
 
             let f = open_userfile_for_read(&cluster_file);
@@ -878,7 +922,7 @@ pub fn load_gex(
 
     let n = results.len();
     for (
-        _i,
+        i,
         (
             _x0,
             x1,
@@ -908,6 +952,9 @@ pub fn load_gex(
             x25,
             x26,
             x27,
+            x28,
+            x29,
+            x30,
         ),
     ) in results.into_iter().take(n).enumerate()
     {
@@ -930,6 +977,8 @@ pub fn load_gex(
         cluster.push(x7);
         cell_type.push(x8);
         pca.push(x9);
+        umap.push(x29);
+        tsne.push(x30);
         cell_type_specified.push(x10);
         feature_metrics.push(x16);
         json_metrics.push(x17);
@@ -942,6 +991,12 @@ pub fn load_gex(
         fb_top_reads_barcodes.push(x25);
         fb_total_reads.push(x26);
         fb_bdcs.push(x27);
+        if !x28.is_empty() {
+            if ctl.origin_info.alt_bc_fields.len() <= i {
+                ctl.origin_info.alt_bc_fields.resize(i + 1, Vec::new());
+            }
+            ctl.origin_info.alt_bc_fields[i].extend(x28);
+        }
     }
 
     // Done.
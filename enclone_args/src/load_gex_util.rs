@@ -26,6 +26,28 @@ pub fn find_pca_file(
     pca_file
 }
 
+// Find a two-dimensional projection file, for method umap or tsne.  These files are optional, so
+// the empty string is returned if none is found.
+
+pub fn find_projection_file(
+    _ctl: &EncloneControl,
+    _outs: &str,
+    analysis: &Vec<String>,
+    method: &str,
+    pathlist: &mut Vec<String>,
+) -> String {
+    for x in analysis.iter() {
+        for dir in ["2_components", "gene_expression_2_components"] {
+            let f = format!("{}/{}/{}/projection.csv", x, method, dir);
+            if path_exists(&f) {
+                pathlist.push(f.clone());
+                return f;
+            }
+        }
+    }
+    String::new()
+}
+
 pub fn find_json_metrics_file(
     ctl: &EncloneControl,
     _outs: &str,
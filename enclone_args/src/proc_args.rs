@@ -115,6 +115,7 @@ pub fn proc_args(mut ctl: &mut EncloneControl, args: &Vec<String>) -> Result<(),
     ctl.gen_opt.jscore_bits_multiplier = 2.2;
     ctl.gen_opt.max_heavies = 1000000;
     ctl.gen_opt.subsample = -1.0;
+    ctl.gen_opt.antigen_min_score = 2.0;
 
     // Set up clonotyping control parameters.
 
@@ -384,6 +385,7 @@ pub fn proc_args(mut ctl: &mut EncloneControl, args: &Vec<String>) -> Result<(),
             &mut ctl.gen_opt.align_jun_align_consistency,
         ),
         ("ALLOW_INCONSISTENT", &mut ctl.gen_opt.allow_inconsistent),
+        ("AMINO_IMGT", &mut ctl.clono_print_opt.amino_imgt),
         ("ANN", &mut ctl.join_print_opt.ann),
         ("ANN0", &mut ctl.join_print_opt.ann0),
         ("BARCODES", &mut ctl.clono_print_opt.barcodes),
@@ -404,6 +406,9 @@ pub fn proc_args(mut ctl: &mut EncloneControl, args: &Vec<String>) -> Result<(),
         ("DEL", &mut ctl.clono_filt_opt.del),
         ("DESCRIP", &mut ctl.gen_opt.descrip),
         ("DIFF_DIST", &mut ctl.gen_opt.diff_dist),
+        ("DIVERSITY", &mut ctl.gen_opt.diversity),
+        ("DIVERSITY_CSV", &mut ctl.gen_opt.diversity_csv),
+        ("DOUBLET_MARK", &mut ctl.clono_filt_opt_def.doublet_mark),
         ("D_INCONSISTENT", &mut ctl.clono_filt_opt.d_inconsistent),
         ("D_NONE", &mut ctl.clono_filt_opt.d_none),
         ("D_SECOND", &mut ctl.clono_filt_opt.d_second),
@@ -456,6 +461,7 @@ pub fn proc_args(mut ctl: &mut EncloneControl, args: &Vec<String>) -> Result<(),
         ("NOPRINTX", &mut ctl.gen_opt.noprintx),
         ("NOSPACES", &mut ctl.gen_opt.nospaces),
         ("NOTE_SIMPLE", &mut ctl.clono_print_opt.note_simple),
+        ("NOVEL_ALLELES", &mut ctl.gen_opt.novel_alleles),
         ("NPLAIN", &mut ctl.pretty),
         ("NWHITEF", &mut ctl.gen_opt.nwhitef),
         ("NWARN", &mut ctl.gen_opt.nwarn),
@@ -486,6 +492,10 @@ pub fn proc_args(mut ctl: &mut EncloneControl, args: &Vec<String>) -> Result<(),
             "SPLIT_PLOT_BY_ORIGIN",
             &mut ctl.plot_opt.split_plot_by_origin,
         ),
+        ("SHARD_DONORS", &mut ctl.gen_opt.shard_donors),
+        ("SHM_PROFILE", &mut ctl.gen_opt.shm_profile),
+        ("STABLE_IDS", &mut ctl.gen_opt.stable_ids),
+        ("STREAM_INPUT", &mut ctl.gen_opt.stream_input),
         ("SUM", &mut ctl.clono_print_opt.sum),
         ("SUMMARY", &mut ctl.gen_opt.summary),
         ("SUMMARY_CLEAN", &mut ctl.gen_opt.summary_clean),
@@ -575,6 +585,7 @@ pub fn proc_args(mut ctl: &mut EncloneControl, args: &Vec<String>) -> Result<(),
     // Define arguments that set something to an f64.
 
     let set_f64 = [
+        ("ANTIGEN_MIN_SCORE", &mut ctl.gen_opt.antigen_min_score),
         ("CDR3_MULT", &mut ctl.join_alg_opt.cdr3_mult),
         ("JSCORE_BITS_MULT", &mut ctl.gen_opt.jscore_bits_multiplier),
         ("MULT_POW", &mut ctl.join_alg_opt.mult_pow),
@@ -597,10 +608,16 @@ pub fn proc_args(mut ctl: &mut EncloneControl, args: &Vec<String>) -> Result<(),
         ("CLUSTAL_DNA", &mut ctl.gen_opt.clustal_dna),
         ("CONFIG", &mut ctl.gen_opt.config_file),
         ("EXT", &mut ctl.gen_opt.ext),
+        ("GERMLINE_REF", &mut ctl.gen_opt.germline_ref),
+        ("GEX_OBS", &mut ctl.gen_opt.gex_obs),
         ("GROUP_CDR3", &mut ctl.clono_group_opt.cdr3),
         ("PCHAINS", &mut ctl.parseable_opt.pchains),
+        ("PDB_MATCH", &mut ctl.gen_opt.pdb_match),
+        ("PDB_MATCH_ID", &mut ctl.gen_opt.pdb_match_id),
+        ("PGEN_MODELS", &mut ctl.gen_opt.pgen_dir),
         ("SESSION_NAME", &mut ctl.gen_opt.session_name),
         ("TRACE_BARCODE", &mut ctl.gen_opt.trace_barcode),
+        ("VDJ_CACHE", &mut ctl.gen_opt.vdj_cache),
     ];
 
     // Define arguments that set something to a string that is an output file name.
@@ -610,15 +627,25 @@ pub fn proc_args(mut ctl: &mut EncloneControl, args: &Vec<String>) -> Result<(),
         ("DONOR_REF_FILE", &mut ctl.gen_opt.dref_file),
         ("HONEY_OUT", &mut ctl.plot_opt.honey_out),
         ("PROTO", &mut ctl.gen_opt.proto),
+        ("SAVE_JOIN", &mut ctl.join_alg_opt.save_join),
         ("SUBSET_JSON", &mut ctl.gen_opt.subset_json),
     ];
 
     // Define arguments that set something to a string that is an output file name or stdout.
 
     let set_string_writeable_or_stdout = [
+        ("AIRR", &mut ctl.gen_opt.airr_filename),
+        ("AIRR_CELL", &mut ctl.gen_opt.airr_cell_filename),
+        ("DIFF_EXP_OUT", &mut ctl.gen_opt.diff_exp_out),
+        ("LINEAGE_JSON", &mut ctl.gen_opt.lineage_json),
+        ("LINEAGE_NEWICK", &mut ctl.gen_opt.lineage_newick),
+        ("NOVEL_ALLELES_FASTA", &mut ctl.gen_opt.novel_alleles_fasta),
         ("PEER_GROUP", &mut ctl.gen_opt.peer_group_filename),
         ("PHYLIP_AA", &mut ctl.gen_opt.phylip_aa),
         ("PHYLIP_DNA", &mut ctl.gen_opt.phylip_dna),
+        ("PUBLIC_CLUSTERS", &mut ctl.gen_opt.public_clusters),
+        ("QC_REPORT", &mut ctl.gen_opt.qc_report),
+        ("SHM_PROFILE_SVG", &mut ctl.gen_opt.shm_profile_svg),
     ];
 
     // Define arguments that set something to a string that is an input file name, represented
@@ -638,7 +665,10 @@ pub fn proc_args(mut ctl: &mut EncloneControl, args: &Vec<String>) -> Result<(),
 
     let set_string_readable_plain = [
         ("BC_JOINT", &mut ctl.gen_opt.bc_joint),
+        ("DONOR_ALLELES", &mut ctl.gen_opt.donor_alleles),
+        ("EMBEDDING", &mut ctl.gen_opt.embedding),
         ("EXTERNAL_REF", &mut ctl.gen_opt.external_ref),
+        ("LOAD_JOIN", &mut ctl.join_alg_opt.load_join),
         ("POST_FILTER", &mut ctl.gen_opt.post_filter),
         ("REF", &mut ctl.gen_opt.refname),
     ];
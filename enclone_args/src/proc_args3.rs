@@ -2,6 +2,7 @@
 
 // This file contains the two functions proc_xcr and proc_meta.
 
+use crate::read_airr::is_airr_path;
 use enclone_base::expand_integer_ranges;
 use enclone_core::defs::{EncloneControl, OriginInfo};
 use enclone_core::{fetch_url, tilde_expand_me};
@@ -673,17 +674,21 @@ pub fn proc_xcr(
             res.3 = resx.unwrap_err();
         } else {
             *p = resx.unwrap();
-            if ctl.gen_opt.bcr && path_exists(&format!("{}/vdj_b", p)) {
-                *p = format!("{}/vdj_b", p);
-            }
-            if ctl.gen_opt.bcr && path_exists(&format!("{}/multi/vdj_b", p)) {
-                *p = format!("{}/multi/vdj_b", p);
-            }
-            if ctl.gen_opt.tcr && path_exists(&format!("{}/vdj_t", p)) {
-                *p = format!("{}/vdj_t", p);
-            }
-            if ctl.gen_opt.tcr && path_exists(&format!("{}/multi/vdj_t", p)) {
-                *p = format!("{}/multi/vdj_t", p);
+            // Descend into the VDJ subdirectory, unless we were given an AIRR rearrangement file.
+
+            if !is_airr_path(p) {
+                if ctl.gen_opt.bcr && path_exists(&format!("{}/vdj_b", p)) {
+                    *p = format!("{}/vdj_b", p);
+                }
+                if ctl.gen_opt.bcr && path_exists(&format!("{}/multi/vdj_b", p)) {
+                    *p = format!("{}/multi/vdj_b", p);
+                }
+                if ctl.gen_opt.tcr && path_exists(&format!("{}/vdj_t", p)) {
+                    *p = format!("{}/vdj_t", p);
+                }
+                if ctl.gen_opt.tcr && path_exists(&format!("{}/multi/vdj_t", p)) {
+                    *p = format!("{}/multi/vdj_t", p);
+                }
             }
             if have_gex {
                 let resx = get_path_or_internal_id(pg, ctl, "GEX", &spinlock);
@@ -106,6 +106,8 @@ pub fn involves_gex_fb(x: &String) -> bool {
         || x == "entropy"
         || x == "cred"
         || x == "cred_cell"
+        || x == "cell_type"
+        || x == "cell_type_cell"
 }
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -220,6 +222,8 @@ fn check_gene_fb(
                 || *x == "entropy".to_string()
                 || *x == "cred".to_string()
                 || *x == "cred_cell".to_string()
+                || *x == "cell_type".to_string()
+                || *x == "cell_type_cell".to_string()
             {
                 if category == "parseable" {
                     return Err(format!(
@@ -535,6 +539,8 @@ pub fn check_pcols(
                     && y.after("aa_").between("_", "_ext").parse::<isize>().is_ok()
                 {
                     ok = true;
+                } else if is_imgt_aa_var(y) {
+                    ok = true;
                 }
             }
         }
@@ -550,6 +556,23 @@ pub fn check_pcols(
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
+// Test for a variable imgt_S_E_aa, where S and E are positive integers, with S <= E.
+
+fn is_imgt_aa_var(x: &str) -> bool {
+    if !x.starts_with("imgt_") || !x.ends_with("_aa") {
+        return false;
+    }
+    let y = x.between("imgt_", "_aa");
+    if !y.contains('_') {
+        return false;
+    }
+    let start = y.before("_").parse::<usize>().unwrap_or(0);
+    let stop = y.after("_").parse::<usize>().unwrap_or(0);
+    start >= 1 && stop >= start
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
 // Check cvars args.
 
 pub fn check_cvars(ctl: &EncloneControl) -> Result<(), String> {
@@ -578,6 +601,8 @@ pub fn check_cvars(ctl: &EncloneControl) -> Result<(), String> {
             && x.after("q").rev_before("_").parse::<usize>().is_ok()
         {
             ok = true;
+        } else if is_imgt_aa_var(&x) {
+            ok = true;
         }
         if !ok {
             return Err(format!(
@@ -3,14 +3,17 @@
 use crate::proc_args2::proc_args_tail;
 use crate::proc_args3::{get_path_fail, proc_meta, proc_meta_core, proc_xcr};
 use crate::proc_args_check::check_cvars;
+use enclone_core::cell_color::CellColor;
 use enclone_core::defs::EncloneControl;
-use enclone_core::tilde_expand_me;
+use enclone_core::pgen::read_pgen_models;
+use enclone_core::{require_readable_file, tilde_expand_me};
 use enclone_vars::encode_arith;
 use evalexpr::build_operator_tree;
 use expr_tools::vars_of_node;
 use io_utils::{open_for_read, open_userfile_for_read, path_exists};
 use std::collections::HashMap;
 use std::io::BufRead;
+use std::path::Path;
 use std::time::Instant;
 use string_utils::{parse_csv, TextUtils};
 use vector_utils::{bin_member, next_diff, sort_sync2, unique_sort};
@@ -439,6 +442,18 @@ pub fn proc_args_post(
                 .to_string(),
         );
     }
+    if !ctl.plot_opt.plot_embedding.is_empty()
+        && (ctl.plot_opt.honey_in.is_some()
+            || !ctl.plot_opt.honey_out.is_empty()
+            || ctl.plot_opt.split_plot_by_dataset
+            || ctl.plot_opt.split_plot_by_origin)
+    {
+        return Err(
+            "\nHONEY=...,embed=... cannot be combined with HONEY_IN, HONEY_OUT, \
+            SPLIT_PLOT_BY_DATASET or SPLIT_PLOT_BY_ORIGIN.\n"
+                .to_string(),
+        );
+    }
     if ctl.clono_print_opt.amino.is_empty() && ctl.clono_print_opt.cvars.is_empty() {
         return Err(
             "\nSorry, use of both CVARS= and AMINO= (setting both to null) is not \
@@ -456,9 +471,30 @@ pub fn proc_args_post(
             "\nIf you use ALIGN_JALIGN_CONSISTENCY, you should also use PLAIN.\n".to_string(),
         );
     }
+    if ctl.gen_opt.gene_scan_exact && !ctl.gen_opt.diff_exp_test_groups.is_empty() {
+        return Err(
+            "\nSCAN_EXACT cannot be used with DIFF_EXP_GROUPS, because groups are made of \
+            clonotypes.\n"
+                .to_string(),
+        );
+    }
     if ctl.gen_opt.gene_scan_exact && ctl.gen_opt.gene_scan_test.is_none() {
         return Err(
-            "\nIt doesn't make sense to specify SCAN_EXIT unless SCAN is also specified.\n"
+            "\nIt doesn't make sense to specify SCAN_EXACT unless SCAN or DIFF_EXP is also \
+            specified.\n"
+                .to_string(),
+        );
+    }
+    if ctl.gen_opt.diff_exp && ctl.gen_opt.gene_scan_threshold.is_some() {
+        return Err("\nSCAN and DIFF_EXP cannot be used together.\n".to_string());
+    }
+    if ctl.gen_opt.gene_scan_test.is_some() && !ctl.gen_opt.diff_exp_test_groups.is_empty() {
+        return Err("\nDIFF_EXP_GROUPS cannot be used with SCAN or DIFF_EXP.\n".to_string());
+    }
+    if !ctl.gen_opt.diff_exp_out.is_empty() && !ctl.gen_opt.diff_exp {
+        return Err(
+            "\nIt doesn't make sense to specify DIFF_EXP_OUT unless DIFF_EXP or \
+            DIFF_EXP_GROUPS is also specified.\n"
                 .to_string(),
         );
     }
@@ -511,6 +547,13 @@ pub fn proc_args_post(
                 .to_string(),
         );
     }
+    if ctl.clono_filt_opt_def.doublet && ctl.clono_filt_opt_def.doublet_mark {
+        return Err(
+            "\nIf you use DOUBLET_MARK, you should also use NDOUBLET, to turn off \
+            the filter,\nas otherwise nothing will be marked.\n"
+                .to_string(),
+        );
+    }
     if ctl.clono_filt_opt_def.umi_ratio_filt && ctl.clono_filt_opt_def.umi_ratio_filt_mark {
         return Err(
             "\nIf you use UMI_RATIO_FILT_MARK, you should also use NUMI_RATIO, to turn off \
@@ -659,6 +702,113 @@ pub fn proc_args_post(
     if ctl.plot_opt.plot_by_mark && (using_plot || ctl.plot_opt.use_legend) {
         return Err("\nPLOT_BY_MARK cannot be used with PLOT or LEGEND.\n".to_string());
     }
+    if ctl.gen_opt.shard_donors {
+        if ctl.clono_filt_opt_def.donor {
+            return Err("\nSHARD_DONORS cannot be used with MIX_DONORS.\n".to_string());
+        }
+        if !ctl.join_alg_opt.save_join.is_empty() || !ctl.join_alg_opt.load_join.is_empty() {
+            return Err("\nSHARD_DONORS cannot be used with SAVE_JOIN or LOAD_JOIN.\n".to_string());
+        }
+        if !ctl.gen_opt.dref_file.is_empty() {
+            return Err("\nSHARD_DONORS cannot be used with DONOR_REF_FILE.\n".to_string());
+        }
+    }
+    if !ctl.gen_opt.public_clusters.is_empty() && ctl.clono_group_opt.style.is_empty() {
+        return Err(
+            "\nPUBLIC_CLUSTERS requires GROUP, which defines the clusters, for example \
+            GROUP=vj_heavy_refname,cdr3_aa_heavy≥90%.\n"
+                .to_string(),
+        );
+    }
+    if !ctl.gen_opt.pgen_dir.is_empty() {
+        ctl.gen_opt.pgen_models = read_pgen_models(&ctl.gen_opt.pgen_dir, ctl.gen_opt.mouse)?;
+    } else {
+        let is_pgen = |v: &String| {
+            v == "pgen" || (v.starts_with("pgen") && v.after("pgen").parse::<usize>().is_ok())
+        };
+        if ctl.clono_print_opt.cvars.iter().any(is_pgen)
+            || ctl.parseable_opt.pcols.iter().any(is_pgen)
+        {
+            return Err(
+                "\nThe variable pgen requires PGEN_MODELS, because no recombination models are \
+                bundled with enclone.\n"
+                    .to_string(),
+            );
+        }
+    }
+    if !ctl.gen_opt.germline_ref.is_empty() {
+        if !ctl.gen_opt.refname.is_empty() {
+            return Err("\nPlease do not specify both REF and GERMLINE_REF.\n".to_string());
+        }
+        let arg = format!("GERMLINE_REF={}", ctl.gen_opt.germline_ref);
+        for f in ctl.gen_opt.germline_ref.split(',') {
+            require_readable_file(f, &arg)?;
+        }
+    }
+    if !ctl.gen_opt.gex_obs.is_empty()
+        && !ctl
+            .origin_info
+            .gex_path
+            .iter()
+            .any(|p| p.ends_with(".h5ad"))
+    {
+        return Err(
+            "\nGEX_OBS can only be used if GEX specifies an AnnData (.h5ad) file.\n".to_string(),
+        );
+    }
+    if !ctl.gen_opt.antigens.is_empty() && ctl.gen_opt.antigen_controls.is_empty() {
+        return Err("\nANTIGENS can only be used with ANTIGEN_CONTROLS.\n".to_string());
+    }
+    if !ctl.gen_opt.antigen_controls.is_empty()
+        && ctl.origin_info.gex_path.iter().all(|p| p.is_empty())
+    {
+        return Err(
+            "\nANTIGEN_CONTROLS requires feature barcode data, which is specified using GEX.\n"
+                .to_string(),
+        );
+    }
+
+    // Determine if cell types are needed, in which case they are computed from marker genes for
+    // datasets lacking celltypes.csv.
+
+    let mut vars = ctl.clono_print_opt.lvars.clone();
+    vars.append(&mut ctl.parseable_opt.pcols.clone());
+    vars.append(&mut ctl.gen_opt.tree.clone());
+    for x in ctl.clono_filt_opt.bounds.iter() {
+        vars.append(&mut x.var.clone());
+    }
+    match ctl.plot_opt.cell_color {
+        CellColor::ByVariableValue(ref x) => vars.push(x.var.clone()),
+        CellColor::ByCategoricalVariableValue(ref x) => vars.append(&mut x.vars.clone()),
+        _ => {}
+    };
+    ctl.gen_opt.marker_cell_types = ctl.clono_filt_opt_def.marked_b
+        || ctl.gen_opt.mark_stats
+        || ctl.gen_opt.mark_stats2
+        || vars
+            .iter()
+            .any(|v| v == "cell_type" || v == "cell_type_cell" || v == "n_b");
+    if !ctl.gen_opt.pdb_match.is_empty() && !Path::new(&ctl.gen_opt.pdb_match).is_dir() {
+        return Err(format!(
+            "\nPDB_MATCH={} is not a directory.\n",
+            ctl.gen_opt.pdb_match
+        ));
+    }
+    if !ctl.gen_opt.pdb_match_id.is_empty() {
+        let id = &ctl.gen_opt.pdb_match_id;
+        if ctl.gen_opt.pdb_match.is_empty() {
+            return Err("\nPDB_MATCH_ID can only be used with PDB_MATCH.\n".to_string());
+        }
+        if !id.contains('.')
+            || id.before(".").parse::<usize>().is_err()
+            || id.after(".").parse::<usize>().is_err()
+        {
+            return Err(format!(
+                "\nPDB_MATCH_ID={} is not a clonotype id of the form g.c, for example 1.1.\n",
+                id
+            ));
+        }
+    }
     if ctl.parseable_opt.pbarcode && ctl.parseable_opt.pout.is_empty() {
         return Err(
             "\nIt does not make sense to specify PCELL unless POUT is also specified.\n"
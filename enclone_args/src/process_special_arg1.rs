@@ -5,7 +5,7 @@
 use crate::proc_args2::test_writeable;
 use crate::proc_args2::{is_simple_arg, is_usize_arg};
 use enclone_core::cell_color::*;
-use enclone_core::defs::EncloneControl;
+use enclone_core::defs::{EncloneControl, POUT_MEMORY};
 use enclone_core::tilde_expand_me;
 use enclone_vars::encode_arith;
 use evalexpr::build_operator_tree;
@@ -215,6 +215,18 @@ pub fn process_special_arg1(
                 }
                 ctl.plot_opt.plot_file = filename;
                 out_count += 1;
+            } else if part_name == "embed" {
+                if !p.solo() || (p[0] != "umap" && p[0] != "tsne") {
+                    return Err(err);
+                }
+                ctl.plot_opt.plot_embedding = p[0].clone();
+            } else if part_name == "highlight" {
+                for x in p.iter() {
+                    if x.parse::<usize>().is_err() || x.force_usize() == 0 {
+                        return Err(err);
+                    }
+                    ctl.plot_opt.plot_highlight.push(x.force_usize());
+                }
             } else if part_name == "legend" {
                 if p.solo() && p[0] == "none" {
                     ctl.plot_opt.use_legend = false;
@@ -303,6 +315,12 @@ pub fn process_special_arg1(
         if color_count > 1 {
             return Err("\nHONEY=... must specify color=... only once.\n".to_string());
         }
+        if !ctl.plot_opt.plot_highlight.is_empty() && ctl.plot_opt.plot_embedding.is_empty() {
+            return Err(
+                "\nHONEY=... may only specify highlight=... if it specifies embed=....\n"
+                    .to_string(),
+            );
+        }
         if schema == "dataset" {
             let v = ColorByDataset {};
             let cc = CellColor::ByDataset(v);
@@ -396,7 +414,7 @@ pub fn process_special_arg1(
         let val = arg.after("POUT=");
         ctl.parseable_opt.pout = val.to_string();
         tilde_expand_me(&mut ctl.parseable_opt.pout);
-        if val != "stdout" && val != "stdouth" && val != "/dev/null" {
+        if val != "stdout" && val != "stdouth" && val != "/dev/null" && val != POUT_MEMORY {
             test_writeable(&val, ctl.gen_opt.evil_eye)?;
         }
     } else if arg.starts_with("SIM_MAT_PLOT=") {
@@ -375,6 +375,66 @@ pub fn process_special_arg2(
             }
         }
         ctl.gen_opt.gene_scan_threshold = Some(threshold);
+    } else if arg.starts_with("DIFF_EXP=") {
+        let x = arg.after("DIFF_EXP=").replace(" ", "");
+        let x = x.split(',').collect::<Vec<&str>>();
+        if x.len() != 2 {
+            return Err("\nArgument to DIFF_EXP must have two components.\n".to_string());
+        }
+        ctl.gen_opt.diff_exp = true;
+        ctl.gen_opt.gene_scan_test = Some(LinearCondition::new(x[0])?);
+        if x[1] == "rest" {
+            ctl.gen_opt.diff_exp_rest = true;
+            ctl.gen_opt.gene_scan_control = ctl.gen_opt.gene_scan_test.clone();
+        } else {
+            ctl.gen_opt.gene_scan_control = Some(LinearCondition::new(x[1])?);
+        }
+    } else if arg.starts_with("DIFF_EXP_GROUPS=") {
+        let x = arg
+            .after("DIFF_EXP_GROUPS=")
+            .split(',')
+            .collect::<Vec<&str>>();
+        let err = "\nArgument to DIFF_EXP_GROUPS must have the form g1+...+gn,h1+...+hm or \
+            g1+...+gn,rest, where the gi and hj are positive integers.\n"
+            .to_string();
+        if x.len() != 2 {
+            return Err(err);
+        }
+        ctl.gen_opt.diff_exp = true;
+        ctl.gen_opt.diff_exp_rest = x[1] == "rest";
+        for (i, side) in x.iter().enumerate() {
+            if i == 1 && ctl.gen_opt.diff_exp_rest {
+                continue;
+            }
+            for g in side.split('+') {
+                if g.parse::<usize>().is_err() || g.force_usize() == 0 {
+                    return Err(err);
+                }
+                if i == 0 {
+                    ctl.gen_opt.diff_exp_test_groups.push(g.force_usize());
+                } else {
+                    ctl.gen_opt.diff_exp_control_groups.push(g.force_usize());
+                }
+            }
+        }
+    } else if arg.starts_with("ANTIGENS=") || arg.starts_with("ANTIGEN_CONTROLS=") {
+        let fields = arg
+            .after("=")
+            .split(',')
+            .map(|f| f.to_string())
+            .collect::<Vec<String>>();
+        if fields.iter().any(|f| f.is_empty()) {
+            return Err(format!(
+                "\nArgument {} is not properly specified: it should be a comma-separated \
+                list of feature names.\n",
+                arg
+            ));
+        }
+        if arg.starts_with("ANTIGENS=") {
+            ctl.gen_opt.antigens = fields;
+        } else {
+            ctl.gen_opt.antigen_controls = fields;
+        }
     } else if arg.starts_with("PLOT=") {
         *using_plot = true;
         let x = arg.after("PLOT=").split(',').collect::<Vec<&str>>();
@@ -497,6 +557,14 @@ pub fn process_special_arg2(
                 || *x == "donorn"
             {
                 ok = true;
+            } else if x.starts_with("imgt:") && x.contains('-') {
+                let (start, stop) = (x.between("imgt:", "-"), x.after("-"));
+                if start.parse::<usize>().is_ok()
+                    && stop.parse::<usize>().is_ok()
+                    && start.force_usize() <= stop.force_usize()
+                {
+                    ok = true;
+                }
             } else if x.contains('-') {
                 let (start, stop) = (x.before("-"), x.after("-"));
                 if start.parse::<usize>().is_ok()
@@ -0,0 +1,480 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Read an AIRR rearrangement TSV file, as an alternative to the contig annotations json file.
+// See https://docs.airr-community.org/en/stable/datarep/rearrangements.html.
+//
+// Each row is one contig.  Because coordinates in AIRR files are relative to whatever reference
+// was used to generate them, we always reannotate the sequence using the enclone reference, and
+// then use the AIRR fields to check and refine the result:
+// • v_call and j_call must be consistent with the chain that we find;
+// • junction is used to choose the CDR3 if more than one is found;
+// • umi_count (or duplicate_count) and consensus_count give the UMI and read counts;
+// • productive = F rows are dropped.
+
+use crate::read_util::{
+    group_tigs_by_barcode, origin_donor_tag, reduce_v_alignments, traverse_annotations,
+};
+use debruijn::dna_string::DnaString;
+use enclone_core::defs::{EncloneControl, TigData};
+use io_utils::{open_for_read, path_exists};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use string_utils::{stringme, TextUtils};
+use vdj_ann::annotate::{annotate_seq, get_cdr3_using_ann};
+use vdj_ann::refx::RefData;
+use vdj_ann::transcript::is_valid;
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Determine if a header line is that of an AIRR rearrangement file.
+
+pub fn is_airr_header(line: &str) -> bool {
+    let fields = line.split('\t').collect::<Vec<&str>>();
+    ["sequence", "v_call", "j_call"]
+        .iter()
+        .all(|f| fields.contains(f))
+}
+
+// Determine if a dataset path is an AIRR rearrangement file rather than a directory.
+
+pub fn is_airr_path(p: &str) -> bool {
+    if !Path::new(p).is_file() {
+        return false;
+    }
+    let mut line = String::new();
+    match File::open(p) {
+        Ok(f) => BufReader::new(f).read_line(&mut line).is_ok() && is_airr_header(line.trim_end()),
+        Err(_) => false,
+    }
+}
+
+// Positions of the fields used from an AIRR rearrangement file.
+
+#[derive(Debug, PartialEq)]
+struct AirrColumns {
+    seq: usize,
+    v: usize,
+    j: usize,
+    junction: usize,
+    cell: usize,
+    productive: usize,
+    umi: usize,
+    read: Option<usize>,
+    name: Option<usize>,
+}
+
+fn airr_columns(fields: &[&str], tsv: &str) -> Result<AirrColumns, String> {
+    let find = |name: &str| -> Option<usize> { fields.iter().position(|x| *x == name) };
+    let required = [
+        "sequence",
+        "v_call",
+        "j_call",
+        "junction",
+        "cell_id",
+        "productive",
+    ];
+    let mut pos = Vec::<usize>::new();
+    for r in required.iter() {
+        match find(*r) {
+            Some(p) => pos.push(p),
+            None => {
+                return Err(format!(
+                    "\nThe AIRR rearrangement file\n{}\nis missing the required field {}.\n\
+                     The required fields are {}.\n",
+                    tsv,
+                    r,
+                    required.join(", ")
+                ));
+            }
+        }
+    }
+    let umi = match find("umi_count").or_else(|| find("duplicate_count")) {
+        Some(p) => p,
+        None => {
+            return Err(format!(
+                "\nThe AIRR rearrangement file\n{}\nis missing the required field \
+                 umi_count (or duplicate_count).\n",
+                tsv
+            ));
+        }
+    };
+    Ok(AirrColumns {
+        seq: pos[0],
+        v: pos[1],
+        j: pos[2],
+        junction: pos[3],
+        cell: pos[4],
+        productive: pos[5],
+        umi,
+        read: find("consensus_count"),
+        name: find("sequence_id"),
+    })
+}
+
+// Parse an AIRR boolean, which may be T/F or true/false.
+
+fn airr_bool(x: &str) -> Option<bool> {
+    match x {
+        "T" | "TRUE" | "True" | "true" => Some(true),
+        "F" | "FALSE" | "False" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+// Strip the allele from a gene call, and if there are multiple calls, use the first one.
+
+fn airr_gene(x: &str) -> String {
+    let mut g = x.to_string();
+    if g.contains(',') {
+        g = g.before(",").to_string();
+    }
+    if g.contains('*') {
+        g = g.before("*").to_string();
+    }
+    g
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Convert one AIRR row to a TigData, or return None if the contig should not be used.
+
+fn airr_row_to_tig(
+    barcode: &str,
+    tigname: &str,
+    full_seq: &str,
+    v_call: &str,
+    j_call: &str,
+    junction: &str,
+    umi_count: usize,
+    read_count: usize,
+    li: usize,
+    refdata: &RefData,
+    ctl: &EncloneControl,
+) -> Option<TigData> {
+    let x = DnaString::from_dna_string(full_seq);
+    let mut ann = Vec::<(i32, i32, i32, i32, i32)>::new();
+    annotate_seq(&x, refdata, &mut ann, true, false, true);
+    reduce_v_alignments(refdata, &mut ann);
+    let mut log = Vec::<u8>::new();
+    if !is_valid(
+        &x,
+        refdata,
+        &ann,
+        false,
+        &mut log,
+        Some(ctl.gen_opt.gamma_delta),
+    ) {
+        return None;
+    }
+
+    // Find the CDR3, preferring the one that matches the AIRR junction.
+
+    let mut cdr3 = Vec::<(usize, Vec<u8>, usize, usize)>::new();
+    get_cdr3_using_ann(&x, refdata, &ann, &mut cdr3);
+    if cdr3.is_empty() {
+        return None;
+    }
+    let mut c = 0;
+    let junction = junction.to_uppercase();
+    for i in 0..cdr3.len() {
+        let start = cdr3[i].0;
+        let stop = start + 3 * cdr3[i].1.len();
+        if stop <= full_seq.len() && full_seq[start..stop] == junction {
+            c = i;
+            break;
+        }
+    }
+    let cdr3_aa = stringme(&cdr3[c].1);
+    let mut cdr3_start = cdr3[c].0;
+    let cdr3_dna = x
+        .slice(cdr3_start, cdr3_start + 3 * cdr3_aa.len())
+        .to_string();
+
+    // Traverse the annotations.
+
+    let a = traverse_annotations(refdata, &ann, ctl.gen_opt.gamma_delta, &mut cdr3_start)?;
+    if a.v_ref_id == 1000000 || a.tig_start < 0 || a.tig_stop < 0 {
+        return None;
+    }
+
+    // Check consistency with the AIRR gene calls.  Allele and gene calls may legitimately
+    // differ between references, but the chain may not.
+
+    let v_call = airr_gene(v_call);
+    let j_call = airr_gene(j_call);
+    if v_call.len() >= 3 && v_call[0..3] != a.chain_type {
+        return None;
+    }
+    if j_call.len() >= 3 && j_call[0..3] != refdata.name[a.j_ref_id][0..3] {
+        return None;
+    }
+
+    // Test for busted CDR3s, as for json input.
+
+    if cdr3_aa.contains('*') {
+        return None;
+    }
+    if cdr3_start + 3 * cdr3_aa.len() > a.tig_stop as usize - a.tig_start as usize {
+        return None;
+    }
+
+    // Assemble.  AIRR files do not carry quality scores, so we assign a uniform high value.
+
+    let (tig_start, tig_stop) = (a.tig_start as usize, a.tig_stop as usize);
+    let full_quals = vec![40_u8; full_seq.len()];
+    let quals = full_quals[tig_start..tig_stop].to_vec();
+    let (origin_index, donor_index, tag_index) = origin_donor_tag(&ctl.origin_info, li, barcode);
+    Some(TigData {
+        cdr3_dna,
+        len: tig_stop - tig_start,
+        v_start: tig_start,
+        v_stop: a.v_stop,
+        v_stop_ref: a.v_stop_ref,
+        d_start: a.d_start,
+        j_start: a.j_start,
+        j_start_ref: a.j_start_ref,
+        j_stop: tig_stop,
+        c_start: a.c_start,
+        full_seq: full_seq.as_bytes().to_vec(),
+        v_ref_id: a.v_ref_id,
+        d_ref_id: a.d_ref_id,
+        j_ref_id: a.j_ref_id,
+        c_ref_id: a.c_ref_id,
+        u_ref_id: a.u_ref_id,
+        fr1_start: 0,
+        cdr1_start: None,
+        fr2_start: None,
+        cdr2_start: None,
+        fr3_start: None,
+        cdr3_aa,
+        cdr3_start,
+        quals,
+        full_quals,
+        barcode: barcode.to_string(),
+        tigname: tigname.to_string(),
+        left: a.left,
+        dataset_index: li,
+        origin_index,
+        donor_index,
+        tag_index,
+        umi_count,
+        read_count,
+        chain_type: a.chain_type,
+        annv: a.annv,
+        validated_umis: None,
+        non_validated_umis: None,
+        invalidated_umis: None,
+        frac_reads_used: None,
+    })
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Read the AIRR rearrangement file for dataset li.  All cell_ids appearing in the file are
+// treated as VDJ cells.
+
+pub fn read_airr(
+    li: usize,
+    refdata: &RefData,
+    ctl: &EncloneControl,
+    vdj_cells: &mut Vec<String>,
+    gex_cells: &mut Vec<String>,
+    gex_cells_specified: &mut bool,
+) -> Result<Vec<Vec<TigData>>, String> {
+    *gex_cells_specified = false;
+    let tsv = &ctl.origin_info.dataset_path[li];
+    if !path_exists(tsv) {
+        return Err(format!(
+            "\nThe AIRR rearrangement file\n{}\ndoes not exist.  Please check how you have \
+             specified the input files to enclone, including the PRE argument.\n",
+            tsv
+        ));
+    }
+
+    // Parse the header.
+
+    let f = open_for_read![&tsv];
+    let mut lines = Vec::<String>::new();
+    for line in f.lines() {
+        let s = line.unwrap();
+        if !s.is_empty() {
+            lines.push(s);
+        }
+    }
+    if lines.is_empty() {
+        return Err(format!(
+            "\nThe AIRR rearrangement file\n{}\nis empty.\n",
+            tsv
+        ));
+    }
+    let fields = lines[0].split('\t').collect::<Vec<&str>>();
+    let col = airr_columns(&fields, tsv)?;
+
+    // Parse the rows.
+
+    let mut tigs = Vec::<TigData>::new();
+    for i in 1..lines.len() {
+        let x = lines[i].split('\t').collect::<Vec<&str>>();
+        if x.len() != fields.len() {
+            return Err(format!(
+                "\nLine {} of the AIRR rearrangement file\n{}\nhas {} fields, whereas the \
+                 header line has {}.\n",
+                i + 1,
+                tsv,
+                x.len(),
+                fields.len()
+            ));
+        }
+        let barcode = x[col.cell];
+        if barcode.is_empty() {
+            continue;
+        }
+        vdj_cells.push(barcode.to_string());
+        let productive = airr_bool(x[col.productive]);
+        if productive.is_none() {
+            return Err(format!(
+                "\nLine {} of the AIRR rearrangement file\n{}\nhas value {} for productive, \
+                 which should be T or F.\n",
+                i + 1,
+                tsv,
+                x[col.productive]
+            ));
+        }
+        if !ctl.gen_opt.reprod && !productive.unwrap() {
+            continue;
+        }
+        if x[col.umi].parse::<usize>().is_err() {
+            return Err(format!(
+                "\nLine {} of the AIRR rearrangement file\n{}\nhas value {} for {}, \
+                 which should be a nonnegative integer.\n",
+                i + 1,
+                tsv,
+                x[col.umi],
+                fields[col.umi]
+            ));
+        }
+        let umi_count = x[col.umi].force_usize();
+        let mut read_count = umi_count;
+        if let Some(p) = col.read {
+            if let Ok(n) = x[p].parse::<usize>() {
+                read_count = n;
+            }
+        }
+        let tigname = match col.name {
+            Some(p) => x[p].to_string(),
+            None => format!("{}_contig_{}", barcode, i),
+        };
+        let full_seq = x[col.seq].to_uppercase();
+        if let Some(mut t) = airr_row_to_tig(
+            barcode,
+            &tigname,
+            &full_seq,
+            x[col.v],
+            x[col.j],
+            x[col.junction],
+            umi_count,
+            read_count,
+            li,
+            refdata,
+            ctl,
+        ) {
+            t.productive = productive.unwrap();
+            tigs.push(t);
+        }
+    }
+
+    // Group by barcode.  Rows for a given cell need not be adjacent in an AIRR file.
+
+    tigs.sort_by(|a, b| a.barcode.cmp(&b.barcode));
+    Ok(group_tigs_by_barcode(&tigs, vdj_cells, gex_cells, ctl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use vdj_ann::refx::make_vdj_ref_data_core;
+    use vdj_ann_ref::human_ref;
+
+    #[test]
+    fn test_airr_header() {
+        let header = "sequence_id\tsequence\trev_comp\tproductive\tv_call\td_call\tj_call\t\
+            junction\tcell_id\tumi_count";
+        assert!(is_airr_header(header));
+        assert!(!is_airr_header("barcode\tcontig_id\tsequence"));
+        let fields = header.split('\t').collect::<Vec<&str>>();
+        let col = airr_columns(&fields, "x.tsv").unwrap();
+        assert_eq!((col.seq, col.v, col.j, col.junction), (1, 4, 6, 7));
+        assert_eq!((col.cell, col.productive, col.umi), (8, 3, 9));
+        assert_eq!((col.read, col.name), (None, Some(0)));
+        assert!(airr_columns(&fields[0..9], "x.tsv").is_err());
+        assert_eq!(airr_bool("T"), Some(true));
+        assert_eq!(airr_bool("false"), Some(false));
+        assert_eq!(airr_bool("maybe"), None);
+        assert_eq!(airr_gene("IGHV3-23*01,IGHV3-23D*01"), "IGHV3-23");
+    }
+
+    #[test]
+    fn test_airr_path() {
+        let dir = std::env::temp_dir().join(format!("enclone_airr_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let airr = dir.join("airr.tsv");
+        let other = dir.join("other.tsv");
+        std::fs::write(&airr, "sequence_id\tsequence\tv_call\tj_call\n").unwrap();
+        std::fs::write(&other, "barcode\tcount\n").unwrap();
+        assert!(is_airr_path(airr.to_str().unwrap()));
+        assert!(!is_airr_path(other.to_str().unwrap()));
+        assert!(!is_airr_path(dir.to_str().unwrap()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_airr_row_to_tig() {
+        let mut refdata = RefData::new();
+        make_vdj_ref_data_core(&mut refdata, &human_ref(), &String::new(), true, true, None);
+        let mut ctl = EncloneControl::default();
+        let oi = &mut ctl.origin_info;
+        oi.origin_id = vec!["s1".to_string()];
+        oi.donor_id = vec!["d1".to_string()];
+        oi.origin_list = oi.origin_id.clone();
+        oi.donor_list = oi.donor_id.clone();
+        oi.origin_for_bc = vec![HashMap::new()];
+        oi.donor_for_bc = vec![HashMap::new()];
+        oi.tag = vec![HashMap::new()];
+
+        // A productive IGK contig, from enclone_exec/testx/inputs/flaky.
+
+        let seq = "GGAGGAACTGCTCAGTTAGGACCCAGAGGGAACCATGGAAGCCCCAGCTCAGCTTCTCTTCCTCCTGCTACTCTGGC\
+            TCCCAGATACCACCGGAGAAATTGTGTTGACACAGTCTCCAGCCACCCTGTCTTTGTCTCCAGGGGAAAGAGCCACCCTCTCC\
+            TGCAGGGCCAGTCAGAGTGTTAGCAGCTACTTAGCCTGGTACCAACAGAAACCTGGCCAGGCTCCCAGGCTCCTCATCTATGA\
+            TGCATCCAACAGGGCCACTGGCATCCCAGCCAGGTTCAGTGGCAGTGGGTCTGGGACAGACTTCACTCTCACCATCAGCAGCC\
+            TAGAGCCTGAAGATTTTGCAGTTTATTACTGTCAGCAGCGTAGCAACTGGCCTCCCACTTTCGGCGGAGGGACCAAGGTGGAG\
+            ATCAAACGAACTGTGGCTGCACCATCTGTCTTCATCTTCCCGCCATCTGATGAGCAGTTGAAATCTGGAACTGCCTCTGTTGT\
+            GTGCCTGCTGAATAACTTCTATCCCAGAGAGGCCAAAGTACAGTGGAAGGTGGATAACGC";
+        let junction = "TGTCAGCAGCGTAGCAACTGGCCTCCCACTTTC";
+        let bc = "TAGACCACAATCGGTT-1";
+        let tig = |seq: &str, v_call: &str| {
+            airr_row_to_tig(
+                bc, "tig1", seq, v_call, "IGKJ4*01", junction, 5, 50, 0, &refdata, &ctl,
+            )
+        };
+        let t = tig(seq, "IGKV3-11*01,IGKV3D-11*02").unwrap();
+        assert_eq!(t.cdr3_aa, "CQQRSNWPPTF");
+        assert_eq!(t.cdr3_dna, junction);
+        assert_eq!(t.chain_type, "IGK");
+        assert_eq!(refdata.name[t.v_ref_id], "IGKV3-11");
+        assert_eq!(refdata.name[t.j_ref_id], "IGKJ4");
+        assert_eq!((t.umi_count, t.read_count), (5, 50));
+        assert_eq!(t.len, t.j_stop - t.v_start);
+        assert_eq!(t.quals.len(), t.len);
+        assert_eq!(t.full_quals.len(), seq.len());
+        assert_eq!((t.origin_index, t.donor_index), (Some(0), Some(0)));
+
+        // A heavy chain V call is inconsistent with the light chain found, and a contig that
+        // stops before the CDR3 is not usable.
+
+        assert!(tig(seq, "IGHV3-23*01").is_none());
+        assert!(tig(&seq[0..300], "IGKV3-11*01").is_none());
+    }
+}
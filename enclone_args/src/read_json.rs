@@ -3,18 +3,24 @@
 use self::annotate::{annotate_seq, get_cdr3_using_ann, print_some_annotations};
 use self::refx::RefData;
 use self::transcript::is_valid;
+use crate::read_airr::{is_airr_path, read_airr};
+use crate::read_util::{
+    group_tigs_by_barcode, origin_donor_tag, reduce_v_alignments, traverse_annotations, AnnFields,
+};
+use crate::vdj_cache::{read_vdj_cache, use_vdj_cache, vdj_cache_path, write_vdj_cache};
 use debruijn::dna_string::DnaString;
 use enclone_core::defs::{EncloneControl, OriginInfo, TigData};
+use enclone_core::tig_bin::{read_tig_bc, write_tig_bc};
 use io_utils::{open_maybe_compressed, path_exists, read_vector_entry_from_json};
-use rand::Rng;
 use rayon::prelude::*;
 use serde_json::Value;
+use std::fs::{create_dir_all, remove_dir_all};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::{collections::HashMap, io::BufReader};
 use string_utils::{stringme, strme, TextUtils};
 use vdj_ann::{annotate, refx, transcript};
-use vector_utils::{bin_position, erase_if, unique_sort};
+use vector_utils::{bin_position, unique_sort};
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
@@ -205,40 +211,7 @@ fn parse_vector_entry_from_json(
         let mut ann = Vec::<(i32, i32, i32, i32, i32)>::new();
         annotate_seq(&x, refdata, &mut ann, true, false, true);
 
-        // If there are multiple V segment alignments, possibly reduce to just one.
-
-        let mut ann2 = Vec::<(i32, i32, i32, i32, i32)>::new();
-        let mut j = 0;
-        while j < ann.len() {
-            let t = ann[j].2 as usize;
-            let mut k = j + 1;
-            while k < ann.len() {
-                if refdata.segtype[ann[k].2 as usize] != refdata.segtype[t] {
-                    break;
-                }
-                k += 1;
-            }
-            if refdata.segtype[t] == "V" && k - j > 1 {
-                let mut entries = 1;
-                if j < ann.len() - 1
-                    && ann[j + 1].2 as usize == t
-                    && ((ann[j].0 + ann[j].1 == ann[j + 1].0 && ann[j].3 + ann[j].1 < ann[j + 1].3)
-                        || (ann[j].0 + ann[j].1 < ann[j + 1].0
-                            && ann[j].3 + ann[j].1 == ann[j + 1].3))
-                {
-                    entries = 2;
-                }
-                for l in j..j + entries {
-                    ann2.push(ann[l]);
-                }
-            } else {
-                for l in j..k {
-                    ann2.push(ann[l]);
-                }
-            }
-            j = k;
-        }
-        ann = ann2;
+        reduce_v_alignments(refdata, &mut ann);
 
         // Proceed.
 
@@ -291,50 +264,31 @@ fn parse_vector_entry_from_json(
         cdr3_dna = x
             .slice(cdr3_start, cdr3_start + 3 * cdr3_aa.len())
             .to_string();
-        let mut seen_j = false;
-        for i in 0..ann.len() {
-            let t = ann[i].2 as usize;
-            if refdata.is_u(t) {
-                u_ref_id = Some(t);
-            } else if refdata.is_v(t) && !seen_j {
-                v_ref_id = t;
-                annv.push(ann[i]);
-                chain_type = refdata.name[t][0..3].to_string();
-                if chain_type == *"IGH"
-                    || chain_type == *"TRB"
-                    || (chain_type == *"TRD" && ctl.gen_opt.gamma_delta)
-                {
-                    left = true;
-                }
-                if ann[i].3 == 0 {
-                    tig_start = ann[i].0 as isize;
-                    if tig_start > cdr3_start as isize {
-                        panic!(
-                            "Something is wrong with the CDR3 start for this contig:\n\n{}.",
-                            &full_seq
-                        );
-                    }
-                    cdr3_start -= tig_start as usize;
-                }
-                v_stop = (ann[i].0 + ann[i].1) as usize;
-                v_stop_ref = (ann[i].3 + ann[i].1) as usize;
-            } else if refdata.is_d(t) {
-                d_start = Some(ann[i].0 as usize);
-                d_ref_id = Some(t);
-            } else if refdata.is_j(t) {
-                j_ref_id = t;
-                tig_stop = (ann[i].0 + ann[i].1) as isize;
-                j_start = ann[i].0 as usize;
-                j_start_ref = ann[i].3 as usize;
-                seen_j = true;
-            } else if refdata.is_c(t) {
-                c_ref_id = Some(t);
-                c_start = Some(ann[i].0 as usize);
-            }
-        }
-        for i in (0..annv.len()).rev() {
-            annv[i].0 -= annv[0].0;
-        }
+        let a = traverse_annotations(refdata, &ann, ctl.gen_opt.gamma_delta, &mut cdr3_start);
+        if a.is_none() {
+            panic!(
+                "Something is wrong with the CDR3 start for this contig:\n\n{}.",
+                &full_seq
+            );
+        }
+        AnnFields {
+            left,
+            v_ref_id,
+            d_ref_id,
+            j_ref_id,
+            c_ref_id,
+            u_ref_id,
+            chain_type,
+            tig_start,
+            tig_stop,
+            v_stop,
+            v_stop_ref,
+            d_start,
+            j_start,
+            j_start_ref,
+            c_start,
+            annv,
+        } = a.unwrap();
     } else {
         // Use annotations from json file.
 
@@ -560,46 +514,7 @@ fn parse_vector_entry_from_json(
     let quals = quals[tig_start..tig_stop].to_vec();
     let umi_count = v["umi_count"].as_i64().unwrap() as usize;
     let read_count = v["read_count"].as_i64().unwrap() as usize;
-    let mut origin = None;
-    let mut donor = None;
-    let mut tag = None;
-    if origin_info.origin_for_bc[li].contains_key(&barcode.clone()) {
-        origin = Some(origin_info.origin_for_bc[li][&barcode.clone()].clone());
-    } else {
-        // the way we use s1 here is flaky
-        if !origin_info.origin_id[li].is_empty()
-            && (origin_info.origin_id[li] != *"s1" || origin_info.origin_for_bc[li].is_empty())
-        {
-            origin = Some(origin_info.origin_id[li].clone());
-        }
-    }
-    if origin_info.donor_for_bc[li].contains_key(&barcode.clone()) {
-        donor = Some(origin_info.donor_for_bc[li][&barcode.clone()].clone());
-    } else {
-        // the way we use d1 here is flaky
-        if !origin_info.origin_id[li].is_empty()
-            && (origin_info.donor_id[li] != *"d1" || origin_info.donor_for_bc[li].is_empty())
-        {
-            donor = Some(origin_info.donor_id[li].clone());
-        }
-    }
-    if origin_info.tag[li].contains_key(&barcode.clone()) {
-        tag = Some(origin_info.tag[li][&barcode.clone()].clone());
-    }
-    let mut origin_index = None;
-    let mut donor_index = None;
-    let mut tag_index = None;
-    if origin.is_some() {
-        if origin.is_some() {
-            origin_index = Some(bin_position(&origin_info.origin_list, &origin.unwrap()) as usize);
-        }
-        if donor.is_some() {
-            donor_index = Some(bin_position(&origin_info.donor_list, &donor.unwrap()) as usize);
-        }
-    }
-    if tag.is_some() {
-        tag_index = Some(bin_position(&origin_info.tag_list, &tag.unwrap()) as usize);
-    }
+    let (origin_index, donor_index, tag_index) = origin_donor_tag(origin_info, li, barcode);
     let mut valu = None;
     if validated_umis_present {
         valu = Some(validated_umis);
@@ -653,6 +568,7 @@ fn parse_vector_entry_from_json(
         non_validated_umis: non_valu,
         invalidated_umis: invalu,
         frac_reads_used,
+        productive: v["productive"].as_bool().unwrap_or(false),
     });
     Ok(())
 }
@@ -724,137 +640,189 @@ pub fn read_json(
         ));
     }
     let mut f = BufReader::new(open_maybe_compressed(&jsonx));
-    // ◼ This loop could be speeded up, see comments above.
-    let mut xs = Vec::<Vec<u8>>::new();
-    loop {
-        let x = read_vector_entry_from_json(&mut f);
-        if x.is_err() {
-            eprintln!("\nProblem reading {}.\n", jsonx);
-            return Err(format!("{}", x.err().unwrap()));
-        }
-        match x.unwrap() {
-            None => break,
-            Some(x) => {
-                xs.push(x);
+
+    // Read and parse the entries in batches, to bound memory usage.
+
+    const BATCH: usize = 10_000;
+    let mut done = false;
+    while !done {
+        // ◼ This loop could be speeded up, see comments above.
+        let mut xs = Vec::<Vec<u8>>::new();
+        while xs.len() < BATCH {
+            let x = read_vector_entry_from_json(&mut f);
+            if x.is_err() {
+                eprintln!("\nProblem reading {}.\n", jsonx);
+                return Err(format!("{}", x.err().unwrap()));
+            }
+            match x.unwrap() {
+                None => {
+                    done = true;
+                    break;
+                }
+                Some(x) => {
+                    xs.push(x);
+                }
+            }
+        }
+        let mut results = Vec::<(
+            usize,
+            Vec<String>,
+            Vec<String>,
+            bool,
+            String,
+            Vec<TigData>,
+            String,
+        )>::new();
+        for i in 0..xs.len() {
+            results.push((
+                i,
+                Vec::<String>::new(),
+                Vec::<String>::new(),
+                false,
+                String::new(),
+                Vec::<TigData>::new(),
+                String::new(),
+            ));
+        }
+        let exiting = AtomicBool::new(false);
+        results.par_iter_mut().for_each(|res| {
+            let i = res.0;
+            let resx = parse_vector_entry_from_json(
+                &xs[i],
+                json,
+                accept_inconsistent,
+                origin_info,
+                li,
+                refdata,
+                to_ref_index,
+                reannotate,
+                ctl,
+                &mut res.1,
+                &mut res.2,
+                &mut res.3,
+                &mut res.4,
+                &mut res.5,
+                &exiting,
+            );
+            if resx.is_err() {
+                res.6 = resx.unwrap_err();
+            }
+        });
+        for i in 0..results.len() {
+            if !results[i].6.is_empty() {
+                return Err(results[i].6.clone());
+            }
+        }
+        for i in 0..xs.len() {
+            vdj_cells.append(&mut results[i].1);
+            gex_cells.append(&mut results[i].2);
+            if results[i].3 {
+                *gex_cells_specified = true;
             }
+            if !results[i].4.is_empty() {
+                *cr_version = results[i].4.clone();
+            }
+            tigs.append(&mut results[i].5);
         }
     }
-    let mut results = Vec::<(
+    unique_sort(gex_cells);
+    let tig_bc = group_tigs_by_barcode(&tigs, vdj_cells, gex_cells, ctl);
+
+    // Done.
+
+    Ok(tig_bc)
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Load the contigs for one dataset.
+
+#[allow(clippy::type_complexity)]
+fn load_dataset(
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    to_ref_index: &HashMap<usize, usize>,
+    ann: &str,
+    res: &mut (
         usize,
+        Vec<(String, usize)>,
+        Vec<Vec<TigData>>,
+        Vec<Vec<u8>>,
+        String,
         Vec<String>,
         Vec<String>,
         bool,
         String,
-        Vec<TigData>,
-        String,
-    )>::new();
-    for i in 0..xs.len() {
-        results.push((
-            i,
-            Vec::<String>::new(),
-            Vec::<String>::new(),
-            false,
-            String::new(),
-            Vec::<TigData>::new(),
-            String::new(),
-        ));
-    }
-    let exiting = AtomicBool::new(false);
-    results.par_iter_mut().for_each(|res| {
-        let i = res.0;
-        let resx = parse_vector_entry_from_json(
-            &xs[i],
-            json,
-            accept_inconsistent,
-            origin_info,
+    ),
+) {
+    let li = res.0;
+    let resx;
+    let mut cache = String::new();
+    if is_airr_path(&ctl.origin_info.dataset_path[li]) {
+        resx = read_airr(li, refdata, ctl, &mut res.5, &mut res.6, &mut res.7);
+    } else {
+        let json = format!("{}/{}", ctl.origin_info.dataset_path[li], ann);
+        let json_lz4 = format!("{}/{}.lz4", ctl.origin_info.dataset_path[li], ann);
+        if !path_exists(&json) && !path_exists(&json_lz4) {
+            res.8 = format!("\ncan't find {} or {}\n", json, json_lz4);
+            return;
+        }
+        if use_vdj_cache(ctl) {
+            let jsonx = if path_exists(&json) { &json } else { &json_lz4 };
+            match vdj_cache_path(ctl, refdata, to_ref_index, li, jsonx) {
+                Ok(path) => cache = path,
+                Err(e) => {
+                    res.8 = e;
+                    return;
+                }
+            }
+            if let Some(x) = read_vdj_cache(&cache, refdata.refs.len()) {
+                res.2 = x.0;
+                res.4 = x.1;
+                res.5 = x.2;
+                res.6 = x.3;
+                res.7 = x.4;
+                return;
+            }
+        }
+        resx = read_json(
+            ctl.gen_opt.accept_inconsistent,
+            &ctl.origin_info,
             li,
+            &json,
             refdata,
             to_ref_index,
-            reannotate,
-            ctl,
-            &mut res.1,
-            &mut res.2,
-            &mut res.3,
+            ctl.gen_opt.reannotate,
             &mut res.4,
+            ctl,
             &mut res.5,
-            &exiting,
+            &mut res.6,
+            &mut res.7,
         );
-        if resx.is_err() {
-            res.6 = resx.unwrap_err();
-        }
-    });
-    for i in 0..results.len() {
-        if !results[i].6.is_empty() {
-            return Err(results[i].6.clone());
-        }
     }
-    for i in 0..xs.len() {
-        vdj_cells.append(&mut results[i].1);
-        gex_cells.append(&mut results[i].2);
-        if results[i].3 {
-            *gex_cells_specified = true;
+    if resx.is_ok() {
+        let tig_bc: Vec<Vec<TigData>> = resx.unwrap();
+        res.5.sort();
+        res.2 = tig_bc;
+        if !cache.is_empty() {
+            write_vdj_cache(&cache, &res.2, &res.4, &res.5, &res.6, res.7);
         }
-        if !results[i].4.is_empty() {
-            *cr_version = results[i].4.clone();
-        }
-        tigs.append(&mut results[i].5);
+    } else {
+        res.8 = resx.err().unwrap();
     }
-    unique_sort(gex_cells);
-    let mut tig_bc = Vec::<Vec<TigData>>::new();
-    let mut r = 0;
-    while r < tigs.len() {
-        let mut s = r + 1;
-        while s < tigs.len() {
-            if tigs[s].barcode != tigs[r].barcode {
-                break;
-            }
-            s += 1;
-        }
+}
 
-        // For now we require at most four contigs (but we don't yet merge foursies).
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
-        if s - r <= 4 || ctl.clono_filt_opt_def.nmax {
-            let mut bc_tigs = Vec::<TigData>::new();
-            for u in r..s {
-                bc_tigs.push(tigs[u].clone());
-            }
-            bc_tigs.sort();
-            tig_bc.push(bc_tigs);
-        }
-        r = s;
-    }
-    unique_sort(vdj_cells);
-
-    // Subsample.
-
-    if ctl.gen_opt.subsample >= 0.0 {
-        let mut rng = rand::thread_rng();
-        let mut to_delete1 = vec![false; tig_bc.len()];
-        let mut to_delete2 = vec![false; vdj_cells.len()];
-        let mut to_delete3 = vec![false; gex_cells.len()];
-        for i in 0..tig_bc.len() {
-            let y: f64 = rng.gen();
-            if y < 1.0 - ctl.gen_opt.subsample {
-                to_delete1[i] = true;
-                let bc = &tig_bc[i][0].barcode;
-                let p = bin_position(&vdj_cells, &bc);
-                if p >= 0 {
-                    to_delete2[p as usize] = true;
-                }
-                let p = bin_position(&gex_cells, &bc);
-                if p >= 0 {
-                    to_delete3[p as usize] = true;
-                }
-            }
-        }
-        erase_if(&mut tig_bc, &to_delete1);
-        erase_if(vdj_cells, &to_delete2);
-        erase_if(gex_cells, &to_delete3);
-    }
+// Temporary directory for STREAM_INPUT, which is removed when it goes out of scope, including
+// on early return with an error.
 
-    // Done.
+struct SpillDir(String);
 
-    Ok(tig_bc)
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
 }
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -903,36 +871,34 @@ pub fn parse_json_annotations_files(
     } else {
         ann = "contig_annotations.json";
     }
-    results.par_iter_mut().for_each(|res| {
-        let li = res.0;
-        let json = format!("{}/{}", ctl.origin_info.dataset_path[li], ann);
-        let json_lz4 = format!("{}/{}.lz4", ctl.origin_info.dataset_path[li], ann);
-        if !path_exists(&json) && !path_exists(&json_lz4) {
-            res.8 = format!("\ncan't find {} or {}\n", json, json_lz4);
-            return;
-        }
-        let resx = read_json(
-            ctl.gen_opt.accept_inconsistent,
-            &ctl.origin_info,
-            li,
-            &json,
-            refdata,
-            to_ref_index,
-            ctl.gen_opt.reannotate,
-            &mut res.4,
-            ctl,
-            &mut res.5,
-            &mut res.6,
-            &mut res.7,
-        );
-        if resx.is_ok() {
-            let tig_bc: Vec<Vec<TigData>> = resx.unwrap();
-            res.5.sort();
-            res.2 = tig_bc;
-        } else {
-            res.8 = resx.err().unwrap();
+
+    // With STREAM_INPUT, datasets are loaded one at a time, and the contigs for each are
+    // written to disk and dropped, then read back once all datasets have been loaded.  This
+    // bounds the memory used while parsing, but not peak memory, since the contigs for all
+    // datasets are held in memory for clonotyping.
+
+    let mut spill_dir = None;
+    if !ctl.gen_opt.stream_input {
+        results
+            .par_iter_mut()
+            .for_each(|res| load_dataset(ctl, refdata, to_ref_index, ann, res));
+    } else {
+        let dir = SpillDir(format!(
+            "{}/enclone_spill_{}",
+            std::env::temp_dir().display(),
+            std::process::id()
+        ));
+        create_dir_all(&dir.0).map_err(|e| format!("\nUnable to create {}: {}.\n", dir.0, e))?;
+        for res in results.iter_mut() {
+            load_dataset(ctl, refdata, to_ref_index, ann, res);
+            if !res.8.is_empty() {
+                break;
+            }
+            write_tig_bc(&format!("{}/{}", dir.0, res.0), &res.2)?;
+            res.2 = Vec::new();
         }
-    });
+        spill_dir = Some(dir);
+    }
     for i in 0..results.len() {
         if !results[i].8.is_empty() {
             return Err(results[i].8.clone());
@@ -940,7 +906,9 @@ pub fn parse_json_annotations_files(
     }
     let mut versions = Vec::<String>::new();
     for i in 0..results.len() {
-        tig_bc.append(&mut results[i].2.clone());
+        if let Some(dir) = &spill_dir {
+            results[i].2 = read_tig_bc(&format!("{}/{}", dir.0, i))?;
+        }
         // ctl.gen_opt.cr_version = results[i].4.clone();
         if results[i].4.is_empty() {
             versions.push("≤3.1".to_string());
@@ -968,6 +936,7 @@ pub fn parse_json_annotations_files(
                 );
             }
         }
+        tig_bc.append(&mut results[i].2);
     }
     /*
     if !ctl.gen_opt.internal_run {
@@ -0,0 +1,264 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Utilities shared by the readers of VDJ contig data.
+
+use enclone_core::defs::{EncloneControl, OriginInfo, TigData};
+use rand::Rng;
+use vdj_ann::refx::RefData;
+use vector_utils::{bin_position, erase_if, unique_sort};
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// If there are multiple V segment alignments, possibly reduce to just one.
+
+pub fn reduce_v_alignments(refdata: &RefData, ann: &mut Vec<(i32, i32, i32, i32, i32)>) {
+    let mut ann2 = Vec::<(i32, i32, i32, i32, i32)>::new();
+    let mut j = 0;
+    while j < ann.len() {
+        let t = ann[j].2 as usize;
+        let mut k = j + 1;
+        while k < ann.len() {
+            if refdata.segtype[ann[k].2 as usize] != refdata.segtype[t] {
+                break;
+            }
+            k += 1;
+        }
+        if refdata.segtype[t] == "V" && k - j > 1 {
+            let mut entries = 1;
+            if j < ann.len() - 1
+                && ann[j + 1].2 as usize == t
+                && ((ann[j].0 + ann[j].1 == ann[j + 1].0 && ann[j].3 + ann[j].1 < ann[j + 1].3)
+                    || (ann[j].0 + ann[j].1 < ann[j + 1].0 && ann[j].3 + ann[j].1 == ann[j + 1].3))
+            {
+                entries = 2;
+            }
+            for l in j..j + entries {
+                ann2.push(ann[l]);
+            }
+        } else {
+            for l in j..k {
+                ann2.push(ann[l]);
+            }
+        }
+        j = k;
+    }
+    *ann = ann2;
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Fields of a contig that are derived from its annotations.
+
+pub struct AnnFields {
+    pub left: bool,
+    pub v_ref_id: usize,
+    pub d_ref_id: Option<usize>,
+    pub j_ref_id: usize,
+    pub c_ref_id: Option<usize>,
+    pub u_ref_id: Option<usize>,
+    pub chain_type: String,
+    pub tig_start: isize,
+    pub tig_stop: isize,
+    pub v_stop: usize,
+    pub v_stop_ref: usize,
+    pub d_start: Option<usize>,
+    pub j_start: usize,
+    pub j_start_ref: usize,
+    pub c_start: Option<usize>,
+    pub annv: Vec<(i32, i32, i32, i32, i32)>,
+}
+
+impl Default for AnnFields {
+    fn default() -> Self {
+        AnnFields {
+            left: false,
+            v_ref_id: 1000000,
+            d_ref_id: None,
+            j_ref_id: 0,
+            c_ref_id: None,
+            u_ref_id: None,
+            chain_type: String::new(),
+            tig_start: -1,
+            tig_stop: -1,
+            v_stop: 0,
+            v_stop_ref: 0,
+            d_start: None,
+            j_start: 0,
+            j_start_ref: 0,
+            c_start: None,
+            annv: Vec::new(),
+        }
+    }
+}
+
+// Traverse the annotations of a contig, as computed by annotate_seq, and make cdr3_start relative
+// to the start of the V segment.  Return None if the V segment starts after the CDR3.
+
+pub fn traverse_annotations(
+    refdata: &RefData,
+    ann: &[(i32, i32, i32, i32, i32)],
+    gamma_delta: bool,
+    cdr3_start: &mut usize,
+) -> Option<AnnFields> {
+    let mut a = AnnFields::default();
+    let mut seen_j = false;
+    for i in 0..ann.len() {
+        let t = ann[i].2 as usize;
+        if refdata.is_u(t) {
+            a.u_ref_id = Some(t);
+        } else if refdata.is_v(t) && !seen_j {
+            a.v_ref_id = t;
+            a.annv.push(ann[i]);
+            a.chain_type = refdata.name[t][0..3].to_string();
+            if a.chain_type == *"IGH"
+                || a.chain_type == *"TRB"
+                || (a.chain_type == *"TRD" && gamma_delta)
+            {
+                a.left = true;
+            }
+            if ann[i].3 == 0 {
+                a.tig_start = ann[i].0 as isize;
+                if a.tig_start > *cdr3_start as isize {
+                    return None;
+                }
+                *cdr3_start -= a.tig_start as usize;
+            }
+            a.v_stop = (ann[i].0 + ann[i].1) as usize;
+            a.v_stop_ref = (ann[i].3 + ann[i].1) as usize;
+        } else if refdata.is_d(t) {
+            a.d_start = Some(ann[i].0 as usize);
+            a.d_ref_id = Some(t);
+        } else if refdata.is_j(t) {
+            a.j_ref_id = t;
+            a.tig_stop = (ann[i].0 + ann[i].1) as isize;
+            a.j_start = ann[i].0 as usize;
+            a.j_start_ref = ann[i].3 as usize;
+            seen_j = true;
+        } else if refdata.is_c(t) {
+            a.c_ref_id = Some(t);
+            a.c_start = Some(ann[i].0 as usize);
+        }
+    }
+    for i in (0..a.annv.len()).rev() {
+        a.annv[i].0 -= a.annv[0].0;
+    }
+    Some(a)
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Find the origin, donor and tag indices for a barcode in a dataset.
+
+pub fn origin_donor_tag(
+    origin_info: &OriginInfo,
+    li: usize,
+    barcode: &str,
+) -> (Option<usize>, Option<usize>, Option<usize>) {
+    let mut origin = None;
+    let mut donor = None;
+    let mut tag = None;
+    if origin_info.origin_for_bc[li].contains_key(barcode) {
+        origin = Some(origin_info.origin_for_bc[li][barcode].clone());
+    } else {
+        // the way we use s1 here is flaky
+        if !origin_info.origin_id[li].is_empty()
+            && (origin_info.origin_id[li] != *"s1" || origin_info.origin_for_bc[li].is_empty())
+        {
+            origin = Some(origin_info.origin_id[li].clone());
+        }
+    }
+    if origin_info.donor_for_bc[li].contains_key(barcode) {
+        donor = Some(origin_info.donor_for_bc[li][barcode].clone());
+    } else {
+        // the way we use d1 here is flaky
+        if !origin_info.origin_id[li].is_empty()
+            && (origin_info.donor_id[li] != *"d1" || origin_info.donor_for_bc[li].is_empty())
+        {
+            donor = Some(origin_info.donor_id[li].clone());
+        }
+    }
+    if origin_info.tag[li].contains_key(barcode) {
+        tag = Some(origin_info.tag[li][barcode].clone());
+    }
+    let mut origin_index = None;
+    let mut donor_index = None;
+    let mut tag_index = None;
+    if origin.is_some() {
+        if origin.is_some() {
+            origin_index = Some(bin_position(&origin_info.origin_list, &origin.unwrap()) as usize);
+        }
+        if donor.is_some() {
+            donor_index = Some(bin_position(&origin_info.donor_list, &donor.unwrap()) as usize);
+        }
+    }
+    if tag.is_some() {
+        tag_index = Some(bin_position(&origin_info.tag_list, &tag.unwrap()) as usize);
+    }
+    (origin_index, donor_index, tag_index)
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Group contigs by barcode, which assumes that they are sorted by barcode, and then subsample
+// if requested.  This also sorts vdj_cells.
+
+pub fn group_tigs_by_barcode(
+    tigs: &Vec<TigData>,
+    vdj_cells: &mut Vec<String>,
+    gex_cells: &mut Vec<String>,
+    ctl: &EncloneControl,
+) -> Vec<Vec<TigData>> {
+    let mut tig_bc = Vec::<Vec<TigData>>::new();
+    let mut r = 0;
+    while r < tigs.len() {
+        let mut s = r + 1;
+        while s < tigs.len() {
+            if tigs[s].barcode != tigs[r].barcode {
+                break;
+            }
+            s += 1;
+        }
+
+        // For now we require at most four contigs (but we don't yet merge foursies).
+
+        if s - r <= 4 || ctl.clono_filt_opt_def.nmax {
+            let mut bc_tigs = Vec::<TigData>::new();
+            for u in r..s {
+                bc_tigs.push(tigs[u].clone());
+            }
+            bc_tigs.sort();
+            tig_bc.push(bc_tigs);
+        }
+        r = s;
+    }
+    unique_sort(vdj_cells);
+
+    // Subsample.
+
+    if ctl.gen_opt.subsample >= 0.0 {
+        let mut rng = rand::thread_rng();
+        let mut to_delete1 = vec![false; tig_bc.len()];
+        let mut to_delete2 = vec![false; vdj_cells.len()];
+        let mut to_delete3 = vec![false; gex_cells.len()];
+        for i in 0..tig_bc.len() {
+            let y: f64 = rng.gen();
+            if y < 1.0 - ctl.gen_opt.subsample {
+                to_delete1[i] = true;
+                let bc = &tig_bc[i][0].barcode;
+                let p = bin_position(&vdj_cells, &bc);
+                if p >= 0 {
+                    to_delete2[p as usize] = true;
+                }
+                let p = bin_position(&gex_cells, &bc);
+                if p >= 0 {
+                    to_delete3[p as usize] = true;
+                }
+            }
+        }
+        erase_if(&mut tig_bc, &to_delete1);
+        erase_if(vdj_cells, &to_delete2);
+        erase_if(gex_cells, &to_delete3);
+    }
+
+    tig_bc
+}
@@ -0,0 +1,244 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// On-disk cache of parsed contig data, turned on by VDJ_CACHE=dir.  For each dataset, the result
+// of reading the contig annotations file is saved, and on later runs it is used instead of
+// reading the file again.  A cache entry is named by a hash of the content of the annotations
+// file, together with a hash of everything else that affects the result: the reference
+// sequence, options that change annotation or filtering, and the origin, donor and tag
+// assignments for the dataset.
+//
+// The cache is not used if results are random (SUBSAMPLE), or if options that print information
+// while reading are used.  A cache entry that cannot be decoded, or whose contigs are not
+// consistent with the reference, is ignored, and the annotations file is read instead.  Failure
+// to write a cache entry is also ignored.
+
+use enclone_core::defs::{EncloneControl, TigData};
+use enclone_core::stable_id::{fnv1a, fnv1a_extend, FNV1A_START};
+use enclone_core::tig_bin::{read_tig_bc_with_header, write_tig_bc_with_header};
+use io_utils::path_exists;
+use std::collections::HashMap;
+use std::fs::{create_dir_all, remove_file, rename, File};
+use std::io::Read;
+use string_utils::TextUtils;
+use vdj_ann::refx::RefData;
+
+// Bump this if the content of TigData or its binary encoding changes.
+
+const VDJ_CACHE_VERSION: usize = 2;
+
+pub fn use_vdj_cache(ctl: &EncloneControl) -> bool {
+    !ctl.gen_opt.vdj_cache.is_empty()
+        && ctl.gen_opt.subsample < 0.0
+        && ctl.gen_opt.trace_barcode.is_empty()
+        && !ctl.gen_opt.log_nonproductive
+}
+
+fn hash_file(path: &str) -> Result<u64, String> {
+    let mut f = File::open(path).map_err(|e| format!("\nUnable to open {}: {}.\n", path, e))?;
+    let mut buf = vec![0_u8; 1 << 20];
+    let mut h = FNV1A_START;
+    loop {
+        let n = f
+            .read(&mut buf)
+            .map_err(|e| format!("\nUnable to read {}: {}.\n", path, e))?;
+        if n == 0 {
+            break;
+        }
+        h = fnv1a_extend(h, &buf[0..n]);
+    }
+    Ok(h)
+}
+
+fn sorted_map(m: &HashMap<String, String>) -> String {
+    let mut x = m.iter().collect::<Vec<_>>();
+    x.sort();
+    format!("{:?}", x)
+}
+
+// Return the path of the cache entry for dataset li, whose annotations are in the file json.
+
+pub fn vdj_cache_path(
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    to_ref_index: &HashMap<usize, usize>,
+    li: usize,
+    json: &str,
+) -> Result<String, String> {
+    let content = hash_file(json)?;
+    let mut h = FNV1A_START;
+    for i in 0..refdata.refs.len() {
+        h = fnv1a_extend(h, refdata.name[i].as_bytes());
+        h = fnv1a_extend(h, refdata.refs[i].to_string().as_bytes());
+    }
+    let mut to_ref = to_ref_index.iter().collect::<Vec<_>>();
+    to_ref.sort();
+    let gen = &ctl.gen_opt;
+    let oi = &ctl.origin_info;
+    let descrip = format!(
+        "{} {:016x} {:?} {} {} {} {} {} {} {} {} {} {} {}\n{:?} {:?} {:?}\n{}\n{}\n{}",
+        VDJ_CACHE_VERSION,
+        h,
+        to_ref,
+        li,
+        gen.reannotate,
+        gen.reprod,
+        gen.ncell,
+        gen.gamma_delta,
+        gen.accept_inconsistent,
+        gen.built_in,
+        gen.imgt,
+        gen.imgt_fix,
+        gen.cellranger,
+        ctl.clono_filt_opt_def.nmax,
+        (&oi.origin_id[li], &oi.donor_id[li]),
+        (&oi.origin_list, &oi.donor_list),
+        &oi.tag_list,
+        sorted_map(&oi.origin_for_bc[li]),
+        sorted_map(&oi.donor_for_bc[li]),
+        sorted_map(&oi.tag[li]),
+    );
+    Ok(format!(
+        "{}/{:016x}_{:016x}.bin",
+        gen.vdj_cache,
+        content,
+        fnv1a(descrip.as_bytes())
+    ))
+}
+
+// Check that a contig read from the cache could have been produced by read_json, for a
+// reference having nrefs entries.
+
+fn valid_tig(t: &TigData, nrefs: usize) -> bool {
+    let ids = [
+        Some(t.v_ref_id),
+        Some(t.j_ref_id),
+        t.u_ref_id,
+        t.d_ref_id,
+        t.c_ref_id,
+    ];
+    ids.iter().all(|id| id.map_or(true, |id| id < nrefs))
+        && t.v_start <= t.j_stop
+        && t.j_stop <= t.full_seq.len()
+        && t.len == t.j_stop - t.v_start
+        && t.quals.len() == t.len
+        && t.full_quals.len() == t.full_seq.len()
+        && t.cdr3_start
+            .checked_add(3 * t.cdr3_aa.len())
+            .map_or(false, |stop| stop <= t.len)
+}
+
+// Read a cache entry, returning the contig data, cr_version, vdj_cells, gex_cells and
+// gex_cells_specified, as returned by read_json.  Return None if the entry does not exist or
+// is not valid.
+
+pub fn read_vdj_cache(
+    path: &str,
+    nrefs: usize,
+) -> Option<(Vec<Vec<TigData>>, String, Vec<String>, Vec<String>, bool)> {
+    if !path_exists(path) {
+        return None;
+    }
+    let (mut header, tig_bc) = read_tig_bc_with_header(path, 3).ok()?;
+    let gex_cells = header.pop()?;
+    let vdj_cells = header.pop()?;
+    let info = header.pop()?;
+    if info.len() != 2 || (info[1] != "true" && info[1] != "false") {
+        return None;
+    }
+    if tig_bc
+        .iter()
+        .any(|tigs| tigs.is_empty() || tigs.iter().any(|t| !valid_tig(t, nrefs)))
+    {
+        return None;
+    }
+    Some((
+        tig_bc,
+        info[0].clone(),
+        vdj_cells,
+        gex_cells,
+        info[1] == "true",
+    ))
+}
+
+// Write a cache entry.  We write to a temporary file first, so that a concurrent run never
+// sees a partial entry.
+
+pub fn write_vdj_cache(
+    path: &str,
+    tig_bc: &[Vec<TigData>],
+    cr_version: &str,
+    vdj_cells: &[String],
+    gex_cells: &[String],
+    gex_cells_specified: bool,
+) {
+    let dir = path.rev_before("/");
+    if create_dir_all(dir).is_err() {
+        return;
+    }
+    let tmp = format!("{}.{}.tmp", path, std::process::id());
+    let header = vec![
+        vec![cr_version.to_string(), format!("{}", gex_cells_specified)],
+        vdj_cells.to_vec(),
+        gex_cells.to_vec(),
+    ];
+    if write_tig_bc_with_header(&tmp, &header, tig_bc).is_ok() {
+        let _ = rename(&tmp, path);
+    } else {
+        let _ = remove_file(&tmp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vdj_cache() {
+        let dir = format!(
+            "{}/enclone_vdj_cache_test_{}",
+            std::env::temp_dir().display(),
+            std::process::id()
+        );
+        let path = format!("{}/entry.bin", dir);
+        let t = TigData {
+            cdr3_aa: "CAR".to_string(),
+            full_seq: b"ACGTACGTACGT".to_vec(),
+            full_quals: vec![30; 12],
+            quals: vec![30; 10],
+            v_start: 1,
+            j_stop: 11,
+            len: 10,
+            j_ref_id: 2,
+            barcode: "AAAC-1".to_string(),
+            ..Default::default()
+        };
+        let cells = vec!["AAAC-1".to_string()];
+
+        // Cache miss.
+
+        assert!(read_vdj_cache(&path, 3).is_none());
+
+        // Cache hit.
+
+        write_vdj_cache(&path, &[vec![t.clone()]], "4.0", &cells, &[], true);
+        let x = read_vdj_cache(&path, 3).unwrap();
+        assert!(x.0 == vec![vec![t.clone()]]);
+        assert_eq!(
+            (x.1.as_str(), &x.2, x.3.is_empty(), x.4),
+            ("4.0", &cells, true, true)
+        );
+
+        // Entries that are inconsistent with the reference, or truncated, are rejected.
+
+        assert!(read_vdj_cache(&path, 2).is_none());
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(read_vdj_cache(&path, 3).is_none());
+        let mut u = t;
+        u.len = 20;
+        write_vdj_cache(&path, &[vec![u]], "4.0", &cells, &[], true);
+        assert!(read_vdj_cache(&path, 3).is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -133,13 +133,21 @@ pub fn parse_atom_pidgin(line: &str, chain_count: usize) -> Result<PdbAtomoid, S
 
 pub fn parse_atom(line: &str) -> Result<PdbAtom, String> {
     let fields = line.split_ascii_whitespace().collect::<Vec<&str>>();
+    if fields.len() < 13 {
+        return Err(format!("in pdb, too few fields in line {}", line));
+    }
     let atom0 = fields[2].as_bytes();
+    if atom0.len() > 2 {
+        return Err(format!(
+            "in pdb, atom name has more than two characters in line {}",
+            line
+        ));
+    }
     let mut atom = [0 as u8; 2];
     atom[0] = atom0[0];
     if atom0.len() == 2 {
         atom[1] = atom0[1];
     }
-    assert!(atom0.len() <= 2);
     let atom_alt0 = fields[3].as_bytes();
     if atom_alt0.len() > 4 {
         return Err(format!(
@@ -165,8 +173,11 @@ pub fn parse_atom(line: &str) -> Result<PdbAtom, String> {
     // Create atom.
 
     let aa = aa3_to_aa(&fields[5].as_bytes());
-    let pos_on_entity = fields[8].force_usize() as u16;
-    let entity = fields[7].force_usize() as u16;
+    let pos_on_entity = fields[8].parse::<u16>().unwrap_or(0);
+    let entity = fields[7].parse::<u16>().unwrap_or(0);
+    if pos_on_entity == 0 || entity == 0 {
+        return Err(format!("in pdb, invalid chain position in line {}", line));
+    }
     let xcoord = fields[10].force_f64() as f32;
     let ycoord = fields[11].force_f64() as f32;
     let zcoord = fields[12].force_f64() as f32;
@@ -217,6 +228,32 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
         eprintln!("No lines read.\n");
         std::process::exit(1);
     }
+    parse_pdb_structure(name, &lines)
+}
+
+// Read a structure from a gzipped mmCIF file dir/name.gz.  Unlike fetch_pdb_structure_gen, this
+// does not download anything, and returns an error rather than exiting.
+
+pub fn read_pdb_structure(name: &str, dir: &str) -> Result<PdbStructure, String> {
+    let path = format!("{}/{}.gz", dir, name);
+    let f = File::open(&path).map_err(|e| format!("unable to open {}: {}", path, e))?;
+    let mut lines = Vec::<String>::new();
+    for line in BufReader::new(MultiGzDecoder::new(f)).lines() {
+        lines.push(line.map_err(|e| format!("error reading {}: {}", path, e))?);
+    }
+    if lines.is_empty() {
+        return Err(format!("no lines read from {}", path));
+    }
+    parse_pdb_structure(name, &lines)
+}
+
+// Parse the lines of an mmCIF file.  A truncated or malformed file yields an error (or possibly
+// a structure with fewer chains), rather than a panic.
+
+pub fn parse_pdb_structure(name: &str, lines: &[String]) -> Result<PdbStructure, String> {
+    // Lines past the end are treated as the end of a section.
+
+    let line = |j: usize| lines.get(j).map_or("#", |s| s.as_str());
     let mut chain_names = Vec::<String>::new();
     let mut chains = Vec::<Vec<u8>>::new();
     let mut ms = Vec::<PdbAtom>::new();
@@ -227,7 +264,7 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
         if lines[i].starts_with("_entity.details") {
             let mut elines = Vec::<String>::new();
             let mut j = i + 1;
-            while !lines[j].starts_with('#') {
+            while !line(j).starts_with('#') {
                 elines.push(lines[j].to_string());
                 j += 1;
             }
@@ -249,8 +286,7 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
                             }
                         }
                         if !known {
-                            eprintln!("unknown type {}", mtype);
-                            std::process::exit(1);
+                            return Err(format!("in pdb {}, unknown type {}", name, mtype));
                         }
                     }
                     starts.push(m);
@@ -271,7 +307,8 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
                 while s.contains("  ") {
                     s = s.replace("  ", " ");
                 }
-                if s.between(" ", " ") == "polymer" {
+                if s.contains(' ') && s.after(" ").contains(' ') && s.between(" ", " ") == "polymer"
+                {
                     let mut t = s.after("polymer").to_string();
                     if t.contains("'") && t.after("'").contains("'") {
                         t = t.between("'", "'").to_string();
@@ -287,17 +324,20 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
             while j < lines.len() && !lines[j].starts_with('#') {
                 if lines[j].contains("'polypeptide(L)'") {
                     if lines[j].ends_with("? ") {
-                        let s = lines[j]
-                            .after("no no ")
-                            .between(" ", " ")
-                            .as_bytes()
-                            .to_vec();
+                        let t = &lines[j];
+                        if !t.contains("no no ")
+                            || !t.after("no no ").contains(' ')
+                            || !t.after("no no ").after(" ").contains(' ')
+                        {
+                            return Err(format!("in pdb {}, unable to parse chain sequence", name));
+                        }
+                        let s = t.after("no no ").between(" ", " ").as_bytes().to_vec();
                         chains.push(s);
                         j += 1;
                         continue;
                     }
                     j += 1;
-                    if lines[j].starts_with("#") {
+                    if line(j).starts_with("#") {
                         break;
                     }
                     if !lines[j].contains(";") {
@@ -311,18 +351,21 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
                     for _ in 0..2 {
                         while j < lines.len() {
                             j += 1;
-                            if lines[j].starts_with(";") {
+                            if line(j).starts_with(";") {
                                 break;
                             }
                         }
                     }
+                    if j >= lines.len() {
+                        break;
+                    }
 
                     // Extract the second entry.
 
                     let mut s = lines[j].after(";").as_bytes().to_vec();
                     while j < lines.len() {
                         j += 1;
-                        if lines[j].starts_with(";") {
+                        if j == lines.len() || lines[j].starts_with(";") {
                             break;
                         }
                         s.append(&mut lines[j].as_bytes().to_vec());
@@ -343,7 +386,7 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
                 let mut s = lines[j].after(";").as_bytes().to_vec();
                 while j < lines.len() {
                     j += 1;
-                    if lines[j].starts_with(";") {
+                    if j == lines.len() || lines[j].starts_with(";") {
                         break;
                     }
                     s.append(&mut lines[j].as_bytes().to_vec());
@@ -352,7 +395,7 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
             }
             i = j;
         } else if lines[i].starts_with("ATOM ") {
-            let m = parse_atom(&lines[i]).unwrap();
+            let m = parse_atom(&lines[i])?;
             ms.push(m);
             i += 1;
         } else {
@@ -377,7 +420,7 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
         for i in 0..chains.len() {
             loop {
                 let mut found = false;
-                for j in 0..chains[i].len() - 4 {
+                for j in 0..chains[i].len().saturating_sub(4) {
                     if chains[i][j] == b'(' && chains[i][j + 4] == b')' {
                         let s = stringme(&chains[i]);
                         let t = s.replace(&strme(&chains[i][j..j + 4]), "X");
@@ -391,8 +434,7 @@ pub fn fetch_pdb_structure_gen(name: &str, dir: &str) -> Result<PdbStructure, St
                 }
             }
             if chains[i].contains(&b'(') {
-                eprintln!("\nProblem with {} in {}.\n", strme(&chains[i]), name);
-                std::process::exit(1);
+                return Err(format!("problem with {} in {}", strme(&chains[i]), name));
             }
         }
         Ok(PdbStructure {
@@ -4,7 +4,7 @@
 
 // Lead variables for exact subclonotypes and cells.
 
-pub const LVARS_ALLOWED: [&str; 42] = [
+pub const LVARS_ALLOWED: [&str; 55] = [
     "datasets",
     "origins",
     "donors",
@@ -24,7 +24,16 @@ pub const LVARS_ALLOWED: [&str; 42] = [
     "clust",
     "cred",
     "cred_cell",
-    "type",
+    "umap_x",
+    "umap_y",
+    "tsne_x",
+    "tsne_y",
+    "ag_score",
+    "ag_score_cell",
+    "ag_spec",
+    "ag_spec_clono",
+    "cell_type",
+    "cell_type_cell",
     "entropy",
     "entropy_cell",
     "near",
@@ -32,6 +41,7 @@ pub const LVARS_ALLOWED: [&str; 42] = [
     "dref",
     "dref_aa",
     "dref_max",
+    "doublet_score",
     "ext",
     "mark",
     "inkt",
@@ -47,11 +57,14 @@ pub const LVARS_ALLOWED: [&str; 42] = [
     "jun_ins",
     "jun_mat",
     "jun_sub",
+    "join_id",
+    "clonotype_sid",
+    "exact_sid",
 ];
 
 // Chain variables that can be used for contigs and chains
 
-pub const CVARS_ALLOWED: [&str; 104] = [
+pub const CVARS_ALLOWED: [&str; 105] = [
     "var",
     "u",
     "u_min",
@@ -156,6 +169,7 @@ pub const CVARS_ALLOWED: [&str; 104] = [
     "ulen_ref",
     "utr",
     "const_dna",
+    "pgen",
 ];
 
 // We should not have to specify this.
@@ -0,0 +1,168 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Antigen specificity scoring, for feature barcodes that represent antigens, as in LIBRA-seq or
+// barcoded multimer experiments (options ANTIGENS, ANTIGEN_CONTROLS and ANTIGEN_MIN_SCORE).
+//
+// The background for a cell is the mean UMI count of the negative control features.  The score
+// of an antigen for a cell is log2((n + 1) / (b + 1)), where n is the UMI count of the antigen
+// and b is the background.  A set of cells, for example an exact subclonotype or a clonotype, is
+// called specific for an antigen if the median score of its cells is at least
+// ANTIGEN_MIN_SCORE.  The call is the list of these antigens, separated by +, or else none.
+
+use enclone_base::median::median_f64;
+
+// Find the antigen and control features amongst the features of a dataset, which have the form
+// id\tname\ttype.  Features may be specified by id or name.  If no antigens are specified, all
+// features of type Antigen Capture that are not controls are used.  Return the antigen names
+// and the indices of the antigen and control features.
+
+pub fn find_antigen_features(
+    features: &[String],
+    antigens: &[String],
+    controls: &[String],
+) -> Result<(Vec<String>, Vec<usize>, Vec<usize>), String> {
+    let find = |f: &str| {
+        features.iter().position(|x| {
+            let fields = x.split('\t').collect::<Vec<&str>>();
+            fields[0] == f || (fields.len() > 1 && fields[1] == f)
+        })
+    };
+    let name = |j: usize| features[j].split('\t').nth(1).unwrap_or("").to_string();
+    let mut control_ids = Vec::<usize>::new();
+    for c in controls.iter() {
+        match find(c) {
+            Some(j) => control_ids.push(j),
+            None => {
+                return Err(format!(
+                    "\nThe control {} in ANTIGEN_CONTROLS is not a feature.\n",
+                    c
+                ));
+            }
+        }
+    }
+    let (mut names, mut ids) = (Vec::<String>::new(), Vec::<usize>::new());
+    if antigens.is_empty() {
+        for j in 0..features.len() {
+            if features[j]
+                .split('\t')
+                .nth(2)
+                .unwrap_or("")
+                .starts_with("Antigen")
+                && !control_ids.contains(&j)
+            {
+                names.push(name(j));
+                ids.push(j);
+            }
+        }
+        if ids.is_empty() {
+            return Err(
+                "\nThere are no features of type Antigen Capture, so ANTIGENS must be \
+                specified.\n"
+                    .to_string(),
+            );
+        }
+    }
+    for a in antigens.iter() {
+        match find(a) {
+            Some(j) => {
+                if control_ids.contains(&j) {
+                    return Err(format!(
+                        "\nThe feature {} is specified by both ANTIGENS and ANTIGEN_CONTROLS.\n",
+                        a
+                    ));
+                }
+                names.push(name(j));
+                ids.push(j);
+            }
+            None => {
+                return Err(format!(
+                    "\nThe antigen {} in ANTIGENS is not a feature.\n",
+                    a
+                ));
+            }
+        }
+    }
+    Ok((names, ids, control_ids))
+}
+
+// Compute the scores of the antigens for a cell, given the UMI counts of the antigens and of the
+// controls.
+
+pub fn antigen_scores(counts: &[f64], control_counts: &[f64]) -> Vec<f64> {
+    let mut b = 0.0;
+    if !control_counts.is_empty() {
+        b = control_counts.iter().sum::<f64>() / control_counts.len() as f64;
+    }
+    counts
+        .iter()
+        .map(|n| ((n + 1.0) / (b + 1.0)).log2())
+        .collect()
+}
+
+// Compute the median score of each antigen, given the scores for each cell.
+
+pub fn median_antigen_scores(scores: &[Vec<f64>]) -> Vec<f64> {
+    let mut medians = Vec::<f64>::new();
+    if scores.is_empty() {
+        return medians;
+    }
+    for j in 0..scores[0].len() {
+        let mut x = scores.iter().map(|s| s[j]).collect::<Vec<f64>>();
+        x.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        medians.push(median_f64(&x));
+    }
+    medians
+}
+
+// Make the specificity call for a set of cells, given the scores for each cell.  This is empty
+// if there are no cells.
+
+pub fn antigen_call(names: &[String], scores: &[Vec<f64>], min_score: f64) -> String {
+    if scores.is_empty() {
+        return String::new();
+    }
+    let medians = median_antigen_scores(scores);
+    let mut call = Vec::<String>::new();
+    for j in 0..names.len() {
+        if medians[j] >= min_score {
+            call.push(names[j].clone());
+        }
+    }
+    if call.is_empty() {
+        "none".to_string()
+    } else {
+        call.join("+")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_antigen_call() {
+        let features = vec![
+            "HA\tHA\tAntigen Capture".to_string(),
+            "NA\tNA\tAntigen Capture".to_string(),
+            "BSA\tBSA\tAntigen Capture".to_string(),
+            "CD19\tCD19\tAntibody Capture".to_string(),
+        ];
+        let (names, ids, controls) =
+            find_antigen_features(&features, &[], &["BSA".to_string()]).unwrap();
+        assert_eq!(names, vec!["HA", "NA"]);
+        assert_eq!((ids, controls), (vec![0, 1], vec![2]));
+        assert!(
+            find_antigen_features(&features, &["HA".to_string()], &["HA".to_string()]).is_err()
+        );
+
+        // The background is 3, so 31 UMIs give a score of 3.
+
+        let s = antigen_scores(&[31.0, 3.0], &[2.0, 4.0]);
+        assert_eq!(s, vec![3.0, 0.0]);
+        let scores = vec![s.clone(), vec![2.5, 2.5], vec![0.0, 0.0]];
+        assert_eq!(antigen_call(&names, &scores, 2.0), "HA");
+        assert_eq!(antigen_call(&names, &scores[1..2], 2.0), "HA+NA");
+        assert_eq!(antigen_call(&names, &scores[2..], 2.0), "none");
+        assert_eq!(antigen_call(&names, &[], 2.0), "");
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Assign cell types to cells from the expression of marker genes.  This is used when Cell Ranger
+// did not provide cell types (the file celltypes.csv).
+//
+// The normalized expression of a gene in a cell is ln(1 + 10000 * n / N), where n is the UMI
+// count of the gene and N is the total gene expression UMI count of the cell.  The score of a
+// marker set is the mean normalized expression of those of its genes that are features of the
+// dataset.  First the lineage is chosen as the highest scoring of B, T and plasmablast, provided
+// that its score is at least MIN_LINEAGE_SCORE, and otherwise the cell type is other.  Then for
+// B and T cells, the subset is chosen as the highest scoring subset, or if no subset marker is
+// expressed, the cell type is just B or T.  Genes are matched to feature names ignoring case, so
+// that for example mouse gene names also work.
+
+pub const MIN_LINEAGE_SCORE: f64 = 0.5;
+
+// Marker sets.  The first three are the lineages, then come the B subsets and the T subsets.
+
+pub const CELL_TYPE_MARKERS: [(&str, &[&str]); 10] = [
+    ("B", &["CD19", "MS4A1", "CD79A", "CD79B"]),
+    ("T", &["CD3D", "CD3E", "CD3G"]),
+    ("B plasmablast", &["JCHAIN", "MZB1", "XBP1", "PRDM1"]),
+    ("B naive", &["IGHD", "TCL1A", "FCER2", "IL4R"]),
+    ("B memory", &["CD27", "AIM2", "TNFRSF13B", "CD80"]),
+    ("T CD4", &["CD4", "IL7R", "CD40LG"]),
+    ("T CD8", &["CD8A", "CD8B"]),
+    ("T MAIT", &["SLC4A10", "KLRB1", "TRAV1-2", "ZBTB16"]),
+    ("T gamma-delta", &["TRDC", "TRGC1", "TRGC2"]),
+    ("T regulatory", &["FOXP3", "IL2RA", "CTLA4", "IKZF2"]),
+];
+
+// Find the indices of the marker genes amongst the features of a dataset, which have the form
+// id\tname\ttype.  Only gene expression features are used.
+
+pub fn marker_feature_ids(features: &[String]) -> Vec<Vec<usize>> {
+    let mut ids = vec![Vec::<usize>::new(); CELL_TYPE_MARKERS.len()];
+    for j in 0..features.len() {
+        let fields = features[j].split('\t').collect::<Vec<&str>>();
+        if fields.len() < 3 || !fields[2].starts_with("Gene") {
+            continue;
+        }
+        for i in 0..CELL_TYPE_MARKERS.len() {
+            for g in CELL_TYPE_MARKERS[i].1.iter() {
+                if fields[1].eq_ignore_ascii_case(g) {
+                    ids[i].push(j);
+                }
+            }
+        }
+    }
+    ids
+}
+
+// Assign a cell type to a cell, given its (feature index, UMI count) pairs for gene expression
+// features, and the output of marker_feature_ids.
+
+pub fn marker_cell_type(counts: &[(usize, usize)], ids: &[Vec<usize>]) -> String {
+    let total = counts.iter().map(|x| x.1).sum::<usize>();
+    if total == 0 {
+        return "other".to_string();
+    }
+    let mut scores = vec![0.0; ids.len()];
+    for i in 0..ids.len() {
+        if ids[i].is_empty() {
+            continue;
+        }
+        for x in counts.iter() {
+            if ids[i].contains(&x.0) {
+                scores[i] += (1.0 + 10000.0 * x.1 as f64 / total as f64).ln();
+            }
+        }
+        scores[i] /= ids[i].len() as f64;
+    }
+    let best = |r: std::ops::Range<usize>| {
+        let mut b = r.start;
+        for i in r {
+            if scores[i] > scores[b] {
+                b = i;
+            }
+        }
+        b
+    };
+    let lineage = best(0..3);
+    if scores[lineage] < MIN_LINEAGE_SCORE {
+        return "other".to_string();
+    }
+    let subset = match lineage {
+        0 => best(3..5),
+        1 => best(5..10),
+        _ => lineage,
+    };
+    if scores[subset] > 0.0 {
+        CELL_TYPE_MARKERS[subset].0.to_string()
+    } else {
+        CELL_TYPE_MARKERS[lineage].0.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marker_cell_type() {
+        let features = vec![
+            "ENSG1\tCD79A\tGene Expression".to_string(),
+            "ENSG2\tCD3E\tGene Expression".to_string(),
+            "ENSG3\tTCL1A\tGene Expression".to_string(),
+            "ENSG4\tCd8a\tGene Expression".to_string(),
+            "ENSG5\tJCHAIN\tGene Expression".to_string(),
+            "CD3\tCD3E\tAntibody Capture".to_string(),
+            "ENSG6\tACTB\tGene Expression".to_string(),
+        ];
+        let ids = marker_feature_ids(&features);
+        assert_eq!(ids[1], vec![1]);
+        assert_eq!(ids[6], vec![3]);
+        assert_eq!(
+            marker_cell_type(&[(0, 20), (2, 5), (6, 500)], &ids),
+            "B naive"
+        );
+        assert_eq!(marker_cell_type(&[(0, 20), (6, 500)], &ids), "B");
+        assert_eq!(
+            marker_cell_type(&[(1, 20), (3, 10), (6, 500)], &ids),
+            "T CD8"
+        );
+        assert_eq!(
+            marker_cell_type(&[(0, 5), (4, 200), (6, 500)], &ids),
+            "B plasmablast"
+        );
+        assert_eq!(marker_cell_type(&[(6, 500)], &ids), "other");
+    }
+}
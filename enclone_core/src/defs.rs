@@ -2,6 +2,7 @@
 
 use crate::cell_color::CellColor;
 use crate::linear_condition::LinearCondition;
+use crate::pgen::PgenModel;
 use debruijn::dna_string::DnaString;
 use evalexpr::Node;
 
@@ -126,10 +127,14 @@ pub struct GeneralOpt {
     pub fasta: String,
     pub fasta_filename: String,
     pub fasta_aa_filename: String,
+    pub airr_filename: String,
+    pub airr_cell_filename: String,
     pub clustal_aa: String,
     pub clustal_dna: String,
     pub phylip_aa: String,
     pub phylip_dna: String,
+    pub lineage_newick: String,
+    pub lineage_json: String,
     pub min_cells_exact: usize,
     pub min_chains_exact: usize,
     pub chains_exact: usize,
@@ -149,6 +154,7 @@ pub struct GeneralOpt {
     pub dref_file: String,
     pub mouse: bool,
     pub refname: String,
+    pub germline_ref: String,
     pub noprint: bool,
     pub noprintx: bool,
     pub required_fps: Option<usize>,
@@ -164,12 +170,40 @@ pub struct GeneralOpt {
     pub summary: bool,
     pub summary_clean: bool,
     pub summary_csv: bool,
+    pub diversity: bool,
+    pub diversity_csv: bool,
+    pub shm_profile: bool,
+    pub shm_profile_svg: String,
+    pub stable_ids: bool,
+    pub stream_input: bool,
+    pub vdj_cache: String,
+    pub shard_donors: bool,
+    pub pgen_dir: String,
+    pub pgen_models: HashMap<String, PgenModel>,
+    pub public_clusters: String,
+    pub pdb_match: String,
+    pub pdb_match_id: String,
+    pub novel_alleles: bool,
+    pub novel_alleles_fasta: String,
+    pub donor_alleles: String,
+    pub qc_report: String,
+    pub gex_obs: String,
+    pub embedding: String,
     pub cr_version: String,
     pub nwarn: bool,
     pub gene_scan_test: Option<LinearCondition>,
     pub gene_scan_control: Option<LinearCondition>,
     pub gene_scan_threshold: Option<LinearCondition>,
     pub gene_scan_exact: bool,
+    pub diff_exp: bool,
+    pub diff_exp_rest: bool,
+    pub diff_exp_test_groups: Vec<usize>,
+    pub diff_exp_control_groups: Vec<usize>,
+    pub diff_exp_out: String,
+    pub antigens: Vec<String>,
+    pub antigen_controls: Vec<String>,
+    pub antigen_min_score: f64,
+    pub marker_cell_types: bool,
     pub clonotype_group_names: Option<String>,
     pub origin_color_map: HashMap<String, String>,
     pub accept_inconsistent: bool, // TEMPORARY!
@@ -297,6 +331,8 @@ pub struct PlotOpt {
     pub split_plot_by_dataset: bool,
     pub split_plot_by_origin: bool,
     pub png_width: Option<usize>,
+    pub plot_embedding: String, // umap or tsne, to plot cells in embedding space
+    pub plot_highlight: Vec<usize>, // clonotype (group) numbers to highlight
 }
 
 // Allele-finding algorithmic options.
@@ -362,6 +398,8 @@ pub struct JoinAlgOpt {
     pub comp_filt: usize,
     pub comp_filt_bound: usize,
     pub super_comp_filt: usize,
+    pub save_join: String, // file to save join state to
+    pub load_join: String, // file to load join state from
 }
 
 // Clonotype filtering options.
@@ -377,6 +415,7 @@ pub struct ClonoFiltOptDefault {
     pub non_cell_mark: bool,
     pub weak_onesies: bool,        // filter weak onesies
     pub doublet: bool,             // filter putative doublets
+    pub doublet_mark: bool,        // doublet filter (but only mark)
     pub fcell: Vec<Node>,          // constraints from FCELL
     pub umi_filt: bool,            // umi count filter
     pub umi_filt_mark: bool,       // umi count filter (but only mark)
@@ -442,6 +481,7 @@ pub struct ClonoPrintOpt {
     pub barcodes: bool, // print the list of barcodes
     pub note_simple: bool, // note if V..J is simple
     pub amino: Vec<String>, // categories for amino acid columns (per-chain per-exact subclonotype)
+    pub amino_imgt: bool, // label amino acid columns by IMGT position
     pub cvars: Vec<String>, // per-chain per-exact-clonotype columns
     pub lvars: Vec<String>, // per-exact-clonotype ('lead') columns
     pub regex_match: Vec<HashMap<String, Vec<usize>>>, // matching features for <regex>_g etc.
@@ -658,6 +698,7 @@ pub struct TigData {
     pub non_validated_umis: Option<Vec<String>>, // non-validated UMIs
     pub invalidated_umis: Option<Vec<String>>, // invalidated UMIs
     pub frac_reads_used: Option<u32>, // fraction of reads passed to assembly stage in CR
+    pub productive: bool, // true if contig was called productive upstream
 }
 
 impl TigData {
@@ -692,7 +733,8 @@ pub struct TigData0 {
     pub non_validated_umis: Option<Vec<String>>, // non-validated UMIs
     pub invalidated_umis: Option<Vec<String>>,   // invalidated UMIs
     pub frac_reads_used: Option<u32>,            // fraction of reads passed to assembly stage in CR
-    pub v_ref_id: usize, // index of V segment reference sequence in ref file
+    pub v_ref_id: usize,  // index of V segment reference sequence in ref file
+    pub productive: bool, // true if contig was called productive upstream
 }
 
 #[derive(Clone, Default)]
@@ -769,6 +811,8 @@ impl TigData1 {
 pub struct ExactClonotype {
     pub share: Vec<TigData1>,       // clone info that is shared
     pub clones: Vec<Vec<TigData0>>, // clone info, excluding shared stuff
+    pub join_id: Option<usize>,     // clonotype id from join state, see SAVE_JOIN
+    pub doublet_score: Option<f64>, // score from doublet filter, if computed
 }
 
 impl ExactClonotype {
@@ -849,6 +893,10 @@ pub struct GexInfo {
     pub cell_type: Vec<HashMap<String, String>>,
     pub cell_type_specified: Vec<bool>,
     pub pca: Vec<HashMap<String, Vec<f64>>>,
+    pub umap: Vec<HashMap<String, (f64, f64)>>,
+    pub tsne: Vec<HashMap<String, (f64, f64)>>,
+    pub antigen_names: Vec<String>,
+    pub antigen_fids: Vec<(Vec<usize>, Vec<usize>)>,
     pub gex_mults: Vec<f64>,
     pub fb_mults: Vec<f64>,
     pub h5_data: Vec<Option<Dataset>>,
@@ -945,6 +993,11 @@ pub fn justification(x: &str) -> u8 {
 
 pub const POUT_SEP: &str = "\x07";
 
+// Value of POUT that causes parseable output to be computed, but not written anywhere.  This is
+// used by the library interface, which reads the values from memory.
+
+pub const POUT_MEMORY: &str = ":memory:";
+
 // Potential join structure.
 
 #[derive(Default)]
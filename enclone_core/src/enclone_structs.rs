@@ -1,7 +1,7 @@
 // Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
 
 use self::refx::RefData;
-use crate::defs::{AlleleData, CloneInfo, EncloneControl, ExactClonotype, GexInfo};
+use crate::defs::{AlleleData, CloneInfo, ColInfo, EncloneControl, ExactClonotype, GexInfo};
 use enclone_proto::types::DonorReferenceItem;
 use qd::Double;
 use std::{collections::HashMap, time::Instant};
@@ -27,6 +27,17 @@ pub struct MainEncloneOutput {
 pub struct EncloneState {
     pub inter: EncloneIntermediates,
     pub outs: MainEncloneOutput,
+    pub clonotypes: EncloneClonotypes,
+}
+
+// The clonotypes that were found.  The order in which they are printed is given by groups.
+
+#[derive(Default)]
+pub struct EncloneClonotypes {
+    pub exacts: Vec<Vec<usize>>, // exact subclonotype ids for each clonotype
+    pub rsi: Vec<ColInfo>,       // column info for each clonotype
+    pub out_datas: Vec<Vec<HashMap<String, String>>>, // parseable values for exact subclonotypes
+    pub groups: Vec<Vec<(i32, String)>>, // groups, as indices into exacts
 }
 
 #[derive(Default)]
@@ -59,5 +70,6 @@ pub struct EncloneExacts {
     pub sr: Vec<Vec<Double>>,
     pub fate: Vec<HashMap<String, String>>, // GETS MODIFIED SUBSEQUENTLY
     pub is_bcr: bool,
+    pub warnings: Vec<String>, // warnings raised, shown in the QC report
     pub allele_data: AlleleData,
 }
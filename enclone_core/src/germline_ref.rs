@@ -0,0 +1,330 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Import of germline FASTA files from IMGT or OGRDB (option GERMLINE_REF), converting them into
+// a reference in the Cell Ranger format, which can then be used to build a RefData object.
+//
+// IMGT headers have the form >accession|IGHV1-2*02|species|functionality|region|..., whereas
+// OGRDB headers begin with the allele name, e.g. >IGHV1-2*02, and the segment type is inferred
+// from the name.  Sequences may be IMGT-gapped, with gaps denoted by dots.  Pseudogenes are
+// skipped, and only the first allele of each gene is kept.  The numbers of records skipped for
+// these reasons, and of ORFs skipped because they fail validation, are returned, so that they
+// can be reported.
+//
+// Region boundaries are validated.  For a gapped V segment, gaps must consist of entire codons,
+// so that IMGT codon positions are preserved, the conserved cysteines at positions 23 and 104
+// must be present, and CDR1 (27-38) and CDR2 (56-65) must not be empty.  For an ungapped V
+// segment, there must be a cysteine in frame near its end, which defines the start of the CDR3.
+// A J segment must contain the F/W-G-X-G motif that defines the end of the CDR3.  A functional
+// gene that fails validation is an error, whereas an ORF that fails validation is skipped.
+
+use amino::aa_seq;
+use string_utils::TextUtils;
+use vector_utils::unique_sort;
+
+pub const LOCI: [&str; 7] = ["IGH", "IGK", "IGL", "TRA", "TRB", "TRD", "TRG"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GermlineRecord {
+    pub gene: String,     // e.g. IGHV1-2
+    pub allele: String,   // e.g. 02
+    pub segment: char,    // V, D, J or C
+    pub functional: bool, // false for ORF or pseudogene
+    pub pseudogene: bool, // true for pseudogene
+    pub species: String,  // empty if unknown
+    pub gapped: bool,
+    pub seq: Vec<u8>, // as given, including any gaps
+}
+
+// Parse the records in a germline FASTA file, named source, that are not regions other than V,
+// D, J and C.
+
+pub fn parse_germline_fasta(fasta: &str, source: &str) -> Result<Vec<GermlineRecord>, String> {
+    let mut recs = Vec::<GermlineRecord>::new();
+    let lines = fasta.lines().collect::<Vec<&str>>();
+    let mut i = 0;
+    while i < lines.len() {
+        let header = lines[i].trim_end();
+        i += 1;
+        if header.is_empty() {
+            continue;
+        }
+        if !header.starts_with('>') {
+            return Err(format!(
+                "\nThe file {} is not a FASTA file: the line\n{}\nshould be a header line.\n",
+                source, header
+            ));
+        }
+        let mut seq = Vec::<u8>::new();
+        while i < lines.len() && !lines[i].starts_with('>') {
+            seq.append(&mut lines[i].trim().to_ascii_uppercase().as_bytes().to_vec());
+            i += 1;
+        }
+        let header = &header[1..];
+        let (name, species, functionality, label);
+        if header.contains('|') {
+            let fields = header.split('|').collect::<Vec<&str>>();
+            if fields.len() < 5 {
+                return Err(format!(
+                    "\nThe header line\n>{}\nin {} has fewer than the five fields of an IMGT \
+                    header.\n",
+                    header, source
+                ));
+            }
+            name = fields[1].trim();
+            species = fields[2].trim();
+            functionality = fields[3].trim().replace(['(', ')', '[', ']'], "");
+            label = fields[4].trim();
+        } else {
+            name = header.split_whitespace().next().unwrap_or("");
+            species = "";
+            functionality = "F".to_string();
+            label = "";
+        }
+        if name.len() < 4 || !LOCI.contains(&&name[0..3]) {
+            return Err(format!(
+                "\nThe allele name {} in {} does not begin with one of {}.\n",
+                name,
+                source,
+                LOCI.join(", ")
+            ));
+        }
+        let segment = if label.is_empty() {
+            name.as_bytes()[3] as char
+        } else if label == "V-REGION" {
+            'V'
+        } else if label == "D-REGION" {
+            'D'
+        } else if label == "J-REGION" {
+            'J'
+        } else if label.starts_with("C-REGION") || label == "CH1" || label == "EX1" {
+            'C'
+        } else {
+            continue;
+        };
+        if !"VDJC".contains(segment) {
+            return Err(format!(
+                "\nUnable to determine the segment type of {} in {}.\n",
+                name, source
+            ));
+        }
+        let (gene, allele) = if name.contains('*') {
+            (name.before("*"), name.after("*"))
+        } else {
+            (name, "00")
+        };
+        recs.push(GermlineRecord {
+            gene: gene.to_string(),
+            allele: allele.to_string(),
+            segment,
+            functional: functionality == "F",
+            pseudogene: functionality == "P",
+            species: species.to_string(),
+            gapped: seq.contains(&b'.'),
+            seq,
+        });
+    }
+    Ok(recs)
+}
+
+fn is_cys(codon: &[u8]) -> bool {
+    codon == b"TGT" || codon == b"TGC"
+}
+
+// Validate a record, returning its ungapped sequence or a description of the problem.
+
+pub fn validate_germline(r: &GermlineRecord) -> Result<Vec<u8>, String> {
+    let seq = r
+        .seq
+        .iter()
+        .filter(|c| **c != b'.')
+        .cloned()
+        .collect::<Vec<u8>>();
+    if seq.is_empty() {
+        return Err("the sequence is empty".to_string());
+    }
+    if let Some(c) = seq.iter().find(|c| !b"ACGT".contains(c)) {
+        return Err(format!("the sequence contains {}", *c as char));
+    }
+    if r.segment == 'V' && r.gapped {
+        let s = &r.seq;
+        if s.len() < 312 {
+            return Err("the gapped sequence ends before IMGT position 104".to_string());
+        }
+        for p in 0..s.len() {
+            if s[p] == b'.' {
+                let start = p - p % 3;
+                if start + 3 > s.len() || s[start..start + 3] != *b"..." {
+                    return Err(format!(
+                        "the gap at base {} is not an entire IMGT codon",
+                        p + 1
+                    ));
+                }
+            }
+        }
+        for pos in [23, 104] {
+            if !is_cys(&s[3 * (pos - 1)..3 * pos]) {
+                return Err(format!("there is no cysteine at IMGT position {}", pos));
+            }
+        }
+        for (cdr, start, stop) in [(1, 27, 38), (2, 56, 65)] {
+            if s[3 * (start - 1)..3 * stop].iter().all(|c| *c == b'.') {
+                return Err(format!("CDR{} is empty", cdr));
+            }
+        }
+    } else if r.segment == 'V' {
+        let n = seq.len() / 3;
+        if (n.saturating_sub(5)..n).all(|i| !is_cys(&seq[3 * i..3 * i + 3])) {
+            return Err("there is no cysteine in frame near the end".to_string());
+        }
+    } else if r.segment == 'J' {
+        let mut found = false;
+        for frame in 0..3 {
+            let aa = aa_seq(&seq, frame);
+            for k in 0..aa.len().saturating_sub(3) {
+                if (aa[k] == b'F' || aa[k] == b'W') && aa[k + 1] == b'G' && aa[k + 3] == b'G' {
+                    found = true;
+                }
+            }
+        }
+        if !found {
+            return Err("there is no F/W-G-X-G motif".to_string());
+        }
+    }
+    Ok(seq)
+}
+
+// Numbers of germline records that were not converted.
+
+#[derive(Default, Debug, PartialEq)]
+pub struct GermlineSkips {
+    pub alleles: usize,     // alleles of a gene after the first
+    pub pseudogenes: usize, // pseudogenes
+    pub orfs: usize,        // ORFs that failed validation
+}
+
+// Convert germline FASTA files into a reference in the Cell Ranger format.  The argument files
+// consists of pairs (name, contents).
+
+pub fn germline_to_ref(files: &[(String, String)]) -> Result<(String, GermlineSkips), String> {
+    let mut refx = String::new();
+    let mut skips = GermlineSkips::default();
+    let mut genes = Vec::<String>::new();
+    let mut species = Vec::<String>::new();
+    let mut id = 0;
+    for (source, fasta) in files.iter() {
+        let recs = parse_germline_fasta(fasta, source)?;
+        for r in recs.iter() {
+            if r.pseudogene {
+                skips.pseudogenes += 1;
+                continue;
+            }
+            if genes.contains(&r.gene) {
+                skips.alleles += 1;
+                continue;
+            }
+            let seq = match validate_germline(r) {
+                Ok(seq) => seq,
+                Err(e) => {
+                    if !r.functional {
+                        skips.orfs += 1;
+                        continue;
+                    }
+                    return Err(format!(
+                        "\nThe germline file {} is not valid: for {}*{}, {}.\n",
+                        source, r.gene, r.allele, e
+                    ));
+                }
+            };
+            genes.push(r.gene.clone());
+            if !r.species.is_empty() {
+                species.push(r.species.clone());
+            }
+            let region = match r.segment {
+                'V' => "L-REGION+V-REGION",
+                'D' => "D-REGION",
+                'J' => "J-REGION",
+                _ => "C-REGION",
+            };
+            let source_type = if r.species.is_empty() {
+                "OGRDB"
+            } else {
+                "IMGT"
+            };
+            id += 1;
+            refx += &format!(
+                ">{}|{} {}|{}|{}|{}|{}|None|{}\n{}\n",
+                id,
+                r.gene,
+                source_type,
+                r.gene,
+                region,
+                &r.gene[0..2],
+                &r.gene[0..3],
+                r.allele,
+                std::str::from_utf8(&seq).unwrap()
+            );
+        }
+    }
+    unique_sort(&mut species);
+    if species.len() > 1 {
+        return Err(format!(
+            "\nThe germline files contain sequences from more than one species: {}.\n",
+            species.join(", ")
+        ));
+    }
+    if id == 0 {
+        return Err("\nThe germline files do not contain any V, D, J or C segments.\n".to_string());
+    }
+    Ok((refx, skips))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_germline_to_ref() {
+        // Make an IMGT-gapped V segment with cysteines at positions 23 and 104, and a gap at
+        // positions 32 and 33.
+
+        let mut v = b"GCT".repeat(104);
+        v[66..69].copy_from_slice(b"TGT");
+        v[309..312].copy_from_slice(b"TGC");
+        v[93..99].copy_from_slice(b"......");
+        v.append(&mut b"GCGAGA".to_vec());
+        let v = String::from_utf8(v).unwrap();
+        let j = "TACTTTGACTACTGGGGCCAAGGAACCCTGGTCACCGTCTCCTCAG";
+        let imgt = format!(
+            ">X1|IGHV9-1*02|Macaca mulatta|F|V-REGION|\n{}\n\
+             >X2|IGHV9-2*01|Macaca mulatta|P|V-REGION|\nACGT\n\
+             >X3|IGHJ1*01|Macaca mulatta|F|J-REGION|\n{}\n\
+             >X4|IGHV9-1*03|Macaca mulatta|F|V-REGION|\n{}\n\
+             >X5|IGHJ2*01|Macaca mulatta|ORF|J-REGION|\nACGT\n",
+            v, j, v
+        );
+        let (r, skips) = germline_to_ref(&[("imgt.fa".to_string(), imgt.clone())]).unwrap();
+        assert_eq!(
+            skips,
+            GermlineSkips {
+                alleles: 1,
+                pseudogenes: 1,
+                orfs: 1
+            }
+        );
+        let lines = r.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[0],
+            ">1|IGHV9-1 IMGT|IGHV9-1|L-REGION+V-REGION|IG|IGH|None|02"
+        );
+        assert_eq!(lines[1].len(), 312);
+        assert_eq!(lines[3], j);
+
+        // Moving the gap out of frame, or removing the J motif, is an error.
+
+        let bad = imgt.replacen("GCT......", "GC......T", 1);
+        assert!(germline_to_ref(&[("imgt.fa".to_string(), bad)]).is_err());
+        let ogrdb = ">IGHJ1*01\nTACTTTGACTACTCCGGCCAAGGAACC\n".to_string();
+        assert!(germline_to_ref(&[("ogrdb.fa".to_string(), ogrdb)]).is_err());
+    }
+}
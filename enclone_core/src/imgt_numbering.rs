@@ -0,0 +1,216 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// IMGT unique numbering of amino acid positions on V..J.  The regions are those computed by
+// enclone (FWR1, CDR1, FWR2, CDR2, FWR3, CDR3, FWR4), and these are mapped to the IMGT ranges
+// 1-26, 27-38, 39-55, 56-65, 66-104, 105-117 and 118-128, where the conserved cysteine at the
+// start of the enclone CDR3 is 104, and the conserved W or F at its end is 118.
+//
+// Short CDRs are numbered from both ends, leaving gaps at the center of the loop, in the order
+// 32, 33, 31, 34, ... (CDR1), 61, 60, 62, 59, ... (CDR2) and 111, 112, 110, 113, ... (CDR3).
+// Long CDRs have insertions at the center, for example 111.1, 111.2, 112.2, 112.1 for CDR3.
+// Short framework regions have gaps at the positions that are usually missing in germline
+// genes (10 in FWR1; 73, 81 and 82 in FWR3), and otherwise at the start of the region.  This
+// agrees with IMGT numbering for typical germline genes, but because no alignment to IMGT
+// gapped reference sequences is done, it may not agree for unusual framework lengths.
+
+// Number a CDR of the given length, occupying positions first..=last, where gap1 and gap2 are
+// the first two positions to be left empty (one of them being one more than the other).
+
+fn number_cdr(len: usize, first: usize, last: usize, gap1: usize, gap2: usize) -> Vec<String> {
+    let n = last - first + 1;
+    let mut labels = Vec::<String>::new();
+    if len <= n {
+        let mut gaps = Vec::<usize>::new();
+        let (mut g1, mut g2) = (gap1 as isize, gap2 as isize);
+        let d = g2 - g1;
+        while gaps.len() < n - len {
+            for g in [g1, g2] {
+                if gaps.len() < n - len && g >= first as isize && g <= last as isize {
+                    gaps.push(g as usize);
+                }
+            }
+            g1 -= d;
+            g2 += d;
+        }
+        for p in first..=last {
+            if !gaps.contains(&p) {
+                labels.push(format!("{}", p));
+            }
+        }
+    } else {
+        let (left, right) = (gap1.min(gap2), gap1.max(gap2));
+        let extra = len - n;
+        let (nleft, nright) = if gap1 < gap2 {
+            ((extra + 1) / 2, extra / 2)
+        } else {
+            (extra / 2, (extra + 1) / 2)
+        };
+        for p in first..=left {
+            labels.push(format!("{}", p));
+        }
+        for k in 1..=nleft {
+            labels.push(format!("{}.{}", left, k));
+        }
+        for k in (1..=nright).rev() {
+            labels.push(format!("{}.{}", right, k));
+        }
+        for p in right..=last {
+            labels.push(format!("{}", p));
+        }
+    }
+    labels
+}
+
+// Number a framework region of the given length, occupying positions first..=last, where gaps
+// lists the positions to be left empty first.
+
+fn number_fwr(len: usize, first: usize, last: usize, gaps: &[usize]) -> Vec<String> {
+    let mut pos = (first..=last).collect::<Vec<usize>>();
+    let mut labels = Vec::<String>::new();
+    if len <= pos.len() {
+        let mut remove = pos.len() - len;
+        for g in gaps.iter() {
+            if remove > 0 {
+                pos.retain(|p| p != g);
+                remove -= 1;
+            }
+        }
+        pos.drain(0..remove);
+        for p in pos.iter() {
+            labels.push(format!("{}", p));
+        }
+    } else {
+        for p in pos.iter() {
+            labels.push(format!("{}", p));
+        }
+        for k in 1..=len - pos.len() {
+            labels.push(format!("{}.{}", last, k));
+        }
+    }
+    labels
+}
+
+// Return the IMGT position for each amino acid on V..J, given the start positions of regions in
+// bases, the length of the CDR3 in amino acids, and the length of V..J in bases.  Positions that
+// are not numbered are empty.  If CDR1, FWR2, CDR2 or FWR3 is unknown, only CDR3 and FWR4 are
+// numbered.
+
+pub fn imgt_numbering(
+    fr1_start: usize,
+    cdr1_start: Option<usize>,
+    fr2_start: Option<usize>,
+    cdr2_start: Option<usize>,
+    fr3_start: Option<usize>,
+    cdr3_start: usize,
+    cdr3_len: usize,
+    seq_len: usize,
+) -> Vec<String> {
+    let naa = seq_len / 3;
+    let mut labels = vec![String::new(); naa];
+    let mut assign = |start: usize, x: Vec<String>| {
+        for (j, s) in x.into_iter().enumerate() {
+            if start + j < naa {
+                labels[start + j] = s;
+            }
+        }
+    };
+    if let (Some(c1), Some(f2), Some(c2), Some(f3)) = (cdr1_start, fr2_start, cdr2_start, fr3_start)
+    {
+        let b = [
+            fr1_start / 3,
+            c1 / 3,
+            f2 / 3,
+            c2 / 3,
+            f3 / 3,
+            cdr3_start / 3,
+        ];
+        if b.windows(2).all(|w| w[0] <= w[1]) {
+            assign(b[0], number_fwr(b[1] - b[0], 1, 26, &[10]));
+            assign(b[1], number_cdr(b[2] - b[1], 27, 38, 32, 33));
+            assign(b[2], number_fwr(b[3] - b[2], 39, 55, &[]));
+            assign(b[3], number_cdr(b[4] - b[3], 56, 65, 61, 60));
+            assign(b[4], number_fwr(b[5] - b[4], 66, 103, &[73, 81, 82]));
+        }
+    }
+    if cdr3_len >= 2 {
+        let c3 = cdr3_start / 3;
+        let mut x = vec!["104".to_string()];
+        x.append(&mut number_cdr(cdr3_len - 2, 105, 117, 111, 112));
+        for p in 118..118 + naa.saturating_sub(c3 + cdr3_len - 1) {
+            if p <= 128 {
+                x.push(format!("{}", p));
+            } else {
+                x.push(format!("128.{}", p - 128));
+            }
+        }
+        assign(c3, x);
+    }
+    labels
+}
+
+// Determine if an IMGT position lies in the given range, ignoring any insertion suffix.
+
+pub fn imgt_in_range(label: &str, start: usize, stop: usize) -> bool {
+    let p = label.split('.').next().unwrap();
+    match p.parse::<usize>() {
+        Ok(p) => p >= start && p <= stop,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_imgt_numbering() {
+        // IGHV3-23-like heavy chain: FWR1 25, CDR1 8, FWR2 17, CDR2 8, FWR3 37 (without the
+        // cysteine), junction 12 amino acids, then ten FWR4 amino acids after the W.
+
+        let (fr1, cdr1, fr2, cdr2, fr3) = (25, 8, 17, 8, 37);
+        let starts = [
+            0,
+            3 * fr1,
+            3 * (fr1 + cdr1),
+            3 * (fr1 + cdr1 + fr2),
+            3 * (fr1 + cdr1 + fr2 + cdr2),
+        ];
+        let cdr3_start = 3 * (fr1 + cdr1 + fr2 + cdr2 + fr3);
+        let seq_len = cdr3_start + 3 * (12 + 10);
+        let x = imgt_numbering(
+            starts[0],
+            Some(starts[1]),
+            Some(starts[2]),
+            Some(starts[3]),
+            Some(starts[4]),
+            cdr3_start,
+            12,
+            seq_len,
+        );
+        assert_eq!(x[8], "9");
+        assert_eq!(x[9], "11");
+        assert_eq!(x[24], "26");
+        assert_eq!(x[28], "30");
+        assert_eq!(x[29], "35");
+        assert_eq!(x[53], "59");
+        assert_eq!(x[54], "62");
+        assert_eq!(x[64], "72");
+        assert_eq!(x[65], "74");
+        assert_eq!(x[95], "104");
+        assert_eq!(x[96], "105");
+        assert_eq!(x[100], "109");
+        assert_eq!(x[101], "113");
+        assert_eq!(x[105], "117");
+        assert_eq!(x[106], "118");
+        assert_eq!(x[116], "128");
+
+        // Long CDR3 and short kappa-like CDR2.
+
+        assert_eq!(
+            number_cdr(15, 105, 117, 111, 112)[6..10].to_vec(),
+            vec!["111", "111.1", "112.1", "112"]
+        );
+        assert_eq!(number_cdr(3, 56, 65, 61, 60), vec!["56", "57", "65"]);
+        assert!(imgt_in_range("111.1", 105, 117));
+    }
+}
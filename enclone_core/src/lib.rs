@@ -1,22 +1,29 @@
 // Copyright (c) 2021 10x Genomics, Inc. All rights reserved.
 
 pub mod allowed_vars;
+pub mod antigen;
 pub mod cell_color;
+pub mod cell_type;
 pub mod combine_group_pics;
 pub mod defs;
 pub mod enclone_structs;
+pub mod germline_ref;
 pub mod hcomp;
+pub mod imgt_numbering;
 pub mod join_one;
 pub mod linear_condition;
 pub mod logging;
 pub mod main_testlist;
 pub mod mammalian_fixed_len;
+pub mod pgen;
 pub mod prepare_for_apocalypse;
 pub mod set_speakers;
 pub mod slurp;
+pub mod stable_id;
 pub mod stringulate;
 pub mod test_def;
 pub mod testlist;
+pub mod tig_bin;
 pub mod var_reg;
 
 use lazy_static::lazy_static;
@@ -1,6 +1,6 @@
 // Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
 
-pub const TESTS: [&str; 310] = [
+pub const TESTS: [&str; 312] = [
     // 1. tests variant base after CDR3, parseable output
     r###"BCR=123089 CDR3=CVRDRQYYFDYW POUT=stdout
      PCOLS=exact_subclonotype_id,n,v_name1,v_name2,nchains,var_indices_aa1,barcodes"###,
@@ -774,4 +774,42 @@ pub const TESTS: [&str; 310] = [
     r###"BCR=86237 CELLS=3 POUT=stdout PCOLS=leader_dna_ref1"###,
     // 310. test leader_aa_ref
     r###"BCR=86237 CELLS=3 POUT=stdout PCOLS=leader_aa_ref1"###,
+    // 311. test DOUBLET_MARK and doublet_score
+    r###"BCR=123085,123089,124547 NDOUBLET DOUBLET_MARK LVARSP=doublet_score POUT=/dev/null
+         PCOLS=doublet_score NOPRINT EXPECT_OK"###,
+    // 312. test that DOUBLET_MARK requires NDOUBLET
+    r###"BCR=123085 DOUBLET_MARK EXPECT_FAIL"###,
+    // 313. test ANTIGEN_CONTROLS and ag_spec and ag_spec_clono
+    r###"BCR=86237 GEX=85679 ANTIGEN_CONTROLS=CD25 ANTIGENS=CD19 LVARSP=ag_spec,ag_spec_clono
+         POUT=/dev/null PCOLS=ag_spec,ag_spec_clono NOPRINT EXPECT_OK"###,
+    // 314. test PUBLIC_CLUSTERS
+    r###"BCR="123085;123089" GROUP=vj_heavy_refname,cdr3_aa_heavy≥80% MIN_GROUP=2
+         PUBLIC_CLUSTERS=stdout NOPRINT EXPECT_OK"###,
+    // 315. test that PUBLIC_CLUSTERS requires GROUP
+    r###"BCR=123085 PUBLIC_CLUSTERS=stdout EXPECT_FAIL"###,
+    // 316. test DIFF_EXP
+    r###"BCR=123085 GEX=123217 LVARSP=IGHV1-69D_g_μ MIN_CELLS=10 NGEX
+         DIFF_EXP="(IGHV1-69D_g_μ)>=100,rest" DIFF_EXP_OUT=/dev/null NOPRINT H5 EXPECT_OK"###,
+    // 317. test DIFF_EXP_GROUPS
+    r###"BCR=123085 GEX=123217 MIN_CELLS=10 NGEX DIFF_EXP_GROUPS=1+2,rest
+         DIFF_EXP_OUT=/dev/null NOPRINT H5 EXPECT_OK"###,
+    // 318. test that DIFF_EXP_GROUPS can't be used with SCAN_EXACT
+    r###"BCR=123085 GEX=123217 DIFF_EXP_GROUPS=1,rest SCAN_EXACT EXPECT_FAIL"###,
+    // 319. test that DIFF_EXP_GROUPS requires positive group numbers
+    r###"BCR=123085 GEX=123217 DIFF_EXP_GROUPS=0,rest EXPECT_FAIL"###,
+    // 320. test AMINO=imgt:a-b and AMINO_IMGT
+    r###"BCR=86237 CDR3=CARSFFGDTAMVMFQAFDPW AMINO=cdr3,imgt:27-38 AMINO_IMGT EXPECT_OK"###,
+    // 321. test imgt_S_E_aa
+    r###"BCR=86237 CELLS=3 CVARS=imgt_27_38_aa POUT=stdout
+         PCOLS=imgt_27_38_aa1,imgt_105_117_aa1,cdr3_aa1 EXPECT_OK"###,
+    // 322. test that AMINO=imgt:a-b requires a <= b
+    r###"BCR=86237 AMINO=imgt:38-27 EXPECT_FAIL"###,
+    // 323. test MARKED_B and cell_type for a dataset lacking celltypes.csv, so that cell types
+    // are computed from marker genes
+    r###"BCR=123085 GEX=123217 MARKED_B LVARSP=cell_type,n_b MIN_CELLS=10 H5 NOPRINT
+         EXPECT_OK"###,
+    // 324. test cell_type with PER_CELL
+    r###"BCR=123085 GEX=123217 LVARSP=cell_type MIN_CELLS=20 PER_CELL H5 EXPECT_OK"###,
+    // 325. test that PGEN_MODELS fails for a directory lacking models
+    r###"BCR=123085 PGEN_MODELS=testx/inputs EXPECT_FAIL"###,
 ];
@@ -0,0 +1,675 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Generation probability (Pgen) of CDR3 nucleotide sequences, computed natively.  Pgen is the
+// probability that V(D)J recombination yields the given sequence, from the conserved C through
+// the conserved W or F, summed over all recombination events that yield it.  The computation
+// follows OLGA.
+//
+// The recombination model for a chain type is read from a directory in the format used by IGoR
+// and OLGA, containing the files model_params.txt, model_marginals.txt, V_gene_CDR3_anchors.csv
+// and J_gene_CDR3_anchors.csv.  No models are bundled with enclone, so they must be supplied
+// using PGEN_MODELS, which may point to the default_models directory of an OLGA installation, to
+// use the human or mouse models distributed with OLGA.  A model specifies:
+// • gene usage: P(V) and P(J|V) for VJ chains, and P(V), P(J) and P(D|J) for VDJ chains;
+// • deletions at each gene end, conditioned on the gene, where a negative deletion adds
+//   palindromic bases;
+// • insertion lengths, and a Markov model for inserted bases, whose first base is drawn from the
+//   stationary distribution.  Bases between D and J are generated starting from the J side.
+
+use crate::defs::{EncloneControl, TigData1};
+use io_utils::path_exists;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use string_utils::TextUtils;
+
+#[derive(Clone, Default, PartialEq)]
+struct Insertion {
+    p_len: Vec<f64>,      // probability of each length
+    first: [f64; 4],      // distribution of the first base
+    trans: [[f64; 4]; 4], // transition probabilities
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct PgenModel {
+    vdj: bool,
+    v: Vec<Vec<usize>>,          // V genes, starting at the conserved C
+    j: Vec<Vec<usize>>,          // J genes, ending with the conserved W or F
+    d: Vec<Vec<usize>>,          // D genes
+    p_vj: Vec<Vec<f64>>,         // P(V,J)
+    p_d: Vec<Vec<f64>>,          // P(D|J)
+    del_v: Vec<i32>,             // deletions at 3' end of V
+    p_del_v: Vec<Vec<f64>>,      // P(deletion|V)
+    del_j: Vec<i32>,             // deletions at 5' end of J
+    p_del_j: Vec<Vec<f64>>,      // P(deletion|J)
+    del_d5: Vec<i32>,            // deletions at 5' end of D
+    del_d3: Vec<i32>,            // deletions at 3' end of D
+    p_del_d: Vec<Vec<Vec<f64>>>, // P(5' deletion, 3' deletion|D)
+    ins1: Insertion,             // VJ or VD insertion
+    ins2: Insertion,             // DJ insertion
+}
+
+// Bases are represented as 0, 1, 2, 3 for A, C, G, T, and 4 for anything else.
+
+fn base_index(b: u8) -> usize {
+    match b {
+        b'A' | b'a' => 0,
+        b'C' | b'c' => 1,
+        b'G' | b'g' => 2,
+        b'T' | b't' => 3,
+        _ => 4,
+    }
+}
+
+fn comp(b: usize) -> usize {
+    if b < 4 {
+        3 - b
+    } else {
+        4
+    }
+}
+
+// Base r of a gene g, where r may lie before or after g, in which case the base is palindromic.
+
+fn gene_base(g: &[usize], r: i64) -> Option<usize> {
+    let n = g.len() as i64;
+    if r < -n || r >= 2 * n {
+        None
+    } else if r < 0 {
+        Some(comp(g[(-r - 1) as usize]))
+    } else if r < n {
+        Some(g[r as usize])
+    } else {
+        Some(comp(g[(2 * n - 1 - r) as usize]))
+    }
+}
+
+// Test if s[start..start + len] equals the bases of g starting at r.
+
+fn gene_matches(s: &[usize], start: usize, len: usize, g: &[usize], r: i64) -> bool {
+    for q in 0..len {
+        if gene_base(g, r + q as i64) != Some(s[start + q]) {
+            return false;
+        }
+    }
+    true
+}
+
+impl Insertion {
+    // Return the matrix whose entry (a, b) is the probability that s[a..b] is inserted.  If
+    // reverse, the bases are generated starting from the end.
+
+    fn probs(&self, s: &[usize], reverse: bool) -> Vec<Vec<f64>> {
+        let n = s.len();
+        let mut m = vec![vec![0.0; n + 1]; n + 1];
+        for e in 0..=n {
+            let mut p = 1.0;
+            for len in 0..self.p_len.len() {
+                if (!reverse && e + len > n) || (reverse && len > e) {
+                    break;
+                }
+                let (a, b) = if reverse { (e - len, e) } else { (e, e + len) };
+                if len == 1 {
+                    p *= self.first[s[a]];
+                } else if len > 1 {
+                    p *= if reverse {
+                        self.trans[s[a + 1]][s[a]]
+                    } else {
+                        self.trans[s[b - 2]][s[b - 1]]
+                    };
+                }
+                m[a][b] = self.p_len[len] * p;
+            }
+        }
+        m
+    }
+}
+
+impl PgenModel {
+    pub fn pgen(&self, cdr3: &[u8]) -> f64 {
+        let s = cdr3.iter().map(|b| base_index(*b)).collect::<Vec<usize>>();
+        if s.contains(&4) {
+            return 0.0;
+        }
+        let n = s.len();
+
+        // Find the probability that each V yields s[0..x], and that each J yields s[y..n].
+
+        let mut pvx = vec![vec![0.0; n + 1]; self.v.len()];
+        for iv in 0..self.v.len() {
+            let v = &self.v[iv];
+            for k in 0..self.del_v.len() {
+                let len = v.len() as i64 - self.del_v[k] as i64;
+                if len >= 0 && len as usize <= n && gene_matches(&s, 0, len as usize, v, 0) {
+                    pvx[iv][len as usize] += self.p_del_v[iv][k];
+                }
+            }
+        }
+        let mut pjy = vec![vec![0.0; n + 1]; self.j.len()];
+        for ij in 0..self.j.len() {
+            let j = &self.j[ij];
+            for k in 0..self.del_j.len() {
+                let del = self.del_j[k] as i64;
+                let len = j.len() as i64 - del;
+                if len >= 0 && len as usize <= n {
+                    let y = n - len as usize;
+                    if gene_matches(&s, y, len as usize, j, del) {
+                        pjy[ij][y] += self.p_del_j[ij][k];
+                    }
+                }
+            }
+        }
+
+        // Find the D windows.  Each is given by (i, k, p), where s[i..k] is yielded by a D and its
+        // deletions with probability p.
+
+        let mut windows = vec![Vec::<(usize, usize, f64)>::new(); self.d.len()];
+        if self.vdj {
+            for id in 0..self.d.len() {
+                let d = &self.d[id];
+                let dlen = d.len() as i64;
+                for k5 in 0..self.del_d5.len() {
+                    let d5 = self.del_d5[k5] as i64;
+                    for i in 0..=n {
+                        let mut m = 0;
+                        while i + m < n && gene_base(d, d5 + m as i64) == Some(s[i + m]) {
+                            m += 1;
+                        }
+                        for k3 in 0..self.del_d3.len() {
+                            let d3 = self.del_d3[k3] as i64;
+                            let len = dlen - d5 - d3;
+                            if len >= 0 && d5.max(0) + d3.max(0) <= dlen && len as usize <= m {
+                                let p = self.p_del_d[id][k5][k3];
+                                if p > 0.0 {
+                                    windows[id].push((i, i + len as usize, p));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Sum over J genes.
+
+        let ins1 = self.ins1.probs(&s, false);
+        let ins2 = self.ins2.probs(&s, true);
+        let mut pgen = 0.0;
+        for ij in 0..self.j.len() {
+            let mut w = vec![0.0; n + 1];
+            for iv in 0..self.v.len() {
+                if self.p_vj[iv][ij] > 0.0 {
+                    for x in 0..=n {
+                        w[x] += self.p_vj[iv][ij] * pvx[iv][x];
+                    }
+                }
+            }
+            let mut a = vec![0.0; n + 1];
+            for x in 0..=n {
+                if w[x] > 0.0 {
+                    for i in x..=n {
+                        a[i] += w[x] * ins1[x][i];
+                    }
+                }
+            }
+            if self.vdj {
+                let mut b = vec![0.0; n + 1];
+                for id in 0..self.d.len() {
+                    let pd = self.p_d[ij][id];
+                    for (i, k, p) in windows[id].iter() {
+                        b[*k] += pd * a[*i] * p;
+                    }
+                }
+                a = b;
+                let mut c = vec![0.0; n + 1];
+                for k in 0..=n {
+                    for y in k..=n {
+                        c[k] += ins2[k][y] * pjy[ij][y];
+                    }
+                }
+                for k in 0..=n {
+                    pgen += a[k] * c[k];
+                }
+            } else {
+                for y in 0..=n {
+                    pgen += a[y] * pjy[ij][y];
+                }
+            }
+        }
+        pgen
+    }
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Conditional distributions from model_marginals.txt.  For an event, these are given by the
+// events it is conditioned on, and the distribution for each combination of their values.
+
+#[derive(Default)]
+struct Marginal {
+    parents: Vec<String>,
+    dist: HashMap<Vec<usize>, Vec<f64>>,
+}
+
+impl Marginal {
+    fn get(&self, given: &[(&str, usize)]) -> Option<&Vec<f64>> {
+        let mut key = Vec::<usize>::new();
+        for p in self.parents.iter() {
+            key.push(given.iter().find(|x| x.0 == p)?.1);
+        }
+        self.dist.get(&key)
+    }
+}
+
+fn bad_model(dir: &str, what: &str) -> String {
+    format!(
+        "\nThe recombination model in {} is not in the expected format: {}.\n",
+        dir, what
+    )
+}
+
+fn parse_marginals(s: &str, dir: &str) -> Result<HashMap<String, Marginal>, String> {
+    let mut marg = HashMap::<String, Marginal>::new();
+    let mut event = String::new();
+    let mut key = Vec::<usize>::new();
+    for line in s.lines() {
+        if line.starts_with('@') {
+            event = line.after("@").to_string();
+            marg.insert(event.clone(), Marginal::default());
+        } else if line.starts_with('#') || line.starts_with('%') {
+            let m = match marg.get_mut(&event) {
+                Some(m) => m,
+                None => return Err(bad_model(dir, "model_marginals.txt lacks an event name")),
+            };
+            if line.starts_with('#') {
+                m.parents.clear();
+                key.clear();
+                for x in line.split('[').skip(1) {
+                    let mut k = None;
+                    if x.contains(']') && x.before("]").contains(',') {
+                        k = x.before("]").rev_after(",").parse::<usize>().ok();
+                    }
+                    if k.is_none() {
+                        return Err(bad_model(dir, &format!("can't parse {}", line)));
+                    }
+                    m.parents.push(x.before("]").rev_before(",").to_string());
+                    key.push(k.unwrap());
+                }
+            } else {
+                let mut p = Vec::<f64>::new();
+                for x in line.after("%").split(',') {
+                    match x.parse::<f64>() {
+                        Ok(y) => p.push(y),
+                        Err(_) => return Err(bad_model(dir, &format!("can't parse {}", line))),
+                    }
+                }
+                m.dist.insert(key.clone(), p);
+            }
+        }
+    }
+    Ok(marg)
+}
+
+// Parse model_params.txt.  For each event, return its realizations, ordered by index.  Each is
+// given by a name (for a gene) or value, and a sequence (for a gene).
+
+fn parse_params(s: &str, dir: &str) -> Result<HashMap<String, Vec<(String, Vec<u8>)>>, String> {
+    let mut events = HashMap::<String, Vec<(String, Vec<u8>)>>::new();
+    let mut event = String::new();
+    for line in s.lines() {
+        if line.starts_with('@') && line != "@Event_list" {
+            break;
+        } else if line.starts_with('#') {
+            if !line.contains(';') {
+                return Err(bad_model(dir, &format!("can't parse {}", line)));
+            }
+            event = line.rev_after(";").to_string();
+            events.insert(event.clone(), Vec::new());
+        } else if line.starts_with('%') {
+            let fields = line.after("%").split(';').collect::<Vec<&str>>();
+            let k = fields[fields.len() - 1].parse::<usize>();
+            if fields.len() < 2 || k.is_err() || !events.contains_key(&event) {
+                return Err(bad_model(dir, &format!("can't parse {}", line)));
+            }
+            let (k, x) = (k.unwrap(), events.get_mut(&event).unwrap());
+            if x.len() <= k {
+                x.resize(k + 1, (String::new(), Vec::new()));
+            }
+            let seq = if fields.len() > 2 { fields[1] } else { "" };
+            x[k] = (fields[0].to_string(), seq.as_bytes().to_vec());
+        }
+    }
+    Ok(events)
+}
+
+// Parse an anchors file, returning the anchor position for each gene.
+
+fn parse_anchors(s: &str) -> HashMap<String, usize> {
+    let mut anchors = HashMap::<String, usize>::new();
+    for line in s.lines().skip(1) {
+        let fields = line.split(',').collect::<Vec<&str>>();
+        if fields.len() >= 2 {
+            if let Ok(a) = fields[1].parse::<usize>() {
+                anchors.insert(fields[0].to_string(), a);
+            }
+        }
+    }
+    anchors
+}
+
+pub fn read_pgen_model(dir: &str) -> Result<PgenModel, String> {
+    let read = |f: &str| -> Result<String, String> {
+        let path = format!("{}/{}", dir, f);
+        read_to_string(&path).map_err(|e| format!("\nUnable to read {}: {}.\n", path, e))
+    };
+    let params = parse_params(&read("model_params.txt")?, dir)?;
+    let marg = parse_marginals(&read("model_marginals.txt")?, dir)?;
+    let v_anchors = parse_anchors(&read("V_gene_CDR3_anchors.csv")?);
+    let j_anchors = parse_anchors(&read("J_gene_CDR3_anchors.csv")?);
+    let event = |name: &str| -> Result<&Vec<(String, Vec<u8>)>, String> {
+        params
+            .get(name)
+            .ok_or_else(|| bad_model(dir, &format!("the event {} is missing", name)))
+    };
+    let dist = |name: &str, given: &[(&str, usize)], n: usize| -> Result<Vec<f64>, String> {
+        match marg.get(name).and_then(|m| m.get(given)) {
+            Some(p) if p.len() == n => Ok(p.clone()),
+            _ => Err(bad_model(
+                dir,
+                &format!("the probabilities for {} are missing or incomplete", name),
+            )),
+        }
+    };
+    let values = |name: &str| -> Result<Vec<i32>, String> {
+        let mut v = Vec::<i32>::new();
+        for x in event(name)?.iter() {
+            match x.0.parse::<i32>() {
+                Ok(y) => v.push(y),
+                Err(_) => return Err(bad_model(dir, &format!("can't parse {} for {}", x.0, name))),
+            }
+        }
+        Ok(v)
+    };
+    let insertion = |ins: &str, dinucl: &str| -> Result<Insertion, String> {
+        let lens = values(ins)?;
+        let p = dist(ins, &[], lens.len())?;
+        let mut x = Insertion::default();
+        for k in 0..lens.len() {
+            if lens[k] < 0 {
+                return Err(bad_model(dir, &format!("negative value for {}", ins)));
+            }
+            let len = lens[k] as usize;
+            if x.p_len.len() <= len {
+                x.p_len.resize(len + 1, 0.0);
+            }
+            x.p_len[len] = p[k];
+        }
+        let bases = event(dinucl)?
+            .iter()
+            .map(|b| base_index(*b.0.as_bytes().first().unwrap_or(&b'N')))
+            .collect::<Vec<usize>>();
+        if bases.len() != 4 || bases.contains(&4) {
+            return Err(bad_model(dir, &format!("unexpected values for {}", dinucl)));
+        }
+        let p = dist(dinucl, &[], 16)?;
+        for a in 0..4 {
+            let total: f64 = p[4 * a..4 * a + 4].iter().sum();
+            for b in 0..4 {
+                if total > 0.0 {
+                    x.trans[bases[a]][bases[b]] = p[4 * a + b] / total;
+                }
+            }
+        }
+
+        // Find the stationary distribution, by iterating from the uniform distribution.
+
+        x.first = [0.25; 4];
+        for _ in 0..1000 {
+            let mut y = [0.0; 4];
+            for a in 0..4 {
+                for b in 0..4 {
+                    y[b] += x.first[a] * x.trans[a][b];
+                }
+            }
+            x.first = y;
+        }
+        Ok(x)
+    };
+
+    // Get the genes.  Genes lacking an anchor cannot yield a CDR3, and are given probability zero.
+
+    let mut m = PgenModel {
+        vdj: params.contains_key("d_gene"),
+        ..Default::default()
+    };
+    let (vg, jg) = (event("v_choice")?, event("j_choice")?);
+    let mut v_ok = vec![false; vg.len()];
+    for (k, (name, seq)) in vg.iter().enumerate() {
+        let mut v = Vec::<usize>::new();
+        if let Some(a) = v_anchors.get(name) {
+            if *a < seq.len() {
+                v_ok[k] = true;
+                v = seq[*a..].iter().map(|b| base_index(*b)).collect();
+            }
+        }
+        m.v.push(v);
+    }
+    let mut j_ok = vec![false; jg.len()];
+    for (k, (name, seq)) in jg.iter().enumerate() {
+        let mut j = Vec::<usize>::new();
+        if let Some(a) = j_anchors.get(name) {
+            if *a + 3 <= seq.len() {
+                j_ok[k] = true;
+                j = seq[..*a + 3].iter().map(|b| base_index(*b)).collect();
+            }
+        }
+        m.j.push(j);
+    }
+    let (nv, nj) = (m.v.len(), m.j.len());
+    let pv = dist("v_choice", &[], nv)?;
+    for iv in 0..nv {
+        let pj = dist("j_choice", &[("v_choice", iv)], nj)?;
+        let mut p = vec![0.0; nj];
+        for ij in 0..nj {
+            if v_ok[iv] && j_ok[ij] {
+                p[ij] = pv[iv] * pj[ij];
+            }
+        }
+        m.p_vj.push(p);
+    }
+
+    // Get the deletions and insertions.
+
+    m.del_v = values("v_3_del")?;
+    for iv in 0..nv {
+        m.p_del_v
+            .push(dist("v_3_del", &[("v_choice", iv)], m.del_v.len())?);
+    }
+    m.del_j = values("j_5_del")?;
+    for ij in 0..nj {
+        m.p_del_j
+            .push(dist("j_5_del", &[("j_choice", ij)], m.del_j.len())?);
+    }
+    if !m.vdj {
+        m.ins1 = insertion("vj_ins", "vj_dinucl")?;
+    } else {
+        for x in event("d_gene")?.iter() {
+            m.d.push(x.1.iter().map(|b| base_index(*b)).collect());
+        }
+        let nd = m.d.len();
+        for ij in 0..nj {
+            m.p_d.push(dist("d_gene", &[("j_choice", ij)], nd)?);
+        }
+        m.del_d5 = values("d_5_del")?;
+        m.del_d3 = values("d_3_del")?;
+        let (n5, n3) = (m.del_d5.len(), m.del_d3.len());
+        for id in 0..nd {
+            let p5 = dist("d_5_del", &[("d_gene", id)], n5)?;
+            let mut p = vec![vec![0.0; n3]; n5];
+            for k5 in 0..n5 {
+                let p3 = dist("d_3_del", &[("d_gene", id), ("d_5_del", k5)], n3)?;
+                for k3 in 0..n3 {
+                    p[k5][k3] = p5[k5] * p3[k3];
+                }
+            }
+            m.p_del_d.push(p);
+        }
+        m.ins1 = insertion("vd_ins", "vd_dinucl")?;
+        m.ins2 = insertion("dj_ins", "dj_dinucl")?;
+    }
+    Ok(m)
+}
+
+// Read the models for PGEN_MODELS=dir.  A model for a chain type is in the subdirectory of dir
+// named by the chain type, or else in the subdirectory named as in the default_models directory
+// of OLGA, for human, or for mouse if MOUSE was specified.
+
+const OLGA_CHAIN_NAMES: [(&str, &str); 5] = [
+    ("IGH", "B_heavy"),
+    ("IGK", "B_kappa"),
+    ("IGL", "B_lambda"),
+    ("TRA", "T_alpha"),
+    ("TRB", "T_beta"),
+];
+
+pub fn read_pgen_models(dir: &str, mouse: bool) -> Result<HashMap<String, PgenModel>, String> {
+    let species = if mouse { "mouse" } else { "human" };
+    let mut models = HashMap::<String, PgenModel>::new();
+    for chain_type in ["IGH", "IGK", "IGL", "TRA", "TRB", "TRD", "TRG"].iter() {
+        let mut sub = format!("{}/{}", dir, chain_type);
+        if !path_exists(&sub) {
+            if let Some(x) = OLGA_CHAIN_NAMES.iter().find(|x| x.0 == *chain_type) {
+                sub = format!("{}/{}_{}", dir, species, x.1);
+            }
+        }
+        if path_exists(&sub) {
+            models.insert(chain_type.to_string(), read_pgen_model(&sub)?);
+        }
+    }
+    if models.is_empty() {
+        return Err(format!(
+            "\nPGEN_MODELS={} does not contain a model for any chain type.  Models should be \
+            in subdirectories named IGH, IGK, IGL, TRA, TRB, TRD or TRG, or named as in the \
+            default_models directory of OLGA, for example {}_T_beta.\n",
+            dir, species
+        ));
+    }
+    Ok(models)
+}
+
+// Return Pgen for a chain, or the empty string if there is no model for its chain type.
+
+pub fn chain_pgen(x: &TigData1, ctl: &EncloneControl) -> String {
+    match ctl.gen_opt.pgen_models.get(&x.chain_type) {
+        Some(m) => format!("{:.3e}", m.pgen(x.cdr3_dna.as_bytes())),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_pgen() {
+        let m = PgenModel {
+            v: vec![vec![3, 2, 3]],
+            j: vec![vec![3, 2, 2]],
+            p_vj: vec![vec![1.0]],
+            del_v: vec![-1, 0],
+            p_del_v: vec![vec![0.2, 0.8]],
+            del_j: vec![0],
+            p_del_j: vec![vec![1.0]],
+            ins1: Insertion {
+                p_len: vec![0.5, 0.5],
+                first: [0.25; 4],
+                trans: [[0.25; 4]; 4],
+            },
+            ..Default::default()
+        };
+        assert_eq!(m.pgen(b"TGTTGG"), 0.4);
+        assert_eq!(m.pgen(b"TGTCTGG"), 0.8 * 0.5 * 0.25);
+        assert_eq!(m.pgen(b"TGTATGG"), 0.8 * 0.5 * 0.25 + 0.2 * 0.5);
+        assert_eq!(m.pgen(b"TGTNTGG"), 0.0);
+    }
+
+    #[test]
+    fn test_read_pgen_models() {
+        let params = "@Event_list\n\
+            #GeneChoice;V_gene;Undefined_side;7;v_choice\n\
+            %TRAV1*01;AAATGT;0\n\
+            #GeneChoice;J_gene;Undefined_side;7;j_choice\n\
+            %TRAJ1*01;TGGGGC;0\n\
+            #Deletion;V_gene;Three_prime;5;v_3_del\n\
+            %-1;0\n\
+            %0;1\n\
+            #Deletion;J_gene;Five_prime;5;j_5_del\n\
+            %0;0\n\
+            #Insertion;VJ_gene;Undefined_side;4;vj_ins\n\
+            %0;0\n\
+            %1;1\n\
+            #DinucMarkov;VJ_gene;Undefined_side;3;vj_dinucl\n\
+            %A;0\n\
+            %C;1\n\
+            %G;2\n\
+            %T;3\n\
+            @Edges\n";
+        let marginals = format!(
+            "@v_choice\n$Dim[1]\n#\n%1\n\
+            @j_choice\n$Dim[1,1]\n#[v_choice,0]\n%1\n\
+            @v_3_del\n$Dim[1,2]\n#[v_choice,0]\n%0.2,0.8\n\
+            @j_5_del\n$Dim[1,1]\n#[j_choice,0]\n%1\n\
+            @vj_ins\n$Dim[2]\n#\n%0.5,0.5\n\
+            @vj_dinucl\n$Dim[16]\n#\n%{}\n",
+            vec!["0.25"; 16].join(",")
+        );
+        let write_model = |dir: &std::path::Path, params: &str| {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("model_params.txt"), params).unwrap();
+            std::fs::write(dir.join("model_marginals.txt"), &marginals).unwrap();
+            std::fs::write(
+                dir.join("V_gene_CDR3_anchors.csv"),
+                "gene,anchor_index\nTRAV1*01,3\n",
+            )
+            .unwrap();
+            std::fs::write(
+                dir.join("J_gene_CDR3_anchors.csv"),
+                "gene,anchor_index\nTRAJ1*01,0\n",
+            )
+            .unwrap();
+        };
+        let expected = PgenModel {
+            v: vec![vec![3, 2, 3]],
+            j: vec![vec![3, 2, 2]],
+            p_vj: vec![vec![1.0]],
+            del_v: vec![-1, 0],
+            p_del_v: vec![vec![0.2, 0.8]],
+            del_j: vec![0],
+            p_del_j: vec![vec![1.0]],
+            ins1: Insertion {
+                p_len: vec![0.5, 0.5],
+                first: [0.25; 4],
+                trans: [[0.25; 4]; 4],
+            },
+            ..Default::default()
+        };
+        let root = std::env::temp_dir().join(format!("enclone_pgen_test_{}", std::process::id()));
+        let path = |x: &std::path::Path| x.to_str().unwrap().to_string();
+
+        // Models named by chain type, and models named as by OLGA.
+
+        let (a, b, c) = (root.join("a"), root.join("b"), root.join("c"));
+        write_model(&a.join("TRA"), params);
+        let models = read_pgen_models(&path(&a), false).unwrap();
+        assert_eq!(models.len(), 1);
+        assert!(models["TRA"] == expected);
+        assert_eq!(models["TRA"].pgen(b"TGTTGG"), 0.4);
+        write_model(&b.join("mouse_T_alpha"), params);
+        assert!(read_pgen_models(&path(&b), true).unwrap()["TRA"] == expected);
+        assert!(read_pgen_models(&path(&b), false).is_err());
+
+        // A model lacking an event.
+
+        write_model(&c.join("TRA"), &params.replace("j_5_del", "j_5_deletion"));
+        assert!(read_pgen_models(&path(&c), false).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
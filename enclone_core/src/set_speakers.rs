@@ -43,7 +43,8 @@ pub fn set_speakers(ctl: &EncloneControl, parseable_fields: &mut Vec<String>, ma
                 || *x == "n_gex".to_string()
                 || *x == "n_b".to_string()
                 || *x == "clust".to_string()
-                || *x == "type".to_string()
+                || *x == "cell_type".to_string()
+                || *x == "cell_type_cell".to_string()
                 || *x == "entropy".to_string()
                 || *x == "cred".to_string()
                 || *x == "cred_cell".to_string())
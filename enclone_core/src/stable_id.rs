@@ -0,0 +1,168 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Stable identifiers for clonotypes and exact subclonotypes.  These are derived from content
+// alone, so that they do not depend on the order in which clonotypes are printed, and are the
+// same in different runs, so long as the content is the same.
+//
+// The identifier of an exact subclonotype is computed from the names of the donors of its cells,
+// and the chain type, V..J sequence and constant region name of each of its chains.  The
+// identifier of a clonotype is computed from the donors and the (V gene name, J gene name, CDR3
+// nucleotide sequence) triples of the chains of its lead exact subclonotype, which is the one
+// having the most cells, with ties broken by content.  Hence it does not change when a smaller
+// exact subclonotype is added or filtered out.  In both cases the chains are sorted, heavy (or
+// beta) chains first, so that column order does not matter, and identical receptors from
+// different donors have different identifiers.
+
+use crate::defs::{EncloneControl, ExactClonotype};
+use string_utils::strme;
+use vdj_ann::refx::RefData;
+use vector_utils::unique_sort;
+
+// Hash using 64-bit FNV-1a.  We use this rather than the standard library hasher because its
+// values are guaranteed not to change.  To hash data in pieces, start from FNV1A_START and call
+// fnv1a_extend on each piece.
+
+pub const FNV1A_START: u64 = 0xcbf29ce484222325;
+
+pub fn fnv1a_extend(mut h: u64, data: &[u8]) -> u64 {
+    for c in data.iter() {
+        h ^= *c as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+pub fn fnv1a(data: &[u8]) -> u64 {
+    fnv1a_extend(FNV1A_START, data)
+}
+
+fn stable_hash(donors: &str, mut chains: Vec<(bool, String)>) -> String {
+    chains.sort();
+    chains.dedup();
+    let mut s = format!("{}|", donors);
+    for (i, x) in chains.iter().enumerate() {
+        if i > 0 {
+            s += ";";
+        }
+        s += &x.1;
+    }
+    format!("{:016x}", fnv1a(s.as_bytes()))
+}
+
+// Return the comma-separated sorted list of the names of the donors of the cells in an exact
+// subclonotype.
+
+fn donors(ex: &ExactClonotype, ctl: &EncloneControl) -> String {
+    let mut donors = Vec::<&str>::new();
+    for clone in ex.clones.iter() {
+        if let Some(d) = clone[0].donor_index {
+            donors.push(&ctl.origin_info.donor_list[d]);
+        }
+    }
+    unique_sort(&mut donors);
+    donors.join(",")
+}
+
+pub fn exact_stable_id(ex: &ExactClonotype, refdata: &RefData, ctl: &EncloneControl) -> String {
+    let mut chains = Vec::<(bool, String)>::new();
+    for x in ex.share.iter() {
+        let c = match x.c_ref_id {
+            Some(c) => refdata.name[c].as_str(),
+            None => "",
+        };
+        chains.push((!x.left, format!("{}:{}:{}", x.chain_type, strme(&x.seq), c)));
+    }
+    stable_hash(&donors(ex, ctl), chains)
+}
+
+pub fn clonotype_stable_id(
+    exacts: &[usize],
+    exact_clonotypes: &[ExactClonotype],
+    refdata: &RefData,
+    ctl: &EncloneControl,
+) -> String {
+    let mut chains = Vec::<(usize, String, Vec<(bool, String)>)>::new();
+    for u in exacts.iter() {
+        let ex = &exact_clonotypes[*u];
+        let mut c = Vec::<(bool, String)>::new();
+        for x in ex.share.iter() {
+            c.push((
+                !x.left,
+                format!(
+                    "{}:{}:{}",
+                    refdata.name[x.v_ref_id], refdata.name[x.j_ref_id], x.cdr3_dna
+                ),
+            ));
+        }
+        chains.push((ex.ncells(), donors(ex, ctl), c));
+    }
+    clonotype_hash(chains)
+}
+
+// Compute the identifier of a clonotype from the number of cells, donors and chains of each of
+// its exact subclonotypes, using only the lead exact subclonotype.
+
+fn clonotype_hash(mut chains: Vec<(usize, String, Vec<(bool, String)>)>) -> String {
+    for x in chains.iter_mut() {
+        x.2.sort();
+    }
+    let lead = chains
+        .into_iter()
+        .min_by(|a, b| b.0.cmp(&a.0).then_with(|| (&a.1, &a.2).cmp(&(&b.1, &b.2))))
+        .unwrap();
+    stable_hash(&lead.1, lead.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clonotype_hash() {
+        let chain = |left: bool, v: &str, cdr3: &str| (!left, format!("{}:IGHJ4:{}", v, cdr3));
+        let e1 = vec![
+            chain(true, "IGHV3-7", "TGTGCGAGA"),
+            chain(false, "IGKV1-5", "TGTCAG"),
+        ];
+        let e2 = vec![
+            chain(false, "IGKV1-5", "TGTCAG"),
+            chain(true, "IGHV3-7", "TGTGCGAGA"),
+        ];
+        let e3 = vec![chain(true, "IGHV3-7", "TGTGCGAGA")];
+        let e4 = vec![
+            chain(true, "IGHV3-7", "TGTGCAAGA"),
+            chain(false, "IGKV1-5", "TGTCAG"),
+        ];
+        let d = |n: usize, donor: &str, e: &Vec<(bool, String)>| (n, donor.to_string(), e.clone());
+        let id = clonotype_hash(vec![d(5, "d1", &e1), d(2, "d1", &e3), d(1, "d1", &e4)]);
+
+        // Permuting exact subclonotypes, or reordering chains, does not change the id.
+
+        assert_eq!(
+            id,
+            clonotype_hash(vec![d(1, "d1", &e4), d(5, "d1", &e1), d(2, "d1", &e3)])
+        );
+        assert_eq!(
+            id,
+            clonotype_hash(vec![d(2, "d1", &e3), d(1, "d1", &e4), d(5, "d1", &e2)])
+        );
+
+        // Adding or removing an exact subclonotype other than the lead does not change the id.
+
+        assert_eq!(id, clonotype_hash(vec![d(5, "d1", &e1)]));
+        assert_eq!(
+            id,
+            clonotype_hash(vec![d(5, "d1", &e1), d(3, "d1", &e4), d(3, "d2", &e3)])
+        );
+        assert_ne!(id, clonotype_hash(vec![d(5, "d1", &e1), d(6, "d1", &e4)]));
+
+        // Ties are broken by content, and the donor is part of the id.
+
+        assert_eq!(
+            clonotype_hash(vec![d(2, "d1", &e1), d(2, "d1", &e4)]),
+            clonotype_hash(vec![d(2, "d1", &e4), d(2, "d1", &e1)])
+        );
+        assert_ne!(id, clonotype_hash(vec![d(5, "d2", &e1)]));
+        assert_ne!(id, clonotype_hash(vec![d(5, "", &e1)]));
+    }
+}
@@ -0,0 +1,333 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Compact binary form of TigData, for writing contig data to disk and reading it back.  Integers
+// are written as little-endian u64, Option values are preceded by a flag byte, and vectors and
+// strings are preceded by their length.  The encoding is only intended to be read by the same
+// version of enclone that wrote it.
+
+use crate::defs::TigData;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+fn put_usize(x: &mut Vec<u8>, n: usize) {
+    x.extend_from_slice(&(n as u64).to_le_bytes());
+}
+
+fn put_bool(x: &mut Vec<u8>, b: bool) {
+    x.push(b as u8);
+}
+
+fn put_opt_usize(x: &mut Vec<u8>, n: Option<usize>) {
+    put_bool(x, n.is_some());
+    if let Some(n) = n {
+        put_usize(x, n);
+    }
+}
+
+fn put_bytes(x: &mut Vec<u8>, b: &[u8]) {
+    put_usize(x, b.len());
+    x.extend_from_slice(b);
+}
+
+fn put_opt_strings(x: &mut Vec<u8>, s: &Option<Vec<String>>) {
+    put_bool(x, s.is_some());
+    if let Some(s) = s {
+        put_usize(x, s.len());
+        for y in s.iter() {
+            put_bytes(x, y.as_bytes());
+        }
+    }
+}
+
+struct Cursor<'a> {
+    x: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let stop = match self.pos.checked_add(n) {
+            Some(stop) if stop <= self.x.len() => stop,
+            _ => return Err("\nBinary contig data are truncated.\n".to_string()),
+        };
+        let y = &self.x[self.pos..stop];
+        self.pos = stop;
+        Ok(y)
+    }
+    // Read the length of a list, each of whose entries occupies at least min_size bytes, so
+    // that a corrupted length is caught before memory is reserved for it.
+    fn len(&mut self, min_size: usize) -> Result<usize, String> {
+        let n = self.usize()?;
+        if n > (self.x.len() - self.pos) / min_size {
+            return Err("\nBinary contig data are truncated.\n".to_string());
+        }
+        Ok(n)
+    }
+    fn usize(&mut self) -> Result<usize, String> {
+        let mut b = [0_u8; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b) as usize)
+    }
+    fn bool(&mut self) -> Result<bool, String> {
+        Ok(self.take(1)?[0] != 0)
+    }
+    fn opt_usize(&mut self) -> Result<Option<usize>, String> {
+        if self.bool()? {
+            Ok(Some(self.usize()?))
+        } else {
+            Ok(None)
+        }
+    }
+    fn bytes(&mut self) -> Result<Vec<u8>, String> {
+        let n = self.usize()?;
+        Ok(self.take(n)?.to_vec())
+    }
+    fn string(&mut self) -> Result<String, String> {
+        String::from_utf8(self.bytes()?)
+            .map_err(|_| "\nBinary contig data are corrupted.\n".to_string())
+    }
+    fn opt_strings(&mut self) -> Result<Option<Vec<String>>, String> {
+        if !self.bool()? {
+            return Ok(None);
+        }
+        let n = self.len(8)?;
+        let mut s = Vec::<String>::with_capacity(n);
+        for _ in 0..n {
+            s.push(self.string()?);
+        }
+        Ok(Some(s))
+    }
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+fn encode_tig(x: &mut Vec<u8>, t: &TigData) {
+    put_bytes(x, t.cdr3_dna.as_bytes());
+    put_usize(x, t.len);
+    put_usize(x, t.v_start);
+    put_usize(x, t.v_stop);
+    put_usize(x, t.v_stop_ref);
+    put_opt_usize(x, t.d_start);
+    put_usize(x, t.j_start);
+    put_usize(x, t.j_start_ref);
+    put_usize(x, t.j_stop);
+    put_opt_usize(x, t.c_start);
+    put_bytes(x, &t.full_seq);
+    put_opt_usize(x, t.u_ref_id);
+    put_usize(x, t.v_ref_id);
+    put_opt_usize(x, t.d_ref_id);
+    put_usize(x, t.j_ref_id);
+    put_opt_usize(x, t.c_ref_id);
+    put_usize(x, t.fr1_start);
+    put_opt_usize(x, t.cdr1_start);
+    put_opt_usize(x, t.fr2_start);
+    put_opt_usize(x, t.cdr2_start);
+    put_opt_usize(x, t.fr3_start);
+    put_bytes(x, t.cdr3_aa.as_bytes());
+    put_usize(x, t.cdr3_start);
+    put_bytes(x, &t.quals);
+    put_bytes(x, &t.full_quals);
+    put_bytes(x, t.barcode.as_bytes());
+    put_bytes(x, t.tigname.as_bytes());
+    put_bool(x, t.left);
+    put_usize(x, t.dataset_index);
+    put_opt_usize(x, t.origin_index);
+    put_opt_usize(x, t.donor_index);
+    put_opt_usize(x, t.tag_index);
+    put_usize(x, t.umi_count);
+    put_usize(x, t.read_count);
+    put_bytes(x, t.chain_type.as_bytes());
+    put_usize(x, t.annv.len());
+    for a in t.annv.iter() {
+        for v in [a.0, a.1, a.2, a.3, a.4].iter() {
+            x.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    put_opt_strings(x, &t.validated_umis);
+    put_opt_strings(x, &t.non_validated_umis);
+    put_opt_strings(x, &t.invalidated_umis);
+    put_opt_usize(x, t.frac_reads_used.map(|f| f as usize));
+    put_bool(x, t.productive);
+}
+
+fn decode_tig(c: &mut Cursor) -> Result<TigData, String> {
+    let mut t = TigData {
+        cdr3_dna: c.string()?,
+        len: c.usize()?,
+        v_start: c.usize()?,
+        v_stop: c.usize()?,
+        v_stop_ref: c.usize()?,
+        d_start: c.opt_usize()?,
+        j_start: c.usize()?,
+        j_start_ref: c.usize()?,
+        j_stop: c.usize()?,
+        c_start: c.opt_usize()?,
+        full_seq: c.bytes()?,
+        u_ref_id: c.opt_usize()?,
+        v_ref_id: c.usize()?,
+        d_ref_id: c.opt_usize()?,
+        j_ref_id: c.usize()?,
+        c_ref_id: c.opt_usize()?,
+        fr1_start: c.usize()?,
+        cdr1_start: c.opt_usize()?,
+        fr2_start: c.opt_usize()?,
+        cdr2_start: c.opt_usize()?,
+        fr3_start: c.opt_usize()?,
+        cdr3_aa: c.string()?,
+        cdr3_start: c.usize()?,
+        quals: c.bytes()?,
+        full_quals: c.bytes()?,
+        barcode: c.string()?,
+        tigname: c.string()?,
+        left: c.bool()?,
+        dataset_index: c.usize()?,
+        origin_index: c.opt_usize()?,
+        donor_index: c.opt_usize()?,
+        tag_index: c.opt_usize()?,
+        umi_count: c.usize()?,
+        read_count: c.usize()?,
+        chain_type: c.string()?,
+        ..Default::default()
+    };
+    let n = c.len(20)?;
+    for _ in 0..n {
+        let mut a = [0_i32; 5];
+        for v in a.iter_mut() {
+            let mut b = [0_u8; 4];
+            b.copy_from_slice(c.take(4)?);
+            *v = i32::from_le_bytes(b);
+        }
+        t.annv.push((a[0], a[1], a[2], a[3], a[4]));
+    }
+    t.validated_umis = c.opt_strings()?;
+    t.non_validated_umis = c.opt_strings()?;
+    t.invalidated_umis = c.opt_strings()?;
+    t.frac_reads_used = c.opt_usize()?.map(|f| f as u32);
+    t.productive = c.bool()?;
+    Ok(t)
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Write and read contig data grouped by barcode.
+
+pub fn decode_tig_bc(x: &[u8]) -> Result<Vec<Vec<TigData>>, String> {
+    let mut c = Cursor { x, pos: 0 };
+    let n = c.len(8)?;
+    let mut tig_bc = Vec::<Vec<TigData>>::with_capacity(n);
+    for _ in 0..n {
+        let m = c.len(8)?;
+        let mut tigs = Vec::<TigData>::with_capacity(m);
+        for _ in 0..m {
+            tigs.push(decode_tig(&mut c)?);
+        }
+        tig_bc.push(tigs);
+    }
+    Ok(tig_bc)
+}
+
+fn write_tig_bin(
+    path: &str,
+    header: &[Vec<String>],
+    tig_bc: &[Vec<TigData>],
+) -> Result<(), String> {
+    let f = File::create(path).map_err(|e| format!("\nUnable to create {}: {}.\n", path, e))?;
+    let mut f = BufWriter::new(f);
+    let mut x = Vec::<u8>::new();
+    for h in header.iter() {
+        put_opt_strings(&mut x, &Some(h.clone()));
+    }
+    put_usize(&mut x, tig_bc.len());
+    for tigs in tig_bc.iter() {
+        put_usize(&mut x, tigs.len());
+        for t in tigs.iter() {
+            encode_tig(&mut x, t);
+        }
+        f.write_all(&x)
+            .map_err(|e| format!("\nUnable to write {}: {}.\n", path, e))?;
+        x.clear();
+    }
+    f.write_all(&x)
+        .and_then(|_| f.flush())
+        .map_err(|e| format!("\nUnable to write {}: {}.\n", path, e))
+}
+
+fn read_bytes(path: &str) -> Result<Vec<u8>, String> {
+    let f = File::open(path).map_err(|e| format!("\nUnable to open {}: {}.\n", path, e))?;
+    let mut x = Vec::<u8>::new();
+    BufReader::new(f)
+        .read_to_end(&mut x)
+        .map_err(|e| format!("\nUnable to read {}: {}.\n", path, e))?;
+    Ok(x)
+}
+
+pub fn write_tig_bc(path: &str, tig_bc: &[Vec<TigData>]) -> Result<(), String> {
+    write_tig_bin(path, &[], tig_bc)
+}
+
+pub fn read_tig_bc(path: &str) -> Result<Vec<Vec<TigData>>, String> {
+    decode_tig_bc(&read_bytes(path)?)
+}
+
+// Same as above, but preceded by a fixed number of string lists, which the caller interprets.
+
+pub fn write_tig_bc_with_header(
+    path: &str,
+    header: &[Vec<String>],
+    tig_bc: &[Vec<TigData>],
+) -> Result<(), String> {
+    write_tig_bin(path, header, tig_bc)
+}
+
+pub fn read_tig_bc_with_header(
+    path: &str,
+    nheader: usize,
+) -> Result<(Vec<Vec<String>>, Vec<Vec<TigData>>), String> {
+    let x = read_bytes(path)?;
+    let mut c = Cursor { x: &x, pos: 0 };
+    let mut header = Vec::<Vec<String>>::new();
+    for _ in 0..nheader {
+        header.push(c.opt_strings()?.unwrap_or_default());
+    }
+    let tig_bc = decode_tig_bc(&x[c.pos..])?;
+    Ok((header, tig_bc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_tig_bc() {
+        let t = TigData {
+            barcode: "AAAC-1".to_string(),
+            full_seq: b"ACGT".to_vec(),
+            annv: vec![(0, 4, 0, 0, 0)],
+            validated_umis: Some(vec!["ACGTACGT".to_string()]),
+            ..Default::default()
+        };
+        let mut x = Vec::<u8>::new();
+        put_usize(&mut x, 1);
+        put_usize(&mut x, 1);
+        encode_tig(&mut x, &t);
+        let tig_bc = decode_tig_bc(&x).unwrap();
+        assert_eq!(tig_bc.len(), 1);
+        assert_eq!(tig_bc[0][0].barcode, t.barcode);
+        assert_eq!(tig_bc[0][0].full_seq, t.full_seq);
+        assert_eq!(tig_bc[0][0].annv, t.annv);
+        assert_eq!(tig_bc[0][0].validated_umis, t.validated_umis);
+
+        // Truncated data, and lengths that are too large, must be rejected rather than
+        // causing a panic or a huge allocation.
+
+        for n in 0..x.len() {
+            assert!(decode_tig_bc(&x[0..n]).is_err());
+        }
+        let mut y = x.clone();
+        y[0..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(decode_tig_bc(&y).is_err());
+        let mut y = x.clone();
+        y[16..24].copy_from_slice(&(u64::MAX - 4).to_le_bytes());
+        assert!(decode_tig_bc(&y).is_err());
+    }
+}
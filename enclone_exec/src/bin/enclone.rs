@@ -7,6 +7,7 @@
 use chrono::prelude::*;
 use enclone_core::combine_group_pics::combine_group_pics;
 use enclone_main::main_enclone::main_enclone;
+use enclone_main::serve::serve;
 use enclone_main::USING_PAGER;
 #[cfg(feature = "enclone_visual")]
 use enclone_visual::enclone_client::enclone_client;
@@ -113,6 +114,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    // Headless server run of enclone.
+
+    if args.iter().any(|x| x.starts_with("SERVE=")) {
+        if let Err(e) = serve(&args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+
     // Standard run of enclone.
 
     if args.len() < 2 || args[1] != "SERVER" {
@@ -552,32 +552,34 @@ fn test_for_broken_links_and_spellcheck() {
     // places and reduce the dictionary exceptions accordingly.
 
     let extra_words =
-        "abybank actgtgcgagag actgtgcgagagc adefghiklmnpqrstvwy amazonaws anarci autoremove \
-        barcode barcodes barcoding bcn \
-        bioinf biorxiv cdiff cellranger chmod clen clono clonotype clonotypes \
+        "abybank actgtgcgagag actgtgcgagagc adefghiklmnpqrstvwy airr amazonaws anarci anndata \
+        autoremove barcode barcodes barcoding bcn benjamini \
+        bioinf biorxiv cdiff cellranger celltypes chao chmod clen clonality clono clonotype clonotypes \
         clonotyping codebase colorn contig contigs cqvwdsssdhpyvf cred crispr cshlp \
         csv ctrlc cvar cvars datalayer dejavusansmono dotplot \
         dref dyiid enclone exe executables false fcell \
         fixedtextbox foursie foursies frameshifted frameshifts frontiersin fwr fwyh ganesh \
         genomics germline ggctttgactactgg gggctttgactactgg github githubusercontent google \
-        googletagmanager grok gz hcomp html \
-        hypermutation hypermutations igblast igh ighd igk igl ighm igkc imgt immunoglobulins \
-        indel indels inkt intradonor ireceptor \
-        jsdelivr json krh levenshtein lgc linux loh lvar lvars \
-        macbook mait metadata minmax mkdir \
-        moresies multiomic nall ncbi nchains ncross ndoublet newick nimproper \
-        nopager noprint nospaces nqual nseg nsegn nsig nwhitef oligos onesie onesies osx parseable \
-        pbmc pcell pcols pdb pgas phad phylip png \
-        plasmablast powershell preinstalled prepends pwm pwms recombinants redownloads \
-        researchsquare rustup samtools screenshot segn \
-        sloooooooow spacebar stackexchange standalone stcrdab stdout sthnqedkr subclonotype \
-        subclonotypes sudo svg tattgtagtggtggtagct tctgtgcgagata tctgtgcgagat tctgtgcgagata \
+        googletagmanager grok gz gzipped hcomp hochberg html \
+        hypermutation hypermutations igblast igh ighd igk igl ighm igkc igor imgt \
+        immunoglobulins indel indels inkt intradonor ireceptor \
+        jsdelivr json krh levenshtein lgc libra linux loh lvar lvars \
+        macbook mait metadata mex minmax mkdir \
+        moresies mtx multimers multiomic nall ncbi nchains ncells ncross ndoublet newick nimproper \
+        nopager noprint nospaces nqual nseg nsegn nsig nwhitef obs ogrdb olga oligos onesie onesies \
+        orfs osx parseable paratope pbmc pcell pcols pdb pgas pgen phad phylip pielou png \
+        plasmablast powershell preinstalled prepends proto pseudocount pseudogenes pwm pwms \
+        qc reannotated recombinants redownloads \
+        researchsquare rustup samtools scanpy screenshot segn seurat \
+        shannon sloooooooow spacebar stackexchange standalone stcrdab stdout sthnqedkr subclonotype \
+        subclonotypes subdirectories sudo svg tattgtagtggtggtagct tctgtgcgagata tctgtgcgagat \
+        tctgtgcgagata \
         testlist thresholding timeline timepoint \
         tracebacks trb tsv \
         tttctgtgcgaga tttctgtgcgagat \
-        twosie ubuntu udiff umi umis underperforming unicode untarring utrs \
-        vddj vdj vdjc vilella vilfwym vilm vjlen wallclock website wget whitef whitelist wikimedia \
-        wikipedia workaround workflow xcode xf xhtml xkcd \
+        twosie ubuntu udiff umap umi umis underperforming unicode untarring utrs \
+        vars vddj vdj vdjc vilella vilfwym vilm vjlen wallclock website wget whitef whitelist wikimedia \
+        wikipedia wilcoxon workaround workflow xcode xf xhtml xkcd \
         xxxxxxxxxxx xxxxxxxxxxxxxxxxxxxxxxx xy yvar zenodo zx";
     let extra_words = extra_words.split(' ').collect::<Vec<&str>>();
 
@@ -3,6 +3,8 @@
 #![allow(unused_imports, dead_code)]
 
 use enclone_core::defs::*;
+use enclone_core::testlist::TEST_FILES_VERSION;
+use enclone_main::api::EncloneBuilder;
 use enclone_main::main_enclone::main_enclone;
 use enclone_ranger::main_enclone::main_enclone_ranger;
 use enclone_vars::export_code::*;
@@ -674,3 +676,108 @@ fn test_newline_bad() {
         std::process::exit(1);
     }
 }
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// 42. Test the library interface, by running it on one dataset, and checking that the returned
+// structures agree with the variable values, and that no parseable output file is written.
+
+// NOT BASIC
+
+#[cfg(not(feature = "basic"))]
+#[cfg(not(feature = "cpu"))]
+#[test]
+fn test_api() {
+    PrettyTrace::new().on();
+    let results = EncloneBuilder::new()
+        .arg(&format!(
+            "PRE=../enclone-data/big_inputs/version{}",
+            TEST_FILES_VERSION
+        ))
+        .bcr("123085")
+        .pcols(&["n", "cdr3_aa1", "v_name1", "clonotype_ncells"])
+        .arg("MIN_CELLS=2")
+        .run()
+        .unwrap();
+    assert!(!results.clonotypes.is_empty());
+    for (k, clono) in results.clonotypes.iter().enumerate() {
+        assert!(k == 0 || clono.group_id >= results.clonotypes[k - 1].group_id);
+        assert!(clono.ncells() >= 2);
+        for ex in clono.exact_subclonotypes.iter() {
+            assert_eq!(ex.value::<usize>("n"), Some(ex.ncells()));
+            assert_eq!(ex.value::<usize>("clonotype_ncells"), Some(clono.ncells()));
+            if let Some(chain) = &ex.chains[0] {
+                assert_eq!(ex.vars["cdr3_aa1"], chain.cdr3_aa);
+                assert_eq!(ex.vars["v_name1"], chain.v_name);
+            }
+            assert_eq!(ex.cell_values("n").unwrap().len(), ex.ncells());
+            for cell in ex.cells.iter() {
+                assert_eq!(cell.dataset, "123085");
+                assert!(cell.barcode.ends_with("-1"));
+            }
+        }
+    }
+    assert!(!path_exists(POUT_MEMORY));
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// 43. Test that SHARD_DONORS yields the same clonotypes and fates as an unsharded run, for
+// datasets from two donors.  Clonotypes are compared by their cells and chains, since their
+// order and internal ids may differ.
+
+// NOT BASIC
+
+#[cfg(not(feature = "basic"))]
+#[cfg(not(feature = "cpu"))]
+#[test]
+fn test_shard_donors() {
+    PrettyTrace::new().on();
+    let run = |shard: bool| {
+        let mut b = EncloneBuilder::new()
+            .arg(&format!(
+                "PRE=../enclone-data/big_inputs/version{}",
+                TEST_FILES_VERSION
+            ))
+            .bcr("123085;123089");
+        if shard {
+            b = b.arg("SHARD_DONORS");
+        }
+        let results = b.run().unwrap();
+        let mut clonotypes = Vec::<Vec<(Vec<(String, String)>, Vec<String>)>>::new();
+        for clono in results.clonotypes.iter() {
+            let mut exacts = Vec::<(Vec<(String, String)>, Vec<String>)>::new();
+            for ex in clono.exact_subclonotypes.iter() {
+                let mut cells = ex
+                    .cells
+                    .iter()
+                    .map(|c| (c.dataset.clone(), c.barcode.clone()))
+                    .collect::<Vec<_>>();
+                cells.sort();
+                let mut chains = ex
+                    .chains
+                    .iter()
+                    .flatten()
+                    .map(|c| format!("{}:{}:{}", c.v_name, c.j_name, c.seq))
+                    .collect::<Vec<String>>();
+                chains.sort();
+                exacts.push((cells, chains));
+            }
+            exacts.sort();
+            clonotypes.push(exacts);
+        }
+        clonotypes.sort();
+        let mut fates = results
+            .fates
+            .iter()
+            .map(|f| format!("{}:{}:{}", f.dataset, f.barcode, f.reason))
+            .collect::<Vec<String>>();
+        fates.sort();
+        (clonotypes, fates)
+    };
+    let (unsharded, sharded) = (run(false), run(true));
+    assert!(!unsharded.0.is_empty());
+    assert_eq!(unsharded.0.len(), sharded.0.len());
+    assert!(unsharded.0 == sharded.0);
+    assert!(unsharded.1 == sharded.1);
+}
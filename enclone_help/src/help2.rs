@@ -231,6 +231,43 @@ pub fn help2(args: &Vec<String>, _ctl: &EncloneControl, h: &mut HelpDesk) -> Res
              same directory (from a multi run), and single argument \\bold{BCR_GEX=...} may be \
              used, and similarly one may use \\bold{TCR_GEX}.\n\n",
         )?;
+        h.print(
+            "The path given by \\bold{GEX} may also be an AnnData file (ending in .h5ad), or a \
+             MEX directory containing matrix.mtx, features.tsv (or genes.tsv) and barcodes.tsv, \
+             any of which may be gzipped.  This allows expression data that have been processed \
+             and exported by scanpy or Seurat to be used.  The data are assumed to be filtered, \
+             so that every barcode is a cell, and must consist of raw UMI counts, which for an \
+             AnnData file are taken from layers/counts, raw/X or X, whichever is found first.  \
+             Barcodes must have the same form as the VDJ barcodes, e.g. AAACCTGAGAAACCAT-1.  For \
+             AnnData, the argument \\bold{GEX_OBS=col1,...,coln} causes the given obs columns \
+             (for example, clusters or cell types) to be imported as lead variables, which behave \
+             like the fields of a \\bold{BC} file.\n\n",
+        )?;
+        h.print(
+            "Two-dimensional UMAP and t-SNE projections of the gene expression data are read from \
+             the Cell Ranger analysis directory if present, or for AnnData, from obsm/X_umap and \
+             obsm/X_tsne.  They may also be given by \\bold{EMBEDDING=f.csv}, where the CSV file \
+             has the fields \\bold{barcode}, \\bold{umap_x} and \\bold{umap_y}, and/or \
+             \\bold{tsne_x} and \\bold{tsne_y}, and if there is more than one dataset, the field \
+             \\bold{dataset}, whose values are dataset names.  These values take precedence, and \
+             for example allow a joint projection of several datasets to be used.  The \
+             coordinates are available as lead variables of the same names, and cells may be \
+             plotted in projection space using \\bold{HONEY=...,embed=umap} or \
+             \\bold{HONEY=...,embed=tsne}.\n\n",
+        )?;
+        h.print(
+            "For antigen capture experiments, in which antigens are labeled by feature barcodes, \
+             as for LIBRA-seq or barcoded multimers, \\bold{ANTIGEN_CONTROLS=f1,...,fn} \
+             specifies the negative control features, by id or name, and turns on antigen \
+             specificity scoring.  The antigens are given by \\bold{ANTIGENS=a1,...,am}, or \
+             otherwise are all other features of type Antigen Capture.  The score of an antigen \
+             for a cell is log2((n+1)/(b+1)), where n is its UMI count and b is the mean UMI \
+             count of the controls.  An exact subclonotype or clonotype is called specific for \
+             an antigen if the median score of its cells is at least \
+             \\bold{ANTIGEN_MIN_SCORE}, which is 2 by default.  The calls are available as the \
+             lead variables \\bold{ag_spec} and \\bold{ag_spec_clono}, and a summary table is \
+             printed after the clonotypes.\n\n",
+        )?;
         h.print(
             "In addition, barcode-level data may be specified using \\bold{BC=...}, whose right \
              side is a list of paths having the same structure as the \\bold{TCR} or \\bold{BCR} \
@@ -434,6 +471,11 @@ pub fn help2(args: &Vec<String>, _ctl: &EncloneControl, h: &mut HelpDesk) -> Res
             "cdr*_dna_ref",
             "same, for the universal reference sequence (but not for cdr3)",
         );
+        h.doc(
+            "imgt_S_E_aa",
+            "amino acids at IMGT positions S through E (inclusive), as described at",
+        );
+        h.doc2("\"enclone help amino\"");
         h.ldoc(
             "cdr3_aa_conx",
             "consensus for CDR3 across the clonotype, showing X for each variant residue",
@@ -633,6 +675,19 @@ pub fn help2(args: &Vec<String>, _ctl: &EncloneControl, h: &mut HelpDesk) -> Res
         h.doc("", "bases of the V and the first 15 bases of the J");
         h.doc("d_donor", "distance from donor reference,");
         h.doc("", "as above but computed using donor reference");
+        h.ldoc(
+            "pgen",
+            "generation probability of the CDR3 nucleotide sequence, from the",
+        );
+        h.doc(
+            "",
+            "conserved C through the conserved W or F, computed using the recombination",
+        );
+        h.doc(
+            "",
+            "model for the chain type given by PGEN_MODELS (see enclone help special);",
+        );
+        h.doc("", "null if there is no such model");
 
         // The rest.
 
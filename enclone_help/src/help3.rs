@@ -37,6 +37,16 @@ pub fn help3(args: &Vec<String>, h: &mut HelpDesk) -> Result<(), String> {
             structure, if GEX or META/gex arguments are provided.  The exact files \
             that are used could be changed in the future.\n\n",
         )?;
+        h.print(
+            "Alternatively, a VDJ dataset may be specified as an AIRR rearrangement file, for \
+            example the file airr_rearrangement.tsv that Cell Ranger generates, or a file \
+            generated by another tool.  Such a file is recognized by its tab-delimited header \
+            line, and must have the fields sequence, v_call, j_call, junction, cell_id and \
+            productive, and also umi_count or duplicate_count.  Every row having a cell_id is \
+            treated as a contig from a cell.  The sequences are always reannotated, so unless the \
+            reference can be determined from another dataset, it must be specified using REF, \
+            or BUILT_IN to use the built-in reference.\n\n",
+        )?;
         h.print(
             "Note that the VDJ outs directories must be from Cell Ranger version \
              \\boldred{≥ 3.1}.  There \
@@ -126,6 +136,18 @@ pub fn help3(args: &Vec<String>, h: &mut HelpDesk) -> Result<(), String> {
              FASTA file.",
             true,
         )?;
+        h.print_with_box(
+            "\\bold{AIRR output.}  This is a separate feature.  \
+             To generate a tab-delimited file in the AIRR rearrangement format, with one line for \
+             each chain in each cell, use the argument \\bold{AIRR=filename}, or \
+             \\bold{AIRR=stdout} to print it.  Alignments, CIGAR strings and region boundaries \
+             are given relative to the full contig, and the V germline is the donor allele, if \
+             one was found.  The fields clone_id and exact_subclonotype_id identify the \
+             clonotype and exact subclonotype, e.g. 1.2 and 1.2.3, as in the visual output.  \
+             Similarly, \\bold{AIRR_CELL=filename} (or stdout) may be used to generate a \
+             matching AIRR cell file, with one line for each cell.",
+            true,
+        )?;
         h.print(
             "\\boldred{───────────────────────}\n\
              \\boldred{parseable output fields}\n\
@@ -616,6 +638,42 @@ pub fn help3(args: &Vec<String>, h: &mut HelpDesk) -> Result<(), String> {
             Currently feature scanning requires that each dataset have identical features.\n\n",
         )?;
 
+        // differential expression
+
+        h.print(
+            "\\bold{differential expression}\n\n\
+            If gene expression data have been generated, enclone can find the genes that are \
+            differentially expressed between two sets of cells, using a proper statistical test.  \
+            This is turned on using the command line argument\n\
+            \\bold{DIFF_EXP=\"test,control\"}\n\
+            where \\bold{test} and \\bold{control} are linear conditions that define the test \
+            and control clonotypes, exactly as for \\bold{SCAN}, except that \\bold{control} may \
+            instead be \\bold{rest}, meaning all other clonotypes.  If in addition the argument \
+            \\bold{SCAN_EXACT} is supplied, then exact subclonotypes are used instead of \
+            clonotypes.  Alternatively, the test and control sets may be defined by the numbers \
+            of the groups that enclone prints, using\n\
+            \\bold{DIFF_EXP_GROUPS=test,control}\n\
+            where \\bold{test} is a list of group numbers separated by plus signs, e.g. 1+3, and \
+            \\bold{control} is either such a list or \\bold{rest}, meaning all other groups.  \
+            Without grouping, each clonotype forms its own group.  A cell in both sets is assigned \
+            to the test set.  SCAN_EXACT cannot be used with DIFF_EXP_GROUPS.\n\n\
+            \
+            Only gene expression UMI counts are used.  Each cell is normalized by a size factor, \
+            which is its total UMI count divided by the median total for the cells in both \
+            sets.  Genes that are expressed in at least 10% of the cells in either set are \
+            tested using the Wilcoxon rank-sum test, and p values are adjusted for multiple \
+            testing using the Benjamini-Hochberg procedure.  The genes having adjusted p value \
+            < 0.05 are then printed, ordered by adjusted p value, and then by the absolute value \
+            of the log2 fold change.  For each gene, enclone shows the mean normalized count and \
+            the percent of cells expressing it, for both sets, the log2 fold change of the \
+            means (using a pseudocount of 0.1), and the p value before and after adjustment.\n\n\
+            \
+            To save the table for all tested genes as a CSV file, use \
+            \\bold{DIFF_EXP_OUT=filename}, or \\bold{DIFF_EXP_OUT=stdout} to print it.  \
+            Currently differential expression requires that each dataset have identical \
+            features.\n\n",
+        )?;
+
         // done
 
         h.end_doc();
@@ -685,6 +743,10 @@ pub fn help3(args: &Vec<String>, h: &mut HelpDesk) -> Result<(), String> {
             "a-b",
             "amino acids numbered a through b (zero-based, inclusive)",
         );
+        h.doc(
+            "imgt:a-b",
+            "amino acids at IMGT positions a through b (inclusive), see below",
+        );
         h.print_tab2()?;
         h.print("\n")?;
         h.print(
@@ -692,6 +754,19 @@ pub fn help3(args: &Vec<String>, h: &mut HelpDesk) -> Result<(), String> {
              positions in amino acid space.  Thus it can happen that a position in amino acid \
              space is shown for both \\bold{var} and \\bold{share}.\n\n",
         )?;
+        h.print(
+            "The option AMINO_IMGT causes amino acid columns to be labeled by IMGT position, \
+             rather than by zero-based position.  IMGT positions are computed from the regions \
+             found by enclone, which are mapped to the IMGT ranges 1-26 (FWR1), 27-38 (CDR1), \
+             39-55 (FWR2), 56-65 (CDR2), 66-104 (FWR3), 105-117 (CDR3) and 118-128 (FWR4), with \
+             the conserved cysteine at 104 and the conserved W or F at 118.  Short CDRs have gaps \
+             at the center of the loop, long CDRs have insertions there (for example 111.1 and \
+             112.1), and short framework regions have gaps at the positions usually missing in \
+             germline genes.  Because sequences are not aligned to IMGT gapped reference \
+             sequences, the numbering may not agree with IMGT for unusual framework lengths.  The \
+             amino acids at a range of IMGT positions may also be displayed using the cvar \
+             imgt_S_E_aa, for example imgt_27_38_aa for CDR1.\n\n",
+        )?;
         h.print(
             "The default value for \\bold{AMINO} is \\bold{cdr3,var,share,donor}.  \
              Note that we only report amino acids that are strictly within V..J, \
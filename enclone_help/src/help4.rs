@@ -174,6 +174,16 @@ pub fn help4(args: &Vec<String>, mut h: &mut HelpDesk) -> Result<(), String> {
             65,
         )?;
 
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "DOUBLET_MARK",
+            "Use with NDOUBLET.  Instead of deleting exact subclonotypes that appear to \
+            represent doublets, mark their cells.  In either case, the barcode fate records the \
+            score and the two pure subclonotypes that explain each doublet.  The score is shown \
+            by the lead variable doublet_score.",
+            65,
+        )?;
+
         h.rows.push(vec!["\\hline".to_string(); 2]);
         h.docf2(
             "NSIG",
@@ -310,6 +320,138 @@ pub fn help4(args: &Vec<String>, mut h: &mut HelpDesk) -> Result<(), String> {
         h.doc2("See also KEEP_CLONO_IF_CELL_MEAN=... and");
         h.doc2("KEEP_CLONO_IF_CELL_MAX=... at \"enclone help filter\".");
 
+        // Documentation section.
+
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "SAVE_JOIN=f",
+            "Save the state of joining to the file f, so that a later run may add datasets \
+            to this analysis using LOAD_JOIN.  The state consists of the donor reference \
+            alleles, the exact subclonotypes, the joins between them, and an identifier for \
+            each clonotype, which is shown by the lead variable join_id.",
+            55,
+        )?;
+        h.docf2(
+            "LOAD_JOIN=f",
+            "Load a join state saved by SAVE_JOIN, typically from a run on a subset of the \
+            datasets now being analyzed, for example earlier time points.  Exact \
+            subclonotypes that are in the saved state are not compared to each other again, \
+            but rather keep their saved joins, and donor reference alleles are not computed \
+            again for donors in the saved state.  New exact subclonotypes are added to \
+            existing clonotypes or form new ones.  A clonotype keeps its saved join_id, and a \
+            new clonotype gets a new join_id.  To continue a series of runs, use SAVE_JOIN \
+            together with LOAD_JOIN.",
+            55,
+        )?;
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "STABLE_IDS",
+            "Populate the stable_id fields of clonotypes and exact subclonotypes in the output \
+            of PROTO.  These are computed from the content of each clonotype and exact \
+            subclonotype, and hence are the same in different runs of enclone, unlike \
+            clonotype numbers.  The same identifiers are shown by the lead variables \
+            clonotype_sid and exact_sid.",
+            55,
+        )?;
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "SERVE=port",
+            "Run enclone as a server, instead of printing.  The datasets are loaded and \
+            clonotypes are computed once, after which queries are answered in JSON, on the \
+            given port of 127.0.0.1.  GET /ping returns {\"ok\":true}.  POST /query takes a \
+            JSON object as body, all of whose fields are optional: lvars, cvars and pcols, \
+            which are lists of variables, as for LVARS, CVARS and PCOLS; filters, grouping \
+            and args, which are lists of enclone arguments; and fates, which is true to \
+            also return the fates of barcodes.  The response is \
+            {\"clonotypes\":[...],\"fates\":[...]}, in which each clonotype has fields \
+            group_id, clonotype_id, ncells and exact_subclonotypes, each exact subclonotype \
+            has fields chains, cells and vars, and each fate has fields dataset, barcode and \
+            reason.  A query may not change the clonotypes or write files.  Errors are \
+            returned as {\"error\":\"...\"}.  Request bodies are limited to one megabyte.",
+            55,
+        )?;
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "STREAM_INPUT",
+            "Load datasets one at a time, rather than all at once.  The contigs for each \
+            dataset are written to a temporary directory in a compact binary form, and read \
+            back once all datasets have been loaded.  This reduces the memory needed while \
+            parsing, at some cost in speed, but the contigs for all datasets are still held in \
+            memory during clonotyping, so peak memory usage is not bounded by the size of one \
+            dataset.",
+            55,
+        )?;
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "VDJ_CACHE=dir",
+            "Save the contig data read for each dataset in the given directory, in a compact \
+            binary form, and on later runs, use these instead of reading the contig annotation \
+            files again.  Saved data are only used if the annotation file, the reference, and \
+            options that affect how contigs are read, such as REANNOTATE, are unchanged.  The \
+            directory may be shared by many runs, and may be deleted at any time.",
+            55,
+        )?;
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "SHARD_DONORS",
+            "Compute clonotypes separately for each donor, and then merge the results.  \
+            Since clonotypes may not contain cells from more than one donor, this gives \
+            the same answer, except for filters that compare different clonotypes, which \
+            then only compare clonotypes from the same donor.  Donors are processed in \
+            parallel, which is faster when there are many donors, but may use more memory.  \
+            Cells that are not assigned to a \
+            donor are treated as coming from one additional donor.  Cannot be used with \
+            MIX_DONORS, SAVE_JOIN, LOAD_JOIN or DONOR_REF_FILE.",
+            55,
+        )?;
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "PGEN_MODELS=dir",
+            "Compute the generation probability (Pgen) of CDR3 nucleotide sequences, \
+            which is available as the chain variable pgen.  The argument is a directory \
+            having subdirectories named by chain type (IGH, IGK, IGL, TRA, TRB, TRD or \
+            TRG), each containing a recombination model for that chain type, in the format \
+            used by IGoR and OLGA, namely the files model_params.txt, model_marginals.txt, \
+            V_gene_CDR3_anchors.csv and J_gene_CDR3_anchors.csv.  Alternatively, the \
+            argument may be the directory default_models of an OLGA installation, in which \
+            case the human models distributed with OLGA are used, or the mouse models if \
+            MOUSE is specified.  No models are bundled with enclone, so pgen can only be used \
+            together with PGEN_MODELS.  The computation is carried out by enclone, and \
+            follows OLGA.",
+            55,
+        )?;
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "NOVEL_ALLELES",
+            "Print a table of the novel V alleles for each donor, which are the donor reference \
+            sequences that are not identical to any reference V segment.  For each allele, the \
+            table shows the nearest reference V segment of the same length, the positions at \
+            which the allele differs from it (for example G123A means that base G at position 123 \
+            is A in the allele), the number of clonotypes and cells that pass filters and have a \
+            chain assigned to the allele, the number u of these clonotypes having such a chain \
+            with no mutations relative to the allele, and a confidence score, which is 1 - 2^-u.",
+            55,
+        )?;
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "NOVEL_ALLELES_FASTA=f",
+            "Save the novel V alleles for each donor as FASTA to the file f (or stdout).  The \
+            header line for each allele begins with reference record id:donor name:allele \
+            number:gene name, and is followed by the information that is shown by NOVEL_ALLELES.",
+            55,
+        )?;
+        h.rows.push(vec!["\\hline".to_string(); 2]);
+        h.docf2(
+            "DONOR_ALLELES=f",
+            "Use the donor V alleles in the FASTA file f, as written by NOVEL_ALLELES_FASTA or \
+            DONOR_REF_FILE, in place of the alleles computed by enclone, for each donor and V \
+            segment named in the file.  Computed alleles that are identical to a reference V \
+            segment are kept, since NOVEL_ALLELES_FASTA does not write them.  The same \
+            reference must be used.  This makes it possible to build a germline set for a \
+            donor and use it in later runs.",
+            55,
+        )?;
+
         // Done.
 
         h.print_tab2()?;
@@ -510,6 +652,34 @@ pub fn help4(args: &Vec<String>, mut h: &mut HelpDesk) -> Result<(), String> {
         h.doc2("The computation is also inefficient, so let us know if it's causing");
         h.doc2("problems for you.  And cred makes much better sense for datasets that");
         h.doc2("consist of mixed cell types, rather than consisting of pure B or T cells.");
+        h.ldoc(
+            "umap_x, umap_y",
+            "coordinates of the cell in the UMAP projection, from Cell Ranger, from an",
+        );
+        h.doc2("AnnData file (obsm/X_umap), or from the EMBEDDING file; for an exact");
+        h.doc2("subclonotype, the mean across its cells");
+        h.doc("tsne_x, tsne_y", "same, for the t-SNE projection");
+        h.ldoc(
+            "ag_score",
+            "maximum over antigens of the score log2((n+1)/(b+1)), where n is the UMI",
+        );
+        h.doc2("count of the antigen and b is the mean UMI count of the control features;");
+        h.doc2("for an exact subclonotype, the maximum of the median scores; see");
+        h.doc2("\"enclone help input\" for ANTIGEN_CONTROLS");
+        h.ldoc(
+            "ag_spec",
+            "antigens whose median score across the cells of the exact subclonotype",
+        );
+        h.doc2("is at least ANTIGEN_MIN_SCORE, separated by +, or none");
+        h.doc("ag_spec_clono", "same, for the clonotype");
+        h.ldoc(
+            "cell_type",
+            "cell type, from the Cell Ranger file celltypes.csv if present, and otherwise",
+        );
+        h.doc2("computed from marker genes: B naive, B memory, B plasmablast, T CD4, T CD8,");
+        h.doc2("T MAIT, T gamma-delta, T regulatory, or just B or T if no subset markers are");
+        h.doc2("expressed, or other; cells may be colored by it, using for example");
+        h.doc2("HONEY=...,color=catvar,cell_type,maxcat:10");
         h.rows.push(vec!["\\hline".to_string(); 2]);
         h.docf2(
             "filter",
@@ -524,6 +694,13 @@ pub fn help4(args: &Vec<String>, mut h: &mut HelpDesk) -> Result<(), String> {
             exploratory tool.",
             75,
         )?;
+        h.ldoc(
+            "doublet_score",
+            "score from the doublet filter: the ratio of the number of cells in the",
+        );
+        h.doc2("smaller of the two pure subclonotypes that best explain this exact");
+        h.doc2("subclonotype as a doublet, to its own number of cells, or zero if there");
+        h.doc2("is no explanation; deleted or marked if at least five, see DOUBLET_MARK");
         h.ldoc(
             "nbc",
             "numerically encoded barcode: a ten-digit number, padded with zeros",
@@ -538,6 +715,22 @@ pub fn help4(args: &Vec<String>, mut h: &mut HelpDesk) -> Result<(), String> {
         h.doc2("and then scoring +1 of each inserted base, +1 for each deletion,");
         h.doc2("regardless of size, and +1 for each substitution");
         h.doc("jun_ins", "like hcomp, but only counts inserted bases");
+        h.ldoc(
+            "join_id",
+            "identifier of the clonotype in the join state saved by SAVE_JOIN, which is",
+        );
+        h.doc2("kept when the state is loaded again by LOAD_JOIN");
+        h.ldoc(
+            "clonotype_sid",
+            "identifier of the clonotype, computed from the donor, V and J genes and",
+        );
+        h.doc2("CDR3 sequences of its largest exact subclonotype, so that it is the same");
+        h.doc2("in every run of enclone");
+        h.doc(
+            "exact_sid",
+            "like clonotype_sid, but for the exact subclonotype, and computed from the",
+        );
+        h.doc2("donor, and the V..J sequences and constant regions of its chains");
         h.print_tab2()?;
         h.print(
             "For gene expression and feature barcode stats, such data must be provided \
@@ -335,6 +335,21 @@ pub fn help5(args: &Vec<String>, ctl: &EncloneControl, h: &mut HelpDesk) -> Resu
         h.print_tab2()?;
         h.print("\n")?;
 
+        h.print(
+            "Public clonotypes.  The option PUBLIC_CLUSTERS=filename reports convergent clusters \
+            of clonotypes across donors.  The clusters are the groups defined by GROUP, so the \
+            rule for clustering is specified there, for example \
+            GROUP=vj_heavy_refname,cdr3_aa_heavy≥90%, and the grouping options above also \
+            apply.  A cluster is reported if it has cells from at least two donors (or \
+            MIN_GROUP_DONORS, if larger).  The report is a CSV file (or stdout), having one \
+            line per cluster, which gives the GROUP number, the number of clonotypes, cells and \
+            donors, the number of cells from each donor, the heavy chain V and J genes and \
+            CDR3 amino acid sequences, the light chain V genes, and light chain coherence.  \
+            Light chain coherence is computed using pairs of cells from different donors, each \
+            having exactly one light chain, and is the percentage of those pairs whose light \
+            chains have the same V gene.  For TCR, heavy and light mean beta and alpha.\n\n",
+        )?;
+
         h.print(
             "\\red{━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\
             ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━}\n\n",
@@ -375,6 +390,101 @@ pub fn help5(args: &Vec<String>, ctl: &EncloneControl, h: &mut HelpDesk) -> Resu
              Please see https://10xgenomics.github.io/enclone/pages/auto/d_genes.html for more \
              information.\n\n",
         )?;
+
+        h.print(
+            "\\red{━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\
+            ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━}\n\n",
+        )?;
+
+        h.print(
+            "\\bold{options that display diversity statistics}\n\n\
+             The option DIVERSITY prints a table of repertoire diversity statistics, and the \
+             option DIVERSITY_CSV prints the same information, in CSV form.  These are computed \
+             from the number of cells in each clonotype, after filtering, for all cells, and \
+             separately for each dataset, origin and donor.  The statistics are the number of \
+             cells and clonotypes, the Shannon entropy (natural log), the Gini-Simpson index, the \
+             bias-corrected Chao1 estimate, the Hill numbers of order zero, one and two, and \
+             clonality, which is one minus Pielou evenness.  In addition, the Hill numbers are \
+             computed for a random subsample (rarefaction), whose size is the smallest number of \
+             cells in a dataset (respectively origin or donor), using the formulas of Chao et al. \
+             (2014).  If there is more than one origin, the Morisita-Horn and Jaccard overlap \
+             indices between each pair of origins are also printed.\n",
+        )?;
+
+        h.print(
+            "\\red{━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\
+            ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━}\n\n",
+        )?;
+
+        h.print(
+            "\\bold{options that display somatic hypermutation}\n\n\
+             The option SHM_PROFILE prints tables of somatic hypermutation in the V segments of \
+             the clonotypes that pass filters, and the option SHM_PROFILE_SVG=filename saves a \
+             plot of the mutation frequency at each position, for heavy and light chains, as an \
+             SVG file (or stdout).  Each exact subclonotype is counted once, and for each chain, \
+             the sequence from the start of FWR1 to the start of CDR3 is compared to the donor \
+             reference (or the universal reference, if there is no donor reference).  Positions \
+             are IMGT positions, computed as for the imgt_S_E_aa variables, and codons at \
+             insertion positions are counted only in the region totals.  A mutation is a \
+             replacement mutation if changing that base alone changes the amino acid, and \
+             otherwise is a silent mutation.  For each region (FWR1, CDR1, FWR2, CDR2 and FWR3), \
+             and for FWR and CDR as a whole, the number of replacement and silent mutations is \
+             shown, together with an estimate of selection, in the spirit of BASELINe, but using \
+             a uniform mutation model.  Selection is the log of the ratio of the observed odds \
+             that a mutation is a replacement, to the expected odds, computed from the germline \
+             sequence.  Positive values suggest positive selection.  Because the mutation model \
+             is uniform, it does not account for the hotspot and coldspot motifs (such as WRCY \
+             and SYC) that target somatic hypermutation, so the expected odds, and hence the \
+             selection estimates, may be biased.  For TCR, heavy and light mean beta and \
+             alpha.\n",
+        )?;
+
+        h.print(
+            "\\red{━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\
+            ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━}\n\n",
+        )?;
+
+        h.print(
+            "\\bold{options that find matching structures}\n\n\
+             The option PDB_MATCH=dir finds known antibody or TCR structures that are similar to \
+             the clonotypes that pass filters, where dir is a directory of compressed structure \
+             files in the format used by the Protein Data Bank, named for example 7B3O.gz.  To \
+             report on just one clonotype, add PDB_MATCH_ID=g.c, where g.c is the id shown for \
+             the clonotype in the clonotype display, for example 1.1.  For each clonotype, the \
+             exact subclonotype having the most cells is used, and its heavy and light chains, \
+             from the start of FWR1 to the end of J, are aligned to the chains of each structure. \
+             The clonotype chains are assigned to distinct structure chains so as to maximize the \
+             number of identical amino acids, and the five structures having the highest paired \
+             identity are shown.  For each chain, the table shows its identity to the assigned \
+             structure chain, the number of CDR1, CDR2 and CDR3 positions that match, and the \
+             number of paratope residues.  Structure chains that are not assigned to the \
+             clonotype are treated as antigen, and a paratope residue is one having an atom \
+             within 5 Å of an antigen atom.  Paratope residues are then listed, using the \
+             clonotype amino acid and its IMGT position (or its position starting at FWR1, \
+             prefixed by #, if it cannot be numbered), followed by the structure amino acid in \
+             parentheses, if different.  For TCR, heavy and light mean beta and alpha.\n",
+        )?;
+
+        h.print(
+            "\\red{━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\
+            ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━}\n\n",
+        )?;
+
+        h.print(
+            "\\bold{options that generate a QC report}\n\n\
+             The option QC_REPORT=filename writes a quality control report for the run, as a \
+             single self-contained HTML file (or stdout).  For each dataset, the report shows the \
+             number of VDJ cells remaining after each filter stage, for the filters that were \
+             applied, followed by the number of cells in clonotypes.  For the cells in \
+             clonotypes, it shows the number of chains per cell and the fraction of cells having \
+             paired chains, and the quartiles of the UMI count per cell and per chain.  If GEX \
+             data are provided, it shows the fraction of VDJ cells, and of cells in clonotypes, \
+             that are also GEX cells.  Then the warnings raised during the run are listed.  These \
+             include inconsistency between VDJ and GEX cells, if ALLOW_INCONSISTENT was \
+             specified, defective reference sequences, pairing rates below 50%, and VDJ-GEX \
+             concordance below 50%.  Finally, there are plots of the distribution of UMI counts \
+             per cell, and of the number of cells per clonotype, for each dataset.\n",
+        )?;
         h.end_doc();
     }
 
@@ -644,7 +754,14 @@ pub fn help5(args: &Vec<String>, ctl: &EncloneControl, h: &mut HelpDesk) -> Resu
              by adding \\bold{REF=f} to your command line, where \\bold{f} is the name of your \
              VDJ reference fasta file, but if that is different than the reference \
              supplied to Cell Ranger, then you will have to add the additional argument \
-             \\bold{RE} to recompute annotations, and that will slow down enclone somewhat.\n\n",
+             \\bold{RE} to recompute annotations, and that will slow down enclone somewhat.\n\n\
+             For species other than human and mouse, you may instead add \
+             \\bold{GERMLINE_REF=f1,...,fn} to your command line, where \\bold{f1,...,fn} are \
+             germline fasta files from IMGT or OGRDB, with or without IMGT gaps.  These are \
+             converted into a VDJ reference, after checking the boundaries of the V and J \
+             segments, and annotations are recomputed.  Only the first allele of each gene is \
+             used, and pseudogenes, and ORFs that fail these checks, are skipped.  The numbers \
+             of records skipped are reported, unless \\bold{NWARN} is specified.\n\n",
         )?;
 
         h.print("\\boldblue{8. Can I provide data from more than one donor?}\n\n")?;
@@ -0,0 +1,264 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+//
+// Library interface to enclone.  This allows enclone to be run in-process, with results returned
+// as structured objects, rather than as text.  A run is configured using EncloneBuilder, which
+// accepts the same arguments as the command line, for example:
+//
+// let results = EncloneBuilder::new()
+//     .bcr("123085")
+//     .lvars(&["n", "cdr3_aa1", "clonotype_sid"])
+//     .arg("MIN_CELLS=2")
+//     .run()?;
+//
+// The values of lead and chain variables are exactly those that would appear in parseable
+// output.  If PCOLS is not specified, they are the variables that would be displayed (as
+// specified by LVARS and CVARS), with chain variables having the chain number appended.
+
+use crate::main_enclone::main_enclone;
+use enclone_core::defs::{POUT_MEMORY, POUT_SEP};
+use enclone_core::enclone_structs::EncloneState;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::str::FromStr;
+use string_utils::strme;
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+#[derive(Clone, Default)]
+pub struct EncloneBuilder {
+    args: Vec<String>,
+}
+
+impl EncloneBuilder {
+    pub fn new() -> EncloneBuilder {
+        EncloneBuilder::default()
+    }
+
+    // Add an arbitrary enclone argument, e.g. "MIN_CELLS=2" or "NUMI".
+
+    pub fn arg(mut self, arg: &str) -> EncloneBuilder {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    fn keyval(self, key: &str, val: &str) -> EncloneBuilder {
+        self.arg(&format!("{}={}", key, val))
+    }
+
+    pub fn bcr(self, path: &str) -> EncloneBuilder {
+        self.keyval("BCR", path)
+    }
+
+    pub fn tcr(self, path: &str) -> EncloneBuilder {
+        self.keyval("TCR", path)
+    }
+
+    pub fn gex(self, path: &str) -> EncloneBuilder {
+        self.keyval("GEX", path)
+    }
+
+    pub fn meta(self, path: &str) -> EncloneBuilder {
+        self.keyval("META", path)
+    }
+
+    pub fn reference(self, path: &str) -> EncloneBuilder {
+        self.keyval("REF", path)
+    }
+
+    pub fn lvars(self, vars: &[&str]) -> EncloneBuilder {
+        self.keyval("LVARS", &vars.iter().format(",").to_string())
+    }
+
+    pub fn cvars(self, vars: &[&str]) -> EncloneBuilder {
+        self.keyval("CVARS", &vars.iter().format(",").to_string())
+    }
+
+    pub fn pcols(self, vars: &[&str]) -> EncloneBuilder {
+        self.keyval("PCOLS", &vars.iter().format(",").to_string())
+    }
+
+    // Return the argument vector that would be passed to enclone.  Output to the terminal is
+    // turned off, and parseable output, which is needed to compute variable values, is kept in
+    // memory only, unless POUT was specified.
+
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec!["enclone".to_string()];
+        args.append(&mut self.args.clone());
+        for x in ["NOPRINT", "NOPAGER", "NO_NEWLINE", "PLAIN"].iter() {
+            if !self.args.contains(&x.to_string()) {
+                args.push(x.to_string());
+            }
+        }
+        if !self.args.iter().any(|x| x.starts_with("POUT=")) {
+            args.push(format!("POUT={}", POUT_MEMORY));
+        }
+        args
+    }
+
+    pub fn run(&self) -> Result<EncloneResults, String> {
+        let state = main_enclone(&self.args())?;
+        Ok(EncloneResults::new(state))
+    }
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chain {
+    pub chain_type: String, // e.g. IGH
+    pub v_name: String,
+    pub d_name: Option<String>,
+    pub j_name: String,
+    pub c_name: Option<String>,
+    pub cdr3_aa: String,
+    pub cdr3_dna: String,
+    pub seq: String, // V..J sequence
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cell {
+    pub dataset: String,
+    pub barcode: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExactSubclonotype {
+    pub id: usize,                     // index in EncloneState.inter.ex.exact_clonotypes
+    pub chains: Vec<Option<Chain>>,    // one entry for each column of the clonotype
+    pub cells: Vec<Cell>,              // the cells, sorted as in parseable output
+    pub vars: HashMap<String, String>, // parseable variable values
+}
+
+impl ExactSubclonotype {
+    pub fn ncells(&self) -> usize {
+        self.cells.len()
+    }
+
+    // Return the value of a variable, parsed as the given type, e.g. usize for n.  Return None
+    // if the variable is not present or cannot be parsed.
+
+    pub fn value<T: FromStr>(&self, var: &str) -> Option<T> {
+        self.vars.get(var)?.parse::<T>().ok()
+    }
+
+    // Return the value of a variable for each cell.  A variable that is defined at the level
+    // of the exact subclonotype has the same value for every cell.
+
+    pub fn cell_values(&self, var: &str) -> Option<Vec<String>> {
+        let vals = self.vars.get(var)?.split(POUT_SEP).collect::<Vec<&str>>();
+        let mut x = Vec::<String>::new();
+        for i in 0..self.cells.len() {
+            if vals.len() == self.cells.len() {
+                x.push(vals[i].to_string());
+            } else {
+                x.push(vals[0].to_string());
+            }
+        }
+        Some(x)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Clonotype {
+    pub group_id: usize,     // one-based, as in parseable output
+    pub clonotype_id: usize, // one-based, within the group
+    pub exact_subclonotypes: Vec<ExactSubclonotype>,
+}
+
+impl Clonotype {
+    pub fn ncells(&self) -> usize {
+        self.exact_subclonotypes.iter().map(|x| x.ncells()).sum()
+    }
+}
+
+// The fate of a barcode that was not placed in a clonotype, as shown by SUMMARY.
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fate {
+    pub dataset: String,
+    pub barcode: String,
+    pub reason: String,
+}
+
+pub struct EncloneResults {
+    pub clonotypes: Vec<Clonotype>,
+    pub fates: Vec<Fate>,
+    pub state: EncloneState, // everything else
+}
+
+impl EncloneResults {
+    pub fn new(state: EncloneState) -> EncloneResults {
+        let ctl = &state.inter.setup.ctl;
+        let refdata = &state.inter.setup.refdata;
+        let exact_clonotypes = &state.inter.ex.exact_clonotypes;
+        let clon = &state.clonotypes;
+        let mut clonotypes = Vec::<Clonotype>::new();
+        for i in 0..clon.groups.len() {
+            for j in 0..clon.groups[i].len() {
+                let oo = clon.groups[i][j].0 as usize;
+                let rsi = &clon.rsi[oo];
+                let mut exacts = Vec::<ExactSubclonotype>::new();
+                for u in 0..clon.exacts[oo].len() {
+                    let id = clon.exacts[oo][u];
+                    let ex = &exact_clonotypes[id];
+                    let mut chains = Vec::<Option<Chain>>::new();
+                    for cx in 0..rsi.mat.len() {
+                        chains.push(rsi.mat[cx][u].map(|m| {
+                            let x = &ex.share[m];
+                            Chain {
+                                chain_type: x.chain_type.clone(),
+                                v_name: refdata.name[x.v_ref_id].clone(),
+                                d_name: x.d_ref_id.map(|d| refdata.name[d].clone()),
+                                j_name: refdata.name[x.j_ref_id].clone(),
+                                c_name: x.c_ref_id.map(|c| refdata.name[c].clone()),
+                                cdr3_aa: x.cdr3_aa.clone(),
+                                cdr3_dna: x.cdr3_dna.clone(),
+                                seq: strme(&x.seq).to_string(),
+                            }
+                        }));
+                    }
+                    let mut cells = Vec::<Cell>::new();
+                    for l in 0..ex.clones.len() {
+                        cells.push(Cell {
+                            dataset: ctl.origin_info.dataset_id[ex.clones[l][0].dataset_index]
+                                .clone(),
+                            barcode: ex.clones[l][0].barcode.clone(),
+                        });
+                    }
+                    let mut vars = HashMap::<String, String>::new();
+                    if u < clon.out_datas[oo].len() {
+                        vars = clon.out_datas[oo][u].clone();
+                    }
+                    exacts.push(ExactSubclonotype {
+                        id,
+                        chains,
+                        cells,
+                        vars,
+                    });
+                }
+                clonotypes.push(Clonotype {
+                    group_id: i + 1,
+                    clonotype_id: j + 1,
+                    exact_subclonotypes: exacts,
+                });
+            }
+        }
+        let mut fates = Vec::<Fate>::new();
+        for (li, f) in state.inter.ex.fate.iter().enumerate() {
+            let mut bcs = f.iter().collect::<Vec<_>>();
+            bcs.sort();
+            for (barcode, reason) in bcs {
+                fates.push(Fate {
+                    dataset: ctl.origin_info.dataset_id[li].clone(),
+                    barcode: barcode.clone(),
+                    reason: reason.clone(),
+                });
+            }
+        }
+        EncloneResults {
+            clonotypes,
+            fates,
+            state,
+        }
+    }
+}
@@ -2,7 +2,9 @@
 
 // Start of code to determine the reference sequence that is to be used.
 
+use enclone_args::read_airr::is_airr_path;
 use enclone_core::defs::EncloneControl;
+use enclone_core::germline_ref::{germline_to_ref, GermlineSkips};
 use io_utils::{open_for_read, open_maybe_compressed, path_exists, read_vector_entry_from_json};
 use serde_json::Value;
 use std::collections::HashMap;
@@ -24,10 +26,15 @@ pub fn determine_ref(ctl: &mut EncloneControl, refx: &mut String) -> Result<(),
     } else {
         ann = "contig_annotations.json";
     }
+    // AIRR rearrangement files carry no reference, so use the first dataset that is not one.
+
+    let airr = (0..ctl.origin_info.n())
+        .map(|li| is_airr_path(&ctl.origin_info.dataset_path[li]))
+        .collect::<Vec<bool>>();
     let mut jsonx = String::new();
-    if ctl.origin_info.n() > 0 {
-        let json = format!("{}/{}", ctl.origin_info.dataset_path[0], ann);
-        let json_lz4 = format!("{}/{}.lz4", ctl.origin_info.dataset_path[0], ann);
+    if let Some(li) = airr.iter().position(|x| !*x) {
+        let json = format!("{}/{}", ctl.origin_info.dataset_path[li], ann);
+        let json_lz4 = format!("{}/{}.lz4", ctl.origin_info.dataset_path[li], ann);
         if !path_exists(&json) && !path_exists(&json_lz4) {
             return Err(format!(
                 "\nUnable to find a VDJ input file: can't find\n{}\nor {}.\n\n\
@@ -181,6 +188,36 @@ pub fn determine_ref(ctl: &mut EncloneControl, refx: &mut String) -> Result<(),
         }
     }
 
+    // Step 3, continued.  Test to see if GERMLINE_REF is specified, and if so, convert the IMGT
+    // or OGRDB files to a reference.  Annotations have to be recomputed.
+
+    if refx.is_empty() && !ctl.gen_opt.germline_ref.is_empty() {
+        let mut files = Vec::<(String, String)>::new();
+        for f in ctl.gen_opt.germline_ref.split(',') {
+            if ctl.gen_opt.descrip {
+                println!("using germline file = {}", f);
+            }
+            let fasta = std::fs::read_to_string(f);
+            if fasta.is_err() {
+                return Err(format!(
+                    "\nProblem with GERMLINE_REF: unable to read from the file\n\"{}\".\n",
+                    f
+                ));
+            }
+            files.push((f.to_string(), fasta.unwrap()));
+        }
+        let (r, skips) = germline_to_ref(&files)?;
+        if !ctl.gen_opt.nwarn && skips != GermlineSkips::default() {
+            eprintln!(
+                "\nGERMLINE_REF: skipped {} additional alleles (only the first allele of each \
+                 gene is used), {} pseudogenes, and {} ORFs that failed validation.\n",
+                skips.alleles, skips.pseudogenes, skips.orfs
+            );
+        }
+        *refx = r;
+        ctl.gen_opt.reannotate = true;
+    }
+
     // Step 4.  Test for presence of a reference file in the VDJ directories.
 
     if refx.is_empty() && ctl.gen_opt.refname.is_empty() {
@@ -329,6 +366,23 @@ pub fn determine_ref(ctl: &mut EncloneControl, refx: &mut String) -> Result<(),
             }
         }
     }
+
+    // Step 6.  If any dataset is an AIRR rearrangement file, its annotations are relative to an
+    // unknown reference, so we require that the reference be specified or determined from
+    // another dataset.  AIRR files are always reannotated, and we force reannotation so that
+    // any json inputs are treated consistently.
+
+    if airr.contains(&true) {
+        if refx.is_empty() {
+            return Err(
+                "\nenclone can't determine the reference sequence to use for an AIRR \
+                rearrangement file.\nPlease use the REF argument to specify the name of the \
+                reference fasta file, or\nspecify BUILT_IN to use the built-in reference.\n"
+                    .to_string(),
+            );
+        }
+        ctl.gen_opt.reannotate = true;
+    }
     if refx.is_empty() && !jsonx.is_empty() {
         return Err(
             "\nenclone was unable to determine the reference sequence that you used.  You \
@@ -1,9 +1,11 @@
 // Copyright (c) 2021 10x Genomics, Inc. All rights reserved.
 
+pub mod api;
 pub mod determine_ref;
 pub mod main_enclone;
 pub mod opt_d_val;
 pub mod sec_mem;
+pub mod serve;
 pub mod setup;
 pub mod stop;
 pub mod subset;
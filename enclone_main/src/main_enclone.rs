@@ -15,6 +15,7 @@ use enclone_args::proc_args2::is_simple_arg;
 use enclone_args::proc_args_check::{
     check_gvars, check_lvars, check_one_lvar, check_pcols, get_known_features,
 };
+use enclone_args::read_airr::is_airr_path;
 use enclone_base::blacklist::profiling_blacklist;
 use enclone_core::cell_color::CellColor;
 use enclone_core::defs::EncloneControl;
@@ -292,7 +293,7 @@ pub fn main_enclone_setup(args: &Vec<String>) -> Result<EncloneSetup, String> {
             }
         }
         let known_features = get_known_features(&gex_info)?;
-        let extras = ["gex", "type", "clust", "cell"];
+        let extras = ["gex", "cell_type", "clust", "cell"];
         for i in 0..ctl.gen_opt.all_bc_fields.len() {
             let var = &ctl.gen_opt.all_bc_fields[i];
             let mut ok = false;
@@ -450,6 +451,10 @@ pub fn main_enclone_setup(args: &Vec<String>) -> Result<EncloneSetup, String> {
     // Get VDJ data paths.
 
     for li in 0..ctl.origin_info.dataset_path.len() {
+        if is_airr_path(&ctl.origin_info.dataset_path[li]) {
+            ctl.pathlist.push(ctl.origin_info.dataset_path[li].clone());
+            continue;
+        }
         let json = format!("{}/{}", ctl.origin_info.dataset_path[li], ann);
         let json_lz4 = format!("{}/{}.lz4", ctl.origin_info.dataset_path[li], ann);
         if !path_exists(&json) && !path_exists(&json_lz4) {
@@ -0,0 +1,370 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+//
+// Headless server mode for enclone, invoked by adding SERVE=port to an otherwise ordinary enclone
+// command.  The datasets are loaded and clonotypes are computed once.  The server then listens on
+// 127.0.0.1:port, and answers queries with JSON, so that several clients can share one loaded
+// analysis.  Requests are handled one at a time.  Request bodies are limited to MAX_BODY bytes,
+// and a client that stalls for TIMEOUT seconds is dropped.
+//
+// The API is plain HTTP:
+//
+// GET /ping
+// returns {"ok":true}.
+//
+// POST /query
+// with a JSON object as body, all of whose fields are optional:
+// {
+//   "lvars": ["n", "cdr3_aa1"],         // lead variables, as LVARS
+//   "cvars": ["u", "const"],            // chain variables, as CVARS
+//   "pcols": ["cdr3_aa1", "n"],         // variables to return, as PCOLS
+//   "filters": ["MIN_CELLS=3"],         // filtering arguments
+//   "grouping": ["GROUP=vj_refname"],   // grouping arguments
+//   "args": ["BARCODE=..."],            // other enclone arguments
+//   "fates": true                       // also return the fates of barcodes
+// }
+// returns {"clonotypes": [...], "fates": [...]}, where each clonotype is given by its group_id,
+// clonotype_id, ncells and exact_subclonotypes, and each exact subclonotype by its chains,
+// cells and variable values, as in the library API.
+//
+// Arguments that would change the clonotypes themselves, rather than how they are filtered,
+// grouped and displayed, cannot be used in queries, and neither can arguments that write files.
+// These result in an error, which is returned as {"error": "..."}.
+
+use crate::api::{Chain, Clonotype, EncloneBuilder, EncloneResults};
+use crate::main_enclone::main_enclone_setup;
+use crate::stop::main_enclone_stop_in_place;
+use enclone_core::defs::EncloneControl;
+use enclone_core::enclone_structs::{EncloneIntermediates, EncloneState};
+use enclone_stuff::start::main_enclone_start;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use string_utils::TextUtils;
+
+const MAX_BODY: usize = 1_000_000;
+const TIMEOUT: u64 = 60;
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Determine if two runs of enclone share their intermediate results (exact subclonotypes and
+// joins), based on their control structures.  We are very conservative about this, and only
+// allow changes to:
+// * start_time
+// * clono_filt_opt or clono_print_opt
+// * clono_group_opt or parseable_opt
+// * plot_opt.
+// More exceptions could be added.
+
+pub fn same_intermediates(ctl1: &EncloneControl, ctl2: &EncloneControl) -> bool {
+    ctl1.perf_opt == ctl2.perf_opt
+        && ctl1.gen_opt == ctl2.gen_opt
+        && ctl1.pretty == ctl2.pretty
+        && ctl1.silent == ctl2.silent
+        && ctl1.force == ctl2.force
+        && ctl1.debug_table_printing == ctl2.debug_table_printing
+        && ctl1.merge_all_impropers == ctl2.merge_all_impropers
+        && ctl1.heur == ctl2.heur
+        && ctl1.origin_info == ctl2.origin_info
+        && ctl1.clono_filt_opt_def == ctl2.clono_filt_opt_def
+        && ctl1.allele_alg_opt == ctl2.allele_alg_opt
+        && ctl1.allele_print_opt == ctl2.allele_print_opt
+        && ctl1.join_alg_opt == ctl2.join_alg_opt
+        && ctl1.pathlist == ctl2.pathlist
+        && ctl1.last_modified == ctl2.last_modified
+}
+
+// Determine if a query would write files, which are only allowed if the server was started with
+// them, as the server may be shared.  Other options that write files are in gen_opt, and hence
+// cannot be changed by a query at all.
+
+fn query_writes_files(query: &EncloneControl, server: &EncloneControl) -> bool {
+    let (p1, p2) = (&query.plot_opt, &server.plot_opt);
+    query.parseable_opt.pout != server.parseable_opt.pout
+        || p1.plot_file != p2.plot_file
+        || p1.plot_xy_filename != p2.plot_xy_filename
+        || p1.sim_mat_plot_file != p2.sim_mat_plot_file
+        || p1.honey_out != p2.honey_out
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+fn chain_json(x: &Option<Chain>) -> Value {
+    match x {
+        None => Value::Null,
+        Some(x) => json!({
+            "chain_type": x.chain_type,
+            "v_name": x.v_name,
+            "d_name": x.d_name,
+            "j_name": x.j_name,
+            "c_name": x.c_name,
+            "cdr3_aa": x.cdr3_aa,
+            "cdr3_dna": x.cdr3_dna,
+            "seq": x.seq,
+        }),
+    }
+}
+
+fn clonotype_json(c: &Clonotype) -> Value {
+    let mut exacts = Vec::<Value>::new();
+    for ex in c.exact_subclonotypes.iter() {
+        let mut cells = Vec::<Value>::new();
+        for cell in ex.cells.iter() {
+            cells.push(json!({"dataset": cell.dataset, "barcode": cell.barcode}));
+        }
+        exacts.push(json!({
+            "chains": ex.chains.iter().map(chain_json).collect::<Vec<_>>(),
+            "cells": cells,
+            "vars": ex.vars,
+        }));
+    }
+    json!({
+        "group_id": c.group_id,
+        "clonotype_id": c.clonotype_id,
+        "ncells": c.ncells(),
+        "exact_subclonotypes": exacts,
+    })
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Run a query against the loaded analysis.
+
+fn query(
+    base: &EncloneBuilder,
+    inter: &mut EncloneIntermediates,
+    body: &str,
+) -> Result<Value, String> {
+    let q: Value =
+        serde_json::from_str(body).map_err(|e| format!("unable to parse query: {}", e))?;
+    let mut builder = base.clone();
+    for (key, arg) in [("lvars", "LVARS"), ("cvars", "CVARS"), ("pcols", "PCOLS")].iter() {
+        if let Some(x) = q.get(key) {
+            let vars = x
+                .as_array()
+                .ok_or_else(|| format!("{} must be a list", key))?
+                .iter()
+                .map(|v| v.as_str().unwrap_or_default().to_string())
+                .collect::<Vec<String>>();
+            builder = builder.arg(&format!("{}={}", arg, vars.join(",")));
+        }
+    }
+    for key in ["filters", "grouping", "args"].iter() {
+        if let Some(x) = q.get(key) {
+            let list = x
+                .as_array()
+                .ok_or_else(|| format!("{} must be a list", key))?;
+            for v in list.iter() {
+                let arg = v
+                    .as_str()
+                    .ok_or_else(|| format!("{} must be strings", key))?;
+                builder = builder.arg(arg);
+            }
+        }
+    }
+    let setup = main_enclone_setup(&builder.args())?;
+    if setup.tall.is_none() {
+        return Err("query does not describe an enclone run".to_string());
+    }
+    if !same_intermediates(&setup.ctl, &inter.setup.ctl) {
+        return Err(
+            "query changes options that determine the clonotypes, which is not \
+            allowed, as these were computed when the server was started"
+                .to_string(),
+        );
+    }
+    if query_writes_files(&setup.ctl, &inter.setup.ctl) {
+        return Err("query uses options that write files, which is not allowed".to_string());
+    }
+
+    // Run the query on the loaded exact subclonotypes, without copying them.  They are returned
+    // afterwards, with the fates of barcodes as they were before the query.
+
+    let fate = inter.ex.fate.clone();
+    let mut qinter = EncloneIntermediates {
+        setup,
+        ex: std::mem::take(&mut inter.ex),
+    };
+    let stop = main_enclone_stop_in_place(&mut qinter);
+    let (outs, clonotypes) = match stop {
+        Ok(x) => x,
+        Err(e) => {
+            inter.ex = qinter.ex;
+            inter.ex.fate = fate;
+            return Err(e);
+        }
+    };
+    let results = EncloneResults::new(EncloneState {
+        inter: qinter,
+        outs,
+        clonotypes,
+    });
+    let mut fates = Vec::<Value>::new();
+    if q.get("fates").and_then(|x| x.as_bool()) == Some(true) {
+        for f in results.fates.iter() {
+            fates.push(json!({"dataset": f.dataset, "barcode": f.barcode, "reason": f.reason}));
+        }
+    }
+    let v = json!({
+        "clonotypes": results.clonotypes.iter().map(clonotype_json).collect::<Vec<_>>(),
+        "fates": fates,
+    });
+    inter.ex = results.state.inter.ex;
+    inter.ex.fate = fate;
+    Ok(v)
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+fn respond(stream: &mut TcpStream, status: &str, body: &Value) {
+    let body = body.to_string();
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+        Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+}
+
+// Parse an HTTP request, returning its method, path and body, or an error status and message.
+
+fn parse_request<R: BufRead>(
+    reader: &mut R,
+) -> Result<(String, String, Vec<u8>), (&'static str, &'static str)> {
+    let bad = ("400 Bad Request", "bad request");
+    let mut request = String::new();
+    if reader.read_line(&mut request).is_err() {
+        return Err(bad);
+    }
+    let fields = request.split_whitespace().collect::<Vec<&str>>();
+    if fields.len() < 2 {
+        return Err(bad);
+    }
+    let mut len = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() {
+            return Err(bad);
+        }
+        if line.trim().is_empty() {
+            break;
+        }
+        if line.to_lowercase().starts_with("content-length:") {
+            len = line.after(":").trim().parse::<usize>().map_err(|_| bad)?;
+        }
+    }
+    if len > MAX_BODY {
+        return Err(("413 Payload Too Large", "request body is too large"));
+    }
+    let mut body = vec![0_u8; len];
+    if reader.read_exact(&mut body).is_err() {
+        return Err(bad);
+    }
+    Ok((fields[0].to_string(), fields[1].to_string(), body))
+}
+
+fn handle(stream: TcpStream, base: &EncloneBuilder, inter: &mut EncloneIntermediates) {
+    let timeout = Some(Duration::from_secs(TIMEOUT));
+    if stream.set_read_timeout(timeout).is_err() || stream.set_write_timeout(timeout).is_err() {
+        return;
+    }
+    let mut reader = BufReader::new(stream);
+    let request = parse_request(&mut reader);
+    let mut stream = reader.into_inner();
+    match request {
+        Err((status, msg)) => respond(&mut stream, status, &json!({ "error": msg })),
+        Ok((method, path, _)) if method == "GET" && path == "/ping" => {
+            respond(&mut stream, "200 OK", &json!({"ok": true}));
+        }
+        Ok((method, path, body)) if method == "POST" && path == "/query" => {
+            match query(base, inter, &String::from_utf8_lossy(&body)) {
+                Ok(v) => respond(&mut stream, "200 OK", &v),
+                Err(e) => respond(&mut stream, "400 Bad Request", &json!({"error": e.trim()})),
+            }
+        }
+        Ok(_) => respond(
+            &mut stream,
+            "404 Not Found",
+            &json!({"error": "unknown request"}),
+        ),
+    }
+}
+
+pub fn serve(args: &[String]) -> Result<(), String> {
+    let mut port = String::new();
+    let mut base = EncloneBuilder::new();
+    for arg in args.iter().skip(1) {
+        if arg.starts_with("SERVE=") {
+            port = arg.after("SERVE=").to_string();
+        } else {
+            base = base.arg(arg);
+        }
+    }
+    if port.parse::<u16>().is_err() {
+        return Err(format!("\nSERVE={} is not a valid port.\n", port));
+    }
+    let setup = main_enclone_setup(&base.args())?;
+    if setup.tall.is_none() {
+        return Ok(());
+    }
+    let mut inter = main_enclone_start(setup)?;
+    if inter.setup.tall.is_none() {
+        return Ok(());
+    }
+    let listener = TcpListener::bind(format!("127.0.0.1:{}", port))
+        .map_err(|e| format!("\nUnable to listen on port {}: {}.\n", port, e))?;
+    eprintln!("enclone server listening on 127.0.0.1:{}", port);
+    for stream in listener.incoming().flatten() {
+        handle(stream, &base, &mut inter);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let mut r = "GET /ping HTTP/1.1\r\nHost: localhost\r\n\r\n".as_bytes();
+        let (method, path, body) = parse_request(&mut r).unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("GET", "/ping"));
+        assert!(body.is_empty());
+        let mut r = "POST /query HTTP/1.1\r\nContent-Length: 14\r\n\r\n{\"fates\":true}".as_bytes();
+        let (method, path, body) = parse_request(&mut r).unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("POST", "/query"));
+        assert_eq!(body, b"{\"fates\":true}");
+
+        // Truncated bodies, bad and huge lengths, and junk are rejected.
+
+        let mut r = "POST /query HTTP/1.1\r\nContent-Length: 20\r\n\r\n{}".as_bytes();
+        assert_eq!(parse_request(&mut r).unwrap_err().0, "400 Bad Request");
+        let mut r = "POST /query HTTP/1.1\r\nContent-Length: x\r\n\r\n".as_bytes();
+        assert_eq!(parse_request(&mut r).unwrap_err().0, "400 Bad Request");
+        let huge = format!(
+            "POST /query HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        let mut r = huge.as_bytes();
+        assert_eq!(
+            parse_request(&mut r).unwrap_err().0,
+            "413 Payload Too Large"
+        );
+        let mut r = "junk\r\n\r\n".as_bytes();
+        assert!(parse_request(&mut r).is_err());
+    }
+
+    #[test]
+    fn test_query_writes_files() {
+        let server = EncloneControl::default();
+        let mut query = EncloneControl::default();
+        assert!(!query_writes_files(&query, &server));
+        query.parseable_opt.pout = "x.csv".to_string();
+        assert!(query_writes_files(&query, &server));
+        query.parseable_opt.pout.clear();
+        query.plot_opt.plot_file = "x.svg".to_string();
+        assert!(query_writes_files(&query, &server));
+    }
+}
@@ -35,6 +35,20 @@ use hdf5x::Reader;
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
 pub fn main_enclone_stop(mut inter: EncloneIntermediates) -> Result<EncloneState, String> {
+    let (outs, clonotypes) = main_enclone_stop_in_place(&mut inter)?;
+    Ok(EncloneState {
+        inter,
+        outs,
+        clonotypes,
+    })
+}
+
+// Same as main_enclone_stop, but leaving the intermediates with the caller, so that they can be
+// reused.  Only the fates of barcodes are modified.
+
+pub fn main_enclone_stop_in_place(
+    inter: &mut EncloneIntermediates,
+) -> Result<(MainEncloneOutput, EncloneClonotypes), String> {
     // Unpack inputs.
 
     let to_bc = &inter.ex.to_bc;
@@ -54,6 +68,7 @@ pub fn main_enclone_stop(mut inter: EncloneIntermediates) -> Result<EncloneState
     let is_bcr = inter.ex.is_bcr;
     let tall = &inter.setup.tall.unwrap();
     let allele_data = &inter.ex.allele_data;
+    let warnings = &inter.ex.warnings;
 
     // Load the GEX and FB data.  This is quite horrible: the code and computation are duplicated
     // verbatim in fcell.rs.
@@ -166,7 +181,7 @@ pub fn main_enclone_stop(mut inter: EncloneIntermediates) -> Result<EncloneState
                         } else {
                             fields.push("empty".to_string());
                         }
-                    } else if var == "type" {
+                    } else if var == "cell_type" {
                         if is_gex_cell {
                             let mut typex = gex_info.cell_type[li][bc].clone();
                             if typex.contains(",") {
@@ -222,7 +237,7 @@ pub fn main_enclone_stop(mut inter: EncloneIntermediates) -> Result<EncloneState
                     for var in ctl.gen_opt.all_bc_fields.iter() {
                         if var == "cell" {
                             fields.push("vdj".to_string());
-                        } else if var == "type" || var == "none" {
+                        } else if var == "cell_type" || var == "none" {
                             fields.push("unknown".to_string());
                         } else {
                             fields.push("0".to_string());
@@ -375,7 +390,7 @@ pub fn main_enclone_stop(mut inter: EncloneIntermediates) -> Result<EncloneState
             .await
             .unwrap();
         */
-        return Ok(EncloneState::default());
+        return Ok((MainEncloneOutput::default(), EncloneClonotypes::default()));
     }
 
     // Tail code.
@@ -399,6 +414,7 @@ pub fn main_enclone_stop(mut inter: EncloneIntermediates) -> Result<EncloneState
         gex_info,
         vdj_cells,
         fate,
+        warnings,
         &tests,
         &controls,
         &h5_data,
@@ -495,5 +511,11 @@ pub fn main_enclone_stop(mut inter: EncloneIntermediates) -> Result<EncloneState
         ngroup: ctl.clono_group_opt.ngroup,
         pretty: ctl.pretty,
     };
-    Ok(EncloneState { inter, outs })
+    let clonotypes = EncloneClonotypes {
+        exacts,
+        rsi,
+        out_datas,
+        groups,
+    };
+    Ok((outs, clonotypes))
 }
@@ -2,6 +2,7 @@
 
 // Process the SUBSET_JSON option.
 
+use enclone_args::read_airr::is_airr_path;
 use enclone_core::defs::{EncloneControl, ExactClonotype};
 use io_utils::{
     fwrite, fwriteln, open_for_write_new, open_maybe_compressed, path_exists,
@@ -37,6 +38,9 @@ pub fn subset_json(
         fwriteln!(g, "[");
         let mut written = false;
         for li in 0..ctl.origin_info.dataset_path.len() {
+            if is_airr_path(&ctl.origin_info.dataset_path[li]) {
+                continue;
+            }
             let json = format!("{}/{}", ctl.origin_info.dataset_path[li], ann);
             let mut jsonx = json.clone();
             if !path_exists(&json) {
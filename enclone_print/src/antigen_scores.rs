@@ -0,0 +1,104 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Fetch the antigen scores of cells, for the lead variables ag_score, ag_spec and ag_spec_clono.
+// See enclone_core::antigen for the definition of the scores.
+
+use enclone_core::antigen::antigen_scores;
+use enclone_core::defs::{EncloneControl, ExactClonotype, GexInfo};
+use ndarray::s;
+use vector_utils::bin_position;
+
+#[cfg(target_os = "windows")]
+use hdf5::Reader;
+#[cfg(not(target_os = "windows"))]
+use hdf5x::Reader;
+
+// Get the antigen scores for a cell, or None if it has no feature barcode data.
+
+pub fn cell_antigen_scores(
+    ctl: &EncloneControl,
+    gex_info: &GexInfo,
+    li: usize,
+    bc: &String,
+    h5_data: &Vec<(usize, Vec<u32>, Vec<u32>)>,
+    d_readers: &Vec<Option<Reader>>,
+    ind_readers: &Vec<Option<Reader>>,
+) -> Option<Vec<f64>> {
+    if li >= gex_info.antigen_fids.len() || gex_info.antigen_fids[li].0.is_empty() {
+        return None;
+    }
+    let p = bin_position(&gex_info.gex_barcodes[li], bc);
+    if p < 0 {
+        return None;
+    }
+    let p = p as usize;
+    let (ag_fids, control_fids) = &gex_info.antigen_fids[li];
+    let (mut counts, mut control_counts) =
+        (vec![0.0; ag_fids.len()], vec![0.0; control_fids.len()]);
+    if gex_info.gex_matrices[li].initialized() {
+        for j in 0..ag_fids.len() {
+            counts[j] = gex_info.gex_matrices[li].value(p, ag_fids[j]) as f64;
+        }
+        for j in 0..control_fids.len() {
+            control_counts[j] = gex_info.gex_matrices[li].value(p, control_fids[j]) as f64;
+        }
+    } else {
+        let z1 = gex_info.h5_indptr[li][p] as usize;
+        let z2 = gex_info.h5_indptr[li][p + 1] as usize;
+        let d: Vec<u32>;
+        let ind: Vec<u32>;
+        if ctl.gen_opt.h5_pre {
+            d = h5_data[li].1[z1..z2].to_vec();
+            ind = h5_data[li].2[z1..z2].to_vec();
+        } else {
+            d = d_readers[li]
+                .as_ref()
+                .unwrap()
+                .read_slice(s![z1..z2])
+                .unwrap()
+                .to_vec();
+            ind = ind_readers[li]
+                .as_ref()
+                .unwrap()
+                .read_slice(s![z1..z2])
+                .unwrap()
+                .to_vec();
+        }
+        for j in 0..d.len() {
+            let fid = ind[j] as usize;
+            if let Some(k) = ag_fids.iter().position(|f| *f == fid) {
+                counts[k] = d[j] as f64;
+            }
+            if let Some(k) = control_fids.iter().position(|f| *f == fid) {
+                control_counts[k] = d[j] as f64;
+            }
+        }
+    }
+    Some(antigen_scores(&counts, &control_counts))
+}
+
+// Get the antigen scores for the cells in the given exact subclonotypes that have feature
+// barcode data.
+
+pub fn exacts_antigen_scores(
+    ctl: &EncloneControl,
+    gex_info: &GexInfo,
+    us: &[usize],
+    exact_clonotypes: &Vec<ExactClonotype>,
+    h5_data: &Vec<(usize, Vec<u32>, Vec<u32>)>,
+    d_readers: &Vec<Option<Reader>>,
+    ind_readers: &Vec<Option<Reader>>,
+) -> Vec<Vec<f64>> {
+    let mut scores = Vec::<Vec<f64>>::new();
+    for u in us.iter() {
+        for clone in exact_clonotypes[*u].clones.iter() {
+            let (li, bc) = (clone[0].dataset_index, &clone[0].barcode);
+            if let Some(s) =
+                cell_antigen_scores(ctl, gex_info, li, bc, h5_data, d_readers, ind_readers)
+            {
+                scores.push(s);
+            }
+        }
+    }
+    scores
+}
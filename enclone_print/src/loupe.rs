@@ -13,6 +13,7 @@ use bio_edit::alignment::pairwise::Aligner;
 
 use debruijn::dna_string::DnaString;
 use enclone_core::defs::{ColInfo, EncloneControl, ExactClonotype};
+use enclone_core::stable_id::{clonotype_stable_id, exact_stable_id};
 use enclone_proto::types::{
     Alignment, Clonotype, ClonotypeChain, DonorReference, DonorReferenceItem, EncloneOutputs,
     ExactSubClonotype, ExactSubClonotypeChain, ExactSubClonotypeChainInfo,
@@ -397,6 +398,10 @@ pub fn make_loupe_clonotype(
             beta_chain_gene_match: ex.share[0].mait_beta_chain_gene_match,
             beta_chain_junction_match: ex.share[0].mait_beta_chain_junction_match,
         };
+        let mut stable_id = None;
+        if ctl.gen_opt.stable_ids {
+            stable_id = Some(exact_stable_id(ex, refdata, ctl));
+        }
         ecl.push(ExactSubClonotype {
             chains: chains
                 .into_iter()
@@ -411,6 +416,7 @@ pub fn make_loupe_clonotype(
             cell_barcodes,
             inkt_evidence,
             mait_evidence,
+            stable_id,
         });
     }
 
@@ -420,10 +426,15 @@ pub fn make_loupe_clonotype(
     for i in 0..ecl.len() {
         n += ecl[i].cell_barcodes.len();
     }
+    let mut stable_id = None;
+    if ctl.gen_opt.stable_ids {
+        stable_id = Some(clonotype_stable_id(exacts, exact_clonotypes, refdata, ctl));
+    }
     Clonotype {
         chains: xchains,
         exact_clonotypes: ecl,
         frequency: n as u32,
+        stable_id,
     }
 }
 
@@ -587,6 +587,7 @@ pub fn print_clonotypes(
                 // Set up to record stats that assign a value to each cell for a given variable.
 
                 let mut stats = Vec::<(String, Vec<String>)>::new();
+                let mut clono_cache = HashMap::<String, String>::new();
 
                 // Compute some stats;
 
@@ -672,6 +673,7 @@ pub fn print_clonotypes(
                         h5_data,
                         &mut these_stats,
                         &stats_pass1,
+                        &mut clono_cache,
                         vdj_cells,
                         &n_vdj_gex,
                         &lvars,
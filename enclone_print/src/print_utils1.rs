@@ -1,5 +1,6 @@
 // Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
 
+use crate::print_utils4::imgt_labels;
 use amino::codon_to_aa;
 use ansi_escape::{
     emit_bold_escape, emit_eight_bit_color_escape, emit_end_escape, emit_red_escape,
@@ -667,6 +668,27 @@ pub fn insert_position_rows(
     let cols = rsi.cdr3_starts.len();
     let mut drows = Vec::<Vec<String>>::new();
     let mut digits = 0;
+
+    // Find the labels for amino acid positions, which are the positions themselves, unless
+    // AMINO_IMGT was specified.
+
+    let mut labels = vec![Vec::<String>::new(); cols];
+    for cx in 0..cols {
+        let imgt = if ctl.clono_print_opt.amino_imgt {
+            imgt_labels(rsi, cx)
+        } else {
+            Vec::new()
+        };
+        for p in show_aa[cx].iter() {
+            if !ctl.clono_print_opt.amino_imgt {
+                labels[cx].push(format!("{}", p));
+            } else if *p < imgt.len() {
+                labels[cx].push(imgt[*p].clone());
+            } else {
+                labels[cx].push(String::new());
+            }
+        }
+    }
     for zpass in 1..=2 {
         if zpass == 2 {
             drows = vec![vec![String::new(); row1.len()]; digits];
@@ -675,8 +697,8 @@ pub fn insert_position_rows(
             for m in 0..rsi.cvars[cx].len() {
                 if zpass == 1 {
                     if rsi.cvars[cx][m] == *"amino" {
-                        for p in show_aa[cx].iter() {
-                            digits = max(digits, ndigits(*p));
+                        for x in labels[cx].iter() {
+                            digits = max(digits, x.len());
                         }
                     } else if rsi.cvars[cx][m] == *"var" {
                         for p in vars[cx].iter() {
@@ -687,14 +709,18 @@ pub fn insert_position_rows(
                     for i in 0..digits {
                         if rsi.cvars[cx][m] == *"amino" {
                             let mut ds = String::new();
-                            for (j, p) in show_aa[cx].iter().enumerate() {
+                            for (j, x) in labels[cx].iter().enumerate() {
                                 if j > 0
                                     && field_types[cx][j] != field_types[cx][j - 1]
                                     && !ctl.gen_opt.nospaces
                                 {
                                     ds += " ";
                                 }
-                                print_digit(*p, i, digits, &mut ds);
+                                if i + x.len() < digits {
+                                    ds.push(' ');
+                                } else {
+                                    ds.push(x.as_bytes()[i + x.len() - digits] as char);
+                                }
                             }
                             drows[i].push(ds);
                         } else if rsi.cvars[cx][m] == *"var" {
@@ -924,6 +950,10 @@ pub fn extra_args(ctl: &EncloneControl) -> Vec<String> {
             extra_args.push(decode_arith(v));
         }
     }
+    if !ctl.gen_opt.antigen_controls.is_empty() {
+        extra_args.push("ag_spec".to_string());
+        extra_args.push("ag_spec_clono".to_string());
+    }
     unique_sort(&mut extra_args);
     extra_args
 }
@@ -64,6 +64,7 @@ pub fn row_fill(
     h5_data: &Vec<(usize, Vec<u32>, Vec<u32>)>,
     stats: &mut Vec<(String, Vec<String>)>,
     stats_pass1: &Vec<Vec<(String, Vec<String>)>>,
+    clono_cache: &mut HashMap<String, String>, // values of clonotype-level variables
     vdj_cells: &Vec<Vec<String>>,
     n_vdj_gex: &Vec<usize>,
     lvarsc: &Vec<String>,
@@ -434,6 +435,7 @@ pub fn row_fill(
             extra_args,
             out_data,
             stats,
+            clono_cache,
             &lvars,
             row,
             fate,
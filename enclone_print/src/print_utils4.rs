@@ -2,6 +2,7 @@
 
 use amino::codon_to_aa;
 use enclone_core::defs::{ColInfo, EncloneControl, ExactClonotype, GexInfo};
+use enclone_core::imgt_numbering::{imgt_in_range, imgt_numbering};
 use enclone_proto::types::DonorReferenceItem;
 use equiv::EquivRel;
 use itertools::Itertools;
@@ -48,6 +49,23 @@ pub fn get_gex_matrix_entry(
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
+// Find the IMGT position of each amino acid in a column.
+
+pub fn imgt_labels(rsi: &ColInfo, cx: usize) -> Vec<String> {
+    imgt_numbering(
+        rsi.fr1_starts[cx],
+        rsi.cdr1_starts[cx],
+        rsi.fr2_starts[cx],
+        rsi.cdr2_starts[cx],
+        rsi.fr3_starts[cx],
+        rsi.cdr3_starts[cx],
+        rsi.cdr3_lens[cx],
+        rsi.seq_del_lens[cx],
+    )
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
 // Define amino acid positions to show.
 
 pub fn build_show_aa(
@@ -64,7 +82,17 @@ pub fn build_show_aa(
     let mut show_aa = vec![Vec::<usize>::new(); cols];
     for cx in 0..cols {
         for x in ctl.clono_print_opt.amino.iter() {
-            if x.contains('-') {
+            if x.starts_with("imgt:") {
+                let (start, stop) = (
+                    x.between("imgt:", "-").force_usize(),
+                    x.after("-").force_usize(),
+                );
+                for (p, label) in imgt_labels(rsi, cx).iter().enumerate() {
+                    if imgt_in_range(label, start, stop) {
+                        show_aa[cx].push(p);
+                    }
+                }
+            } else if x.contains('-') {
                 let (start, stop) = (x.before("-").force_usize(), x.after("-").force_usize());
                 for p in start..=stop {
                     show_aa[cx].push(p);
@@ -649,7 +677,7 @@ pub fn compute_bu(
                     row.push(ppe[k][cell_count + bcl.2].to_string());
                 } else if var == *"cred" && have_gex {
                     row.push(cred[k][cell_count + bcl.2].to_string());
-                } else if var == *"type" && have_gex {
+                } else if var == *"cell_type" && have_gex {
                     let mut cell_type = "".to_string();
                     if gex_info.cell_type[li].contains_key(&bc.clone()) {
                         cell_type = gex_info.cell_type[li][&bc.clone()].clone();
@@ -1089,6 +1089,41 @@ pub fn proc_cvar_auto(
             y = format!("{}", c.unwrap().len() / 3);
         }
 
+        (y, Vec::new(), "exact".to_string())
+    } else if vname.starts_with("imgt_")
+        && vname.after("imgt_").contains('_')
+        && vname.after("imgt_").after("_").ends_with("_aa")
+        && vname.between2("imgt_", "_").parse::<i64>().is_ok()
+        && vname.between2("imgt_", "_").force_i64() >= 1
+        && vname
+            .after("imgt_")
+            .between2("_", "_aa")
+            .parse::<i64>()
+            .is_ok()
+        && vname.after("imgt_").between2("_", "_aa").force_i64() >= 1
+    {
+        let arg1 = vname.between2("imgt_", "_").force_i64();
+        let arg2 = vname.after("imgt_").between2("_", "_aa").force_i64();
+        let x = &ex.share[mid];
+        let labels = enclone_core::imgt_numbering::imgt_numbering(
+            x.fr1_start,
+            x.cdr1_start,
+            x.fr2_start,
+            x.cdr2_start,
+            x.fr3_start,
+            x.cdr3_start,
+            x.cdr3_aa.len(),
+            x.seq_del_amino.len(),
+        );
+        let aa = aa_seq(&x.seq_del_amino, 0);
+        let mut y = String::new();
+        for p in 0..min(labels.len(), aa.len()) {
+            if enclone_core::imgt_numbering::imgt_in_range(&labels[p], arg1 as usize, arg2 as usize)
+            {
+                y.push(aa[p] as char);
+            }
+        }
+
         (y, Vec::new(), "exact".to_string())
     } else if vname == "ivalbcumis" {
         let mut vals = Vec::<String>::new();
@@ -1277,6 +1312,12 @@ pub fn proc_cvar_auto(
         }
 
         (String::new(), vals, "cell".to_string())
+    } else if vname == "pgen" {
+        (
+            enclone_core::pgen::chain_pgen(&ex.share[mid], ctl),
+            Vec::new(),
+            "exact".to_string(),
+        )
     } else if vname.starts_with('q')
         && vname.ends_with('_')
         && vname.between2("q", "_").parse::<i64>().is_ok()
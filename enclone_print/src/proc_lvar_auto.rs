@@ -31,6 +31,7 @@ pub fn proc_lvar_auto(
     extra_args: &Vec<String>,
     out_data: &mut Vec<HashMap<String, String>>,
     stats: &mut Vec<(String, Vec<String>)>,
+    clono_cache: &mut HashMap<String, String>,
     lvars: &Vec<String>,
     row: &mut Vec<String>,
     fate: &Vec<HashMap<String, String>>,
@@ -123,6 +124,139 @@ pub fn proc_lvar_auto(
         }
 
         (val, Vec::new(), "exact".to_string())
+    } else if vname == "ag_score" {
+        let mut vals = Vec::<String>::new();
+        let mut scores = Vec::<Vec<f64>>::new();
+        for j in 0..ex.clones.len() {
+            let li = ex.clones[j][0].dataset_index;
+            let bc = &ex.clones[j][0].barcode;
+            let mut val = String::new();
+            if let Some(s) = crate::antigen_scores::cell_antigen_scores(
+                ctl,
+                gex_info,
+                li,
+                bc,
+                h5_data,
+                d_readers,
+                ind_readers,
+            ) {
+                val = format!("{:.2}", s.iter().cloned().fold(f64::MIN, f64::max));
+                scores.push(s);
+            }
+            vals.push(val);
+        }
+        let mut best = String::new();
+        if !scores.is_empty() {
+            let m = enclone_core::antigen::median_antigen_scores(&scores);
+            best = format!("{:.2}", m.iter().cloned().fold(f64::MIN, f64::max));
+        }
+
+        (best, vals, "cell-exact".to_string())
+    } else if vname == "ag_score_cell" {
+        let mut vals = Vec::<String>::new();
+        let mut scores = Vec::<Vec<f64>>::new();
+        for j in 0..ex.clones.len() {
+            let li = ex.clones[j][0].dataset_index;
+            let bc = &ex.clones[j][0].barcode;
+            let mut val = String::new();
+            if let Some(s) = crate::antigen_scores::cell_antigen_scores(
+                ctl,
+                gex_info,
+                li,
+                bc,
+                h5_data,
+                d_readers,
+                ind_readers,
+            ) {
+                val = format!("{:.2}", s.iter().cloned().fold(f64::MIN, f64::max));
+                scores.push(s);
+            }
+            vals.push(val);
+        }
+        let mut best = String::new();
+        if !scores.is_empty() {
+            let m = enclone_core::antigen::median_antigen_scores(&scores);
+            best = format!("{:.2}", m.iter().cloned().fold(f64::MIN, f64::max));
+        }
+
+        let _exact = best;
+        (String::new(), vals, "cell-exact".to_string())
+    } else if vname == "ag_spec" {
+        let scores = crate::antigen_scores::exacts_antigen_scores(
+            ctl,
+            gex_info,
+            &[exacts[u]],
+            exact_clonotypes,
+            h5_data,
+            d_readers,
+            ind_readers,
+        );
+        let call = enclone_core::antigen::antigen_call(
+            &gex_info.antigen_names,
+            &scores,
+            ctl.gen_opt.antigen_min_score,
+        );
+
+        (call, Vec::new(), "exact".to_string())
+    } else if vname == "ag_spec_clono" {
+        // The call is the same for every exact subclonotype, so compute it once per pass.  It
+        // is recomputed on the second pass, after junk exact subclonotypes are removed.
+        let call = match clono_cache.get(&abbr) {
+            Some(call) => call.clone(),
+            None => {
+                let scores = crate::antigen_scores::exacts_antigen_scores(
+                    ctl,
+                    gex_info,
+                    exacts,
+                    exact_clonotypes,
+                    h5_data,
+                    d_readers,
+                    ind_readers,
+                );
+                let call = enclone_core::antigen::antigen_call(
+                    &gex_info.antigen_names,
+                    &scores,
+                    ctl.gen_opt.antigen_min_score,
+                );
+                clono_cache.insert(abbr.clone(), call.clone());
+                call
+            }
+        };
+
+        (call, Vec::new(), "clono".to_string())
+    } else if vname == "cell_type" {
+        let mut types = Vec::<String>::new();
+        for j in 0..ex.clones.len() {
+            let li = ex.clones[j][0].dataset_index;
+            let bc = &ex.clones[j][0].barcode;
+            let mut t = String::new();
+            if gex_info.cell_type[li].contains_key(&bc.clone()) {
+                t = gex_info.cell_type[li][&bc.clone()].clone();
+            }
+            types.push(t);
+        }
+        let mut types_sorted = types.clone();
+        types_sorted.retain(|t| !t.is_empty());
+        types_sorted.sort();
+
+        (abbrev_list(&types_sorted), types, "cell-exact".to_string())
+    } else if vname == "cell_type_cell" {
+        let mut types = Vec::<String>::new();
+        for j in 0..ex.clones.len() {
+            let li = ex.clones[j][0].dataset_index;
+            let bc = &ex.clones[j][0].barcode;
+            let mut t = String::new();
+            if gex_info.cell_type[li].contains_key(&bc.clone()) {
+                t = gex_info.cell_type[li][&bc.clone()].clone();
+            }
+            types.push(t);
+        }
+        let mut types_sorted = types.clone();
+        types_sorted.retain(|t| !t.is_empty());
+        types_sorted.sort();
+
+        let _exact = abbrev_list(&types_sorted);
+        (String::new(), types, "cell-exact".to_string())
     } else if vname == "clonotype_ncells" {
         let mut n = 0;
         for u in exacts.iter() {
@@ -130,6 +264,12 @@ pub fn proc_lvar_auto(
         }
 
         (format!("{}", n), Vec::new(), "clono".to_string())
+    } else if vname == "clonotype_sid" {
+        (
+            enclone_core::stable_id::clonotype_stable_id(exacts, exact_clonotypes, refdata, ctl),
+            Vec::new(),
+            "clono".to_string(),
+        )
     } else if vname == "clust" {
         let mut clust = Vec::<usize>::new();
         for j in 0..ex.clones.len() {
@@ -694,6 +834,13 @@ pub fn proc_lvar_auto(
 
         let _exact = format!("{}", donors.iter().format(","));
         (String::new(), donors_unsorted, "cell-exact".to_string())
+    } else if vname == "doublet_score" {
+        let mut score = String::new();
+        if let Some(x) = ex.doublet_score {
+            score = format!("{:.1}", x);
+        }
+
+        (score, Vec::new(), "exact".to_string())
     } else if vname == "dref" {
         let mut diffs = 0;
         for m in 0..cols {
@@ -1014,6 +1161,12 @@ pub fn proc_lvar_auto(
 
         let _exact = format!("{:.2}", entropy);
         (String::new(), e, "cell-exact".to_string())
+    } else if vname == "exact_sid" {
+        (
+            enclone_core::stable_id::exact_stable_id(ex, refdata, ctl),
+            Vec::new(),
+            "exact".to_string(),
+        )
     } else if vname == "far" {
         let mut dist = -1_isize;
         for i2 in 0..varmat.len() {
@@ -1252,6 +1405,17 @@ pub fn proc_lvar_auto(
         }
 
         (s, Vec::new(), "exact".to_string())
+    } else if vname == "join_id" {
+        let mut id = String::new();
+        for u in exacts.iter() {
+            if let Some(x) = exact_clonotypes[*u].join_id {
+                if id.is_empty() {
+                    id = format!("{}", x);
+                }
+            }
+        }
+
+        (id, Vec::new(), "clono".to_string())
     } else if vname == "jun_ins" {
         let mut jun_ins = String::new();
         if ex.share.len() == 2 {
@@ -1681,22 +1845,178 @@ pub fn proc_lvar_auto(
 
         let _exact = format!("{}", n);
         (String::new(), y, "cell-exact".to_string())
-    } else if vname == "type" {
-        let mut cell_types = Vec::<String>::new();
-        /*
+    } else if vname == "tsne_x" {
+        let mut vals = Vec::<String>::new();
+        let (mut sum, mut n) = (0.0, 0);
         for j in 0..ex.clones.len() {
-            let mut cell_type = "".to_string();
             let bc = &ex.clones[j][0].barcode;
             let li = ex.clones[j][0].dataset_index;
-            if gex_info.cell_type[li].contains_key(&bc.clone()) {
-                cell_type = gex_info.cell_type[li][&bc.clone()].clone();
+            let mut val = String::new();
+            if gex_info.tsne[li].contains_key(&bc.clone()) {
+                let x = gex_info.tsne[li][&bc.clone()].0;
+                val = format!("{:.3}", x);
+                sum += x;
+                n += 1;
             }
-            cell_types.push(cell_type);
+            vals.push(val);
+        }
+        let mut mean = String::new();
+        if n > 0 {
+            mean = format!("{:.3}", sum / n as f64);
+        }
+
+        (mean, vals, "cell-exact".to_string())
+    } else if vname == "tsne_x_cell" {
+        let mut vals = Vec::<String>::new();
+        let (mut sum, mut n) = (0.0, 0);
+        for j in 0..ex.clones.len() {
+            let bc = &ex.clones[j][0].barcode;
+            let li = ex.clones[j][0].dataset_index;
+            let mut val = String::new();
+            if gex_info.tsne[li].contains_key(&bc.clone()) {
+                let x = gex_info.tsne[li][&bc.clone()].0;
+                val = format!("{:.3}", x);
+                sum += x;
+                n += 1;
+            }
+            vals.push(val);
+        }
+        let mut mean = String::new();
+        if n > 0 {
+            mean = format!("{:.3}", sum / n as f64);
+        }
+
+        let _exact = mean;
+        (String::new(), vals, "cell-exact".to_string())
+    } else if vname == "tsne_y" {
+        let mut vals = Vec::<String>::new();
+        let (mut sum, mut n) = (0.0, 0);
+        for j in 0..ex.clones.len() {
+            let bc = &ex.clones[j][0].barcode;
+            let li = ex.clones[j][0].dataset_index;
+            let mut val = String::new();
+            if gex_info.tsne[li].contains_key(&bc.clone()) {
+                let x = gex_info.tsne[li][&bc.clone()].1;
+                val = format!("{:.3}", x);
+                sum += x;
+                n += 1;
+            }
+            vals.push(val);
+        }
+        let mut mean = String::new();
+        if n > 0 {
+            mean = format!("{:.3}", sum / n as f64);
+        }
+
+        (mean, vals, "cell-exact".to_string())
+    } else if vname == "tsne_y_cell" {
+        let mut vals = Vec::<String>::new();
+        let (mut sum, mut n) = (0.0, 0);
+        for j in 0..ex.clones.len() {
+            let bc = &ex.clones[j][0].barcode;
+            let li = ex.clones[j][0].dataset_index;
+            let mut val = String::new();
+            if gex_info.tsne[li].contains_key(&bc.clone()) {
+                let x = gex_info.tsne[li][&bc.clone()].1;
+                val = format!("{:.3}", x);
+                sum += x;
+                n += 1;
+            }
+            vals.push(val);
+        }
+        let mut mean = String::new();
+        if n > 0 {
+            mean = format!("{:.3}", sum / n as f64);
+        }
+
+        let _exact = mean;
+        (String::new(), vals, "cell-exact".to_string())
+    } else if vname == "umap_x" {
+        let mut vals = Vec::<String>::new();
+        let (mut sum, mut n) = (0.0, 0);
+        for j in 0..ex.clones.len() {
+            let bc = &ex.clones[j][0].barcode;
+            let li = ex.clones[j][0].dataset_index;
+            let mut val = String::new();
+            if gex_info.umap[li].contains_key(&bc.clone()) {
+                let x = gex_info.umap[li][&bc.clone()].0;
+                val = format!("{:.3}", x);
+                sum += x;
+                n += 1;
+            }
+            vals.push(val);
+        }
+        let mut mean = String::new();
+        if n > 0 {
+            mean = format!("{:.3}", sum / n as f64);
+        }
+
+        (mean, vals, "cell-exact".to_string())
+    } else if vname == "umap_x_cell" {
+        let mut vals = Vec::<String>::new();
+        let (mut sum, mut n) = (0.0, 0);
+        for j in 0..ex.clones.len() {
+            let bc = &ex.clones[j][0].barcode;
+            let li = ex.clones[j][0].dataset_index;
+            let mut val = String::new();
+            if gex_info.umap[li].contains_key(&bc.clone()) {
+                let x = gex_info.umap[li][&bc.clone()].0;
+                val = format!("{:.3}", x);
+                sum += x;
+                n += 1;
+            }
+            vals.push(val);
+        }
+        let mut mean = String::new();
+        if n > 0 {
+            mean = format!("{:.3}", sum / n as f64);
+        }
+
+        let _exact = mean;
+        (String::new(), vals, "cell-exact".to_string())
+    } else if vname == "umap_y" {
+        let mut vals = Vec::<String>::new();
+        let (mut sum, mut n) = (0.0, 0);
+        for j in 0..ex.clones.len() {
+            let bc = &ex.clones[j][0].barcode;
+            let li = ex.clones[j][0].dataset_index;
+            let mut val = String::new();
+            if gex_info.umap[li].contains_key(&bc.clone()) {
+                let x = gex_info.umap[li][&bc.clone()].1;
+                val = format!("{:.3}", x);
+                sum += x;
+                n += 1;
+            }
+            vals.push(val);
+        }
+        let mut mean = String::new();
+        if n > 0 {
+            mean = format!("{:.3}", sum / n as f64);
+        }
+
+        (mean, vals, "cell-exact".to_string())
+    } else if vname == "umap_y_cell" {
+        let mut vals = Vec::<String>::new();
+        let (mut sum, mut n) = (0.0, 0);
+        for j in 0..ex.clones.len() {
+            let bc = &ex.clones[j][0].barcode;
+            let li = ex.clones[j][0].dataset_index;
+            let mut val = String::new();
+            if gex_info.umap[li].contains_key(&bc.clone()) {
+                let x = gex_info.umap[li][&bc.clone()].1;
+                val = format!("{:.3}", x);
+                sum += x;
+                n += 1;
+            }
+            vals.push(val);
+        }
+        let mut mean = String::new();
+        if n > 0 {
+            mean = format!("{:.3}", sum / n as f64);
         }
-        */
-        cell_types.sort();
 
-        (abbrev_list(&cell_types), Vec::new(), "exact".to_string())
+        let _exact = mean;
+        (String::new(), vals, "cell-exact".to_string())
     } else {
         (
             "$UNDEFINED".to_string(),
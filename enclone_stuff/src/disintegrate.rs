@@ -44,6 +44,8 @@ pub fn disintegrate_onesies(
                     exacts2.push(ExactClonotype {
                         share: ex.share.clone(),
                         clones: vec![ex.clones[j].clone()],
+                        join_id: None,
+                        doublet_score: None,
                     });
                     disintegrated.push(true);
                 }
@@ -2,7 +2,12 @@
 
 // Delete exact subclonotypes that appear to represent doublets.
 //
-// THIS FILTER DOESN'T PROPERLY TRACK FATE.
+// A pure subclonotype u is explained by a pair of pure subclonotypes v1 and v2 if u shares a CDR3
+// with each of them, v1 and v2 share no CDR3, and both have at least MIN_MULT_DOUBLET times as
+// many cells as u.  The doublet score of u is the maximum over such pairs of the ratio of the
+// number of cells in the smaller of v1 and v2 to the number of cells in u, and is zero if there
+// is no such pair.  For each barcode in an explained pure subclonotype, the fate records the score
+// and the best pair.  The cells are deleted, or if DOUBLET_MARK is specified, marked.
 
 use enclone_core::defs::{CloneInfo, EncloneControl, ExactClonotype};
 use enclone_print::define_mat::define_mat;
@@ -10,25 +15,87 @@ use enclone_proto::types::DonorReferenceItem;
 use itertools::Itertools;
 use qd::Double;
 use rayon::prelude::*;
+use std::cmp::min;
 use std::collections::HashMap;
 use std::time::Instant;
 use vdj_ann::refx::RefData;
 use vector_utils::{bin_member, erase_if, next_diff, next_diff12_3, next_diff1_2, sort_sync2};
 
+const MIN_MULT_DOUBLET: usize = 5;
+
+// Find triples of pure subclonotypes in which the first two have no share, but both of the first
+// two share with the third, and for each third member, find the triple having the highest score.
+// The input npure gives the number of cells in each pure subclonotype, and shares is the sorted
+// symmetric list of pairs of pure subclonotypes that share a CDR3.  The output gives, for each
+// pure subclonotype, the score and the explaining pair, or a score of zero.
+
+fn best_doublet_pairs(npure: &[usize], shares: &[(usize, usize)]) -> Vec<(f64, usize, usize)> {
+    let mut best = vec![(0.0, 0, 0); npure.len()];
+    let mut us = Vec::<usize>::new();
+    let mut vs = Vec::<Vec<usize>>::new();
+    let mut j = 0;
+    while j < shares.len() {
+        // not using next_diff1_2 here because of i32 overflow issue
+        let mut k = j + 1;
+        loop {
+            if k == shares.len() || shares[k].0 != shares[j].0 {
+                break;
+            }
+            k += 1;
+        }
+        let u = shares[j].0;
+        us.push(u);
+        let mut x = Vec::<usize>::new();
+        for l in j..k {
+            let v = shares[l].1;
+            if MIN_MULT_DOUBLET * npure[u] <= npure[v] {
+                x.push(v);
+            }
+        }
+        vs.push(x);
+        j = k;
+    }
+    let mut results = Vec::<(usize, (f64, usize, usize))>::new();
+    for i in 0..us.len() {
+        results.push((i, (0.0, 0, 0)));
+    }
+    results.par_iter_mut().for_each(|res| {
+        let i = res.0;
+        let u = us[i];
+        let vs = &vs[i];
+        for l1 in 0..vs.len() {
+            for l2 in l1 + 1..vs.len() {
+                let v1 = vs[l1];
+                let v2 = vs[l2];
+                if !bin_member(shares, &(v1, v2)) {
+                    let score = min(npure[v1], npure[v2]) as f64 / npure[u] as f64;
+                    if score > res.1 .0 {
+                        res.1 = (score, v1, v2);
+                    }
+                }
+            }
+        }
+    });
+    for j in 0..results.len() {
+        best[us[j]] = results[j].1;
+    }
+    best
+}
+
 pub fn delete_doublets(
     orbits: &mut Vec<Vec<i32>>,
     is_bcr: bool,
     to_bc: &HashMap<(usize, usize), Vec<String>>,
     sr: &Vec<Vec<Double>>,
     ctl: &EncloneControl,
-    exact_clonotypes: &Vec<ExactClonotype>,
+    exact_clonotypes: &mut Vec<ExactClonotype>,
     info: &Vec<CloneInfo>,
     raw_joins: &Vec<Vec<usize>>,
     refdata: &RefData,
     dref: &Vec<DonorReferenceItem>,
     fate: &mut Vec<HashMap<String, String>>,
 ) {
-    if ctl.clono_filt_opt_def.doublet {
+    if ctl.clono_filt_opt_def.doublet || ctl.clono_filt_opt_def.doublet_mark {
         let t = Instant::now();
         // Define pure subclonotypes.  To do this we break each clonotype up by chain signature.
         // Note duplication of code with print_clonotypes.rs.  And this is doing some
@@ -141,104 +208,45 @@ pub fn delete_doublets(
             ctl.perf_stats(&t, "doublet filtering shares");
         }
 
-        // Find triples of pure subclonotypes in which the first two have no share, but both
-        // of the first two share with the third.
+        // Find the best explaining pair for each pure subclonotype.
 
         let t = Instant::now();
-        const MIN_MULT_DOUBLET: usize = 5;
-        let mut trips = Vec::<(usize, usize, usize)>::new();
-        {
-            let mut us = Vec::<usize>::new();
-            let mut vs = Vec::<Vec<usize>>::new();
-            let mut j = 0;
-            while j < shares.len() {
-                // not using next_diff1_2 here because of i32 overflow issue
-                let mut k = j + 1;
-                loop {
-                    if k == shares.len() || shares[k].0 != shares[j].0 {
-                        break;
-                    }
-                    k += 1;
-                }
-                let u = shares[j].0;
-                us.push(u);
-                let mut x = Vec::<usize>::new();
-                for l in j..k {
-                    let v = shares[l].1;
-                    if MIN_MULT_DOUBLET * npure[u] <= npure[v] {
-                        x.push(v);
-                    }
-                }
-                vs.push(x);
-                j = k;
-            }
-            let mut results = Vec::<(usize, Vec<(usize, usize, usize)>)>::new();
-            for i in 0..us.len() {
-                results.push((i, Vec::new()));
-            }
-            results.par_iter_mut().for_each(|res| {
-                let i = res.0;
-                let u = us[i];
-                let vs = &vs[i];
-                for l1 in 0..vs.len() {
-                    for l2 in l1 + 1..vs.len() {
-                        let v1 = vs[l1];
-                        let v2 = vs[l2];
-                        if !bin_member(&shares, &(v1, v2)) {
-                            res.1.push((v1, v2, u));
-                        }
-                    }
-                }
-            });
-            for j in 0..results.len() {
-                trips.append(&mut results[j].1.clone());
-            }
-        }
+        let best = best_doublet_pairs(&npure, &shares);
         ctl.perf_stats(&t, "doublet filtering trips");
 
-        // Delete some of the third members of the triples.
+        // Record scores, and delete or mark the third members of the triples.
 
         let t = Instant::now();
+        let cdr3s = |v: usize| {
+            let ex = &exact_clonotypes[pures[v][0]];
+            format!(
+                "{}",
+                ex.share.iter().map(|x| x.cdr3_aa.as_str()).format(",")
+            )
+        };
         let mut to_delete = vec![false; exact_clonotypes.len()];
-        for j in 0..trips.len() {
-            let (v0, v1, v2) = (trips[j].2, trips[j].0, trips[j].1);
-            {
-                let verbose = false;
-                if verbose {
-                    println!("\n{}, {}, {}", v0, v1, v2);
-                    println!("DELETING");
-                    for (u, m) in pures[v0].iter().enumerate() {
-                        let ex = &exact_clonotypes[*m];
-                        let mut cdrs = Vec::<String>::new();
-                        for k in 0..ex.share.len() {
-                            cdrs.push(ex.share[k].cdr3_aa.clone());
-                        }
-                        println!("[{}] {}", u + 1, cdrs.iter().format(","));
-                    }
-                    println!("USING");
-                    for (u, m) in pures[v1].iter().enumerate() {
-                        let ex = &exact_clonotypes[*m];
-                        let mut cdrs = Vec::<String>::new();
-                        for k in 0..ex.share.len() {
-                            cdrs.push(ex.share[k].cdr3_aa.clone());
-                        }
-                        println!("[{}] {}", u + 1, cdrs.iter().format(","));
-                    }
-                    println!("AND");
-                    for (u, m) in pures[v2].iter().enumerate() {
-                        let ex = &exact_clonotypes[*m];
-                        let mut cdrs = Vec::<String>::new();
-                        for k in 0..ex.share.len() {
-                            cdrs.push(ex.share[k].cdr3_aa.clone());
-                        }
-                        println!("[{}] {}", u + 1, cdrs.iter().format(","));
-                    }
-                }
+        let mut why = vec![String::new(); exact_clonotypes.len()];
+        for v0 in 0..pures.len() {
+            let (score, v1, v2) = best[v0];
+            if score > 0.0 {
+                let reason = format!(
+                    "failed DOUBLET filter: score = {:.1}, explained by pure subclonotypes \
+                    with CDR3s {} and {}",
+                    score,
+                    cdr3s(v1),
+                    cdr3s(v2)
+                );
                 for m in pures[v0].iter() {
                     to_delete[*m] = true;
+                    why[*m] = reason.clone();
                 }
             }
         }
+        for v0 in 0..pures.len() {
+            for m in pures[v0].iter() {
+                exact_clonotypes[*m].doublet_score = Some(best[v0].0);
+            }
+        }
         let mut orbits2 = Vec::<Vec<i32>>::new();
         for i in 0..orbits.len() {
             let mut o = orbits[i].clone();
@@ -247,19 +255,46 @@ pub fn delete_doublets(
                 let id = info[o[j] as usize].clonotype_index;
                 if to_delete[id] {
                     del2[j] = true;
-                    let x: &CloneInfo = &info[o[j] as usize];
-                    let ex = &exact_clonotypes[x.clonotype_index];
+                    let ex = &mut exact_clonotypes[id];
                     for k in 0..ex.ncells() {
                         let li = ex.clones[k][0].dataset_index;
                         let bc = &ex.clones[k][0].barcode;
-                        fate[li].insert(bc.clone(), "failed DOUBLET filter".to_string());
+                        fate[li].insert(bc.clone(), why[id].clone());
+                        if ctl.clono_filt_opt_def.doublet_mark {
+                            ex.clones[k][0].marked = true;
+                        }
                     }
                 }
             }
-            erase_if(&mut o, &del2);
+            if ctl.clono_filt_opt_def.doublet {
+                erase_if(&mut o, &del2);
+            }
             orbits2.push(o);
         }
         *orbits = orbits2;
         ctl.perf_stats(&t, "doublet filtering tail");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_best_doublet_pairs() {
+        // Pure subclonotype 0 (one cell) shares a CDR3 with each of 1, 2, 3 and 4.  Of these, 4
+        // is too small to explain it, and 1 and 3 share a CDR3, so the candidate pairs are (1, 2)
+        // and (2, 3), and the best is (2, 3), with score 20.
+
+        let npure = [1, 10, 20, 30, 4];
+        let mut shares = Vec::<(usize, usize)>::new();
+        for (a, b) in [(0, 1), (0, 2), (0, 3), (0, 4), (1, 3)] {
+            shares.push((a, b));
+            shares.push((b, a));
+        }
+        shares.sort_unstable();
+        let best = best_doublet_pairs(&npure, &shares);
+        assert_eq!(best[0], (20.0, 2, 3));
+        assert!(best[1..].iter().all(|x| x.0 == 0.0));
+    }
+}
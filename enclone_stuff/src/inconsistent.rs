@@ -11,6 +11,9 @@
 // appropriately lower when n is small.  When we tested on 260 libraries, the lowest value
 // observed for k/n was 0.65, and the vast majority of values were 0.9 or higher.
 //
+// If ALLOW_INCONSISTENT is specified, the test is skipped, unless QC_REPORT is also specified, in
+// which case failures are recorded as warnings, which appear in the QC report.
+//
 // This code is inefficient because for every dataset, it searches the entirety of tig_bc, but
 // it doesn't matter much because not much time is spent here.
 
@@ -22,10 +25,11 @@ use vector_utils::{bin_member, bin_position, reverse_sort};
 
 pub fn test_vdj_gex_inconsistent(
     ctl: &EncloneControl,
-    tig_bc: &Vec<Vec<TigData>>,
-    exact_clonotypes: &Vec<ExactClonotype>,
+    tig_bc: &[&Vec<TigData>],
+    exact_clonotypes: &[&ExactClonotype],
     vdj_cells: &Vec<Vec<String>>,
     gex_info: &GexInfo,
+    warnings: &mut Vec<String>,
 ) -> Result<(), String> {
     let tinc = Instant::now();
 
@@ -35,7 +39,9 @@ pub fn test_vdj_gex_inconsistent(
     }
     results.par_iter_mut().for_each(|res| {
         let li = res.0;
-        if !ctl.origin_info.gex_path[li].is_empty() && !ctl.gen_opt.allow_inconsistent {
+        if !ctl.origin_info.gex_path[li].is_empty()
+            && (!ctl.gen_opt.allow_inconsistent || !ctl.gen_opt.qc_report.is_empty())
+        {
             let vdj = &vdj_cells[li];
             let gex = &gex_info.gex_cell_barcodes[li];
             let (mut heavy, mut light) = (vec![false; vdj.len()], vec![false; vdj.len()]);
@@ -114,7 +120,13 @@ pub fn test_vdj_gex_inconsistent(
             fail = true;
         }
     }
-    if fail {
+    if fail && ctl.gen_opt.allow_inconsistent {
+        for i in 0..results.len() {
+            if !results[i].1.is_empty() {
+                warnings.push(results[i].1.trim().replace('\n', " "));
+            }
+        }
+    } else if fail {
         for i in 0..results.len() {
             eprint!("{}", results[i].1);
         }
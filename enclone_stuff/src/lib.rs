@@ -9,6 +9,7 @@ pub mod flag_defective;
 pub mod inconsistent;
 pub mod merge_onesies;
 pub mod populate_features;
+pub mod shard;
 pub mod some_filters;
 pub mod split_orbits;
 pub mod start;
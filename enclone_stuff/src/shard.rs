@@ -0,0 +1,90 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Support for SHARD_DONORS.  Because clonotypes are not allowed to mix donors (unless MIX_DONORS
+// is specified), the computation of exact subclonotypes, alleles, joins and filters can be
+// carried out separately, and in parallel, for each donor.  The results are then merged, so that
+// all later steps see a single set of exact subclonotypes.  Barcodes that are not assigned to a
+// donor are treated as if they came from an additional donor.
+
+use enclone_core::defs::TigData;
+use enclone_core::enclone_structs::EncloneExacts;
+use std::collections::HashMap;
+
+// Partition contig data by donor.  Each part retains the order of the input.
+
+pub fn shard_by_donor(tig_bc: Vec<Vec<TigData>>) -> Vec<Vec<Vec<TigData>>> {
+    let mut donors = Vec::<Option<usize>>::new();
+    for x in tig_bc.iter() {
+        donors.push(x[0].donor_index);
+    }
+    donors.sort_unstable();
+    donors.dedup();
+    let mut shards = vec![Vec::<Vec<TigData>>::new(); donors.len()];
+    for x in tig_bc.into_iter() {
+        let d = donors.binary_search(&x[0].donor_index).unwrap();
+        shards[d].push(x);
+    }
+    shards
+}
+
+// Merge the results for the shards.  The fates found before sharding are given by fate.  All
+// indices into exact subclonotypes, info entries and alternate alleles are offset accordingly.
+
+pub fn merge_shards(
+    mut fate: Vec<HashMap<String, String>>,
+    shards: Vec<EncloneExacts>,
+) -> EncloneExacts {
+    let mut ex = EncloneExacts::default();
+    for mut s in shards.into_iter() {
+        let nexact = ex.exact_clonotypes.len();
+        let ninfo = ex.info.len();
+        let nalt = ex.allele_data.alt_refs.len();
+        for ((li, u), bcs) in s.to_bc.drain() {
+            ex.to_bc.insert((li, u + nexact), bcs);
+        }
+        for x in s.exact_clonotypes.iter_mut() {
+            for y in x.share.iter_mut() {
+                if let Some(m) = y.v_ref_id_donor.as_mut() {
+                    *m += nalt;
+                }
+            }
+        }
+        ex.exact_clonotypes.append(&mut s.exact_clonotypes);
+        for x in s.raw_joins.iter_mut() {
+            for j in x.iter_mut() {
+                *j += ninfo;
+            }
+        }
+        ex.raw_joins.append(&mut s.raw_joins);
+        for x in s.info.iter_mut() {
+            x.clonotype_id += nexact;
+            x.clonotype_index += nexact;
+            for d in x.dref.iter_mut().flatten() {
+                *d += nalt;
+            }
+        }
+        ex.info.append(&mut s.info);
+        for o in s.orbits.iter_mut() {
+            for j in o.iter_mut() {
+                *j += ninfo as i32;
+            }
+        }
+        ex.orbits.append(&mut s.orbits);
+        for x in s.join_info.iter_mut() {
+            x.0 += nexact;
+            x.1 += nexact;
+        }
+        ex.join_info.append(&mut s.join_info);
+        ex.drefs.append(&mut s.drefs);
+        ex.allele_data.alt_refs.append(&mut s.allele_data.alt_refs);
+        ex.warnings.append(&mut s.warnings);
+        for li in 0..s.fate.len() {
+            for (bc, f) in s.fate[li].drain() {
+                fate[li].insert(bc, f);
+            }
+        }
+        ex.is_bcr = s.is_bcr;
+    }
+    ex.fate = fate;
+    ex
+}
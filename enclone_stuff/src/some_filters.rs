@@ -23,7 +23,7 @@ pub fn some_filters(
     to_bc: &HashMap<(usize, usize), Vec<String>>,
     sr: &Vec<Vec<Double>>,
     ctl: &EncloneControl,
-    exact_clonotypes: &Vec<ExactClonotype>,
+    exact_clonotypes: &mut Vec<ExactClonotype>,
     info: &Vec<CloneInfo>,
     raw_joins: &Vec<Vec<usize>>,
     eq: &EquivRel,
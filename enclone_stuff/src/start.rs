@@ -9,17 +9,21 @@ use crate::filter_umi::filter_umi;
 use crate::flag_defective::flag_defective;
 use crate::inconsistent::test_vdj_gex_inconsistent;
 use crate::populate_features::populate_features;
+use crate::shard::{merge_shards, shard_by_donor};
 use crate::some_filters::some_filters;
 use debruijn::dna_string::DnaString;
-use enclone::allele::{find_alleles, sub_alts};
+use enclone::allele::{find_alleles, inject_alleles, sub_alts};
 use enclone::graph_filter::graph_filter;
 use enclone::info::build_info;
 use enclone::join::join_exacts;
+use enclone::join_state::{
+    assign_join_ids, read_join_state, restore_alleles, saved_donors, saved_joins, write_join_state,
+};
 use enclone::misc1::{cross_filter, lookup_heavy_chain_reuse};
 use enclone::misc2::{check_for_barcode_reuse, find_exact_subclonotypes, search_for_shm_indels};
 use enclone::misc3::sort_tig_bc;
 use enclone_args::read_json::parse_json_annotations_files;
-use enclone_core::defs::{AlleleData, CloneInfo, TigData};
+use enclone_core::defs::{AlleleData, CloneInfo, ExactClonotype, TigData};
 use enclone_core::enclone_structs::*;
 use enclone_core::hcomp::heavy_complexity;
 use enclone_core::version_string;
@@ -214,30 +218,140 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
     check_for_barcode_reuse(ctl, &tig_bc)?;
     ctl.perf_stats(&tproto, "in proto stuff");
 
-    // Find exact subclonotypes.
+    // Make stirling ratio table.  Not sure that fixing the size of this is safe.
 
-    let mut exact_clonotypes = find_exact_subclonotypes(ctl, &tig_bc, refdata, &mut fate);
+    let tsr = Instant::now();
+    let sr = stirling2_ratio_table_double(3000);
+    ctl.perf_stats(&tsr, "computing stirling number table");
+
+    // Find exact subclonotypes, either for all the data at once, or if SHARD_DONORS was
+    // specified, separately and in parallel for each donor.
+
+    let shards = if !ctl.gen_opt.shard_donors {
+        vec![tig_bc]
+    } else {
+        shard_by_donor(tig_bc)
+    };
+    let mut found = Vec::<(Vec<ExactClonotype>, Vec<HashMap<String, String>>)>::new();
+    if !ctl.gen_opt.shard_donors {
+        let exact_clonotypes = find_exact_subclonotypes(ctl, &shards[0], refdata, &mut fate);
+        found.push((exact_clonotypes, std::mem::take(&mut fate)));
+    } else {
+        found = shards
+            .par_iter()
+            .map(|tig_bc| {
+                let mut fate = vec![HashMap::<String, String>::new(); ctl.origin_info.n()];
+                let exact_clonotypes = find_exact_subclonotypes(ctl, tig_bc, refdata, &mut fate);
+                (exact_clonotypes, fate)
+            })
+            .collect();
+    }
     if ctl.gen_opt.utr_con || ctl.gen_opt.con_con {
         return Ok(EncloneIntermediates::default());
     }
     if ctl.gen_opt.trace_barcode.len() > 0 {
-        for u in 0..exact_clonotypes.len() {
-            let ex = &exact_clonotypes[u];
-            for j in 0..ex.clones.len() {
-                if ex.clones[j][0].barcode == ctl.gen_opt.trace_barcode {
-                    println!(
-                        "\nfound {} in an initial exact subclonotype having {} cells",
-                        ctl.gen_opt.trace_barcode,
-                        ex.ncells(),
-                    );
+        for (exact_clonotypes, _) in found.iter() {
+            for ex in exact_clonotypes.iter() {
+                for j in 0..ex.clones.len() {
+                    if ex.clones[j][0].barcode == ctl.gen_opt.trace_barcode {
+                        println!(
+                            "\nfound {} in an initial exact subclonotype having {} cells",
+                            ctl.gen_opt.trace_barcode,
+                            ex.ncells(),
+                        );
+                    }
                 }
             }
         }
     }
 
-    // Test for consistency between VDJ cells and GEX cells.
+    // Test for consistency between VDJ cells and GEX cells, using all the data.
+
+    let mut warnings = Vec::<String>::new();
+    test_vdj_gex_inconsistent(
+        ctl,
+        &shards.iter().flatten().collect::<Vec<_>>(),
+        &found.iter().flat_map(|x| x.0.iter()).collect::<Vec<_>>(),
+        &vdj_cells,
+        gex_info,
+        &mut warnings,
+    )?;
+    drop(shards);
+
+    // Compute everything that depends on exact subclonotypes, separately for each shard, and
+    // then merge.
+
+    let mut ex = if !ctl.gen_opt.shard_donors {
+        let (exact_clonotypes, fate) = found.pop().unwrap();
+        let x = start_exacts(
+            &setup,
+            exact_clonotypes,
+            &vdj_cells,
+            &gex_cells,
+            &gex_cells_specified,
+            fate,
+            &sr,
+        )?;
+        match x {
+            Some(x) => x,
+            None => return Ok(EncloneIntermediates::default()),
+        }
+    } else {
+        let results = found
+            .into_par_iter()
+            .map(|(exact_clonotypes, fate)| {
+                start_exacts(
+                    &setup,
+                    exact_clonotypes,
+                    &vdj_cells,
+                    &gex_cells,
+                    &gex_cells_specified,
+                    fate,
+                    &sr,
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut shards = Vec::<EncloneExacts>::new();
+        for x in results.into_iter() {
+            match x? {
+                Some(x) => shards.push(x),
+                None => return Ok(EncloneIntermediates::default()),
+            }
+        }
+        merge_shards(fate, shards)
+    };
+    warnings.append(&mut ex.warnings);
+    ex.warnings = warnings;
+    let nbroken = broken.iter().filter(|b| **b).count();
+    if nbroken > 0 {
+        ex.warnings.insert(
+            0,
+            format!("{} reference sequences were flagged as defective.", nbroken),
+        );
+    }
+    ex.vdj_cells = vdj_cells;
+    ex.sr = sr;
+    Ok(EncloneIntermediates { setup, ex })
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
-    test_vdj_gex_inconsistent(ctl, &tig_bc, &exact_clonotypes, &vdj_cells, gex_info)?;
+// Starting from exact subclonotypes, find alleles, join and filter.  Return None if the run is to
+// stop here.
+
+fn start_exacts(
+    setup: &EncloneSetup,
+    mut exact_clonotypes: Vec<ExactClonotype>,
+    vdj_cells: &Vec<Vec<String>>,
+    gex_cells: &[Vec<String>],
+    gex_cells_specified: &[bool],
+    mut fate: Vec<HashMap<String, String>>,
+    sr: &Vec<Vec<Double>>,
+) -> Result<Option<EncloneExacts>, String> {
+    let ctl = &setup.ctl;
+    let gex_info = &setup.gex_info;
+    let refdata = &setup.refdata;
+    let is_bcr = setup.is_bcr;
 
     // Filter out some foursie artifacts.
 
@@ -304,15 +418,32 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
     ctl.perf_stats(&tinfo, "building info");
 
     // Derive consensus sequences for alternate alleles of V segments.  Then create donor
-    // reference sequences for Loupe.
+    // reference sequences for Loupe.  If a join state is loaded (LOAD_JOIN), alleles are not
+    // computed for the donors in it, but restored from it.
 
+    let join_state = read_join_state(ctl)?;
     let talt = Instant::now();
     // {(donor, ref id, alt seq, support, is_ref)}:
     let mut alt_refs = Vec::<(usize, usize, DnaString, usize, bool)>::new();
     if !ctl.gen_opt.no_alt_alleles {
-        alt_refs = find_alleles(refdata, ctl, &exact_clonotypes);
+        let skip = match &join_state {
+            Some(state) => saved_donors(ctl, state),
+            None => Vec::new(),
+        };
+        alt_refs = find_alleles(refdata, ctl, &exact_clonotypes, &skip);
     }
     ctl.perf_stats(&talt, "finding alt alleles");
+    if let Some(state) = &join_state {
+        if !ctl.gen_opt.no_alt_alleles {
+            restore_alleles(ctl, refdata, state, &mut alt_refs)?;
+        }
+    }
+
+    // Inject a donor allele set (DONOR_ALLELES).
+
+    if !ctl.gen_opt.donor_alleles.is_empty() {
+        inject_alleles(ctl, refdata, &mut alt_refs)?;
+    }
     if !ctl.gen_opt.dref_file.is_empty() {
         let f = File::create(&ctl.gen_opt.dref_file);
         if f.is_err() {
@@ -322,6 +453,10 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
             );
         }
         let mut f = BufWriter::new(f.unwrap());
+
+        // The donor index in alt_refs is an index into donor_list, whereas donor_id is indexed
+        // by dataset, so the donor name must be taken from donor_list.
+
         let mut count = 0;
         for i in 0..alt_refs.len() {
             let donor = alt_refs[i].0;
@@ -334,7 +469,7 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
                 f,
                 ">{}:{}:{}:{} (reference record id : donor name : allele number : gene name)\n{}",
                 refdata.id[ref_id],
-                ctl.origin_info.donor_id[donor],
+                ctl.origin_info.donor_list[donor],
                 count + 1,
                 refdata.name[ref_id],
                 alt_seq.to_string()
@@ -375,12 +510,6 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
     }
     ctl.perf_stats(&tbc, "computing to_bc");
 
-    // Make stirling ratio table.  Not sure that fixing the size of this is safe.
-
-    let tsr = Instant::now();
-    let sr = stirling2_ratio_table_double(3000);
-    ctl.perf_stats(&tsr, "computing stirling number table");
-
     // Compute complexity.
 
     let tcomp = Instant::now();
@@ -402,6 +531,16 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
 
     let mut join_info = Vec::<(usize, usize, bool, Vec<u8>)>::new();
     let mut raw_joins = Vec::<(i32, i32)>::new();
+    let (mut known, mut saved) = (Vec::<bool>::new(), Vec::<(usize, usize)>::new());
+    saved_joins(
+        ctl,
+        refdata,
+        &join_state,
+        &exact_clonotypes,
+        &info,
+        &mut known,
+        &mut saved,
+    );
     let mut eq: EquivRel = join_exacts(
         is_bcr,
         &to_bc,
@@ -411,8 +550,10 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
         &info,
         &mut join_info,
         &mut raw_joins,
-        &sr,
+        sr,
         &drefs,
+        &known,
+        &saved,
     );
 
     // If NWEAK_ONESIES is not specified, disintegrate certain onesie clonotypes into single cell
@@ -466,7 +607,7 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
 
     lookup_heavy_chain_reuse(ctl, &exact_clonotypes, info, &eq);
     if ctl.gen_opt.heavy_chain_reuse {
-        return Ok(EncloneIntermediates::default());
+        return Ok(None);
     }
     if ctl.gen_opt.trace_barcode.len() > 0 {
         for u in 0..exact_clonotypes.len() {
@@ -562,9 +703,9 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
         &mut orbits,
         is_bcr,
         &to_bc,
-        &sr,
+        sr,
         ctl,
-        &exact_clonotypes,
+        &mut exact_clonotypes,
         info,
         &raw_joins,
         &eq,
@@ -841,6 +982,26 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
         }
     }
     ctl.perf_stats(&tmark, "marking vdj noncells");
+
+    // Assign join ids and save join state (SAVE_JOIN).
+
+    assign_join_ids(
+        ctl,
+        refdata,
+        &join_state,
+        info,
+        &orbits,
+        &mut exact_clonotypes,
+    );
+    write_join_state(
+        ctl,
+        refdata,
+        &join_state,
+        &exact_clonotypes,
+        info,
+        &raw_joins,
+        &alt_refs,
+    )?;
     if ctl.gen_opt.trace_barcode.len() > 0 {
         for u in 0..exact_clonotypes.len() {
             let ex = &exact_clonotypes[u];
@@ -855,25 +1016,23 @@ pub fn main_enclone_start(setup: EncloneSetup) -> Result<EncloneIntermediates, S
             }
         }
     }
-    Ok(EncloneIntermediates {
-        setup,
-        ex: EncloneExacts {
-            to_bc,
-            exact_clonotypes,
-            raw_joins,
-            info: info.to_vec(),
-            orbits,
-            vdj_cells,
-            join_info,
-            drefs,
-            sr,
-            fate,
-            is_bcr,
-            allele_data: AlleleData {
-                alt_refs: alt_refs,
-                var_pos: Vec::new(),
-                var_bases: Vec::new(),
-            },
+    Ok(Some(EncloneExacts {
+        to_bc,
+        exact_clonotypes,
+        raw_joins,
+        info: info.to_vec(),
+        orbits,
+        vdj_cells: Vec::new(),
+        join_info,
+        drefs,
+        sr: Vec::new(),
+        fate,
+        is_bcr,
+        warnings: Vec::new(),
+        allele_data: AlleleData {
+            alt_refs: alt_refs,
+            var_pos: Vec::new(),
+            var_bases: Vec::new(),
         },
-    })
+    }))
 }
@@ -0,0 +1,471 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Generate AIRR rearrangement and AIRR cell output (options AIRR and AIRR_CELL).  See
+// https://docs.airr-community.org/en/stable/datarep/rearrangements.html.
+//
+// There is one rearrangement row per contig, and one cell row per barcode.  Coordinates are
+// one-based and closed, and refer to the full contig sequence.  Note that the enclone CDR3
+// is the AIRR junction, i.e. it includes the flanking conserved residues.  Contigs are always
+// in the orientation of the reference, so rev_comp is always F, whereas productive is as
+// called upstream, and can only be F if REPROD was specified.
+
+use enclone_core::defs::{ColInfo, EncloneControl, ExactClonotype, TigData1};
+use enclone_proto::types::DonorReferenceItem;
+use io_utils::fwriteln;
+use itertools::Itertools;
+use std::io::Write;
+use string_utils::strme;
+use vdj_ann::refx::RefData;
+
+pub const AIRR_FIELDS: [&str; 44] = [
+    "sequence_id",
+    "sequence",
+    "rev_comp",
+    "productive",
+    "v_call",
+    "d_call",
+    "j_call",
+    "sequence_alignment",
+    "germline_alignment",
+    "junction",
+    "junction_aa",
+    "v_cigar",
+    "d_cigar",
+    "j_cigar",
+    "c_call",
+    "locus",
+    "cell_id",
+    "clone_id",
+    "exact_subclonotype_id",
+    "v_donor_allele",
+    "umi_count",
+    "consensus_count",
+    "v_sequence_start",
+    "v_sequence_end",
+    "v_germline_start",
+    "v_germline_end",
+    "v_identity",
+    "j_sequence_start",
+    "j_sequence_end",
+    "j_germline_start",
+    "j_germline_end",
+    "j_identity",
+    "cdr3",
+    "cdr3_aa",
+    "fwr1_start",
+    "fwr1_end",
+    "cdr1_start",
+    "cdr1_end",
+    "fwr2_start",
+    "fwr2_end",
+    "cdr2_start",
+    "cdr2_end",
+    "fwr3_start",
+    "fwr3_end",
+];
+
+pub const AIRR_CELL_FIELDS: [&str; 9] = [
+    "cell_id",
+    "rearrangements",
+    "receptors",
+    "repertoire_id",
+    "virtual_pairing",
+    "clone_id",
+    "exact_subclonotype_id",
+    "donor",
+    "origin",
+];
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Append a cigar operation, if it has positive length.
+
+fn cigar_push(cigar: &mut String, n: usize, op: char) {
+    if n > 0 {
+        *cigar += &format!("{}{}", n, op);
+    }
+}
+
+// Data for one chain of an exact subclonotype that is the same for all its cells.
+
+struct AirrChain {
+    v_cigar: String,
+    d_cigar: String,
+    j_cigar: String,
+    sequence_alignment: String,
+    germline_alignment: String,
+    v_identity: f64,
+    j_identity: f64,
+    v_sequence_end: usize,
+    v_germline_end: usize,
+    v_donor_allele: String,
+}
+
+// Align the V..J part of a contig to its germline.  The V segment is the donor allele if one
+// was assigned, and otherwise the universal reference.
+
+fn airr_chain(x: &TigData1, refdata: &RefData, dref: &Vec<DonorReferenceItem>) -> AirrChain {
+    let mut vref = refdata.refs[x.v_ref_id].to_ascii_vec();
+    let mut v_donor_allele = String::new();
+    if x.v_ref_id_donor_alt_id.is_some() {
+        vref = dref[x.v_ref_id_donor.unwrap()].nt_sequence.clone();
+        v_donor_allele = dref[x.v_ref_id_donor.unwrap()].display_name.clone();
+    }
+    let jref = refdata.refs[x.j_ref_id].to_ascii_vec();
+    let mut c = airr_align(
+        &x.seq,
+        x.full_seq.len(),
+        x.v_start,
+        &x.annv,
+        (x.j_start, x.j_start_ref, x.j_stop),
+        &vref,
+        &jref,
+    );
+    c.v_donor_allele = v_donor_allele;
+    if let (Some(d_start), Some(d)) = (x.d_start, x.d_ref_id) {
+        c.d_cigar = d_cigar(&x.full_seq, d_start, &refdata.refs[d].to_ascii_vec());
+    }
+    c
+}
+
+// Find the CIGAR string for the alignment of a D segment that starts at d_start on the contig.
+// Only the start of the D alignment on the contig is known, so we take the longest ungapped
+// exact match of a suffix of the D reference at that position, preferring the longest suffix in
+// case of a tie.  The CIGAR string is empty if there is no such match.
+
+fn d_cigar(full_seq: &[u8], d_start: usize, dref: &[u8]) -> String {
+    let (mut best_len, mut best_start) = (0, 0);
+    for r in 0..dref.len() {
+        let mut n = 0;
+        while r + n < dref.len()
+            && d_start + n < full_seq.len()
+            && dref[r + n] == full_seq[d_start + n]
+        {
+            n += 1;
+        }
+        if n > best_len {
+            best_len = n;
+            best_start = r;
+        }
+    }
+    let mut cigar = String::new();
+    if best_len > 0 {
+        cigar_push(&mut cigar, d_start, 'S');
+        cigar_push(&mut cigar, best_start, 'N');
+        cigar_push(&mut cigar, best_len, 'M');
+        cigar_push(&mut cigar, full_seq.len() - d_start - best_len, 'S');
+    }
+    cigar
+}
+
+// Align q = V..J, which starts at v_start on a contig of length full_len.  The V alignment is
+// given by annv, which has one entry, or two entries flanking an indel.  The J alignment is
+// ungapped, and is given by its start on the contig, its start on the reference, and its stop on
+// the contig.  Bases between V and J are not assigned to a germline segment, and are
+// represented by Ns in the germline.
+
+fn airr_align(
+    q: &[u8],
+    full_len: usize,
+    v_start: usize,
+    annv: &[(i32, i32, i32, i32, i32)],
+    j: (usize, usize, usize),
+    vref: &[u8],
+    jref: &[u8],
+) -> AirrChain {
+    let (j_start, j_start_ref, j_stop) = j;
+    let (mut saln, mut galn) = (Vec::<u8>::new(), Vec::<u8>::new());
+    let (mut v_matches, mut v_len) = (0, 0);
+
+    // Traverse the V alignment.
+
+    let mut v_cigar = String::new();
+    cigar_push(&mut v_cigar, v_start, 'S');
+    cigar_push(&mut v_cigar, annv[0].3 as usize, 'N');
+    let mut qpos = 0;
+    let mut rpos = annv[0].3 as usize;
+    for (i, a) in annv.iter().enumerate() {
+        let (qstart, len, rstart) = (a.0 as usize, a.1 as usize, a.3 as usize);
+        if i > 0 {
+            if qstart > qpos {
+                cigar_push(&mut v_cigar, qstart - qpos, 'I');
+                saln.extend(&q[qpos..qstart]);
+                galn.append(&mut vec![b'-'; qstart - qpos]);
+            }
+            if rstart > rpos {
+                cigar_push(&mut v_cigar, rstart - rpos, 'D');
+                saln.append(&mut vec![b'-'; rstart - rpos]);
+                galn.extend(&vref[rpos..rstart]);
+            }
+        }
+        cigar_push(&mut v_cigar, len, 'M');
+        for p in 0..len {
+            if qstart + p < q.len() && rstart + p < vref.len() {
+                saln.push(q[qstart + p]);
+                galn.push(vref[rstart + p]);
+                v_len += 1;
+                if q[qstart + p] == vref[rstart + p] {
+                    v_matches += 1;
+                }
+            }
+        }
+        qpos = qstart + len;
+        rpos = rstart + len;
+    }
+    cigar_push(&mut v_cigar, full_len - (v_start + qpos).min(full_len), 'S');
+    let v_germline_end = rpos;
+
+    // Fill in the junction, then traverse the J alignment.  If the V and J alignments overlap,
+    // the overlap is assigned to V.
+
+    let js = j_start - v_start;
+    if js > qpos {
+        saln.extend(&q[qpos..js]);
+        galn.append(&mut vec![b'N'; js - qpos]);
+    }
+    let off = qpos.saturating_sub(js);
+    let jlen = j_stop - j_start;
+    let (mut j_matches, mut j_len) = (0, 0);
+    for p in off..jlen {
+        if js + p < q.len() && j_start_ref + p < jref.len() {
+            saln.push(q[js + p]);
+            galn.push(jref[j_start_ref + p]);
+            j_len += 1;
+            if q[js + p] == jref[j_start_ref + p] {
+                j_matches += 1;
+            }
+        }
+    }
+    let mut j_cigar = String::new();
+    cigar_push(&mut j_cigar, j_start, 'S');
+    cigar_push(&mut j_cigar, j_start_ref, 'N');
+    cigar_push(&mut j_cigar, jlen, 'M');
+    cigar_push(&mut j_cigar, full_len - j_stop, 'S');
+    AirrChain {
+        v_cigar,
+        d_cigar: String::new(),
+        j_cigar,
+        sequence_alignment: strme(&saln).to_string(),
+        germline_alignment: strme(&galn).to_string(),
+        v_identity: v_matches as f64 / v_len.max(1) as f64,
+        j_identity: j_matches as f64 / j_len.max(1) as f64,
+        v_sequence_end: v_start + qpos,
+        v_germline_end,
+        v_donor_allele: String::new(),
+    }
+}
+
+// Format an AIRR boolean.
+
+fn airr_bool(b: bool) -> String {
+    if b { "T" } else { "F" }.to_string()
+}
+
+// Form a cell row.  The cell has a single receptor, which is defined by the chains of its exact
+// subclonotype, so we use the exact subclonotype id as the receptor id.  A pairing of chains
+// is always observed in one cell, and hence is never virtual.
+
+fn airr_cell_row(
+    barcode: &str,
+    tignames: &[String],
+    repertoire_id: &str,
+    clone_id: &str,
+    exact_id: &str,
+    donor: &str,
+    origin: &str,
+) -> Vec<String> {
+    vec![
+        barcode.to_string(),
+        format!("{}", tignames.iter().format(",")),
+        exact_id.to_string(),
+        repertoire_id.to_string(),
+        airr_bool(false),
+        clone_id.to_string(),
+        exact_id.to_string(),
+        donor.to_string(),
+        origin.to_string(),
+    ]
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Write the rows for clonotype oo, which is clonotype j in group i.  Chains are traversed in the
+// column order defined by rsi, so that the first rearrangement for a cell is its first chain
+// as displayed.  Rows that are to go to stdout are written to logx instead, so that they appear
+// in order with the rest of the output.
+
+pub fn generate_airr_output(
+    i: usize,
+    j: usize,
+    oo: usize,
+    exacts: &Vec<Vec<usize>>,
+    rsi: &Vec<ColInfo>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    dref: &Vec<DonorReferenceItem>,
+    logx: &mut Vec<u8>,
+    aout: &mut Box<dyn Write>,
+    acout: &mut Box<dyn Write>,
+) {
+    if ctl.gen_opt.airr_filename.is_empty() && ctl.gen_opt.airr_cell_filename.is_empty() {
+        return;
+    }
+    let clone_id = format!("{}.{}", i + 1, j + 1);
+    let mat = &rsi[oo].mat;
+    for (k, u) in exacts[oo].iter().enumerate() {
+        let ex = &exact_clonotypes[*u];
+        let exact_id = format!("{}.{}", clone_id, k + 1);
+        let mut chains = Vec::<(usize, AirrChain)>::new();
+        for cx in 0..mat.len() {
+            if let Some(m) = mat[cx][k] {
+                chains.push((m, airr_chain(&ex.share[m], refdata, dref)));
+            }
+        }
+        for l in 0..ex.clones.len() {
+            let mut tignames = Vec::<String>::new();
+            for (m, c) in chains.iter() {
+                let (x, y) = (&ex.share[*m], &ex.clones[l][*m]);
+                tignames.push(y.tigname.clone());
+                if ctl.gen_opt.airr_filename.is_empty() {
+                    continue;
+                }
+                let v = x.v_start;
+                let n = x.cdr3_aa.len();
+                let name = |id: Option<usize>| id.map(|t| refdata.name[t].clone());
+                let start = |p: Option<usize>| p.map(|p| v + p + 1);
+                let stop = |p: Option<usize>| p.map(|p| v + p);
+                let cdr3_start = v + x.cdr3_start;
+                let opt = |p: Option<usize>| p.map(|p| format!("{}", p)).unwrap_or_default();
+                let mut row = vec![
+                    y.tigname.clone(),
+                    strme(&y.full_seq).to_string(),
+                    airr_bool(false),
+                    airr_bool(y.productive),
+                    refdata.name[x.v_ref_id].clone(),
+                    name(x.d_ref_id).unwrap_or_default(),
+                    refdata.name[x.j_ref_id].clone(),
+                    c.sequence_alignment.clone(),
+                    c.germline_alignment.clone(),
+                    x.cdr3_dna.clone(),
+                    x.cdr3_aa.clone(),
+                    c.v_cigar.clone(),
+                    c.d_cigar.clone(),
+                    c.j_cigar.clone(),
+                    name(x.c_ref_id).unwrap_or_default(),
+                    x.chain_type.clone(),
+                    y.barcode.clone(),
+                    clone_id.clone(),
+                    exact_id.clone(),
+                    c.v_donor_allele.clone(),
+                    format!("{}", y.umi_count),
+                    format!("{}", y.read_count),
+                    format!("{}", v + 1),
+                    format!("{}", c.v_sequence_end),
+                    format!("{}", x.annv[0].3 + 1),
+                    format!("{}", c.v_germline_end),
+                    format!("{:.4}", c.v_identity),
+                    format!("{}", x.j_start + 1),
+                    format!("{}", x.j_stop),
+                    format!("{}", x.j_start_ref + 1),
+                    format!("{}", x.j_start_ref + x.j_stop - x.j_start),
+                    format!("{:.4}", c.j_identity),
+                ];
+                if n >= 2 {
+                    row.push(x.cdr3_dna[3..3 * n - 3].to_string());
+                    row.push(x.cdr3_aa[1..n - 1].to_string());
+                } else {
+                    row.append(&mut vec![String::new(); 2]);
+                }
+                row.push(format!("{}", v + x.fr1_start + 1));
+                row.push(opt(stop(x.cdr1_start)));
+                row.push(opt(start(x.cdr1_start)));
+                row.push(opt(stop(x.fr2_start)));
+                row.push(opt(start(x.fr2_start)));
+                row.push(opt(stop(x.cdr2_start)));
+                row.push(opt(start(x.cdr2_start)));
+                row.push(opt(stop(x.fr3_start)));
+                row.push(opt(start(x.fr3_start)));
+                row.push(format!("{}", cdr3_start + 3));
+                if ctl.gen_opt.airr_filename == *"stdout" {
+                    fwriteln!(logx, "{}", row.iter().format("\t"));
+                } else {
+                    fwriteln!(aout, "{}", row.iter().format("\t"));
+                }
+            }
+            if !ctl.gen_opt.airr_cell_filename.is_empty() {
+                let y = &ex.clones[l][0];
+                let donor = match y.donor_index {
+                    Some(d) => ctl.origin_info.donor_list[d].clone(),
+                    None => String::new(),
+                };
+                let origin = match y.origin_index {
+                    Some(s) => ctl.origin_info.origin_list[s].clone(),
+                    None => String::new(),
+                };
+                let row = airr_cell_row(
+                    &y.barcode,
+                    &tignames,
+                    &ctl.origin_info.dataset_id[y.dataset_index],
+                    &clone_id,
+                    &exact_id,
+                    &donor,
+                    &origin,
+                );
+                if ctl.gen_opt.airr_cell_filename == *"stdout" {
+                    fwriteln!(logx, "{}", row.iter().format("\t"));
+                } else {
+                    fwriteln!(acout, "{}", row.iter().format("\t"));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_airr_align() {
+        // Contig = 2 bases of UTR, then V..J = 10 bases of V, with one mismatch and a one base
+        // deletion relative to the reference, then 2 junction bases, then 5 bases of J, then 3
+        // bases of C.
+
+        let vref = b"ACGTACGTACGT";
+        let jref = b"TTGGCC";
+        let q = b"ACGTAGTTCGAATGGCC";
+        let annv = vec![(0, 5, 0, 0, 0), (5, 5, 0, 6, 1)];
+        let c = airr_align(q, 22, 2, &annv, (14, 1, 19), vref, jref);
+        assert_eq!(c.v_cigar, "2S5M1D5M10S");
+        assert_eq!(c.j_cigar, "14S1N5M3S");
+        assert_eq!(c.sequence_alignment, "ACGTA-GTTCGAATGGCC");
+        assert_eq!(c.germline_alignment, "ACGTACGTACGNNTGGCC");
+        assert_eq!(c.v_sequence_end, 12);
+        assert_eq!(c.v_germline_end, 11);
+        assert_eq!(c.v_identity, 0.9);
+        assert_eq!(c.j_identity, 1.0);
+    }
+
+    #[test]
+    fn test_d_cigar() {
+        // The D reference is TTAGGGTA, of which the contig contains AGGGT, starting at
+        // contig position 4.
+
+        let full_seq = b"CCCCAGGGTCCC";
+        assert_eq!(d_cigar(full_seq, 4, b"TTAGGGTA"), "4S2N5M3S");
+        assert_eq!(d_cigar(full_seq, 0, b"GGGG"), "");
+    }
+
+    #[test]
+    fn test_airr_cell_row() {
+        let tignames = vec!["AAAC-1_contig_1".to_string(), "AAAC-1_contig_2".to_string()];
+        let row = airr_cell_row("AAAC-1", &tignames, "123085", "1.1", "1.1.2", "d1", "s1");
+        assert_eq!(row.len(), AIRR_CELL_FIELDS.len());
+        assert_eq!(
+            row.join("\t"),
+            "AAAC-1\tAAAC-1_contig_1,AAAC-1_contig_2\t1.1.2\t123085\tF\t1.1\t1.1.2\td1\ts1"
+        );
+        assert_eq!(airr_bool(true), "T");
+    }
+}
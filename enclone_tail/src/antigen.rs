@@ -0,0 +1,106 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Print a summary of antigen specificity calls (option ANTIGEN_CONTROLS).  Clonotypes are
+// counted by their call ag_spec_clono, and exact subclonotypes and cells by their call ag_spec.
+// Exact subclonotypes having no cells with feature barcode data are omitted.
+
+use enclone_core::defs::{EncloneControl, ExactClonotype};
+use std::collections::HashMap;
+use tables::print_tabular_vbox;
+
+// Tally calls.  The input is, for each clonotype, its call, and the call and number of cells
+// for each of its exact subclonotypes.  The output is, for each call, the number of clonotypes,
+// exact subclonotypes and cells, sorted by decreasing number of cells.
+
+pub fn tally_antigen_calls(
+    calls: &[(String, Vec<(String, usize)>)],
+) -> Vec<(String, usize, usize, usize)> {
+    let mut counts = HashMap::<String, (usize, usize, usize)>::new();
+    for i in 0..calls.len() {
+        if !calls[i].0.is_empty() {
+            counts.entry(calls[i].0.clone()).or_default().0 += 1;
+        }
+        for j in 0..calls[i].1.len() {
+            let (call, n) = &calls[i].1[j];
+            if !call.is_empty() {
+                let x = counts.entry(call.clone()).or_default();
+                x.1 += 1;
+                x.2 += n;
+            }
+        }
+    }
+    let mut tally = counts
+        .into_iter()
+        .map(|(call, x)| (call, x.0, x.1, x.2))
+        .collect::<Vec<_>>();
+    tally.sort_by(|a, b| b.3.cmp(&a.3).then(b.1.cmp(&a.1)).then(a.0.cmp(&b.0)));
+    tally
+}
+
+pub fn antigen_summary(
+    ctl: &EncloneControl,
+    exacts: &Vec<Vec<usize>>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    out_datas: &Vec<Vec<HashMap<String, String>>>,
+) {
+    let mut calls = Vec::<(String, Vec<(String, usize)>)>::new();
+    for i in 0..exacts.len() {
+        let get = |j: usize, var: &str| out_datas[i][j].get(var).cloned().unwrap_or_default();
+        let mut x = (String::new(), Vec::<(String, usize)>::new());
+        if !out_datas[i].is_empty() {
+            x.0 = get(0, "ag_spec_clono");
+        }
+        for j in 0..exacts[i].len() {
+            if j < out_datas[i].len() {
+                x.1.push((get(j, "ag_spec"), exact_clonotypes[exacts[i][j]].ncells()));
+            }
+        }
+        calls.push(x);
+    }
+    let tally = tally_antigen_calls(&calls);
+    println!("\nANTIGEN SPECIFICITY\n");
+    println!(
+        "antigen calls require a median score of at least {}\n",
+        ctl.gen_opt.antigen_min_score
+    );
+    let mut rows = vec![vec![
+        "specificity".to_string(),
+        "clonotypes".to_string(),
+        "exacts".to_string(),
+        "cells".to_string(),
+    ]];
+    rows.push(vec!["\\hline".to_string(); 4]);
+    for x in tally.iter() {
+        rows.push(vec![
+            x.0.clone(),
+            format!("{}", x.1),
+            format!("{}", x.2),
+            format!("{}", x.3),
+        ]);
+    }
+    let mut log = String::new();
+    print_tabular_vbox(&mut log, &rows, 2, &b"l|r|r|r".to_vec(), false, false);
+    print!("{}", log);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tally_antigen_calls() {
+        let calls = vec![
+            (
+                "HA".to_string(),
+                vec![("HA".to_string(), 5), ("none".to_string(), 1)],
+            ),
+            ("none".to_string(), vec![("none".to_string(), 2)]),
+            (String::new(), vec![(String::new(), 3)]),
+        ];
+        let tally = tally_antigen_calls(&calls);
+        assert_eq!(
+            tally,
+            vec![("HA".to_string(), 1, 1, 5), ("none".to_string(), 1, 2, 3)]
+        );
+    }
+}
@@ -2,7 +2,8 @@
 //
 // Given a collection of circles having specified colors, create an svg string that shows the
 // circles on a canvas of fixed size.  The circles are moved and resized accordingly.
-// Also shades smoothed polygons.  Also add tooltip notes if requested.
+// Also shades smoothed polygons.  Also add tooltip notes if requested.  Highlighted circles are
+// outlined in black.  Circles whose group index is usize::MAX get no tooltip.
 
 use crate::polygon::Polygon;
 use std::collections::HashMap;
@@ -24,6 +25,7 @@ pub fn circles_to_svg(
     by_var: bool,
     var: String,
     barcode_to_var_value: &HashMap<(usize, String), String>,
+    highlight: &Vec<bool>,
 ) -> String {
     let n = center.len();
     assert!(!center.is_empty());
@@ -98,7 +100,7 @@ pub fn circles_to_svg(
     }
     for i in 0..center.len() {
         let mut tooltipx = String::new();
-        if tooltip {
+        if tooltip && group_index2[i] != usize::MAX {
             let mut var_val = String::new();
             if by_var && barcode_to_var_value.contains_key(&barcodes[i]) {
                 var_val = format!(",{}={}", var, barcode_to_var_value[&barcodes[i]]);
@@ -111,7 +113,18 @@ pub fn circles_to_svg(
                 var_val,
             );
         }
-        if color[i] != "undefined" {
+        if !highlight.is_empty() && highlight[i] {
+            out += &format!(
+                "<circle{} cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" stroke=\"black\" \
+                 stroke-width=\"{:.2}\" fill=\"{}\" />\n",
+                tooltipx,
+                center[i].0,
+                center[i].1,
+                radius[i],
+                radius[i] / 4.0,
+                color[i]
+            );
+        } else if color[i] != "undefined" {
             out += &format!(
                 "<circle{} cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" fill=\"{}\" />\n",
                 tooltipx, center[i].0, center[i].1, radius[i], color[i]
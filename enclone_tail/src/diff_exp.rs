@@ -0,0 +1,476 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Differential expression between two sets of cells (options DIFF_EXP, DIFF_EXP_GROUPS and
+// DIFF_EXP_OUT).  The test and control sets are defined either by linear conditions on
+// clonotypes (or exact subclonotypes, with SCAN_EXACT), as for SCAN, or by group numbers.  The
+// control set may instead be the rest of the cells.  Cells in both sets are assigned to the test
+// set.
+//
+// Only gene expression UMI counts are used.  Each cell is normalized by a size factor, which is
+// its total UMI count divided by the median total over both sets.  Genes expressed in at least
+// 10% of the cells in either set are tested using the Wilcoxon rank-sum test, with a normal
+// approximation corrected for ties, and p values are adjusted for multiple testing using the
+// Benjamini-Hochberg procedure.  The log2 fold change is computed from the mean normalized
+// counts, with a pseudocount.
+
+use enclone_base::median::median_f64;
+use enclone_core::defs::{EncloneControl, ExactClonotype, GexInfo};
+use io_utils::{fwriteln, open_for_write_new};
+use ndarray::s;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use string_utils::TextUtils;
+use tables::print_tabular;
+use vector_utils::{bin_member, bin_position, unique_sort};
+
+#[cfg(target_os = "windows")]
+use hdf5::Reader;
+#[cfg(not(target_os = "windows"))]
+use hdf5x::Reader;
+
+const MIN_PCT: f64 = 10.0;
+const PSEUDOCOUNT: f64 = 0.1;
+const ALPHA: f64 = 0.05;
+
+// Complementary error function, from Numerical Recipes, having fractional error less than
+// 1.2e-7 everywhere.
+
+fn erfc(x: f64) -> f64 {
+    const COEFFS: [f64; 10] = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ];
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let mut poly = 0.0;
+    for c in COEFFS.iter().rev() {
+        poly = c + t * poly;
+    }
+    let r = t * (-z * z + poly).exp();
+    if x >= 0.0 {
+        r
+    } else {
+        2.0 - r
+    }
+}
+
+// Two-sided Wilcoxon rank-sum test for two samples of nonnegative values, of sizes nx and ny,
+// given only their positive values x and y.  The zeros form a single block of ties.
+
+pub fn rank_sum_test(x: &[f64], nx: usize, y: &[f64], ny: usize) -> f64 {
+    let (n1, n) = (nx as f64, (nx + ny) as f64);
+    let zeros = nx + ny - x.len() - y.len();
+    let mut vals = Vec::<(f64, bool)>::new();
+    for v in x.iter() {
+        vals.push((*v, true));
+    }
+    for v in y.iter() {
+        vals.push((*v, false));
+    }
+    vals.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let mut r1 = (nx - x.len()) as f64 * (zeros as f64 + 1.0) / 2.0;
+    let mut ties = (zeros as f64).powi(3) - zeros as f64;
+    let mut i = 0;
+    while i < vals.len() {
+        let mut j = i + 1;
+        while j < vals.len() && vals[j].0 == vals[i].0 {
+            j += 1;
+        }
+        // The values in vals[i..j] have ranks zeros + i + 1 through zeros + j.
+
+        let rank = (2 * zeros + i + j + 1) as f64 / 2.0;
+        for k in i..j {
+            if vals[k].1 {
+                r1 += rank;
+            }
+        }
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+    let u = r1 - n1 * (n1 + 1.0) / 2.0;
+    let mu = n1 * ny as f64 / 2.0;
+    let var = n1 * ny as f64 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if var <= 0.0 {
+        return 1.0;
+    }
+    let d = ((u - mu).abs() - 0.5).max(0.0);
+    erfc(d / var.sqrt() / 2.0_f64.sqrt()).min(1.0)
+}
+
+// Benjamini-Hochberg adjustment of p values.
+
+pub fn bh_adjust(p: &[f64]) -> Vec<f64> {
+    let m = p.len();
+    let mut ids = (0..m).collect::<Vec<usize>>();
+    ids.sort_by(|a, b| p[*a].partial_cmp(&p[*b]).unwrap());
+    let mut adj = vec![1.0; m];
+    let mut min_so_far = 1.0_f64;
+    for k in (0..m).rev() {
+        min_so_far = min_so_far.min(p[ids[k]] * m as f64 / (k + 1) as f64);
+        adj[ids[k]] = min_so_far;
+    }
+    adj
+}
+
+// Find the cells in the given clonotypes, or exact subclonotypes, as pairs (dataset index,
+// barcode).
+
+fn cells_of(
+    clonotypes: &[usize],
+    exact: bool,
+    exacts: &Vec<Vec<usize>>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+) -> Vec<(usize, String)> {
+    let mut cells = Vec::<(usize, String)>::new();
+    for i in clonotypes.iter() {
+        let us = if exact { vec![*i] } else { exacts[*i].clone() };
+        for u in us.iter() {
+            for clone in exact_clonotypes[*u].clones.iter() {
+                cells.push((clone[0].dataset_index, clone[0].barcode.clone()));
+            }
+        }
+    }
+    unique_sort(&mut cells);
+    cells
+}
+
+// Get the gene expression UMI counts for a cell, as pairs (feature index, count).
+
+fn gex_counts(
+    ctl: &EncloneControl,
+    gex_info: &GexInfo,
+    li: usize,
+    p: usize,
+    h5_data: &Vec<(usize, Vec<u32>, Vec<u32>)>,
+    d_readers: &Vec<Option<Reader>>,
+    ind_readers: &Vec<Option<Reader>>,
+) -> Vec<(usize, usize)> {
+    let mut counts = Vec::<(usize, usize)>::new();
+    if gex_info.gex_matrices[li].initialized() {
+        counts = gex_info.gex_matrices[li].row(p);
+    } else {
+        let z1 = gex_info.h5_indptr[li][p] as usize;
+        let z2 = gex_info.h5_indptr[li][p + 1] as usize;
+        let d: Vec<u32>;
+        let ind: Vec<u32>;
+        if ctl.gen_opt.h5_pre {
+            d = h5_data[li].1[z1..z2].to_vec();
+            ind = h5_data[li].2[z1..z2].to_vec();
+        } else {
+            d = d_readers[li]
+                .as_ref()
+                .unwrap()
+                .read_slice(s![z1..z2])
+                .unwrap()
+                .to_vec();
+            ind = ind_readers[li]
+                .as_ref()
+                .unwrap()
+                .read_slice(s![z1..z2])
+                .unwrap()
+                .to_vec();
+        }
+        for j in 0..d.len() {
+            counts.push((ind[j] as usize, d[j] as usize));
+        }
+    }
+    counts.retain(|x| gex_info.is_gex[li][x.0] && x.1 > 0);
+    counts
+}
+
+pub fn diff_exp(
+    ctl: &EncloneControl,
+    exacts: &Vec<Vec<usize>>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    gex_info: &GexInfo,
+    tests: &Vec<usize>,
+    controls: &Vec<usize>,
+    groups: &Vec<Vec<(i32, String)>>,
+    h5_data: &Vec<(usize, Vec<u32>, Vec<u32>)>,
+    d_readers: &Vec<Option<Reader>>,
+    ind_readers: &Vec<Option<Reader>>,
+) -> Result<(), String> {
+    if gex_info.gex_features.is_empty() || gex_info.gex_features[0].is_empty() {
+        return Err("\nDIFF_EXP requires gene expression data.\n".to_string());
+    }
+
+    // Define the test and control clonotypes.
+
+    let exact = ctl.gen_opt.gene_scan_exact;
+    let (mut test, mut control);
+    let mut all = Vec::<usize>::new();
+    if !ctl.gen_opt.diff_exp_test_groups.is_empty() {
+        let mut gs = ctl.gen_opt.diff_exp_test_groups.clone();
+        gs.append(&mut ctl.gen_opt.diff_exp_control_groups.clone());
+        for g in gs.iter() {
+            if *g > groups.len() {
+                return Err(format!(
+                    "\nThe group {} in DIFF_EXP_GROUPS does not exist, as the groups are \
+                    numbered from 1 to {}.\n",
+                    g,
+                    groups.len()
+                ));
+            }
+        }
+        let members = |gs: &[usize]| {
+            let mut x = Vec::<usize>::new();
+            for g in gs.iter() {
+                for m in groups[g - 1].iter() {
+                    x.push(m.0 as usize);
+                }
+            }
+            x
+        };
+        test = members(&ctl.gen_opt.diff_exp_test_groups);
+        control = members(&ctl.gen_opt.diff_exp_control_groups);
+        for g in groups.iter() {
+            for m in g.iter() {
+                all.push(m.0 as usize);
+            }
+        }
+    } else {
+        test = tests.clone();
+        control = controls.clone();
+        for i in 0..exacts.len() {
+            if !exact {
+                all.push(i);
+            } else {
+                all.append(&mut exacts[i].clone());
+            }
+        }
+    }
+    unique_sort(&mut test);
+    if ctl.gen_opt.diff_exp_rest {
+        control = all
+            .iter()
+            .filter(|i| !bin_member(&test, i))
+            .cloned()
+            .collect::<Vec<usize>>();
+    }
+    unique_sort(&mut control);
+
+    // Find the cells having gene expression data, and get their counts.
+
+    let mut cells = Vec::<(bool, usize, usize)>::new();
+    let test_cells = cells_of(&test, exact, exacts, exact_clonotypes);
+    for (li, bc) in test_cells.iter() {
+        let p = bin_position(&gex_info.gex_barcodes[*li], bc);
+        if p >= 0 {
+            cells.push((true, *li, p as usize));
+        }
+    }
+    for (li, bc) in cells_of(&control, exact, exacts, exact_clonotypes).iter() {
+        let p = bin_position(&gex_info.gex_barcodes[*li], bc);
+        if p >= 0 && !bin_member(&test_cells, &(*li, bc.clone())) {
+            cells.push((false, *li, p as usize));
+        }
+    }
+    let mut counts = vec![Vec::<(usize, usize)>::new(); cells.len()];
+    counts.par_iter_mut().enumerate().for_each(|(i, res)| {
+        let (_, li, p) = cells[i];
+        *res = gex_counts(ctl, gex_info, li, p, h5_data, d_readers, ind_readers);
+    });
+    let mut totals = Vec::<f64>::new();
+    for i in 0..cells.len() {
+        totals.push(counts[i].iter().map(|x| x.1).sum::<usize>() as f64);
+    }
+    let (mut nt, mut nc) = (0, 0);
+    for i in 0..cells.len() {
+        if totals[i] > 0.0 {
+            if cells[i].0 {
+                nt += 1;
+            } else {
+                nc += 1;
+            }
+        }
+    }
+    let units = if exact {
+        "exact subclonotypes"
+    } else {
+        "clonotypes"
+    };
+    println!("\nDIFFERENTIAL EXPRESSION\n");
+    println!(
+        "{} test {} containing {} cells with gene expression data",
+        test.len(),
+        units,
+        nt
+    );
+    println!(
+        "{} control {} containing {} cells with gene expression data\n",
+        control.len(),
+        units,
+        nc
+    );
+    if nt == 0 || nc == 0 {
+        return Err(format!(
+            "\nDifferential expression failed, because there are no {} cells with gene \
+            expression data.\n",
+            if nt == 0 { "test" } else { "control" }
+        ));
+    }
+
+    // Normalize the counts and gather the positive values for each gene.
+
+    let mut sorted_totals = totals
+        .iter()
+        .filter(|t| **t > 0.0)
+        .cloned()
+        .collect::<Vec<_>>();
+    sorted_totals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median_total = median_f64(&sorted_totals);
+    let nf = gex_info.gex_features[0].len();
+    let mut vals = vec![(Vec::<f64>::new(), Vec::<f64>::new()); nf];
+    for i in 0..cells.len() {
+        if totals[i] == 0.0 {
+            continue;
+        }
+        let sf = totals[i] / median_total;
+        for (fid, n) in counts[i].iter() {
+            if *fid < nf {
+                if cells[i].0 {
+                    vals[*fid].0.push(*n as f64 / sf);
+                } else {
+                    vals[*fid].1.push(*n as f64 / sf);
+                }
+            }
+        }
+    }
+
+    // Test the genes, yielding (fid, test mean, control mean, test pct, control pct, log2 fold
+    // change, p value).
+
+    let mut results = Vec::<(usize, f64, f64, f64, f64, f64, f64)>::new();
+    for fid in 0..nf {
+        let (x, y) = (&vals[fid].0, &vals[fid].1);
+        let pt = 100.0 * x.len() as f64 / nt as f64;
+        let pc = 100.0 * y.len() as f64 / nc as f64;
+        if pt >= MIN_PCT || pc >= MIN_PCT {
+            let mt = x.iter().sum::<f64>() / nt as f64;
+            let mc = y.iter().sum::<f64>() / nc as f64;
+            let lfc = ((mt + PSEUDOCOUNT) / (mc + PSEUDOCOUNT)).log2();
+            results.push((fid, mt, mc, pt, pc, lfc, 0.0));
+        }
+    }
+    results.par_iter_mut().for_each(|res| {
+        res.6 = rank_sum_test(&vals[res.0].0, nt, &vals[res.0].1, nc);
+    });
+    let padj = bh_adjust(&results.iter().map(|r| r.6).collect::<Vec<f64>>());
+    let mut ids = (0..results.len()).collect::<Vec<usize>>();
+    ids.sort_by(|a, b| {
+        padj[*a]
+            .partial_cmp(&padj[*b])
+            .unwrap()
+            .then(
+                results[*b]
+                    .5
+                    .abs()
+                    .partial_cmp(&results[*a].5.abs())
+                    .unwrap(),
+            )
+            .then(results[*a].0.cmp(&results[*b].0))
+    });
+
+    // Print the significant genes, and write all tested genes to DIFF_EXP_OUT.
+
+    let fields = [
+        "id",
+        "name",
+        "test_mean",
+        "control_mean",
+        "test_pct",
+        "control_pct",
+        "log2fc",
+        "pval",
+        "padj",
+    ];
+    let mut rows = vec![fields
+        .iter()
+        .map(|f| f.to_string())
+        .collect::<Vec<String>>()];
+    let mut csv = Vec::<u8>::new();
+    fwriteln!(csv, "{}", fields.join(","));
+    for i in ids.iter() {
+        let r = &results[*i];
+        let feature = &gex_info.gex_features[0][r.0];
+        let row = vec![
+            feature.before("\t").to_string(),
+            feature.between("\t", "\t").to_string(),
+            format!("{:.2}", r.1),
+            format!("{:.2}", r.2),
+            format!("{:.1}", r.3),
+            format!("{:.1}", r.4),
+            format!("{:.2}", r.5),
+            format!("{:.2e}", r.6),
+            format!("{:.2e}", padj[*i]),
+        ];
+        fwriteln!(csv, "{}", row.join(","));
+        if padj[*i] < ALPHA {
+            rows.push(row);
+        }
+    }
+    println!(
+        "{} genes tested, of which {} have adjusted p value < {}\n",
+        results.len(),
+        rows.len() - 1,
+        ALPHA
+    );
+    if rows.len() > 1 {
+        let mut log = Vec::<u8>::new();
+        print_tabular(&mut log, &rows, 2, Some(b"llrrrrrrr".to_vec()));
+        print!("{}", String::from_utf8_lossy(&log));
+    }
+    if !ctl.gen_opt.diff_exp_out.is_empty() {
+        if ctl.gen_opt.diff_exp_out == "stdout" {
+            print!("\n{}", String::from_utf8_lossy(&csv));
+        } else {
+            let mut f = open_for_write_new![&ctl.gen_opt.diff_exp_out];
+            f.write_all(&csv).unwrap();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_exp_stats() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-6);
+        assert!((erfc(1.0) - 0.157299).abs() < 1e-6);
+        assert!((erfc(-1.0) - 1.842701).abs() < 1e-6);
+
+        // Identical samples are not different, whereas disjoint ones are.
+
+        assert!((rank_sum_test(&[1.0, 2.0], 4, &[1.0, 2.0], 4) - 1.0).abs() < 1e-6);
+        let x = (1..=20).map(|v| v as f64 + 100.0).collect::<Vec<f64>>();
+        let y = (1..=10).map(|v| v as f64).collect::<Vec<f64>>();
+        assert!(rank_sum_test(&x, 20, &y, 20) < 1e-6);
+        assert_eq!(rank_sum_test(&[], 5, &[], 5), 1.0);
+
+        // With many zeros, identical samples are still not different, but a sample having many
+        // more nonzero values is.
+
+        let ones = |n: usize| vec![1.0; n];
+        assert!((rank_sum_test(&ones(5), 100, &ones(5), 100) - 1.0).abs() < 1e-6);
+        assert!(rank_sum_test(&ones(50), 100, &ones(5), 100) < 1e-6);
+        assert!(rank_sum_test(&ones(6), 100, &ones(5), 100) > 0.5);
+
+        let adj = bh_adjust(&[0.01, 0.04, 0.03, 0.5]);
+        assert!((adj[0] - 0.04).abs() < 1e-9);
+        assert!((adj[1] - 0.04 * 4.0 / 3.0).abs() < 1e-9);
+        assert!((adj[2] - 0.04 * 4.0 / 3.0).abs() < 1e-9);
+        assert!((adj[3] - 0.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,322 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Repertoire diversity and overlap statistics (options DIVERSITY and DIVERSITY_CSV).  These are
+// computed from the number of cells in each clonotype, after filtering, separately for all cells
+// and for each dataset, origin and donor.  Cells whose origin or donor is unknown are omitted
+// from the origin and donor rows.
+//
+// Rarefied Hill numbers are the expected values at a depth equal to the smallest number of cells
+// in any dataset (respectively origin or donor), computed using the interpolation formulas of
+// Chao et al. (2014), Ecological Monographs 84:45-67.
+
+use enclone_core::defs::{EncloneControl, ExactClonotype};
+use itertools::Itertools;
+use std::cmp::min;
+use tables::print_tabular_vbox;
+
+pub const DIVERSITY_FIELDS: [&str; 15] = [
+    "level",
+    "name",
+    "cells",
+    "clonotypes",
+    "shannon",
+    "simpson",
+    "chao1",
+    "hill0",
+    "hill1",
+    "hill2",
+    "clonality",
+    "depth",
+    "hill0_rarefied",
+    "hill1_rarefied",
+    "hill2_rarefied",
+];
+
+pub const OVERLAP_FIELDS: [&str; 4] = ["origin1", "origin2", "morisita_horn", "jaccard"];
+
+// Diversity statistics for a vector of clonotype sizes.  Zero entries are ignored.
+
+#[derive(Default, Debug, PartialEq)]
+pub struct Diversity {
+    pub cells: usize,
+    pub clonotypes: usize,
+    pub shannon: f64,   // natural log
+    pub simpson: f64,   // Gini-Simpson index 1 - sum p^2
+    pub chao1: f64,     // bias-corrected
+    pub hill: [f64; 3], // Hill numbers of order 0, 1, 2
+    pub clonality: f64, // 1 - Pielou evenness
+}
+
+pub fn diversity(x: &[usize]) -> Diversity {
+    let n = x.iter().sum::<usize>();
+    let s = x.iter().filter(|c| **c > 0).count();
+    if n == 0 {
+        return Diversity::default();
+    }
+    let (mut shannon, mut sum_p2) = (0.0, 0.0);
+    let (mut f1, mut f2) = (0, 0);
+    for c in x.iter() {
+        if *c > 0 {
+            let p = *c as f64 / n as f64;
+            shannon -= p * p.ln();
+            sum_p2 += p * p;
+        }
+        if *c == 1 {
+            f1 += 1;
+        } else if *c == 2 {
+            f2 += 1;
+        }
+    }
+    let chao1 = s as f64 + (f1 * (f1.max(1) - 1)) as f64 / (2 * (f2 + 1)) as f64;
+    let clonality = if s > 1 {
+        1.0 - shannon / (s as f64).ln()
+    } else {
+        1.0
+    };
+    Diversity {
+        cells: n,
+        clonotypes: s,
+        shannon,
+        simpson: 1.0 - sum_p2,
+        chao1,
+        hill: [s as f64, shannon.exp(), 1.0 / sum_p2],
+        clonality,
+    }
+}
+
+// Return the expected Hill numbers of order 0, 1, 2 for a random subsample of m cells.  The
+// vector lnfact must contain ln(k!) for k up to at least the total number of cells.
+
+pub fn rarefied_hill(x: &[usize], m: usize, lnfact: &[f64]) -> [f64; 3] {
+    let n = x.iter().sum::<usize>();
+    if m == 0 || m > n {
+        return [0.0; 3];
+    }
+    let lnc = |a: usize, b: usize| lnfact[a] - lnfact[b] - lnfact[a - b];
+    let total = lnc(n, m);
+    let (mut h0, mut h1, mut sum2) = (0.0, 0.0, 0.0);
+    for c in x.iter() {
+        let c = *c;
+        if c == 0 {
+            continue;
+        }
+
+        // The number of cells from this clonotype in the subsample is hypergeometric.
+
+        if n - c >= m {
+            h0 += 1.0 - (lnc(n - c, m) - total).exp();
+        } else {
+            h0 += 1.0;
+        }
+        let kmin = if m > n - c { m - (n - c) } else { 1 };
+        for k in kmin.max(1)..=min(c, m) {
+            let p = (lnc(c, k) + lnc(n - c, m - k) - total).exp();
+            let q = k as f64 / m as f64;
+            h1 -= q * q.ln() * p;
+        }
+        if n > 1 {
+            sum2 += (c * (c - 1)) as f64 / (n * (n - 1)) as f64;
+        }
+    }
+    let h2 = 1.0 / (1.0 / m as f64 + (m - 1) as f64 / m as f64 * sum2);
+    [h0, h1.exp(), h2]
+}
+
+// Overlap indices between two vectors of clonotype sizes.
+
+pub fn morisita_horn(x: &[usize], y: &[usize]) -> f64 {
+    let (nx, ny) = (
+        x.iter().sum::<usize>() as f64,
+        y.iter().sum::<usize>() as f64,
+    );
+    if nx == 0.0 || ny == 0.0 {
+        return 0.0;
+    }
+    let (mut xy, mut xx, mut yy) = (0.0, 0.0, 0.0);
+    for i in 0..x.len() {
+        let (a, b) = (x[i] as f64, y[i] as f64);
+        xy += a * b;
+        xx += a * a;
+        yy += b * b;
+    }
+    2.0 * xy / ((xx / (nx * nx) + yy / (ny * ny)) * nx * ny)
+}
+
+pub fn jaccard(x: &[usize], y: &[usize]) -> f64 {
+    let (mut both, mut either) = (0, 0);
+    for i in 0..x.len() {
+        if x[i] > 0 && y[i] > 0 {
+            both += 1;
+        }
+        if x[i] > 0 || y[i] > 0 {
+            either += 1;
+        }
+    }
+    if either == 0 {
+        return 0.0;
+    }
+    both as f64 / either as f64
+}
+
+pub fn print_diversity(
+    exacts: &Vec<Vec<usize>>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    ctl: &EncloneControl,
+) {
+    // Find the number of cells in each clonotype, for each dataset, origin and donor.
+
+    let oi = &ctl.origin_info;
+    let nclono = exacts.len();
+    let mut all = vec![0; nclono];
+    let mut by_dataset = vec![vec![0; nclono]; oi.n()];
+    let mut by_origin = vec![vec![0; nclono]; oi.origin_list.len()];
+    let mut by_donor = vec![vec![0; nclono]; oi.donor_list.len()];
+    for i in 0..nclono {
+        for u in exacts[i].iter() {
+            for clone in exact_clonotypes[*u].clones.iter() {
+                all[i] += 1;
+                by_dataset[clone[0].dataset_index][i] += 1;
+                if let Some(o) = clone[0].origin_index {
+                    by_origin[o][i] += 1;
+                }
+                if let Some(d) = clone[0].donor_index {
+                    by_donor[d][i] += 1;
+                }
+            }
+        }
+    }
+    let ncells = all.iter().sum::<usize>();
+    let mut lnfact = vec![0.0; ncells + 1];
+    for k in 1..=ncells {
+        lnfact[k] = lnfact[k - 1] + (k as f64).ln();
+    }
+
+    // Compute the diversity statistics.
+
+    let mut lines = Vec::<Vec<String>>::new();
+    let levels = [
+        ("all", vec!["all".to_string()], vec![all]),
+        ("dataset", oi.dataset_id.clone(), by_dataset),
+        ("origin", oi.origin_list.clone(), by_origin.clone()),
+        ("donor", oi.donor_list.clone(), by_donor),
+    ];
+    for (level, names, counts) in levels.iter() {
+        let depth = counts
+            .iter()
+            .map(|x| x.iter().sum::<usize>())
+            .filter(|n| *n > 0)
+            .min()
+            .unwrap_or(0);
+        for j in 0..counts.len() {
+            let d = diversity(&counts[j]);
+            if d.cells == 0 {
+                continue;
+            }
+            let r = rarefied_hill(&counts[j], depth, &lnfact);
+            lines.push(vec![
+                level.to_string(),
+                names[j].clone(),
+                format!("{}", d.cells),
+                format!("{}", d.clonotypes),
+                format!("{:.3}", d.shannon),
+                format!("{:.3}", d.simpson),
+                format!("{:.1}", d.chao1),
+                format!("{:.1}", d.hill[0]),
+                format!("{:.1}", d.hill[1]),
+                format!("{:.1}", d.hill[2]),
+                format!("{:.3}", d.clonality),
+                format!("{}", depth),
+                format!("{:.1}", r[0]),
+                format!("{:.1}", r[1]),
+                format!("{:.1}", r[2]),
+            ]);
+        }
+    }
+
+    // Compute the overlap between origins.
+
+    let mut overlaps = Vec::<Vec<String>>::new();
+    for o1 in 0..by_origin.len() {
+        for o2 in o1 + 1..by_origin.len() {
+            overlaps.push(vec![
+                oi.origin_list[o1].clone(),
+                oi.origin_list[o2].clone(),
+                format!("{:.3}", morisita_horn(&by_origin[o1], &by_origin[o2])),
+                format!("{:.3}", jaccard(&by_origin[o1], &by_origin[o2])),
+            ]);
+        }
+    }
+
+    // Print the tables.
+
+    if ctl.gen_opt.diversity {
+        println!("\nDIVERSITY STATISTICS\n");
+        let mut rows = vec![DIVERSITY_FIELDS.iter().map(|x| x.to_string()).collect()];
+        rows.push(vec!["\\hline".to_string(); DIVERSITY_FIELDS.len()]);
+        rows.append(&mut lines.clone());
+        let mut log = String::new();
+        print_tabular_vbox(
+            &mut log,
+            &rows,
+            2,
+            &b"l|l|r|r|r|r|r|r|r|r|r|r|r|r|r".to_vec(),
+            false,
+            false,
+        );
+        print!("{}", log);
+        if !overlaps.is_empty() {
+            println!("\nOVERLAP BETWEEN ORIGINS\n");
+            let mut rows = vec![OVERLAP_FIELDS.iter().map(|x| x.to_string()).collect()];
+            rows.push(vec!["\\hline".to_string(); OVERLAP_FIELDS.len()]);
+            rows.append(&mut overlaps.clone());
+            let mut log = String::new();
+            print_tabular_vbox(&mut log, &rows, 2, &b"l|l|r|r".to_vec(), false, false);
+            print!("{}", log);
+        }
+    }
+    if ctl.gen_opt.diversity_csv {
+        println!("\n{}", DIVERSITY_FIELDS.iter().format(","));
+        for x in lines.iter() {
+            println!("{}", x.iter().format(","));
+        }
+        if !overlaps.is_empty() {
+            println!("\n{}", OVERLAP_FIELDS.iter().format(","));
+            for x in overlaps.iter() {
+                println!("{}", x.iter().format(","));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diversity() {
+        let x = [2, 1, 1, 0];
+        let d = diversity(&x);
+        assert_eq!(d.cells, 4);
+        assert_eq!(d.clonotypes, 3);
+        assert!((d.simpson - 0.625).abs() < 1e-9);
+        assert!((d.chao1 - 3.5).abs() < 1e-9);
+        assert!((d.hill[2] - 2.666_666_667).abs() < 1e-6);
+
+        // At full depth, rarefied Hill numbers of order 0 and 1 are the observed ones.
+
+        let mut lnfact = vec![0.0; 5];
+        for k in 1..5 {
+            lnfact[k] = lnfact[k - 1] + (k as f64).ln();
+        }
+        let r = rarefied_hill(&x, 4, &lnfact);
+        assert!((r[0] - 3.0).abs() < 1e-9);
+        assert!((r[1] - d.hill[1]).abs() < 1e-9);
+
+        // Subsampling two cells, the chance of seeing two distinct clonotypes is 5/6.
+
+        let r = rarefied_hill(&x, 2, &lnfact);
+        assert!((r[0] - 11.0 / 6.0).abs() < 1e-9);
+        assert!((morisita_horn(&x, &x) - 1.0).abs() < 1e-9);
+        assert!((jaccard(&x, &[0, 1, 0, 5]) - 0.25).abs() < 1e-9);
+    }
+}
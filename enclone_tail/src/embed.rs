@@ -0,0 +1,169 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Place cells at their coordinates in a two-dimensional embedding (UMAP or t-SNE), for
+// HONEY=...,embed=..., replacing the honeycomb layout.  Clonotype cells lacking coordinates are
+// dropped.  Other cells having coordinates are shown in gray underneath, and cells in highlighted
+// clonotypes are enlarged, outlined and drawn on top.
+
+use std::collections::{HashMap, HashSet};
+
+pub const BACKGROUND_COLOR: &str = "rgb(220,220,220)";
+
+// Cells that are not in clonotypes are given group index usize::MAX.  The argument
+// highlight_groups consists of one-based group numbers.
+
+pub fn embed_cells(
+    emb: &[HashMap<String, (f64, f64)>],
+    name: &str,
+    highlight_groups: &[usize],
+    ngroups: usize,
+    center: &mut Vec<(f64, f64)>,
+    radius: &mut Vec<f64>,
+    color: &mut Vec<String>,
+    barcodes: &mut Vec<(usize, String)>,
+    group_index2: &mut Vec<usize>,
+    clonotype_index2: &mut Vec<usize>,
+    highlight: &mut Vec<bool>,
+) -> Result<(), String> {
+    for g in highlight_groups.iter() {
+        if *g == 0 || *g > ngroups {
+            return Err(format!(
+                "\nThe clonotype {} in HONEY=...,highlight=... does not exist, as the clonotypes \
+                are numbered from 1 to {}.\n",
+                g, ngroups
+            ));
+        }
+    }
+
+    // Gather the cells as (layer, coordinates, color, barcode, group index, clonotype index),
+    // where the layer is 0 for other cells, 1 for clonotype cells and 2 for highlighted cells.
+
+    let mut cells = Vec::<(usize, (f64, f64), String, (usize, String), usize, usize)>::new();
+    let mut vdj = HashSet::<(usize, String)>::new();
+    for i in 0..center.len() {
+        vdj.insert(barcodes[i].clone());
+        let (li, bc) = &barcodes[i];
+        if *li >= emb.len() || !emb[*li].contains_key(bc) {
+            continue;
+        }
+        let mut layer = 1;
+        if highlight_groups.contains(&(group_index2[i] + 1)) {
+            layer = 2;
+        }
+        cells.push((
+            layer,
+            emb[*li][bc],
+            color[i].clone(),
+            barcodes[i].clone(),
+            group_index2[i],
+            clonotype_index2[i],
+        ));
+    }
+    if cells.is_empty() {
+        return Err(format!(
+            "\nNone of the cells in clonotypes have {} coordinates, so the plot requested by \
+            HONEY=...,embed=... cannot be made.\n",
+            name
+        ));
+    }
+    let mut others = Vec::<(usize, String)>::new();
+    for li in 0..emb.len() {
+        for bc in emb[li].keys() {
+            if !vdj.contains(&(li, bc.clone())) {
+                others.push((li, bc.clone()));
+            }
+        }
+    }
+    others.sort();
+    for x in others.into_iter() {
+        let p = emb[x.0][&x.1];
+        cells.push((
+            0,
+            p,
+            BACKGROUND_COLOR.to_string(),
+            x,
+            usize::MAX,
+            usize::MAX,
+        ));
+    }
+    cells.sort_by_key(|c| c.0);
+
+    // Size the circles relative to the extent of the embedding.  The y coordinates are negated
+    // because svg coordinates increase downward.
+
+    let (mut xmin, mut xmax) = (f64::MAX, f64::MIN);
+    let (mut ymin, mut ymax) = (f64::MAX, f64::MIN);
+    for c in cells.iter() {
+        xmin = xmin.min(c.1 .0);
+        xmax = xmax.max(c.1 .0);
+        ymin = ymin.min(c.1 .1);
+        ymax = ymax.max(c.1 .1);
+    }
+    let mut r = (xmax - xmin).max(ymax - ymin) / 250.0;
+    if r <= 0.0 {
+        r = 1.0;
+    }
+    center.clear();
+    radius.clear();
+    color.clear();
+    barcodes.clear();
+    group_index2.clear();
+    clonotype_index2.clear();
+    highlight.clear();
+    for (layer, p, col, bc, g, c) in cells.into_iter() {
+        center.push((p.0, -p.1));
+        radius.push([0.6 * r, r, 1.8 * r][layer]);
+        color.push(col);
+        barcodes.push(bc);
+        group_index2.push(g);
+        clonotype_index2.push(c);
+        highlight.push(layer == 2);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embed_cells() {
+        let mut emb = vec![HashMap::new(); 2];
+        emb[0].insert("A-1".to_string(), (0.0, 0.0));
+        emb[0].insert("B-1".to_string(), (10.0, 5.0));
+        emb[1].insert("A-1".to_string(), (5.0, -5.0));
+        let mut center = vec![(0.0, 0.0); 3];
+        let mut radius = vec![1.0; 3];
+        let mut color = vec!["red".to_string(), "blue".to_string(), "green".to_string()];
+        let mut barcodes = vec![
+            (1, "A-1".to_string()),
+            (0, "B-1".to_string()),
+            (0, "C-1".to_string()),
+        ];
+        let (mut group_index2, mut clonotype_index2) = (vec![0, 1, 1], vec![0, 0, 0]);
+        let mut highlight = Vec::<bool>::new();
+        embed_cells(
+            &emb,
+            "UMAP",
+            &[1],
+            2,
+            &mut center,
+            &mut radius,
+            &mut color,
+            &mut barcodes,
+            &mut group_index2,
+            &mut clonotype_index2,
+            &mut highlight,
+        )
+        .unwrap();
+
+        // C-1 is dropped, the cell A-1 of the first dataset is drawn underneath, and the
+        // highlighted cell is drawn last.
+
+        assert_eq!(color, vec![BACKGROUND_COLOR, "blue", "red"]);
+        assert_eq!(center[2], (5.0, 5.0));
+        assert_eq!(highlight, vec![false, false, true]);
+        assert_eq!(group_index2[0], usize::MAX);
+        assert!(radius[0] < radius[1] && radius[1] < radius[2]);
+    }
+}
@@ -7,6 +7,49 @@ use std::io::Write;
 use tables::*;
 use vector_utils::*;
 
+// Determine if a barcode fate corresponds to a filter that was applied.  Fates are also
+// recorded for filters that were turned off.
+
+pub fn fate_applied(ctl: &EncloneControl, f: &str) -> bool {
+    if f.contains(" GEX ") && ctl.clono_filt_opt_def.ngex {
+        return false;
+    }
+    if f.contains(" CROSS ") && ctl.clono_filt_opt_def.ncross {
+        return false;
+    }
+    if f.contains(" UMI ") && !ctl.clono_filt_opt_def.umi_filt {
+        return false;
+    }
+    if f.contains(" UMI_RATIO ") && !ctl.clono_filt_opt_def.umi_ratio_filt {
+        return false;
+    }
+    if f.contains(" GRAPH_FILTER ") && ctl.gen_opt.ngraph_filter {
+        return false;
+    }
+    if f.contains(" QUAL") && !ctl.clono_filt_opt.qual_filter {
+        return false;
+    }
+    if f.contains(" WEAK_CHAINS ") && !ctl.clono_filt_opt_def.weak_chains {
+        return false;
+    }
+    if f.contains(" DOUBLET ") && !ctl.clono_filt_opt_def.doublet {
+        return false;
+    }
+    if f.contains(" FOURSIE_KILL ") && !ctl.clono_filt_opt_def.weak_foursies {
+        return false;
+    }
+    if f.contains(" WHITEF ") && ctl.gen_opt.nwhitef {
+        return false;
+    }
+    if f.contains(" BC_DUP ") && !ctl.clono_filt_opt_def.bc_dup {
+        return false;
+    }
+    if f.contains(" IMPROPER ") && ctl.merge_all_impropers {
+        return false;
+    }
+    true
+}
+
 pub fn print_fate(ctl: &EncloneControl, fate: &Vec<HashMap<String, String>>, logx: &mut Vec<u8>) {
     // Print barcode fate.
 
@@ -14,37 +57,7 @@ pub fn print_fate(ctl: &EncloneControl, fate: &Vec<HashMap<String, String>>, log
     let mut fates = Vec::<String>::new();
     for i in 0..fate.len() {
         for f in fate[i].iter() {
-            if f.1.contains(" GEX ") && ctl.clono_filt_opt_def.ngex {
-                continue;
-            }
-            if f.1.contains(" CROSS ") && ctl.clono_filt_opt_def.ncross {
-                continue;
-            }
-            if f.1.contains(" UMI ") && !ctl.clono_filt_opt_def.umi_filt {
-                continue;
-            }
-            if f.1.contains(" UMI_RATIO ") && !ctl.clono_filt_opt_def.umi_ratio_filt {
-                continue;
-            }
-            if f.1.contains(" GRAPH_FILTER ") && ctl.gen_opt.ngraph_filter {
-                continue;
-            }
-            if f.1.contains(" QUAL") && !ctl.clono_filt_opt.qual_filter {
-                continue;
-            }
-            if f.1.contains(" WEAK_CHAINS ") && !ctl.clono_filt_opt_def.weak_chains {
-                continue;
-            }
-            if f.1.contains(" FOURSIE_KILL ") && !ctl.clono_filt_opt_def.weak_foursies {
-                continue;
-            }
-            if f.1.contains(" WHITEF ") && ctl.gen_opt.nwhitef {
-                continue;
-            }
-            if f.1.contains(" BC_DUP ") && !ctl.clono_filt_opt_def.bc_dup {
-                continue;
-            }
-            if f.1.contains(" IMPROPER ") && ctl.merge_all_impropers {
+            if !fate_applied(ctl, f.1) {
                 continue;
             }
             fates.push(f.1.clone());
@@ -5,9 +5,11 @@
 //
 // To keep compilation time down, this crate should not reach into the enclone crate.
 
+use crate::airr::{generate_airr_output, AIRR_CELL_FIELDS, AIRR_FIELDS};
 use crate::align_n::align_n;
 use crate::clustal::print_clustal;
 use crate::fasta::generate_fasta;
+use crate::lineage::print_lineage;
 use crate::parseable::generate_parseable_output;
 use crate::phylip::print_phylip;
 use crate::plot::plot_clonotypes;
@@ -23,7 +25,7 @@ use ansi_escape::ansi_to_html::{
 use ansi_escape::{emit_bold_escape, emit_eight_bit_color_escape, emit_end_escape};
 use enclone_base::print_tools::font_face_in_css;
 use enclone_core::combine_group_pics::combine_group_pics;
-use enclone_core::defs::{ColInfo, EncloneControl, ExactClonotype, GexInfo};
+use enclone_core::defs::{ColInfo, EncloneControl, ExactClonotype, GexInfo, POUT_MEMORY};
 use enclone_core::mammalian_fixed_len::mammalian_fixed_len_peer_groups;
 use enclone_core::set_speakers::set_speakers;
 use enclone_core::version_string;
@@ -34,7 +36,7 @@ use std::cmp::max;
 use std::collections::HashMap;
 use std::env;
 use std::fs::File;
-use std::io::{stdout, Write};
+use std::io::{sink, stdout, BufWriter, Write};
 use std::path::Path;
 use std::time::Instant;
 use string_utils::{stringme, strme, TextUtils};
@@ -85,6 +87,7 @@ pub fn group_and_print_clonotypes(
         "" => Box::new(stdout()) as Box<Write>,
         "stdout" => Box::new(stdout()) as Box<Write>,
         "stdouth" => Box::new(stdout()) as Box<Write>,
+        POUT_MEMORY => Box::new(sink()) as Box<Write>,
         _ => {
             let path = Path::new(&ctl.parseable_opt.pout);
             Box::new(File::create(&path).unwrap()) as Box<Write>
@@ -114,6 +117,7 @@ pub fn group_and_print_clonotypes(
     if !ctl.parseable_opt.pout.is_empty()
         && ctl.parseable_opt.pout != *"stdout"
         && ctl.parseable_opt.pout != *"stdouth"
+        && ctl.parseable_opt.pout != POUT_MEMORY
         && !ctl.parseable_opt.pno_header
     {
         fwriteln!(pout, "{}", pcols_show.iter().format(","));
@@ -140,6 +144,33 @@ pub fn group_and_print_clonotypes(
         }
     };
 
+    // Set up for AIRR output.
+
+    #[allow(bare_trait_objects)]
+    let mut aout = match ctl.gen_opt.airr_filename.as_str() {
+        "" => Box::new(stdout()) as Box<Write>,
+        "stdout" => Box::new(stdout()) as Box<Write>,
+        _ => {
+            let path = Path::new(&ctl.gen_opt.airr_filename);
+            Box::new(File::create(&path).unwrap()) as Box<Write>
+        }
+    };
+    if !ctl.gen_opt.airr_filename.is_empty() && ctl.gen_opt.airr_filename != *"stdout" {
+        fwriteln!(aout, "{}", AIRR_FIELDS.iter().format("\t"));
+    }
+    #[allow(bare_trait_objects)]
+    let mut acout = match ctl.gen_opt.airr_cell_filename.as_str() {
+        "" => Box::new(stdout()) as Box<Write>,
+        "stdout" => Box::new(stdout()) as Box<Write>,
+        _ => {
+            let path = Path::new(&ctl.gen_opt.airr_cell_filename);
+            Box::new(File::create(&path).unwrap()) as Box<Write>
+        }
+    };
+    if !ctl.gen_opt.airr_cell_filename.is_empty() && ctl.gen_opt.airr_cell_filename != *"stdout" {
+        fwriteln!(acout, "{}", AIRR_CELL_FIELDS.iter().format("\t"));
+    }
+
     // Set up for clustal output.
 
     let (mut clustal_aa, mut clustal_dna) = (None, None);
@@ -164,6 +195,18 @@ pub fn group_and_print_clonotypes(
         phylip_dna = Some(Builder::new(file));
     }
 
+    // Set up for lineage tree output.
+
+    let (mut lineage_newick, mut lineage_json) = (None, None);
+    if !ctl.gen_opt.lineage_newick.is_empty() && ctl.gen_opt.lineage_newick != *"stdout" {
+        let file = File::create(&ctl.gen_opt.lineage_newick).unwrap();
+        lineage_newick = Some(BufWriter::new(file));
+    }
+    if !ctl.gen_opt.lineage_json.is_empty() && ctl.gen_opt.lineage_json != *"stdout" {
+        let file = File::create(&ctl.gen_opt.lineage_json).unwrap();
+        lineage_json = Some(BufWriter::new(file));
+    }
+
     // Set up for peer group output.
 
     #[allow(bare_trait_objects)]
@@ -215,6 +258,12 @@ pub fn group_and_print_clonotypes(
             fwriteln!(logx, "");
         }
     }
+    if ctl.gen_opt.airr_filename == *"stdout" {
+        fwriteln!(logx, "{}", AIRR_FIELDS.iter().format("\t"));
+    }
+    if ctl.gen_opt.airr_cell_filename == *"stdout" {
+        fwriteln!(logx, "{}", AIRR_CELL_FIELDS.iter().format("\t"));
+    }
 
     // Parallized precompute for ALIGN<n>.
 
@@ -518,6 +567,23 @@ pub fn group_and_print_clonotypes(
                 &mut glog,
             );
 
+            // Generate lineage tree output (options LINEAGE_NEWICK and LINEAGE_JSON).
+
+            print_lineage(
+                i,
+                j,
+                oo,
+                exacts,
+                rsi,
+                exact_clonotypes,
+                ctl,
+                refdata,
+                dref,
+                &mut glog,
+                &mut lineage_newick,
+                &mut lineage_json,
+            );
+
             // Generate peer group output.
 
             if !ctl.gen_opt.peer_group_filename.is_empty() {
@@ -622,6 +688,23 @@ pub fn group_and_print_clonotypes(
                 &mut faaout,
             );
 
+            // Generate AIRR output.
+
+            generate_airr_output(
+                i,
+                j,
+                oo,
+                exacts,
+                rsi,
+                exact_clonotypes,
+                ctl,
+                refdata,
+                dref,
+                &mut glog,
+                &mut aout,
+                &mut acout,
+            );
+
             // Generate parseable output.
 
             generate_parseable_output(
@@ -815,6 +898,7 @@ pub fn group_and_print_clonotypes(
         exacts,
         exact_clonotypes,
         out_datas,
+        gex_info,
         groups,
         &mut svg,
     )?;
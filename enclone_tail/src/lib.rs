@@ -1,14 +1,19 @@
 // Copyright (c) 2021 10x Genomics, Inc. All rights reserved.
 
+pub mod airr;
 pub mod align_n;
 pub mod alluvial_fb;
+pub mod antigen;
 pub mod assign_cell_color;
 pub mod cat_var;
 pub mod circles_to_svg;
 pub mod clustal;
 pub mod colors;
 pub mod convert_svg_to_png;
+pub mod diff_exp;
 pub mod display_tree;
+pub mod diversity;
+pub mod embed;
 pub mod fasta;
 pub mod fate;
 pub mod group;
@@ -17,10 +22,13 @@ pub mod grouper;
 pub mod hex;
 pub mod keeper_group;
 pub mod legend;
+pub mod lineage;
 pub mod neighbor;
 pub mod newick;
+pub mod novel_alleles;
 pub mod pack_circles;
 pub mod parseable;
+pub mod pdb_match;
 pub mod phylip;
 pub mod plot;
 pub mod plot_points;
@@ -28,8 +36,11 @@ pub mod plot_utils;
 pub mod polygon;
 pub mod print_dataset_vars;
 pub mod print_stats;
+pub mod public;
+pub mod qc_report;
 pub mod requirements;
 pub mod sens_spec;
+pub mod shm;
 pub mod sim_mat_plot;
 pub mod string_width;
 pub mod tail;
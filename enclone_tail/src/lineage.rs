@@ -0,0 +1,415 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Generate lineage trees (options LINEAGE_NEWICK and LINEAGE_JSON).
+//
+// For each clonotype, we build a tree whose root is the germline and whose leaves are the exact
+// subclonotypes.  Each exact subclonotype is represented by the concatenation of the V..J
+// nucleotide sequences of its chains (N if a chain is missing).  The germline is the donor
+// reference away from the junction, and is unknown (N) within it.
+//
+// The tree is a maximum parsimony tree, found heuristically by stepwise addition: exact
+// subclonotypes are added in order of increasing distance from the germline, and each is attached
+// to the branch that minimizes the increase in parsimony score.  Ancestral sequences are then
+// inferred using the Fitch algorithm, with the root fixed to the germline where it is known.
+// Finally, internal nodes that are identical to their parent are removed, and an internal node
+// that is identical to one of its leaves is replaced by that leaf, so that the corresponding exact
+// subclonotype appears as an ancestor.
+
+use crate::newick::newick;
+use enclone_core::defs::{ColInfo, EncloneControl, ExactClonotype};
+use enclone_proto::types::DonorReferenceItem;
+use io_utils::fwriteln;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use string_utils::strme;
+use vdj_ann::refx::RefData;
+
+// Represent a base as the set of states it allows.
+
+fn mask(b: u8) -> u8 {
+    match b {
+        b'A' => 1,
+        b'C' => 2,
+        b'G' => 4,
+        b'T' => 8,
+        b'-' => 16,
+        _ => 31,
+    }
+}
+
+fn first_base(m: u8) -> u8 {
+    for (k, b) in b"ACGT-".iter().enumerate() {
+        if m & (1 << k) != 0 {
+            return *b;
+        }
+    }
+    b'N'
+}
+
+// Number of positions at which two sequences are known to differ.
+
+fn diffs(s1: &[u8], s2: &[u8]) -> usize {
+    let mut d = 0;
+    for p in 0..s1.len() {
+        if mask(s1[p]) & mask(s2[p]) == 0 {
+            d += 1;
+        }
+    }
+    d
+}
+
+// Nodes in top-down order.
+
+fn top_down(parent: &[Option<usize>]) -> Vec<usize> {
+    let mut children = vec![Vec::<usize>::new(); parent.len()];
+    for v in 0..parent.len() {
+        if let Some(p) = parent[v] {
+            children[p].push(v);
+        }
+    }
+    let mut order = vec![0];
+    let mut i = 0;
+    while i < order.len() {
+        order.extend(children[order[i]].iter());
+        i += 1;
+    }
+    order
+}
+
+// Build a tree, given the germline and the leaf sequences.  Node 0 is the root, nodes 1..=n are
+// the leaves, and nodes after that are internal.  Return the sequence and parent of each node.
+// Nodes that have been removed have no parent.
+
+pub fn lineage_tree(germ: &[u8], leaves: &[Vec<u8>]) -> (Vec<Vec<u8>>, Vec<Option<usize>>) {
+    let n = leaves.len();
+    let len = germ.len();
+    let mut seqs = vec![germ.to_vec()];
+    seqs.extend(leaves.iter().cloned());
+    let mut parent = vec![None; n + 1];
+
+    // Add leaves, in order of increasing distance from the germline.  Attaching leaf x to the
+    // edge from u to v creates an internal node whose sequence is the per-position majority of
+    // u, v and x.
+
+    let mut order = Vec::<(usize, usize)>::new();
+    for v in 1..=n {
+        order.push((diffs(germ, &seqs[v]), v));
+    }
+    order.sort_unstable();
+    let mut placed = Vec::<usize>::new();
+    for (_, x) in order.into_iter() {
+        if placed.is_empty() {
+            parent[x] = Some(0);
+            placed.push(x);
+            continue;
+        }
+        let (mut best, mut best_cost) = (0, usize::MAX);
+        for v in placed.iter() {
+            let (s1, s2, s3) = (&seqs[parent[*v].unwrap()], &seqs[*v], &seqs[x]);
+            let mut cost = 0;
+            for p in 0..len {
+                if mask(s3[p]) & (mask(s1[p]) | mask(s2[p])) == 0 {
+                    cost += 1;
+                }
+            }
+            if cost < best_cost {
+                best = *v;
+                best_cost = cost;
+            }
+        }
+        let u = parent[best].unwrap();
+        let mut mid = Vec::<u8>::with_capacity(len);
+        for p in 0..len {
+            let c = [seqs[u][p], seqs[best][p], seqs[x][p]];
+            let (mut b, mut count) = (b'N', 0);
+            for y in c.iter() {
+                let k = c.iter().filter(|z| *z == y).count();
+                if *y != b'N' && k > count {
+                    b = *y;
+                    count = k;
+                }
+            }
+            mid.push(b);
+        }
+        let w = seqs.len();
+        seqs.push(mid);
+        parent.push(Some(u));
+        parent[best] = Some(w);
+        parent[x] = Some(w);
+        placed.push(w);
+        placed.push(x);
+    }
+
+    // Infer ancestral sequences.  First compute state sets bottom up, then assign states top
+    // down, preferring the state of the parent.
+
+    let order = top_down(&parent);
+    let mut children = vec![Vec::<usize>::new(); seqs.len()];
+    for v in 1..seqs.len() {
+        children[parent[v].unwrap()].push(v);
+    }
+    let mut sets = vec![Vec::<u8>::new(); seqs.len()];
+    for v in order.iter().rev() {
+        let v = *v;
+        if v >= 1 && v <= n {
+            sets[v] = seqs[v].iter().map(|b| mask(*b)).collect();
+            continue;
+        }
+        let mut set = vec![31_u8; len];
+        for p in 0..len {
+            let mut count = [0; 5];
+            for c in children[v].iter() {
+                for k in 0..5 {
+                    if sets[*c][p] & (1 << k) != 0 {
+                        count[k] += 1;
+                    }
+                }
+            }
+            let max = *count.iter().max().unwrap();
+            if max > 0 {
+                set[p] = 0;
+                for k in 0..5 {
+                    if count[k] == max {
+                        set[p] |= 1 << k;
+                    }
+                }
+            }
+        }
+        sets[v] = set;
+    }
+    for p in 0..len {
+        if germ[p] == b'N' {
+            seqs[0][p] = first_base(sets[0][p]);
+        }
+    }
+    for v in order.iter() {
+        let v = *v;
+        if v > n {
+            let u = parent[v].unwrap();
+            for p in 0..len {
+                let b = seqs[u][p];
+                seqs[v][p] = if b != b'N' && sets[v][p] & mask(b) != 0 {
+                    b
+                } else {
+                    first_base(sets[v][p])
+                };
+            }
+        }
+    }
+
+    // Remove internal nodes that are identical to their parent.
+
+    for v in top_down(&parent).into_iter() {
+        if v > n && seqs[v] == seqs[parent[v].unwrap()] {
+            let u = parent[v];
+            for c in 1..seqs.len() {
+                if parent[c] == Some(v) {
+                    parent[c] = u;
+                }
+            }
+            parent[v] = None;
+        }
+    }
+
+    // Replace internal nodes that are identical to one of their leaves by that leaf.
+
+    for v in top_down(&parent).into_iter() {
+        if v > n {
+            let children = (1..seqs.len())
+                .filter(|c| parent[*c] == Some(v))
+                .collect::<Vec<usize>>();
+            for x in children.iter() {
+                if *x <= n && diffs(&seqs[v], &seqs[*x]) == 0 {
+                    parent[*x] = parent[v];
+                    for c in children.iter() {
+                        if c != x {
+                            parent[*c] = Some(*x);
+                        }
+                    }
+                    parent[v] = None;
+                    break;
+                }
+            }
+        }
+    }
+    (seqs, parent)
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+pub fn print_lineage(
+    i: usize,
+    j: usize,
+    oo: usize,
+    exacts: &Vec<Vec<usize>>,
+    rsi: &Vec<ColInfo>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    ctl: &EncloneControl,
+    refdata: &RefData,
+    dref: &Vec<DonorReferenceItem>,
+    logx: &mut Vec<u8>,
+    lineage_newick: &mut Option<BufWriter<File>>,
+    lineage_json: &mut Option<BufWriter<File>>,
+) {
+    if ctl.gen_opt.lineage_newick.is_empty() && ctl.gen_opt.lineage_json.is_empty() {
+        return;
+    }
+
+    // Define the germline and leaf sequences.
+
+    let rsi = &rsi[oo];
+    let n = exacts[oo].len();
+    let cols = rsi.mat.len();
+    let mut starts = Vec::<usize>::new();
+    let mut germ = Vec::<u8>::new();
+    let mut leaves = vec![Vec::<u8>::new(); n];
+    let mut chain_types = Vec::<String>::new();
+    for m in 0..cols {
+        starts.push(germ.len());
+        let z = rsi.seq_del_lens[m];
+        let mut vref = refdata.refs[rsi.vids[m]].to_ascii_vec();
+        if let Some(d) = rsi.vpids[m] {
+            vref = dref[d].nt_sequence.clone();
+        }
+        let jref = refdata.refs[rsi.jids[m]].to_ascii_vec();
+        let vlen = vref.len().saturating_sub(ctl.heur.ref_v_trim);
+        let jlen = jref.len().saturating_sub(ctl.heur.ref_j_trim);
+        for p in 0..z {
+            if p < vlen {
+                germ.push(vref[p]);
+            } else if p + jlen >= z && z - p <= jref.len() {
+                germ.push(jref[jref.len() - (z - p)]);
+            } else {
+                germ.push(b'N');
+            }
+        }
+        let mut chain_type = String::new();
+        for k in 0..n {
+            match rsi.mat[m][k] {
+                Some(r) => {
+                    let x = &exact_clonotypes[exacts[oo][k]].share[r];
+                    leaves[k].extend(x.seq_del_amino.iter());
+                    if chain_type.is_empty() {
+                        chain_type = x.chain_type.clone();
+                    }
+                }
+                None => leaves[k].extend(vec![b'N'; z]),
+            }
+        }
+        chain_types.push(chain_type);
+    }
+
+    // Build the tree and name its nodes.
+
+    let (seqs, parent) = lineage_tree(&germ, &leaves);
+    let order = top_down(&parent);
+    let mut id = vec![0; seqs.len()];
+    let mut names = Vec::<String>::new();
+    let mut nodes = Vec::<Value>::new();
+    let mut ninternal = 0;
+    for (k, v) in order.iter().enumerate() {
+        id[*v] = k;
+        let name = if *v == 0 {
+            "germline".to_string()
+        } else if *v <= n {
+            format!("{}", v)
+        } else {
+            ninternal += 1;
+            format!("I{}", ninternal)
+        };
+        let mut chains = Vec::<String>::new();
+        for m in 0..cols {
+            let start = starts[m];
+            chains.push(strme(&seqs[*v][start..start + rsi.seq_del_lens[m]]).to_string());
+        }
+        let (mut exact, mut ncells) = (Value::Null, 0);
+        if *v >= 1 && *v <= n {
+            exact = json!(v);
+            ncells = exact_clonotypes[exacts[oo][v - 1]].ncells();
+        }
+        nodes.push(json!({
+            "name": name,
+            "exact_subclonotype": exact,
+            "ncells": ncells,
+            "seqs": chains,
+        }));
+        names.push(name);
+    }
+
+    // Find the mutations on each edge.
+
+    let mut edges = Vec::<(usize, usize, String)>::new();
+    let mut jedges = Vec::<Value>::new();
+    for v in order.iter().skip(1) {
+        let u = parent[*v].unwrap();
+        let mut muts = Vec::<String>::new();
+        for m in 0..cols {
+            for p in starts[m]..starts[m] + rsi.seq_del_lens[m] {
+                let (b1, b2) = (seqs[u][p], seqs[*v][p]);
+                if b1 != b'N' && b2 != b'N' && b1 != b2 {
+                    muts.push(format!(
+                        "{}:{}{}{}",
+                        m + 1,
+                        b1 as char,
+                        p - starts[m] + 1,
+                        b2 as char
+                    ));
+                }
+            }
+        }
+        edges.push((id[u], id[*v], format!("{}", muts.len())));
+        jedges.push(json!({
+            "parent": names[id[u]],
+            "child": names[id[*v]],
+            "mutations": muts,
+        }));
+    }
+
+    // Generate output.
+
+    if !ctl.gen_opt.lineage_newick.is_empty() {
+        let nw = newick(&names, 0, &edges);
+        if ctl.gen_opt.lineage_newick == "stdout" {
+            fwriteln!(logx, "\n{}", nw);
+        } else {
+            fwriteln!(lineage_newick.as_mut().unwrap(), "{}", nw);
+        }
+    }
+    if !ctl.gen_opt.lineage_json.is_empty() {
+        let tree = json!({
+            "group_id": i + 1,
+            "clonotype_id": j + 1,
+            "chains": chain_types,
+            "nodes": nodes,
+            "edges": jedges,
+        });
+        if ctl.gen_opt.lineage_json == "stdout" {
+            fwriteln!(logx, "\n{}", tree);
+        } else {
+            fwriteln!(lineage_json.as_mut().unwrap(), "{}", tree);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_lineage_tree() {
+        let germ = b"AAANNAAA".to_vec();
+        let leaves = vec![
+            b"AAACCAAA".to_vec(),
+            b"TAACCAAA".to_vec(),
+            b"TAACCAAG".to_vec(),
+        ];
+        let (seqs, parent) = lineage_tree(&germ, &leaves);
+        assert_eq!(seqs[0], b"AAACCAAA".to_vec());
+        assert_eq!(parent[1], Some(0));
+        assert_eq!(parent[2], Some(0));
+        assert_eq!(parent[3], Some(2));
+        for v in 4..seqs.len() {
+            assert_eq!(parent[v], None);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Report novel V alleles for each donor (options NOVEL_ALLELES and NOVEL_ALLELES_FASTA).  These
+// are the donor reference sequences, either inferred by enclone or injected using DONOR_ALLELES,
+// that are not identical to any reference V segment.
+//
+// For each allele, we find the nearest reference V segment of the same length, preferring the
+// segment the allele was derived from, and list the positions at which the allele differs from
+// it.  Support is given by the clonotypes that pass filters and have a chain assigned to the
+// allele.  A supporting clonotype is unmutated if on such a chain, the V segment agrees exactly
+// with the allele, excluding its last bases, which are not inferred.  The confidence score is
+// 1 - 2^-u, where u is the number of unmutated supporting clonotypes: independent rearrangements
+// are unlikely to share the same somatic mutations and have no others.
+
+use enclone_core::defs::{EncloneControl, ExactClonotype};
+use enclone_proto::types::DonorReferenceItem;
+use io_utils::{fwriteln, open_for_write_new};
+use itertools::Itertools;
+use std::cmp::min;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use tables::print_tabular_vbox;
+use vdj_ann::refx::RefData;
+use vector_utils::unique_sort;
+
+pub const NOVEL_ALLELE_FIELDS: [&str; 9] = [
+    "donor",
+    "gene",
+    "allele",
+    "nearest_ref",
+    "differences",
+    "clonotypes",
+    "cells",
+    "unmutated",
+    "confidence",
+];
+
+#[derive(Default)]
+struct NovelAllele {
+    m: usize,       // index in dref
+    number: usize,  // allele number for the donor and gene, as in DONOR_REF_FILE
+    nearest: usize, // nearest reference V segment
+    diffs: Vec<String>,
+    clonotypes: usize,
+    cells: usize,
+    unmutated: usize,
+}
+
+// Find the positions at which an allele differs from a reference sequence, formatted e.g. as
+// G123A, meaning that the reference base G at one-based position 123 is A in the allele.
+
+pub fn allele_diffs(allele: &[u8], reference: &[u8]) -> Vec<String> {
+    let mut diffs = Vec::<String>::new();
+    for p in 0..min(allele.len(), reference.len()) {
+        if allele[p] != reference[p] {
+            diffs.push(format!(
+                "{}{}{}",
+                reference[p] as char,
+                p + 1,
+                allele[p] as char
+            ));
+        }
+    }
+    diffs
+}
+
+pub fn allele_confidence(unmutated: usize) -> f64 {
+    1.0 - 0.5_f64.powi(unmutated as i32)
+}
+
+pub fn novel_alleles(
+    exacts: &Vec<Vec<usize>>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    refdata: &RefData,
+    dref: &Vec<DonorReferenceItem>,
+    ctl: &EncloneControl,
+) {
+    // Find the novel alleles.
+
+    let mut vrefs = vec![Vec::<u8>::new(); refdata.refs.len()];
+    for id in 0..refdata.refs.len() {
+        if refdata.is_v(id) {
+            vrefs[id] = refdata.refs[id].to_ascii_vec();
+        }
+    }
+    let mut alleles = Vec::<NovelAllele>::new();
+    let mut to_allele = vec![None; dref.len()];
+    let mut number = 0;
+    for m in 0..dref.len() {
+        let (donor, id) = (dref[m].donor_idx, dref[m].universal_idx as usize);
+        if m > 0 && (donor != dref[m - 1].donor_idx || id != dref[m - 1].universal_idx as usize) {
+            number = 0;
+        }
+        number += 1;
+        let seq = &dref[m].nt_sequence;
+        let mut nearest = (allele_diffs(seq, &vrefs[id]).len(), id);
+        for j in 0..vrefs.len() {
+            if vrefs[j].len() == seq.len() && j != id {
+                let d = allele_diffs(seq, &vrefs[j]).len();
+                if d < nearest.0 {
+                    nearest = (d, j);
+                }
+            }
+        }
+        if nearest.0 == 0 {
+            continue;
+        }
+        to_allele[m] = Some(alleles.len());
+        alleles.push(NovelAllele {
+            m,
+            number,
+            nearest: nearest.1,
+            diffs: allele_diffs(seq, &vrefs[nearest.1]),
+            ..Default::default()
+        });
+    }
+
+    // Find the supporting clonotypes.
+
+    for i in 0..exacts.len() {
+        let (mut used, mut unmutated) = (Vec::<usize>::new(), Vec::<usize>::new());
+        for u in exacts[i].iter() {
+            let ex = &exact_clonotypes[*u];
+            let mut ex_used = Vec::<usize>::new();
+            for x in ex.share.iter() {
+                let m = match x.v_ref_id_donor {
+                    Some(m) => m,
+                    None => continue,
+                };
+                if let Some(a) = to_allele[m] {
+                    ex_used.push(a);
+                    let seq = &dref[m].nt_sequence;
+                    let n = seq.len().saturating_sub(ctl.heur.ref_v_trim);
+                    if x.seq_del.len() >= n && x.seq_del[..n] == seq[..n] {
+                        unmutated.push(a);
+                    }
+                }
+            }
+            unique_sort(&mut ex_used);
+            for a in ex_used.iter() {
+                alleles[*a].cells += ex.ncells();
+            }
+            used.append(&mut ex_used);
+        }
+        unique_sort(&mut used);
+        unique_sort(&mut unmutated);
+        for a in used.iter() {
+            alleles[*a].clonotypes += 1;
+        }
+        for a in unmutated.iter() {
+            alleles[*a].unmutated += 1;
+        }
+    }
+
+    // Print the report.
+
+    if ctl.gen_opt.novel_alleles {
+        println!("\nNOVEL ALLELES\n");
+        if alleles.is_empty() {
+            println!("no novel alleles were found");
+        } else {
+            let mut rows = vec![NOVEL_ALLELE_FIELDS
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()];
+            rows.push(vec!["\\hline".to_string(); NOVEL_ALLELE_FIELDS.len()]);
+            for x in alleles.iter() {
+                let d = &dref[x.m];
+                rows.push(vec![
+                    ctl.origin_info.donor_list[d.donor_idx as usize].clone(),
+                    refdata.name[d.universal_idx as usize].clone(),
+                    format!("{}", x.number),
+                    refdata.name[x.nearest].clone(),
+                    format!("{}", x.diffs.iter().format(",")),
+                    format!("{}", x.clonotypes),
+                    format!("{}", x.cells),
+                    format!("{}", x.unmutated),
+                    format!("{:.3}", allele_confidence(x.unmutated)),
+                ]);
+            }
+            let mut log = String::new();
+            print_tabular_vbox(
+                &mut log,
+                &rows,
+                2,
+                &b"l|l|r|l|l|r|r|r|r".to_vec(),
+                false,
+                false,
+            );
+            print!("{}", log);
+        }
+    }
+
+    // Generate FASTA output.  The header begins in the same way as for DONOR_REF_FILE, so that
+    // the file can be read using DONOR_ALLELES.
+
+    if !ctl.gen_opt.novel_alleles_fasta.is_empty() {
+        let mut log = Vec::<u8>::new();
+        for x in alleles.iter() {
+            let d = &dref[x.m];
+            let id = d.universal_idx as usize;
+            fwriteln!(
+                log,
+                ">{}:{}:{}:{} nearest={} differences={} clonotypes={} cells={} unmutated={} \
+                confidence={:.3}\n{}",
+                refdata.id[id],
+                ctl.origin_info.donor_list[d.donor_idx as usize],
+                x.number,
+                refdata.name[id],
+                refdata.name[x.nearest],
+                x.diffs.iter().format(","),
+                x.clonotypes,
+                x.cells,
+                x.unmutated,
+                allele_confidence(x.unmutated),
+                String::from_utf8_lossy(&d.nt_sequence)
+            );
+        }
+        if ctl.gen_opt.novel_alleles_fasta == "stdout" {
+            print!("{}", String::from_utf8_lossy(&log));
+        } else {
+            let mut f = open_for_write_new![&ctl.gen_opt.novel_alleles_fasta];
+            f.write_all(&log).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_novel_alleles() {
+        assert_eq!(allele_diffs(b"ACGTAC", b"ACCTAG"), vec!["C3G", "G6C"]);
+        assert!(allele_diffs(b"ACGT", b"ACGT").is_empty());
+        assert_eq!(allele_confidence(0), 0.0);
+        assert!((allele_confidence(2) - 0.75).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,450 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Find known antibody or TCR structures that are similar to clonotypes (options PDB_MATCH and
+// PDB_MATCH_ID).  The structures are gzipped mmCIF files in a directory, named e.g. 7B3O.gz, as
+// produced by download_pdbs.
+//
+// For each clonotype, we use the exact subclonotype having the most cells, and its first heavy
+// (or TRB) chain and first light (or TRA) chain, from the start of FWR1 to the end of J.  Each
+// of these is aligned to each chain in each structure, and they are assigned to distinct
+// structure chains, so as to maximize the number of identical amino acids.  Structures are
+// ranked by paired identity, which is that number, divided by the total length of the clonotype
+// chains.
+//
+// Every structure chain that is not assigned to a clonotype chain is treated as antigen, and the
+// paratope consists of the residues on the assigned chains having an atom within 5 Å of an
+// antigen atom.  These are shown at the aligned clonotype positions, using IMGT numbering.
+//
+// Each structure is parsed once.  Its atoms are then replaced by the list of contacts between
+// residues and other chains, from which the paratope for any assignment of chains is found.
+
+use amino::aa_seq;
+use bio_edit::alignment::pairwise::Aligner;
+use bio_edit::alignment::AlignmentOperation::*;
+use enclone_base::pdb::{read_pdb_structure, PdbStructure};
+use enclone_core::defs::{EncloneControl, ExactClonotype, TigData1};
+use enclone_core::imgt_numbering::imgt_numbering;
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::read_dir;
+use string_utils::TextUtils;
+use tables::print_tabular_vbox;
+use vector_utils::unique_sort;
+
+const TOP_MATCHES: usize = 5;
+const MAX_DIST: f32 = 5.0;
+
+// A clonotype chain, from the start of FWR1 to the end of J.
+
+struct QueryChain {
+    chain_type: String,
+    aa: Vec<u8>,
+    labels: Vec<String>, // IMGT position labels, or #n if not numbered
+    cdrs: [Option<(usize, usize)>; 3], // CDR1, CDR2 and CDR3 ranges on aa
+}
+
+fn query_chain(x: &TigData1) -> QueryChain {
+    let aa_all = aa_seq(&x.seq_del_amino, 0);
+    let f1 = x.fr1_start / 3;
+    let imgt = imgt_numbering(
+        x.fr1_start,
+        x.cdr1_start,
+        x.fr2_start,
+        x.cdr2_start,
+        x.fr3_start,
+        x.cdr3_start,
+        x.cdr3_aa.len(),
+        x.seq_del_amino.len(),
+    );
+    let mut labels = Vec::<String>::new();
+    for k in f1..aa_all.len() {
+        if imgt[k].is_empty() {
+            labels.push(format!("#{}", k - f1 + 1));
+        } else {
+            labels.push(imgt[k].clone());
+        }
+    }
+    let n = aa_all.len() - f1;
+    let range = |a: Option<usize>, b: Option<usize>| match (a, b) {
+        (Some(a), Some(b)) if a / 3 >= f1 && a <= b && b / 3 <= aa_all.len() => {
+            Some((a / 3 - f1, b / 3 - f1))
+        }
+        _ => None,
+    };
+    let c3 = x.cdr3_start / 3;
+    let cdr3 = if c3 >= f1 && c3 - f1 + x.cdr3_aa.len() <= n {
+        Some((c3 - f1, c3 - f1 + x.cdr3_aa.len()))
+    } else {
+        None
+    };
+    QueryChain {
+        chain_type: x.chain_type.clone(),
+        aa: aa_all[f1..].to_vec(),
+        labels,
+        cdrs: [
+            range(x.cdr1_start, x.fr2_start),
+            range(x.cdr2_start, x.fr3_start),
+            cdr3,
+        ],
+    }
+}
+
+// Align a clonotype chain x to a structure chain y, globally on x.  Return the number of
+// identical amino acids, and for each position on x, the aligned position on y, if any.
+
+pub fn align_chain(x: &[u8], y: &[u8]) -> (usize, Vec<Option<usize>>) {
+    let score = |a: u8, b: u8| if a == b { 1i32 } else { -1i32 };
+    let mut aligner = Aligner::new(-6, -1, &score);
+    let al = aligner.semiglobal(x, y);
+    let mut to_pdb = vec![None; x.len()];
+    let (mut matches, mut p1, mut p2) = (0, al.xstart, al.ystart);
+    for op in al.operations.iter() {
+        match op {
+            Match | Subst => {
+                if x[p1] == y[p2] {
+                    matches += 1;
+                }
+                to_pdb[p1] = Some(p2);
+                p1 += 1;
+                p2 += 1;
+            }
+            Del => p2 += 1,
+            Ins => p1 += 1,
+            _ => {}
+        }
+    }
+    (matches, to_pdb)
+}
+
+// Find the contacts between chains: for each chain, the pairs (residue position, other chain)
+// such that an atom of the residue is within MAX_DIST of an atom of the other chain.  Atoms are
+// binned in cubes of side MAX_DIST, so that only atoms in adjacent cubes need be compared.
+
+pub fn chain_contacts(pdb: &PdbStructure) -> Vec<Vec<(usize, usize)>> {
+    let cube = |x: f32| (x / MAX_DIST).floor() as i32;
+    let mut bins = HashMap::<(i32, i32, i32), Vec<usize>>::new();
+    for (i, a) in pdb.atoms.iter().enumerate() {
+        bins.entry((cube(a.x), cube(a.y), cube(a.z)))
+            .or_default()
+            .push(i);
+    }
+    let nchains = pdb
+        .atoms
+        .iter()
+        .map(|a| a.chain as usize + 1)
+        .max()
+        .unwrap_or(0);
+    let mut contacts = vec![Vec::<(usize, usize)>::new(); nchains.max(pdb.chains.len())];
+    for a in pdb.atoms.iter() {
+        let (cx, cy, cz) = (cube(a.x), cube(a.y), cube(a.z));
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(v) = bins.get(&(cx + dx, cy + dy, cz + dz)) {
+                        for j in v.iter() {
+                            let b = &pdb.atoms[*j];
+                            if b.chain == a.chain {
+                                continue;
+                            }
+                            let (ex, ey, ez) = (a.x - b.x, a.y - b.y, a.z - b.z);
+                            if ex * ex + ey * ey + ez * ez <= MAX_DIST * MAX_DIST {
+                                contacts[a.chain as usize]
+                                    .push((a.chain_pos as usize, b.chain as usize));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    for k in 0..contacts.len() {
+        unique_sort(&mut contacts[k]);
+    }
+    contacts
+}
+
+// Find the residues on the given chains that contact a chain not in the list.
+
+pub fn paratope(contacts: &[Vec<(usize, usize)>], chains: &[usize]) -> Vec<Vec<usize>> {
+    let mut p = vec![Vec::<usize>::new(); chains.len()];
+    for (k, c) in chains.iter().enumerate() {
+        if let Some(x) = contacts.get(*c) {
+            for (pos, other) in x.iter() {
+                if !chains.contains(other) {
+                    p[k].push(*pos);
+                }
+            }
+        }
+        unique_sort(&mut p[k]);
+    }
+    p
+}
+
+// A match of a clonotype to a structure.  For each clonotype chain, we record the assigned
+// structure chain, if any, the number of identical amino acids, and the alignment.
+
+struct StructureMatch {
+    s: usize,
+    total: usize,
+    chains: Vec<Option<(usize, usize, Vec<Option<usize>>)>>,
+}
+
+fn match_structure(q: &[QueryChain], s: usize, pdb: &PdbStructure) -> StructureMatch {
+    let n = pdb.chains.len();
+    let mut al = Vec::<Vec<(usize, Vec<Option<usize>>)>>::new();
+    for i in 0..q.len() {
+        al.push(Vec::new());
+        for c in 0..n {
+            al[i].push(align_chain(&q[i].aa, &pdb.chains[c]));
+        }
+    }
+
+    // Try all assignments of clonotype chains to distinct structure chains (or to none).
+
+    let k = q.len();
+    let mut best = (0, vec![None; k]);
+    for z in 0..(n + 1).pow(k as u32) {
+        let mut assign = Vec::<Option<usize>>::new();
+        let mut y = z;
+        for _ in 0..k {
+            let c = y % (n + 1);
+            assign.push(if c < n { Some(c) } else { None });
+            y /= n + 1;
+        }
+        if k == 2 && assign[0].is_some() && assign[0] == assign[1] {
+            continue;
+        }
+        let total = (0..k)
+            .map(|i| assign[i].map_or(0, |c| al[i][c].0))
+            .sum::<usize>();
+        if total > best.0 {
+            best = (total, assign);
+        }
+    }
+    let mut chains = Vec::new();
+    for i in 0..k {
+        chains.push(best.1[i].map(|c| (c, al[i][c].0, al[i][c].1.clone())));
+    }
+    StructureMatch {
+        s,
+        total: best.0,
+        chains,
+    }
+}
+
+pub fn pdb_match(
+    groups: &Vec<Vec<(i32, String)>>,
+    exacts: &Vec<Vec<usize>>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    ctl: &EncloneControl,
+) -> Result<(), String> {
+    // Find the clonotypes to report.
+
+    let mut clonotypes = Vec::<(String, usize)>::new();
+    for i in 0..groups.len() {
+        for j in 0..groups[i].len() {
+            let id = format!("{}.{}", i + 1, j + 1);
+            if ctl.gen_opt.pdb_match_id.is_empty() || ctl.gen_opt.pdb_match_id == id {
+                clonotypes.push((id, groups[i][j].0 as usize));
+            }
+        }
+    }
+    if clonotypes.is_empty() {
+        return Err(format!(
+            "\nThe clonotype {} specified by PDB_MATCH_ID was not found.\n",
+            ctl.gen_opt.pdb_match_id
+        ));
+    }
+
+    // Load the structures, replacing their atoms by the contacts between chains.
+
+    let dir = &ctl.gen_opt.pdb_match;
+    let mut names = Vec::<String>::new();
+    let err =
+        |e: std::io::Error| format!("\nUnable to read the PDB_MATCH directory {}: {}.\n", dir, e);
+    for f in read_dir(dir).map_err(err)? {
+        let f = f.map_err(err)?.file_name().to_string_lossy().to_string();
+        if f.ends_with(".gz") {
+            names.push(f.rev_before(".gz").to_string());
+        }
+    }
+    names.sort();
+    let mut pdbs = vec![None; names.len()];
+    pdbs.par_iter_mut().enumerate().for_each(|(i, res)| {
+        if let Ok(mut pdb) = read_pdb_structure(&names[i], dir) {
+            let contacts = chain_contacts(&pdb);
+            pdb.atoms.clear();
+            *res = Some((pdb, contacts));
+        }
+    });
+    let unparsed = pdbs.iter().filter(|p| p.is_none()).count();
+
+    // Report each clonotype.
+
+    for (id, oo) in clonotypes.iter() {
+        let ex = exacts[*oo]
+            .iter()
+            .max_by_key(|u| exact_clonotypes[**u].ncells())
+            .unwrap();
+        let ex = &exact_clonotypes[*ex];
+        let mut q = Vec::<QueryChain>::new();
+        for left in [true, false] {
+            if let Some(x) = ex.share.iter().find(|x| x.left == left) {
+                q.push(query_chain(x));
+            }
+        }
+        let mut hits = pdbs
+            .par_iter()
+            .enumerate()
+            .filter(|(_, p)| p.is_some())
+            .map(|(s, p)| match_structure(&q, s, &p.as_ref().unwrap().0))
+            .collect::<Vec<StructureMatch>>();
+        hits.sort_by(|a, b| b.total.cmp(&a.total).then(a.s.cmp(&b.s)));
+        hits.truncate(TOP_MATCHES);
+        let qlen = q.iter().map(|x| x.aa.len()).sum::<usize>();
+
+        // Print a table of the matches, followed by their paratopes.
+
+        println!("\nPDB STRUCTURE MATCHES FOR CLONOTYPE [{}]\n", id);
+        if unparsed > 0 {
+            println!(
+                "({} structures could not be parsed and were skipped)\n",
+                unparsed
+            );
+        }
+        let mut rows = vec![[
+            "structure",
+            "identity",
+            "chain",
+            "pdb_chain",
+            "chain_identity",
+            "cdr1",
+            "cdr2",
+            "cdr3",
+            "paratope",
+        ]
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>()];
+        rows.push(vec!["\\hline".to_string(); rows[0].len()]);
+        let mut paratopes = Vec::<String>::new();
+        for h in hits.iter() {
+            let name = &names[h.s];
+            let assigned = h
+                .chains
+                .iter()
+                .filter_map(|x| x.as_ref().map(|x| x.0))
+                .collect::<Vec<usize>>();
+            let (pdb, contacts) = pdbs[h.s].as_ref().unwrap();
+            let contacts = paratope(contacts, &assigned);
+            for i in 0..q.len() {
+                let mut row = vec![String::new(); 2];
+                if i == 0 {
+                    row[0] = name.clone();
+                    row[1] = format!("{:.1}%", 100.0 * h.total as f64 / qlen as f64);
+                }
+                row.push(q[i].chain_type.clone());
+                if h.chains[i].is_none() {
+                    row.append(&mut vec![".".to_string(); 6]);
+                    rows.push(row);
+                    continue;
+                }
+                let (c, matches, to_pdb) = h.chains[i].as_ref().unwrap();
+                let mut cname = format!("{}", c + 1);
+                if *c < pdb.chain_names.len() {
+                    cname = format!("{} {}", c + 1, pdb.chain_names[*c]);
+                }
+                row.push(cname.chars().take(30).collect());
+                row.push(format!(
+                    "{:.1}%",
+                    100.0 * *matches as f64 / q[i].aa.len() as f64
+                ));
+                for r in q[i].cdrs.iter() {
+                    match r {
+                        Some((start, stop)) => {
+                            let same = (*start..*stop)
+                                .filter(|p| {
+                                    to_pdb[*p].map_or(false, |y| pdb.chains[*c][y] == q[i].aa[*p])
+                                })
+                                .count();
+                            row.push(format!("{}/{}", same, stop - start));
+                        }
+                        None => row.push(".".to_string()),
+                    }
+                }
+                let k = assigned.iter().position(|x| x == c).unwrap();
+                let mut res = Vec::<String>::new();
+                for p in 0..q[i].aa.len() {
+                    if let Some(y) = to_pdb[p] {
+                        if contacts[k].contains(&y) {
+                            let mut r = format!("{}{}", q[i].aa[p] as char, q[i].labels[p]);
+                            if pdb.chains[*c][y] != q[i].aa[p] {
+                                r += &format!("({})", pdb.chains[*c][y] as char);
+                            }
+                            res.push(r);
+                        }
+                    }
+                }
+                row.push(format!("{}", res.len()));
+                rows.push(row);
+                if !res.is_empty() {
+                    paratopes.push(format!(
+                        "{} {}: {}",
+                        name,
+                        q[i].chain_type,
+                        res.iter().format(" ")
+                    ));
+                }
+            }
+        }
+        let mut log = String::new();
+        print_tabular_vbox(
+            &mut log,
+            &rows,
+            2,
+            &b"l|r|l|l|r|r|r|r|r".to_vec(),
+            false,
+            false,
+        );
+        print!("{}", log);
+        if !paratopes.is_empty() {
+            println!("\nparatope residues (with the structure residue, if different)\n");
+            for x in paratopes.iter() {
+                println!("{}", x);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use enclone_base::pdb::PdbAtom;
+
+    #[test]
+    fn test_pdb_match() {
+        let (matches, to_pdb) = align_chain(b"QVQLVESGG", b"MKQVQLLESGGSS");
+        assert_eq!(matches, 8);
+        assert_eq!(to_pdb[0], Some(2));
+        assert_eq!(to_pdb[8], Some(10));
+
+        // Residue 1 of chain 0 is 4 Å from the antigen, and residue 0 is 6 Å from it.
+
+        let atom = |chain: u16, chain_pos: u16, x: f32| PdbAtom {
+            chain,
+            chain_pos,
+            x,
+            ..Default::default()
+        };
+        let pdb = PdbStructure {
+            atoms: vec![atom(0, 0, 0.0), atom(0, 1, 2.0), atom(1, 0, 6.0)],
+            ..Default::default()
+        };
+        let contacts = chain_contacts(&pdb);
+        assert_eq!(contacts, vec![vec![(1, 1)], vec![(0, 0)]]);
+        assert_eq!(paratope(&contacts, &[0]), vec![vec![1]]);
+        assert_eq!(paratope(&contacts, &[0, 1]), vec![vec![], vec![]]);
+    }
+}
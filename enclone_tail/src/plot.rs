@@ -10,6 +10,7 @@ use crate::cat_var::setup_cat_var;
 use crate::circles_to_svg::circles_to_svg;
 use crate::colors::*;
 use crate::convert_svg_to_png::convert_svg_to_png;
+use crate::embed::embed_cells;
 use crate::group_colors::make_group_colors;
 use crate::legend::add_legend_for_color_by_variable;
 use crate::pack_circles::pack_circles;
@@ -19,7 +20,7 @@ use crate::string_width::arial_width;
 use crate::{get_svg_height, set_svg_height, set_svg_width, substitute_enclone_color, BOUNDARY};
 use ansi_escape::print_color13;
 use enclone_core::cell_color::CellColor;
-use enclone_core::defs::{EncloneControl, ExactClonotype, PlotOpt, POUT_SEP};
+use enclone_core::defs::{EncloneControl, ExactClonotype, GexInfo, PlotOpt, POUT_SEP};
 use io_utils::{fwriteln, open_for_read, open_for_write_new};
 use std::collections::HashMap;
 use std::fs::File;
@@ -41,6 +42,7 @@ pub fn plot_clonotypes(
     exacts: &Vec<Vec<usize>>,
     exact_clonotypes: &Vec<ExactClonotype>,
     out_datas: &Vec<Vec<HashMap<String, String>>>,
+    gex_info: &GexInfo,
     // groups: There is one entry for each group of clonotypes.  The first entries of the inner
     // vectors indexes into exacts, and the second entry (String) is not used here.
     groups: &Vec<Vec<(i32, String)>>,
@@ -593,6 +595,30 @@ pub fn plot_clonotypes(
         }
     }
 
+    // Implement HONEY=...,embed=....
+
+    let mut highlight = Vec::<bool>::new();
+    if !plot_opt.plot_embedding.is_empty() {
+        let (emb, name) = if plot_opt.plot_embedding == "umap" {
+            (&gex_info.umap, "UMAP")
+        } else {
+            (&gex_info.tsne, "t-SNE")
+        };
+        embed_cells(
+            emb,
+            name,
+            &plot_opt.plot_highlight,
+            groups.len(),
+            &mut center,
+            &mut radius,
+            &mut color,
+            &mut barcodes,
+            &mut group_index2,
+            &mut clonotype_index2,
+            &mut highlight,
+        )?;
+    }
+
     // Determine if we are coloring cells by variable value.
 
     let mut by_var = false;
@@ -645,6 +671,7 @@ pub fn plot_clonotypes(
         by_var,
         var,
         &barcode_to_var_value,
+        &highlight,
     );
 
     // Calculate the actual height and width of the svg.
@@ -0,0 +1,201 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Report public (convergent) clonotype clusters (option PUBLIC_CLUSTERS).  The clusters are the
+// clonotype groups defined by GROUP, and a cluster is public if it contains cells from at least
+// two donors (or MIN_GROUP_DONORS, if that is larger).  For each public cluster we report the
+// number of cells from each donor, the heavy chain genes and CDR3s, and light chain coherence.
+// The cluster_id is the group number shown in the GROUP line of the human-readable output.
+//
+// Light chain coherence is defined as in enclone_paper: among pairs of cells from different donors,
+// each having exactly one light chain, it is the percentage of pairs whose light chains have
+// the same V gene.  For TCR, heavy and light mean beta and alpha.
+
+use crate::keeper_group::keeper_group;
+use enclone_core::defs::{ColInfo, EncloneControl, ExactClonotype};
+use enclone_proto::types::DonorReferenceItem;
+use io_utils::{fwriteln, open_for_write_new};
+use itertools::Itertools;
+use std::cmp::max;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use vdj_ann::refx::RefData;
+use vector_utils::unique_sort;
+
+pub const PUBLIC_FIELDS: [&str; 12] = [
+    "cluster_id",
+    "nclonotypes",
+    "ncells",
+    "ndonors",
+    "donor_cells",
+    "heavy_v",
+    "heavy_j",
+    "heavy_cdr3_aa",
+    "light_v",
+    "light_pairs",
+    "light_pairs_same_v",
+    "light_coherence",
+];
+
+// Given counts n[d][v] of cells from donor d having light chain V gene v, return the number
+// of pairs of cells from different donors, and the number of those having the same V gene.
+
+fn cross_donor_pairs(n: &[HashMap<String, usize>]) -> (usize, usize) {
+    let mut totals = Vec::<usize>::new();
+    let mut by_v = HashMap::<&String, Vec<usize>>::new();
+    for d in 0..n.len() {
+        totals.push(n[d].values().sum());
+        for (v, c) in n[d].iter() {
+            by_v.entry(v).or_default().push(*c);
+        }
+    }
+    let pairs = |x: &[usize]| -> usize {
+        let s: usize = x.iter().sum();
+        (s * s - x.iter().map(|c| c * c).sum::<usize>()) / 2
+    };
+    let mut same = 0;
+    for x in by_v.values() {
+        same += pairs(x);
+    }
+    (pairs(&totals), same)
+}
+
+pub fn public_clusters(
+    groups: &Vec<Vec<(i32, String)>>,
+    exacts: &Vec<Vec<usize>>,
+    rsi: &Vec<ColInfo>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    refdata: &RefData,
+    ctl: &EncloneControl,
+    dref: &Vec<DonorReferenceItem>,
+) {
+    let min_donors = max(2, ctl.clono_group_opt.min_group_donors);
+    let mut lines = Vec::<String>::new();
+    let (mut all_pairs, mut all_same) = (0, 0);
+    for (i, g) in groups.iter().enumerate() {
+        let o = g.iter().map(|x| x.0).collect::<Vec<i32>>();
+        if !keeper_group(&o, refdata, exacts, exact_clonotypes, ctl, rsi, dref) {
+            continue;
+        }
+
+        // Gather the data for the cluster.
+
+        let ndonors = ctl.origin_info.donor_list.len();
+        let mut donor_cells = vec![0; ndonors];
+        let mut light = vec![HashMap::<String, usize>::new(); ndonors];
+        let (mut ncells, mut hv, mut hj, mut hcdr3, mut lv) =
+            (0, Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        for i in o.iter() {
+            for u in exacts[*i as usize].iter() {
+                let ex = &exact_clonotypes[*u];
+                ncells += ex.ncells();
+                let mut lights = Vec::<String>::new();
+                for x in ex.share.iter() {
+                    let vname = refdata.name[x.v_ref_id].clone();
+                    if x.left {
+                        hv.push(vname);
+                        hj.push(refdata.name[x.j_ref_id].clone());
+                        hcdr3.push(x.cdr3_aa.clone());
+                    } else {
+                        lv.push(vname.clone());
+                        lights.push(vname);
+                    }
+                }
+                for clone in ex.clones.iter() {
+                    if let Some(d) = clone[0].donor_index {
+                        donor_cells[d] += 1;
+                        if lights.len() == 1 {
+                            *light[d].entry(lights[0].clone()).or_default() += 1;
+                        }
+                    }
+                }
+            }
+        }
+        let present = (0..ndonors)
+            .filter(|d| donor_cells[*d] > 0)
+            .collect::<Vec<usize>>();
+        if present.len() < min_donors {
+            continue;
+        }
+        let (pairs, same) = cross_donor_pairs(&light);
+        all_pairs += pairs;
+        all_same += same;
+        let coherence = if pairs > 0 {
+            format!("{:.1}", 100.0 * same as f64 / pairs as f64)
+        } else {
+            String::new()
+        };
+        unique_sort(&mut hv);
+        unique_sort(&mut hj);
+        unique_sort(&mut hcdr3);
+        unique_sort(&mut lv);
+        let dc = present
+            .iter()
+            .map(|d| format!("{}:{}", ctl.origin_info.donor_list[*d], donor_cells[*d]))
+            .format(";");
+        lines.push(format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            i + 1,
+            o.len(),
+            ncells,
+            present.len(),
+            dc,
+            hv.iter().format(";"),
+            hj.iter().format(";"),
+            hcdr3.iter().format(";"),
+            lv.iter().format(";"),
+            pairs,
+            same,
+            coherence,
+        ));
+    }
+
+    // Generate output.
+
+    let mut log = Vec::<u8>::new();
+    fwriteln!(log, "{}", PUBLIC_FIELDS.iter().format(","));
+    for x in lines.iter() {
+        fwriteln!(log, "{}", x);
+    }
+    if ctl.gen_opt.public_clusters == "stdout" {
+        print!("{}", String::from_utf8_lossy(&log));
+    } else {
+        let mut f = open_for_write_new![&ctl.gen_opt.public_clusters];
+        f.write_all(&log).unwrap();
+    }
+    if ctl.gen_opt.public_clusters != "stdout" && all_pairs > 0 {
+        println!(
+            "\nfound {} public clusters, with light chain coherence {:.1}% ({} of {} pairs)",
+            lines.len(),
+            100.0 * all_same as f64 / all_pairs as f64,
+            all_same,
+            all_pairs
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross_donor_pairs() {
+        let counts = |x: &[(&str, usize)]| {
+            x.iter()
+                .map(|(v, n)| (v.to_string(), *n))
+                .collect::<HashMap<String, usize>>()
+        };
+
+        // Donor 0 has two cells with IGKV1 and one with IGKV2; donor 1 has one cell with IGKV1;
+        // donor 2 has no cells.  Of the three cross-donor pairs, two have the same V gene.
+
+        let n = vec![
+            counts(&[("IGKV1", 2), ("IGKV2", 1)]),
+            counts(&[("IGKV1", 1)]),
+            counts(&[]),
+        ];
+        assert_eq!(cross_donor_pairs(&n), (3, 2));
+        assert_eq!(cross_donor_pairs(&n[0..1]), (0, 0));
+        assert_eq!(cross_donor_pairs(&[]), (0, 0));
+    }
+}
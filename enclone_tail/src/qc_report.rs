@@ -0,0 +1,451 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Generate a single self-contained HTML quality control report (QC_REPORT=file.html).  For each
+// dataset, this shows the number of VDJ cells remaining after each filter stage, chain pairing
+// rates, UMI distributions and the concordance between VDJ and GEX cell barcodes.  These are
+// followed by the warnings raised during the run, and by plots of the cell UMI count and
+// clonotype size distributions, which are inlined as SVG.
+//
+// Cell counts through the filter stages are computed from the barcode fates of VDJ cells, using
+// only filters that were applied.  Other statistics are computed from the cells in clonotypes.
+
+use crate::fate::fate_applied;
+use crate::substitute_enclone_color;
+use ansi_escape::ansi_to_html::convert_text_with_ansi_escapes_to_html;
+use enclone_base::print_tools::font_face_in_css;
+use enclone_core::defs::{EncloneControl, ExactClonotype, GexInfo};
+use io_utils::{fwrite, fwriteln, open_for_write_new};
+use std::collections::HashMap;
+use std::io::Write;
+use string_utils::{strme, TextUtils};
+use tables::print_tabular_vbox;
+use vector_utils::bin_member;
+
+// Filter stages, in the order in which they are applied.  The first stage removes VDJ cells for
+// which no productive contig was found.
+
+pub const QC_STAGES: [&str; 14] = [
+    "PRODUCTIVE",
+    "GRAPH_FILTER",
+    "CROSS",
+    "WHITEF",
+    "BC_DUP",
+    "FOURSIE_KILL",
+    "IMPROPER",
+    "UMI",
+    "UMI_RATIO",
+    "GEX",
+    "DOUBLET",
+    "SIGNATURE",
+    "WEAK_CHAINS",
+    "QUAL",
+];
+
+// Find the filter stage of a barcode fate.
+
+pub fn qc_stage(f: &str) -> Option<usize> {
+    if f.contains("productive contig") {
+        return Some(0);
+    }
+    let name = f.between(" ", " ");
+    QC_STAGES.iter().position(|s| *s == name)
+}
+
+// Return the value at fraction q of the way through a sorted list.
+
+fn quantile(x: &[usize], q: f64) -> usize {
+    if x.is_empty() {
+        return 0;
+    }
+    x[((x.len() - 1) as f64 * q).round() as usize]
+}
+
+fn percent(k: usize, n: usize) -> String {
+    if n == 0 {
+        return "-".to_string();
+    }
+    format!("{:.1}%", 100.0 * k as f64 / n as f64)
+}
+
+fn add_table(log: &mut Vec<u8>, rows: &Vec<Vec<String>>, justify: &[u8]) {
+    let mut s = String::new();
+    print_tabular_vbox(&mut s, rows, 2, &justify.to_vec(), false, false);
+    s.truncate(s.len() - 1);
+    s = s.replace("\n", "\n   ");
+    fwrite!(log, "   {}\n", s);
+}
+
+// Make an SVG plot of the distributions of values, one curve per dataset.  Values are binned
+// by powers of two, and each curve shows the fraction of values in each bin.
+
+pub fn log2_histogram_svg(title: &str, names: &[String], values: &[Vec<usize>]) -> String {
+    let (width, height, margin, font) = (600.0, 250.0, 60.0, 12.0);
+    let bin = |v: usize| (v.max(1) as f64).log2().floor() as usize;
+    let nbins = values
+        .iter()
+        .flatten()
+        .map(|v| bin(*v) + 1)
+        .max()
+        .unwrap_or(1);
+    let mut freqs = Vec::<Vec<f64>>::new();
+    let mut ymax = 0.0_f64;
+    for i in 0..values.len() {
+        let mut f = vec![0.0; nbins];
+        for v in values[i].iter() {
+            f[bin(*v)] += 1.0 / values[i].len() as f64;
+        }
+        ymax = f.iter().fold(ymax, |m, x| m.max(*x));
+        freqs.push(f);
+    }
+    if ymax == 0.0 {
+        ymax = 1.0;
+    }
+    let x = |b: usize| margin + width * (b as f64 + 0.5) / nbins as f64;
+    let y = |f: f64| margin + height * (1.0 - f / ymax);
+    let mut svg = format!(
+        "<svg version=\"1.1\"\n\
+         baseProfile=\"full\"\n\
+         width=\"{}\" height=\"{}\"\n\
+         xmlns=\"http://www.w3.org/2000/svg\">\n",
+        width + 2.0 * margin + 150.0,
+        height + 2.0 * margin,
+    );
+    svg += &format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"arial\" font-size=\"{}\" \
+         text-anchor=\"middle\">{}</text>\n",
+        margin + width / 2.0,
+        margin / 2.0,
+        font + 2.0,
+        title,
+    );
+    svg += &format!(
+        "<polyline points=\"{},{} {},{} {},{}\" \
+         style=\"fill:none;stroke:black;stroke-width:1\" />\n",
+        margin,
+        margin,
+        margin,
+        margin + height,
+        margin + width,
+        margin + height
+    );
+    for b in 0..nbins {
+        svg += &format!(
+            "<text x=\"{:.2}\" y=\"{}\" font-family=\"arial\" font-size=\"{}\" \
+             text-anchor=\"middle\">{}</text>\n",
+            x(b),
+            margin + height + font + 4.0,
+            font,
+            1_usize << b
+        );
+    }
+    for k in 0..=4 {
+        let f = ymax * k as f64 / 4.0;
+        svg += &format!(
+            "<text x=\"{}\" y=\"{:.2}\" font-family=\"arial\" font-size=\"{}\" \
+             text-anchor=\"end\">{:.2}</text>\n",
+            margin - 4.0,
+            y(f) + font / 3.0,
+            font,
+            f
+        );
+    }
+    for i in 0..freqs.len() {
+        let mut color = format!("@{}", i % 6 + 1);
+        substitute_enclone_color(&mut color);
+        let mut points = Vec::<String>::new();
+        for b in 0..nbins {
+            points.push(format!("{:.2},{:.2}", x(b), y(freqs[i][b])));
+        }
+        svg += &format!(
+            "<polyline points=\"{}\" style=\"fill:none;stroke:{};stroke-width:2\" />\n",
+            points.join(" "),
+            color
+        );
+        svg += &format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" style=\"fill:{}\" />\n\
+             <text x=\"{}\" y=\"{}\" font-family=\"arial\" font-size=\"{}\">{}</text>\n",
+            margin + width + 20.0,
+            margin + i as f64 * 1.5 * font,
+            font,
+            font,
+            color,
+            margin + width + 20.0 + font * 1.5,
+            margin + i as f64 * 1.5 * font + font,
+            font,
+            names[i]
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+pub fn qc_report(
+    exacts: &Vec<Vec<usize>>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    ctl: &EncloneControl,
+    gex_info: &GexInfo,
+    vdj_cells: &Vec<Vec<String>>,
+    fate: &Vec<HashMap<String, String>>,
+    warnings: &Vec<String>,
+) {
+    let n = ctl.origin_info.n();
+    let names = ctl.origin_info.dataset_id.clone();
+
+    // Gather statistics for the cells in clonotypes.
+
+    let mut cells = vec![0; n];
+    let mut nchains = vec![[0; 4]; n];
+    let mut paired = vec![0; n];
+    let mut also_gex = vec![0; n];
+    let mut cell_umis = vec![Vec::<usize>::new(); n];
+    let mut chain_umis = vec![vec![Vec::<usize>::new(); 2]; n];
+    let mut sizes = vec![Vec::<usize>::new(); n];
+    for i in 0..exacts.len() {
+        let mut size = vec![0; n];
+        for u in exacts[i].iter() {
+            let ex = &exact_clonotypes[*u];
+            let (mut heavy, mut light) = (false, false);
+            for m in 0..ex.share.len() {
+                if ex.share[m].left {
+                    heavy = true;
+                } else {
+                    light = true;
+                }
+            }
+            for j in 0..ex.clones.len() {
+                let li = ex.clones[j][0].dataset_index;
+                cells[li] += 1;
+                size[li] += 1;
+                nchains[li][ex.share.len().min(4) - 1] += 1;
+                if heavy && light {
+                    paired[li] += 1;
+                }
+                if !ctl.origin_info.gex_path[li].is_empty()
+                    && bin_member(&gex_info.gex_cell_barcodes[li], &ex.clones[j][0].barcode)
+                {
+                    also_gex[li] += 1;
+                }
+                let mut total = 0;
+                for m in 0..ex.clones[j].len() {
+                    let umis = ex.clones[j][m].umi_count;
+                    total += umis;
+                    chain_umis[li][if ex.share[m].left { 0 } else { 1 }].push(umis);
+                }
+                cell_umis[li].push(total);
+            }
+        }
+        for li in 0..n {
+            if size[li] > 0 {
+                sizes[li].push(size[li]);
+            }
+        }
+    }
+    for li in 0..n {
+        cell_umis[li].sort_unstable();
+        for k in 0..2 {
+            chain_umis[li][k].sort_unstable();
+        }
+    }
+    let mut warnings = warnings.clone();
+
+    // Count VDJ cells removed at each filter stage.
+
+    let mut removed = vec![vec![0; QC_STAGES.len()]; n];
+    for li in 0..n {
+        for bc in vdj_cells[li].iter() {
+            if let Some(f) = fate[li].get(bc) {
+                if fate_applied(ctl, f) {
+                    if let Some(s) = qc_stage(f) {
+                        removed[li][s] += 1;
+                    }
+                }
+            }
+        }
+    }
+    let mut log = Vec::<u8>::new();
+    fwriteln!(log, "enclone QC report\n");
+    fwriteln!(log, "1. VDJ cells remaining after each filter stage");
+    let mut rows = vec![vec!["stage".to_string()]];
+    rows[0].append(&mut names.clone());
+    rows.push(vec!["\\hline".to_string(); n + 1]);
+    let mut row = vec!["VDJ cells".to_string()];
+    for li in 0..n {
+        row.push(format!("{}", vdj_cells[li].len()));
+    }
+    rows.push(row);
+    let mut remaining = vdj_cells.iter().map(|x| x.len()).collect::<Vec<usize>>();
+    for s in 0..QC_STAGES.len() {
+        if (0..n).all(|li| removed[li][s] == 0) {
+            continue;
+        }
+        let mut row = vec![format!("after {}", QC_STAGES[s])];
+        for li in 0..n {
+            remaining[li] = remaining[li].saturating_sub(removed[li][s]);
+            row.push(format!("{}", remaining[li]));
+        }
+        rows.push(row);
+    }
+    let mut row = vec!["in clonotypes".to_string()];
+    for li in 0..n {
+        row.push(format!("{}", cells[li]));
+    }
+    rows.push(row);
+    let mut justify = b"l".to_vec();
+    justify.append(&mut vec![b'r'; n]);
+    add_table(&mut log, &rows, &justify);
+
+    // Show chain pairing.
+
+    fwriteln!(log, "\n2. chains per cell, for cells in clonotypes");
+    let mut rows = vec![vec![
+        "dataset".to_string(),
+        "cells".to_string(),
+        "1 chain".to_string(),
+        "2 chains".to_string(),
+        "3 chains".to_string(),
+        "4+ chains".to_string(),
+        "paired".to_string(),
+    ]];
+    rows.push(vec!["\\hline".to_string(); 7]);
+    for li in 0..n {
+        let mut row = vec![names[li].clone(), format!("{}", cells[li])];
+        for k in 0..4 {
+            row.push(format!("{}", nchains[li][k]));
+        }
+        row.push(percent(paired[li], cells[li]));
+        rows.push(row);
+        if cells[li] > 0 && 2 * paired[li] < cells[li] {
+            warnings.push(format!(
+                "In dataset {}, only {} of cells in clonotypes have paired chains.",
+                names[li],
+                percent(paired[li], cells[li])
+            ));
+        }
+    }
+    add_table(&mut log, &rows, b"lrrrrrr");
+
+    // Show UMI distributions.
+
+    fwriteln!(log, "\n3. UMI count quartiles, for cells in clonotypes");
+    let mut rows = vec![vec![
+        "dataset".to_string(),
+        "cell".to_string(),
+        "heavy/TRB chain".to_string(),
+        "light/TRA chain".to_string(),
+    ]];
+    rows.push(vec!["\\hline".to_string(); 4]);
+    for li in 0..n {
+        let mut row = vec![names[li].clone()];
+        for x in [&cell_umis[li], &chain_umis[li][0], &chain_umis[li][1]] {
+            row.push(format!(
+                "{} / {} / {}",
+                quantile(x, 0.25),
+                quantile(x, 0.5),
+                quantile(x, 0.75)
+            ));
+        }
+        rows.push(row);
+    }
+    add_table(&mut log, &rows, b"lrrr");
+
+    // Show concordance between VDJ and GEX cell barcodes.
+
+    fwriteln!(log, "\n4. VDJ-GEX barcode concordance");
+    if ctl.origin_info.gex_path.iter().all(|p| p.is_empty()) {
+        fwriteln!(log, "   no GEX data");
+    } else {
+        let mut rows = vec![vec![
+            "dataset".to_string(),
+            "GEX cells".to_string(),
+            "VDJ cells".to_string(),
+            "also GEX".to_string(),
+            "clonotype cells".to_string(),
+            "also GEX".to_string(),
+        ]];
+        rows.push(vec!["\\hline".to_string(); 6]);
+        for li in 0..n {
+            if ctl.origin_info.gex_path[li].is_empty() {
+                continue;
+            }
+            let gex = &gex_info.gex_cell_barcodes[li];
+            let vdj = &vdj_cells[li];
+            let shared = vdj.iter().filter(|bc| bin_member(gex, *bc)).count();
+            rows.push(vec![
+                names[li].clone(),
+                format!("{}", gex.len()),
+                format!("{}", vdj.len()),
+                percent(shared, vdj.len()),
+                format!("{}", cells[li]),
+                percent(also_gex[li], cells[li]),
+            ]);
+            if !vdj.is_empty() && 2 * shared < vdj.len() {
+                warnings.push(format!(
+                    "In dataset {}, only {} of VDJ cells are GEX cells.",
+                    names[li],
+                    percent(shared, vdj.len())
+                ));
+            }
+        }
+        add_table(&mut log, &rows, b"lrrrrr");
+    }
+
+    // Show warnings.
+
+    fwriteln!(log, "\n5. warnings");
+    if warnings.is_empty() {
+        fwriteln!(log, "   none");
+    }
+    for i in 0..warnings.len() {
+        fwriteln!(log, "   • {}", warnings[i]);
+    }
+    fwriteln!(log, "\n6. plots");
+
+    // Generate the html, adding plots at the end.
+
+    let mut html = convert_text_with_ansi_escapes_to_html(
+        strme(&log),
+        "", // source
+        "enclone QC report",
+        &format!("<style type=\"text/css\">\n{}</style>", font_face_in_css()),
+        "DejaVuSansMono",
+        14,
+    );
+    let mut plots = log2_histogram_svg("UMIs per cell", &names, &cell_umis);
+    plots += &log2_histogram_svg("cells per clonotype", &names, &sizes);
+    if html.contains("</body>") {
+        html = format!(
+            "{}{}</body>{}",
+            html.rev_before("</body>"),
+            plots,
+            html.rev_after("</body>")
+        );
+    } else {
+        html += &plots;
+    }
+    let filename = &ctl.gen_opt.qc_report;
+    if filename == "stdout" {
+        print!("{}", html);
+    } else {
+        let mut f = open_for_write_new![&filename];
+        fwrite!(f, "{}", html);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qc_stage() {
+        assert_eq!(qc_stage("failed to find productive contig"), Some(0));
+        assert_eq!(qc_stage("failed UMI filter"), Some(7));
+        assert_eq!(
+            qc_stage("failed DOUBLET filter: score = 2.0, explained by ..."),
+            Some(10)
+        );
+        assert_eq!(qc_stage("fails CELL filter"), None);
+        assert_eq!(quantile(&[1, 2, 3, 4, 5], 0.5), 3);
+        let svg = log2_histogram_svg("x", &["a".to_string()], &[vec![1, 3, 4, 9]]);
+        assert_eq!(svg.matches("<polyline").count(), 2);
+    }
+}
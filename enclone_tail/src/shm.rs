@@ -0,0 +1,522 @@
+// Copyright (c) 2021 10X Genomics, Inc. All rights reserved.
+
+// Somatic hypermutation profiles (options SHM_PROFILE and SHM_PROFILE_SVG).  For each exact
+// subclonotype in the clonotypes that pass filters, and each chain, we compare the V segment,
+// from the start of FWR1 to the start of CDR3, to the donor reference sequence for it (or the
+// universal reference, if there is no donor reference).  Each exact subclonotype is counted once,
+// regardless of its number of cells.
+//
+// A mutation is a replacement (R) mutation if changing that base alone in the germline codon
+// changes the amino acid, and otherwise a silent (S) mutation.  Codons containing a non-ACGT
+// base, and mutations that would create a stop codon, are ignored.  Positions are IMGT positions
+// (1-103), as computed by enclone_core::imgt_numbering, so that the same position in sequences
+// having different region lengths is counted together.  Codons at insertion positions (such as
+// 111.1 in a long CDR) are counted in the region totals but not in the position totals.
+//
+// Selection is estimated in the spirit of BASELINe (Yaari et al. 2012), but using a uniform
+// mutation model: the expected fraction of replacement mutations in a region is the fraction of
+// single base substitutions of the germline that are replacements, and selection is the log of
+// the ratio of the observed to expected odds of a mutation being a replacement.  Positive values
+// suggest positive selection, and negative values suggest negative selection.  The uniform model
+// ignores the hotspot and coldspot motifs (such as WRCY and SYC) that target somatic
+// hypermutation, so that for example a region rich in hotspots at silent positions would show
+// spuriously negative selection.  A targeting model such as S5F would be needed to correct this.
+
+use crate::ticks::ticks;
+use amino::aa_seq;
+use enclone_core::defs::{EncloneControl, ExactClonotype};
+use enclone_core::imgt_numbering::imgt_numbering;
+use enclone_proto::types::DonorReferenceItem;
+use io_utils::{fwrite, open_for_write_new};
+use std::cmp::min;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use tables::print_tabular_vbox;
+use vdj_ann::refx::RefData;
+
+const REGIONS: [&str; 5] = ["fwr1", "cdr1", "fwr2", "cdr2", "fwr3"];
+const CHAINS: [&str; 2] = ["heavy", "light"];
+
+// Counts for one region.
+
+#[derive(Default, Clone)]
+struct RegionCounts {
+    r: usize,       // observed replacement mutations
+    s: usize,       // observed silent mutations
+    bases: usize,   // germline bases examined
+    exp_r: usize,   // single base substitutions of germline that are replacements
+    exp_all: usize, // single base substitutions of germline that are not stops
+}
+
+impl RegionCounts {
+    fn add(&mut self, x: &RegionCounts) {
+        self.r += x.r;
+        self.s += x.s;
+        self.bases += x.bases;
+        self.exp_r += x.exp_r;
+        self.exp_all += x.exp_all;
+    }
+
+    // Return the selection estimate and 95% confidence interval.  Pseudocounts of 0.5 are used.
+
+    fn selection(&self) -> Option<(f64, f64, f64)> {
+        if self.exp_r == 0 || self.exp_r == self.exp_all {
+            return None;
+        }
+        let pi = self.exp_r as f64 / self.exp_all as f64;
+        let (r, s) = (self.r as f64 + 0.5, self.s as f64 + 0.5);
+        let sigma = (r / s).ln() - (pi / (1.0 - pi)).ln();
+        let se = (1.0 / r + 1.0 / s).sqrt();
+        Some((sigma, sigma - 1.96 * se, sigma + 1.96 * se))
+    }
+}
+
+// Counts for one IMGT position.
+
+#[derive(Default, Clone)]
+struct PositionCounts {
+    r: usize,
+    s: usize,
+    seqs: usize,         // number of sequences covering the codon
+    regions: [usize; 5], // number of those sequences in which the codon lies in each region
+}
+
+impl PositionCounts {
+    fn region(&self) -> usize {
+        let mut best = 0;
+        for i in 1..5 {
+            if self.regions[i] > self.regions[best] {
+                best = i;
+            }
+        }
+        best
+    }
+}
+
+fn acgt(x: &[u8]) -> bool {
+    x.iter().all(|c| b"ACGT".contains(c))
+}
+
+// Add the mutations in a sequence, relative to a germline sequence.  Both start at the start of
+// the V segment, and starts gives the start positions of the five regions, followed by the start
+// of CDR3.  Position counts are indexed by IMGT position minus one.
+
+fn add_mutations(
+    seq: &[u8],
+    germ: &[u8],
+    starts: &[usize; 6],
+    regions: &mut [RegionCounts],
+    positions: &mut Vec<PositionCounts>,
+) {
+    let stop = min(starts[5], min(seq.len(), germ.len()));
+    let labels = imgt_numbering(
+        starts[0],
+        Some(starts[1]),
+        Some(starts[2]),
+        Some(starts[3]),
+        Some(starts[4]),
+        starts[5],
+        0,
+        starts[5],
+    );
+    let mut c = starts[0];
+    while c + 3 <= stop {
+        let (g, q) = (&germ[c..c + 3], &seq[c..c + 3]);
+        let mut reg = 0;
+        while reg < 4 && c >= starts[reg + 1] {
+            reg += 1;
+        }
+        let pos = labels.get(c / 3).and_then(|x| x.parse::<usize>().ok());
+        c += 3;
+        if !acgt(g) || !acgt(q) {
+            continue;
+        }
+        let aa = aa_seq(g, 0)[0];
+        if aa == b'*' {
+            continue;
+        }
+        if let Some(p) = pos {
+            if positions.len() < p {
+                positions.resize(p, PositionCounts::default());
+            }
+            positions[p - 1].seqs += 1;
+            positions[p - 1].regions[reg] += 1;
+        }
+        for j in 0..3 {
+            regions[reg].bases += 1;
+            for b in b"ACGT".iter() {
+                if *b == g[j] {
+                    continue;
+                }
+                let mut m = g.to_vec();
+                m[j] = *b;
+                let a = aa_seq(&m, 0)[0];
+                if a == b'*' {
+                    continue;
+                }
+                regions[reg].exp_all += 1;
+                if a != aa {
+                    regions[reg].exp_r += 1;
+                }
+                if *b == q[j] {
+                    if a != aa {
+                        regions[reg].r += 1;
+                        if let Some(p) = pos {
+                            positions[p - 1].r += 1;
+                        }
+                    } else {
+                        regions[reg].s += 1;
+                        if let Some(p) = pos {
+                            positions[p - 1].s += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Make an SVG showing the mutation frequency at each IMGT position, for each chain type.
+// Replacement and silent mutations are stacked, and CDRs are shaded.
+
+fn shm_svg(positions: &[Vec<PositionCounts>]) -> String {
+    let (width, panel, margin, font) = (800.0, 200.0, 50.0, 12.0);
+    let npos = positions.iter().map(|p| p.len()).max().unwrap_or(0).max(1);
+    let bar = width / npos as f64;
+    let mut svg = format!(
+        "<svg version=\"1.1\"\n\
+         baseProfile=\"full\"\n\
+         width=\"{}\" height=\"{}\"\n\
+         xmlns=\"http://www.w3.org/2000/svg\">\n",
+        width + 2.0 * margin,
+        positions.len() as f64 * (panel + 2.0 * margin) + margin,
+    );
+    for k in 0..positions.len() {
+        let p = &positions[k];
+        let y0 = margin + k as f64 * (panel + 2.0 * margin);
+        let freq = |i: usize, n: usize| -> f64 {
+            if p[i].seqs == 0 {
+                0.0
+            } else {
+                n as f64 / p[i].seqs as f64
+            }
+        };
+        let mut ymax = 0.0_f64;
+        for i in 0..p.len() {
+            ymax = ymax.max(freq(i, p[i].r + p[i].s));
+        }
+        if ymax == 0.0 {
+            ymax = 1.0;
+        }
+        let y = |f: f64| y0 + panel * (1.0 - f / ymax);
+        svg += &format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"arial\" font-size=\"{}\" \
+             text-anchor=\"middle\">{} chain mutations per sequence</text>\n",
+            margin + width / 2.0,
+            y0 - font,
+            font + 2.0,
+            CHAINS[k],
+        );
+        for i in 0..p.len() {
+            let x = margin + i as f64 * bar;
+            if p[i].seqs > 0 && p[i].region() % 2 == 1 {
+                svg += &format!(
+                    "<rect x=\"{:.2}\" y=\"{}\" width=\"{:.2}\" height=\"{}\" \
+                     style=\"fill:#e0e0e0\" />\n",
+                    x, y0, bar, panel
+                );
+            }
+            let (fs, fr) = (freq(i, p[i].s), freq(i, p[i].r));
+            if fr > 0.0 {
+                svg += &format!(
+                    "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" \
+                     style=\"fill:#c23621\" />\n",
+                    x,
+                    y(fr),
+                    bar,
+                    y0 + panel - y(fr)
+                );
+            }
+            if fs > 0.0 {
+                svg += &format!(
+                    "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" \
+                     style=\"fill:#2d5dbd\" />\n",
+                    x,
+                    y(fr + fs),
+                    bar,
+                    y(fr) - y(fr + fs)
+                );
+            }
+            if (i + 1) % 10 == 0 {
+                svg += &format!(
+                    "<text x=\"{:.2}\" y=\"{}\" font-family=\"arial\" font-size=\"{}\" \
+                     text-anchor=\"middle\">{}</text>\n",
+                    x + bar / 2.0,
+                    y0 + panel + font + 4.0,
+                    font,
+                    i + 1
+                );
+            }
+        }
+
+        // Draw axes and y axis ticks.
+
+        svg += &format!(
+            "<polyline points=\"{},{} {},{} {},{}\" \
+             style=\"fill:none;stroke:black;stroke-width:1\" />\n",
+            margin,
+            y0,
+            margin,
+            y0 + panel,
+            margin + width,
+            y0 + panel
+        );
+        for t in ticks(0.0, ymax as f32, 5, false).iter() {
+            let v = t.parse::<f64>().unwrap();
+            svg += &format!(
+                "<text x=\"{}\" y=\"{:.2}\" font-family=\"arial\" font-size=\"{}\" \
+                 text-anchor=\"end\">{}</text>\n",
+                margin - 4.0,
+                y(v) + font / 3.0,
+                font,
+                t
+            );
+        }
+    }
+
+    // Add legend.
+
+    let yl = positions.len() as f64 * (panel + 2.0 * margin) + margin / 2.0;
+    let legend = [
+        ("replacement", "#c23621"),
+        ("silent", "#2d5dbd"),
+        ("CDR", "#e0e0e0"),
+    ];
+    for i in 0..legend.len() {
+        let x = margin + i as f64 * 150.0;
+        svg += &format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" style=\"fill:{}\" />\n\
+             <text x=\"{}\" y=\"{}\" font-family=\"arial\" font-size=\"{}\">{}</text>\n",
+            x,
+            yl - font,
+            font,
+            font,
+            legend[i].1,
+            x + font * 1.5,
+            yl,
+            font,
+            legend[i].0
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+pub fn shm_profile(
+    exacts: &Vec<Vec<usize>>,
+    exact_clonotypes: &Vec<ExactClonotype>,
+    refdata: &RefData,
+    dref: &Vec<DonorReferenceItem>,
+    ctl: &EncloneControl,
+) {
+    // Gather counts.
+
+    let mut regions = vec![vec![RegionCounts::default(); 5]; 2];
+    let mut positions = vec![Vec::<PositionCounts>::new(); 2];
+    let mut nseqs = [0; 2];
+    for i in 0..exacts.len() {
+        for u in exacts[i].iter() {
+            for x in exact_clonotypes[*u].share.iter() {
+                let (s1, s2, s3) = (x.cdr1_start, x.fr2_start, x.cdr2_start);
+                if s1.is_none() || s2.is_none() || s3.is_none() || x.fr3_start.is_none() {
+                    continue;
+                }
+                let starts = [
+                    x.fr1_start,
+                    s1.unwrap(),
+                    s2.unwrap(),
+                    s3.unwrap(),
+                    x.fr3_start.unwrap(),
+                    x.cdr3_start,
+                ];
+                if starts.windows(2).any(|w| w[0] > w[1]) {
+                    continue;
+                }
+                let mut germ = refdata.refs[x.v_ref_id].to_ascii_vec();
+                if let Some(d) = x.v_ref_id_donor {
+                    germ = dref[d].nt_sequence.clone();
+                }
+                germ.truncate(germ.len().saturating_sub(ctl.heur.ref_v_trim));
+                let k = if x.left { 0 } else { 1 };
+                nseqs[k] += 1;
+                add_mutations(
+                    &x.seq_del_amino,
+                    &germ,
+                    &starts,
+                    &mut regions[k],
+                    &mut positions[k],
+                );
+            }
+        }
+    }
+
+    // Print tables.
+
+    if ctl.gen_opt.shm_profile {
+        let mut rows = vec![
+            [
+                "chain",
+                "region",
+                "seqs",
+                "bases",
+                "R",
+                "S",
+                "mut/kb",
+                "expected %R",
+                "%R",
+                "selection",
+                "95% CI",
+            ]
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>(),
+            vec!["\\hline".to_string(); 11],
+        ];
+        for k in 0..2 {
+            if nseqs[k] == 0 {
+                continue;
+            }
+            let (mut cdr, mut fwr) = (RegionCounts::default(), RegionCounts::default());
+            for j in 0..5 {
+                if j % 2 == 0 {
+                    fwr.add(&regions[k][j]);
+                } else {
+                    cdr.add(&regions[k][j]);
+                }
+            }
+            let mut all = regions[k].clone();
+            all.push(fwr);
+            all.push(cdr);
+            for j in 0..all.len() {
+                let c = &all[j];
+                let name = if j < 5 {
+                    REGIONS[j]
+                } else {
+                    ["FWR", "CDR"][j - 5]
+                };
+                let mut row = vec![
+                    CHAINS[k].to_string(),
+                    name.to_string(),
+                    format!("{}", nseqs[k]),
+                    format!("{}", c.bases),
+                    format!("{}", c.r),
+                    format!("{}", c.s),
+                ];
+                if c.bases > 0 {
+                    row.push(format!(
+                        "{:.1}",
+                        1000.0 * (c.r + c.s) as f64 / c.bases as f64
+                    ));
+                    row.push(format!("{:.1}", 100.0 * c.exp_r as f64 / c.exp_all as f64));
+                } else {
+                    row.push(String::new());
+                    row.push(String::new());
+                }
+                if c.r + c.s > 0 {
+                    row.push(format!("{:.1}", 100.0 * c.r as f64 / (c.r + c.s) as f64));
+                } else {
+                    row.push(String::new());
+                }
+                if let Some((sigma, low, high)) = c.selection() {
+                    row.push(format!("{:.2}", sigma));
+                    row.push(format!("{:.2} to {:.2}", low, high));
+                } else {
+                    row.push(String::new());
+                    row.push(String::new());
+                }
+                rows.push(row);
+            }
+        }
+        let mut log = String::new();
+        print_tabular_vbox(
+            &mut log,
+            &rows,
+            2,
+            &b"l|l|r|r|r|r|r|r|r|r|r".to_vec(),
+            false,
+            false,
+        );
+        println!("\nSOMATIC HYPERMUTATION BY REGION\n");
+        print!("{}", log);
+        let mut rows = vec![
+            ["chain", "position", "region", "seqs", "R", "S", "mut/seq"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>(),
+            vec!["\\hline".to_string(); 7],
+        ];
+        for k in 0..2 {
+            for i in 0..positions[k].len() {
+                let p = &positions[k][i];
+                if p.seqs == 0 {
+                    continue;
+                }
+                rows.push(vec![
+                    CHAINS[k].to_string(),
+                    format!("{}", i + 1),
+                    REGIONS[p.region()].to_string(),
+                    format!("{}", p.seqs),
+                    format!("{}", p.r),
+                    format!("{}", p.s),
+                    format!("{:.3}", (p.r + p.s) as f64 / p.seqs as f64),
+                ]);
+            }
+        }
+        let mut log = String::new();
+        print_tabular_vbox(&mut log, &rows, 2, &b"l|r|l|r|r|r|r".to_vec(), false, false);
+        println!("\nSOMATIC HYPERMUTATION BY POSITION\n");
+        print!("{}", log);
+    }
+
+    // Make plot.
+
+    let filename = &ctl.gen_opt.shm_profile_svg;
+    if !filename.is_empty() {
+        let svg = shm_svg(&positions);
+        if filename == "stdout" {
+            print!("{}", svg);
+        } else {
+            let mut f = open_for_write_new![&filename];
+            fwrite!(f, "{}", svg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_mutations() {
+        // Germline codons are GCT (Ala), TGG (Trp), GAA (Glu).  The first mutation is silent,
+        // the second is a replacement, and the third would create a stop codon.  With one codon
+        // per region, these are IMGT positions 26, 38 and 55.
+
+        let germ = b"GCTTGGGAA".to_vec();
+        let seq = b"GCCTTGTAA".to_vec();
+        let starts = [0, 3, 6, 9, 9, 9];
+        let mut regions = vec![RegionCounts::default(); 5];
+        let mut positions = Vec::<PositionCounts>::new();
+        add_mutations(&seq, &germ, &starts, &mut regions, &mut positions);
+        assert_eq!((regions[0].r, regions[0].s), (0, 1));
+        assert_eq!((regions[1].r, regions[1].s), (1, 0));
+        assert_eq!((regions[2].r, regions[2].s), (0, 0));
+        assert_eq!(regions[0].bases, 3);
+        assert_eq!(positions.len(), 55);
+        assert_eq!((positions[25].seqs, positions[25].s), (1, 1));
+        assert_eq!((positions[37].r, positions[37].region()), (1, 1));
+        assert_eq!(positions[54].seqs, 1);
+        assert_eq!(positions[0].seqs, 0);
+        assert!(regions[0].selection().is_some());
+    }
+}
@@ -2,7 +2,15 @@
 
 // Group and print clonotypes.  For now, limited grouping functionality.
 
+use crate::antigen::antigen_summary;
+use crate::diff_exp::diff_exp;
+use crate::diversity::print_diversity;
 use crate::group::group_and_print_clonotypes;
+use crate::novel_alleles::novel_alleles;
+use crate::pdb_match::pdb_match;
+use crate::public::public_clusters;
+use crate::qc_report::qc_report;
+use crate::shm::shm_profile;
 use enclone_base::median::median_f64;
 use enclone_core::defs::{ColInfo, EncloneControl, ExactClonotype, GexInfo};
 use enclone_proto::types::DonorReferenceItem;
@@ -38,6 +46,7 @@ pub fn tail_code(
     gex_info: &GexInfo,
     vdj_cells: &Vec<Vec<String>>,
     fate: &Vec<HashMap<String, String>>,
+    warnings: &Vec<String>,
     tests: &Vec<usize>,
     controls: &Vec<usize>,
     h5_data: &Vec<(usize, Vec<u32>, Vec<u32>)>,
@@ -73,10 +82,60 @@ pub fn tail_code(
         summary,
     )?;
 
+    // Report public clusters.
+
+    if !ctl.gen_opt.public_clusters.is_empty() {
+        public_clusters(groups, exacts, rsi, exact_clonotypes, refdata, ctl, dref);
+    }
+
+    // Report diversity statistics.
+
+    if ctl.gen_opt.diversity || ctl.gen_opt.diversity_csv {
+        print_diversity(exacts, exact_clonotypes, ctl);
+    }
+
+    // Report somatic hypermutation profiles.
+
+    if ctl.gen_opt.shm_profile || !ctl.gen_opt.shm_profile_svg.is_empty() {
+        shm_profile(exacts, exact_clonotypes, refdata, dref, ctl);
+    }
+
+    // Report antigen specificity.
+
+    if !ctl.gen_opt.antigen_controls.is_empty() {
+        antigen_summary(ctl, exacts, exact_clonotypes, out_datas);
+    }
+
+    // Report novel alleles.
+
+    if ctl.gen_opt.novel_alleles || !ctl.gen_opt.novel_alleles_fasta.is_empty() {
+        novel_alleles(exacts, exact_clonotypes, refdata, dref, ctl);
+    }
+
+    // Report matching PDB structures.
+
+    if !ctl.gen_opt.pdb_match.is_empty() {
+        pdb_match(groups, exacts, exact_clonotypes, ctl)?;
+    }
+
+    // Generate QC report.
+
+    if !ctl.gen_opt.qc_report.is_empty() {
+        qc_report(
+            exacts,
+            exact_clonotypes,
+            ctl,
+            gex_info,
+            vdj_cells,
+            fate,
+            warnings,
+        );
+    }
+
     // Do gene scan.
 
     let t = Instant::now();
-    if ctl.gen_opt.gene_scan_test.is_some() {
+    if ctl.gen_opt.gene_scan_threshold.is_some() {
         println!("\nFEATURE SCAN\n");
         let mut test_cells = 0;
         if !ctl.gen_opt.gene_scan_exact {
@@ -332,6 +391,23 @@ pub fn tail_code(
         print!("{}", strme(&log));
     }
 
+    // Do differential expression.
+
+    if ctl.gen_opt.diff_exp {
+        diff_exp(
+            ctl,
+            exacts,
+            exact_clonotypes,
+            gex_info,
+            tests,
+            controls,
+            groups,
+            h5_data,
+            d_readers,
+            ind_readers,
+        )?;
+    }
+
     // Print top genes.
 
     if ctl.gen_opt.top_genes {
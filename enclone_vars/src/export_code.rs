@@ -422,7 +422,7 @@ fn emit_code_to_test_for_var<W: Write>(var: &str, f: &mut BufWriter<W>, class: &
         );
         fwriteln!(
             f,
-            r###"let arg2 = vname.after("{}"),between2("{}", "{}").force_i64();"###,
+            r###"let arg2 = vname.after("{}").between2("{}", "{}").force_i64();"###,
             begin,
             middle,
             end,
@@ -742,6 +742,7 @@ pub fn export_code(level: usize) -> Vec<(String, String)> {
             extra_args: &Vec<String>,
             out_data: &mut Vec<HashMap<String, String>>,
             stats: &mut Vec<(String, Vec<String>)>,
+            clono_cache: &mut HashMap<String, String>,
             lvars: &Vec<String>,
             row: &mut Vec<String>,
             fate: &Vec<HashMap<String, String>>,
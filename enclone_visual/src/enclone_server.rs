@@ -13,6 +13,7 @@ use enclone_core::logging::*;
 use enclone_core::parse_bsv;
 use enclone_core::version_string;
 use enclone_main::main_enclone::*;
+use enclone_main::serve::same_intermediates;
 use enclone_main::stop::*;
 use enclone_stuff::start::main_enclone_start;
 // use enclone_version::*;
@@ -132,68 +133,17 @@ impl Analyzer for EncloneAnalyzer {
             return Ok(Response::new(response));
         }
 
-        // Check for change to setup that could change intermediates.  We are very conservative
-        // about this, and only allow changes to:
-        // * start_time
-        // * clono_filt_opt or clono_print_opt
-        // * plot_opt.
-        // More exceptions could be added.
+        // Check for change to setup that could change intermediates.  In addition to the
+        // options that are checked by same_intermediates, we also disallow changes to
+        // clono_group_opt and parseable_opt.
 
-        let mut changed = false;
+        let changed;
         {
             // last_setup must be scoped or enclone VIS will mysteriously fail
             let last_setup = &self.enclone_state.lock().unwrap().inter.setup;
-            if setup.ctl.perf_opt != last_setup.ctl.perf_opt {
-                changed = true;
-            }
-            if setup.ctl.gen_opt != last_setup.ctl.gen_opt {
-                changed = true;
-            }
-            if setup.ctl.pretty != last_setup.ctl.pretty {
-                changed = true;
-            }
-            if setup.ctl.silent != last_setup.ctl.silent {
-                changed = true;
-            }
-            if setup.ctl.force != last_setup.ctl.force {
-                changed = true;
-            }
-            if setup.ctl.debug_table_printing != last_setup.ctl.debug_table_printing {
-                changed = true;
-            }
-            if setup.ctl.merge_all_impropers != last_setup.ctl.merge_all_impropers {
-                changed = true;
-            }
-            if setup.ctl.heur != last_setup.ctl.heur {
-                changed = true;
-            }
-            if setup.ctl.origin_info != last_setup.ctl.origin_info {
-                changed = true;
-            }
-            if setup.ctl.clono_filt_opt_def != last_setup.ctl.clono_filt_opt_def {
-                changed = true;
-            }
-            if setup.ctl.allele_alg_opt != last_setup.ctl.allele_alg_opt {
-                changed = true;
-            }
-            if setup.ctl.allele_print_opt != last_setup.ctl.allele_print_opt {
-                changed = true;
-            }
-            if setup.ctl.join_alg_opt != last_setup.ctl.join_alg_opt {
-                changed = true;
-            }
-            if setup.ctl.clono_group_opt != last_setup.ctl.clono_group_opt {
-                changed = true;
-            }
-            if setup.ctl.parseable_opt != last_setup.ctl.parseable_opt {
-                changed = true;
-            }
-            if setup.ctl.pathlist != last_setup.ctl.pathlist {
-                changed = true;
-            }
-            if setup.ctl.last_modified != last_setup.ctl.last_modified {
-                changed = true;
-            }
+            changed = !same_intermediates(&setup.ctl, &last_setup.ctl)
+                || setup.ctl.clono_group_opt != last_setup.ctl.clono_group_opt
+                || setup.ctl.parseable_opt != last_setup.ctl.parseable_opt;
         }
 
         // Now proceed with the computation.